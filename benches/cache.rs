@@ -0,0 +1,29 @@
+//! Reloading a MessagePack cache against reparsing the MIDI file it came from.
+//! Run with `cargo bench --bench cache`.
+
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use midiparse_core::Sequence;
+
+const ROUNDS: u32 = 50;
+
+fn time(mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    for path in ["tests/test_mid.mid", "tests/test_mid1.mid"] {
+        let data = fs::read(path).unwrap();
+        let cache = Sequence::from_bytes(&data).unwrap().to_msgpack().unwrap();
+        let parse = time(|| { black_box(Sequence::from_bytes(black_box(&data)).unwrap()); });
+        let reload = time(|| { black_box(Sequence::from_msgpack(black_box(&cache)).unwrap()); });
+        println!("{}: parse {:?}, msgpack {:?}, {:.1}x", path, parse, reload,
+                 parse.as_secs_f64() / reload.as_secs_f64());
+    }
+}
@@ -1,9 +1,146 @@
-from typing import Tuple, List
+from typing import Callable, Dict, Iterator, Tuple, List, Optional, Union
 
 class Sequence:
-    def __init__(self, path: str): ...
+    """Supports pickle, copy and deepcopy, as do the other value classes."""
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: Sequence, tolerance: float = 1e-4) -> bool: ...
+    def __copy__(self) -> Sequence: ...
+    def __deepcopy__(self, memo: Dict[int, object]) -> Sequence: ...
+    def __init__(
+        self,
+        path: str,
+        strict: Optional[bool] = None,
+        mmap: Optional[bool] = None,
+        *,
+        options: Optional[ParseOptions] = None,
+        sustain: Optional[bool] = None,
+        overlap: Optional[str] = None,
+        encoding: Optional[str] = None,
+    ): ...
+    def _repr_html_(self) -> str: ...
+    def to_html(self, piano_roll: bool = True) -> str: ...
+    @staticmethod
+    def from_bytes(data: bytes, strict: bool = True) -> Sequence: ...
+    @staticmethod
+    def from_zip(archive: str, member: str, strict: bool = True) -> Sequence: ...
+    @staticmethod
+    def from_midi_multi(path: str, strict: bool = True) -> List[Sequence]: ...
+    @staticmethod
+    def from_dir(
+        path: str,
+        strict: bool = True,
+        on_progress: Optional[Callable[[int, int, str], None]] = None,
+        cancel: Optional[CancelToken] = None,
+    ) -> Tuple[List[Tuple[str, Sequence]], List[Tuple[str, str]]]: ...
+    def to_bytes(self, options: Optional[WriteOptions] = None) -> bytes: ...
+    def write(self, path: str, options: Optional[WriteOptions] = None) -> None: ...
+    def to_json(self) -> str: ...
+    def transpose(self, concat: bool = False) -> SequenceTrans: ...
+    def to_soa(self, concat: bool = False) -> SequenceTrans: ...
+    @staticmethod
+    def from_arrays(
+        pitch: List[int],
+        start: List[float],
+        duration: List[float],
+        velocity: List[int],
+        program: int = 0,
+        is_drum: bool = False,
+        qpm: float = 120.0,
+    ) -> Sequence: ...
+    @staticmethod
+    def from_json(json: str) -> Sequence: ...
+    def to_yaml(self) -> str: ...
+    @staticmethod
+    def from_yaml(yaml: str) -> Sequence: ...
+    def to_msgpack(self) -> bytes: ...
+    def notes_dataframe(self) -> Dict[str, "numpy.ndarray"]: ...
+    def to_arrow(self) -> Tuple["pyarrow.RecordBatch", "pyarrow.RecordBatch", "pyarrow.RecordBatch"]: ...
+    def write_parquet(self, path: str, table: str = "notes") -> None: ...
+    @staticmethod
+    def from_msgpack(data: bytes) -> Sequence: ...
+    def save(self, path: str) -> None: ...
+    def synthesize(self, soundfont: str, sample_rate: int = 44100) -> Samples: ...
+    def to_wav(self, path: str, soundfont: str, sample_rate: int = 44100) -> None: ...
+    def play(self, port_name: str = "") -> Player: ...
+    @staticmethod
+    def load(path: str) -> Sequence: ...
+    def to_csv(self, path: str) -> None: ...
+    def tempo_to_csv(self, path: str) -> None: ...
+    def controls_to_csv(self, path: str) -> None: ...
+    @staticmethod
+    def from_csv(path: str, tempo_path: Optional[str] = None, controls_path: Optional[str] = None) -> Sequence: ...
+    def allocate_channels(self, max_ports: Optional[int] = None) -> List[Tuple[int, int]]: ...
     @property
     def tracks(self) -> List[Track]: ...
+    def event_times_us(self) -> EventTimes: ...
+    def chroma(self, fps: float) -> List[List[float]]: ...
+    def polyphony(self, fps: float) -> List[int]: ...
+    def frame_labels(
+        self,
+        fps: float,
+        pitch_range: Tuple[int, int] = (21, 108),
+        include_velocity: bool = True,
+    ) -> Tuple[FrameArray, FrameArray, Optional[FrameArray]]: ...
+    def note_density(self, per: str = "bar") -> List[int]: ...
+    texts: List[TextEvent]
+    def add_text_event(self, time: float, text: str, kind: str = "marker") -> None: ...
+    def text_events(self, kind: Optional[str] = None) -> List[TextEvent]: ...
+    def remove_text_events(self, kind: Optional[str] = None) -> int: ...
+    def markers(self) -> List[Tuple[float, str]]: ...
+    @property
+    def warnings(self) -> ParseReport: ...
+    @property
+    def tpq(self) -> Optional[int]: ...
+    metadata: Metadata
+    @property
+    def karaoke(self) -> Optional[Karaoke]: ...
+    def align_lyrics(self, track_index: int) -> List[Tuple[Note, str]]: ...
+    def drum_matrix(
+        self,
+        track_index: int,
+        steps_per_bar: int = 16,
+        groups: Optional[List[List[int]]] = None,
+    ) -> List[List[List[int]]]: ...
+    def extract_melody(self) -> Track: ...
+    def humanize(self, timing_std: float = 0.01, velocity_std: float = 5.0, seed: Optional[int] = None) -> None: ...
+    def preview(self, max_notes: int) -> Sequence: ...
+    def diff(self, other: Sequence, tolerance: float = 1e-4) -> SequenceDiff: ...
+    def align_to(self, other: Sequence) -> List[Tuple[float, float]]: ...
+    def warp_time(self, mapping: List[Tuple[float, float]]) -> None: ...
+    def fingerprint(self, steps_per_quarter: int = 4, transpose_invariant: bool = False) -> int: ...
+    def split_at(self, times: List[float]) -> List[Sequence]: ...
+    def remove_doublings(self, threshold: float = 0.9) -> List[int]: ...
+    def velocity_histogram(self) -> List[int]: ...
+    def merge_tracks(self, by: str = "program") -> None: ...
+    def remove_empty_tracks(self) -> int: ...
+    def filter_tracks(self, predicate: Optional[Callable[[Track], bool]] = None, programs: Optional[List[int]] = None, drums_only: bool = False) -> int: ...
+    def pitch_range(self) -> Optional[Tuple[int, int]]: ...
+    def fit_to_range(self, low: int, high: int) -> List[int]: ...
+    def beats(self, unit: str = "quarter") -> List[float]: ...
+    def clicks(self, unit: str = "quarter") -> List[float]: ...
+    def to_abc(self) -> str: ...
+    def make_click_track(self, strong_pitch: int = 76, weak_pitch: int = 77) -> Track: ...
+    def downbeats(self, unit: str = "quarter") -> List[float]: ...
+    def end_time(self, unit: str = "quarter") -> float: ...
+    def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[Track]: ...
+    def iter_notes(self) -> Iterator[Tuple[int, Note]]: ...
+    def is_empty(self) -> bool: ...
+    def split_notes_at_barlines(self) -> None: ...
+    def note_positions(self) -> List[List[Tuple[int, int, int]]]: ...
+    def merge_14bit_controls(self) -> None: ...
+    def check_gm_compliance(self) -> List[GmViolation]: ...
+    def detect_chords(self, window: float) -> List[Tuple[float, str]]: ...
+    def detect_cadences(self, window: float = 1.0) -> List[Tuple[float, float]]: ...
+    def self_similarity(self) -> List[List[float]]: ...
+    def label_sections(self, section_bars: Optional[int] = None, threshold: float = 0.9) -> List[Tuple[float, float, str]]: ...
+    def mark_sections(self, section_bars: Optional[int] = None, threshold: float = 0.9) -> None: ...
+    def tone_roles(self, window: float) -> List[List[str]]: ...
+    def classify_tracks(self) -> List[Optional[str]]: ...
+    def note_features(self, window: float) -> List[List[List[float]]]: ...
+    def estimate_tempo(self) -> Optional[float]: ...
+    def estimate_key(self) -> Optional[Tuple[bool, int]]: ...
+    def detect_quantization(self) -> List[Optional[Tuple[str, float]]]: ...
 
 class TrackTrans:
     @property
@@ -14,23 +151,333 @@ class TrackTrans:
     def duration(self) -> List[float]: ...
     @property
     def velocity(self) -> List[int]: ...
+    def column(self, name: str) -> NoteArray: ...
+    def to_track(self) -> Track: ...
+
+class SequenceTrans:
+    @property
+    def tracks(self) -> List[TrackTrans]: ...
+    @property
+    def track(self) -> List[int]: ...
+    @property
+    def pitch(self) -> List[int]: ...
+    @property
+    def start(self) -> List[float]: ...
+    @property
+    def duration(self) -> List[float]: ...
+    @property
+    def velocity(self) -> List[int]: ...
+    def __len__(self) -> int: ...
+
+class GrooveHit:
+    pitch: int
+    step: int
+    offset: float
+    velocity: int
+    velocity_deviation: float
+
+class NoteArray:
+    """Read-only buffer over a TrackTrans column; wrap with numpy.asarray without copying."""
+    def __len__(self) -> int: ...
+
+class FrameArray:
+    """Read-only uint8 (frames, pitches) buffer of frame labels; wrap with numpy.asarray without copying."""
+    shape: Tuple[int, int]
+    def __len__(self) -> int: ...
+
+class Samples:
+    """Read-only float32 (frames, 2) buffer of stereo audio; requires the synth feature."""
+    sample_rate: int
+    def __len__(self) -> int: ...
+
+class Player:
+    """Background playback to a MIDI output port; requires the playback feature."""
+    position: float
+    is_playing: bool
+    def start(self) -> None: ...
+    def stop(self) -> None: ...
+    def seek(self, seconds: float) -> None: ...
+    def close(self) -> None: ...
+
+class Recorder:
+    """Captures a MIDI input port into a Sequence; requires the record feature."""
+    @staticmethod
+    def open(port_name: str = "", qpm: float = 120.0) -> Recorder: ...
+    def __len__(self) -> int: ...
+    def stop(self) -> Sequence: ...
 
 class Track:
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: Track, tolerance: float = 1e-4) -> bool: ...
+    def __copy__(self) -> Track: ...
+    def __deepcopy__(self, memo: Dict[int, object]) -> Track: ...
     def transpose(self) -> TrackTrans: ...
+    def split_notes_at_barlines(self, time_signatures: List[TimeSignature]) -> None: ...
+    def drum_matrix(
+        self,
+        steps_per_bar: int = 16,
+        groups: Optional[List[List[int]]] = None,
+        time_signatures: List[TimeSignature] = [],
+    ) -> List[List[List[int]]]: ...
+    def groove(self, grid: float = 0.25) -> List[GrooveHit]: ...
+    def to_abc(self, time_signatures: List[TimeSignature] = [], key: str = "C major") -> str: ...
+    def pitch_class_histogram(self) -> List[float]: ...
+    def detect_quantization(self) -> Optional[Tuple[str, float]]: ...
+    def end_time(self) -> float: ...
+    def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[Note]: ...
+    def scale_velocity(self, factor: float) -> None: ...
+    def normalize_velocity(self, target_mean: float) -> None: ...
+    def add_control_lane(self, lane: ControlLane) -> None: ...
+    def to_monophonic(self, policy: str = "highest") -> Track: ...
+    def limit_polyphony(self, max_voices: int, strategy: str = "oldest") -> None: ...
+    def filter_notes(self, min_pitch: int = 0, max_pitch: int = 127, min_velocity: int = 1, min_duration: float = 0.0) -> int: ...
+    def remove_duplicate_notes(self) -> int: ...
+    def apply_sustain(self) -> None: ...
+    def adjust_durations(self, gate: float = 0.9) -> None: ...
+    def legato(self) -> None: ...
+    @staticmethod
+    def from_step_pattern(pattern: str, pitch: int, steps_per_beat: int) -> Track: ...
+    @staticmethod
+    def from_kit_pattern(pattern: str, steps_per_beat: int) -> Track: ...
     @property
     def notes(self) -> List[Note]: ...
+    sysex: List[SysEx]
+    fine_controls: Dict[int, List[FineControlChange]]
+    channel_pressure: List[ControlChange]
+    poly_pressure: Dict[int, List[ControlChange]]
+    def note_pressure(self, idx: int) -> List[ControlChange]: ...
+    def merge_14bit_controls(self) -> None: ...
+    bank_msb: int
+    bank_lsb: int
+    instrument: Instrument
+    def program_name(self) -> str: ...
+    def instrument_family(self) -> str: ...
+    channel: Optional[int]
+    midi_track_index: Optional[int]
+    port: Optional[int]
+    channel_prefix: Optional[int]
 
 class Note:
-    __slots__ = ['pitch', 'start', 'duration', 'velocity']
-    def __init__(self, pitch: int, start: float, duration: float, velocity: int): ...
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: Note, tolerance: float = 1e-4) -> bool: ...
+    def __copy__(self) -> Note: ...
+    def __deepcopy__(self, memo: Dict[int, object]) -> Note: ...
+    __slots__ = ['pitch', 'start', 'duration', 'velocity', 'tie']
+    def __init__(self, pitch: int, start: float, duration: float, velocity: int, tie: bool = False): ...
     def end(self) -> float: ...
+    def name(self, middle_c_octave: int = 4) -> str: ...
+    def octave(self, middle_c_octave: int = 4) -> int: ...
+    @property
+    def pitch_class(self) -> int: ...
 class Tempo:
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: Tempo, tolerance: float = 1e-4) -> bool: ...
     def __init__(self, time: float, qpm: int): ...
 
+class TrackTimes:
+    notes: List[Tuple[int, int]]
+    controls: Dict[int, List[int]]
+    fine_controls: Dict[int, List[int]]
+    channel_pressure: List[int]
+    poly_pressure: Dict[int, List[int]]
+    sysex: List[int]
+
+class EventTimes:
+    tracks: List[TrackTimes]
+    qpm: List[int]
+    time_signatures: List[int]
+    key_signatures: List[int]
+    texts: List[int]
+
 class TimeSignature:
-    __slots__ = ['time', 'numerator', 'denominator']
-    def __init__(self, time: float, numerator: int, denominator: int): ...
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: TimeSignature, tolerance: float = 1e-4) -> bool: ...
+    __slots__ = ['time', 'numerator', 'denominator', 'clocks_per_click', 'thirty_seconds_per_quarter']
+    def __init__(
+        self,
+        time: float,
+        numerator: int,
+        denominator: int,
+        clocks_per_click: Optional[int] = None,
+        thirty_seconds_per_quarter: Optional[int] = None,
+    ): ...
 
 class KeySignature:
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: KeySignature, tolerance: float = 1e-4) -> bool: ...
     __slots__ = ['time', 'key']
-    def __init__(self, time: float, key: Tuple[bool, int]): ...
\ No newline at end of file
+    def __init__(self, time: float, key: Tuple[bool, int]): ...
+    @staticmethod
+    def from_name(name: str, time: float = 0.0) -> KeySignature: ...
+    def name(self) -> str: ...
+    def tonic_pitch_class(self) -> int: ...
+
+class ControlChange:
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: ControlChange, tolerance: float = 1e-4) -> bool: ...
+    __slots__ = ['time', 'value']
+    def __init__(self, time: float, value: int): ...
+
+class ControlLane:
+    __slots__ = ['number', 'controls']
+    def __init__(self, number: int, controls: List[ControlChange]): ...
+    @staticmethod
+    def from_breakpoints(number: int, breakpoints: List[Tuple[float, float]], resolution: float) -> ControlLane: ...
+    @staticmethod
+    def ramp(number: int, start: float, end: float, from_value: float, to_value: float, resolution: float) -> ControlLane: ...
+    @staticmethod
+    def lfo(number: int, start: float, end: float, rate: float, center: float, depth: float, resolution: float) -> ControlLane: ...
+
+class GmViolation:
+    @property
+    def time(self) -> float: ...
+    @property
+    def track(self) -> Optional[int]: ...
+    @property
+    def message(self) -> str: ...
+
+class WriteOptions:
+    tpq: int
+    per_note: bool
+    max_ports: Optional[int]
+    def __init__(self, tpq: int = 480, rounding: str = "nearest", per_note: bool = False, max_ports: Optional[int] = None): ...
+    @property
+    def rounding(self) -> str: ...
+
+class ParseOptions:
+    strict: bool
+    mmap: bool
+    sustain: bool
+    overlap: str
+    encoding: str
+    def __init__(
+        self,
+        strict: bool = True,
+        mmap: bool = False,
+        sustain: bool = False,
+        overlap: str = "replace",
+        encoding: str = "utf-8",
+    ): ...
+
+class TextEvent:
+    time: float
+    text: str
+    kind: str
+    def __init__(self, time: float, text: str, kind: str = "marker"): ...
+
+class Metadata:
+    title: Optional[str]
+    copyright: Optional[str]
+    instruments: List[str]
+    cue_points: List[Tuple[float, str]]
+
+class Syllable:
+    time: float
+    text: str
+
+class LyricLine:
+    paragraph: bool
+    syllables: List[Syllable]
+    def text(self) -> str: ...
+
+class Karaoke:
+    title: List[str]
+    language: Optional[str]
+    info: List[str]
+    lines: List[LyricLine]
+
+class ParseReport:
+    cancelled_running_status: int
+    zero_length_notes: int
+    notes_closed_at_eot: int
+    unknown_metas: int
+    chunk_length_mismatches: int
+    default_tempo_assumed: bool
+    messages: List[str]
+    def is_clean(self) -> bool: ...
+    def __bool__(self) -> bool: ...
+
+class SysEx:
+    time: float
+    data: bytes
+    escape: bool
+    def __init__(self, time: float, data: bytes, escape: bool = False): ...
+
+class Instrument:
+    bank_msb: int
+    bank_lsb: int
+    program: int
+    def __init__(self, program: int, bank_msb: int = 0, bank_lsb: int = 0): ...
+
+class FineControlChange:
+    time: float
+    value: int
+    def __init__(self, time: float, value: int): ...
+    @property
+    def normalized(self) -> float: ...
+
+def drum_name(pitch: int) -> Optional[str]: ...
+def pitch_name(pitch: int, middle_c_octave: int = 4) -> str: ...
+def pitch_from_name(name: str, middle_c_octave: int = 4) -> int: ...
+
+class CorpusStatistics:
+    sequences: int
+    notes: int
+    duration: float
+    keys: Dict[str, int]
+    tempi: Dict[int, int]
+    lengths: Dict[int, int]
+    instruments: Dict[str, int]
+    densities: Dict[int, int]
+    def to_json(self) -> str: ...
+
+def load(source: Union[str, bytes], strict: bool = True) -> Tuple[Sequence, str]: ...
+
+def aggregate_statistics(sequences: List[Sequence]) -> CorpusStatistics: ...
+
+class SequenceDiff:
+    added_notes: List[Tuple[int, Note]]
+    removed_notes: List[Tuple[int, Note]]
+    changed_notes: List[Tuple[int, Note, Note]]
+    added_meta: List[Tuple[float, str]]
+    removed_meta: List[Tuple[float, str]]
+    def is_empty(self) -> bool: ...
+    def __bool__(self) -> bool: ...
+
+class EventStream:
+    def __iter__(self) -> "EventStream": ...
+    def __next__(self) -> Tuple[int, int, bytes]: ...
+
+class Probe:
+    format: int
+    tracks: int
+    division: int
+    duration: float
+    instruments: List[str]
+    tempos: int
+    time_signatures: int
+
+class CancelToken:
+    def __init__(self) -> None: ...
+    def cancel(self) -> None: ...
+    @property
+    def cancelled(self) -> bool: ...
+
+class MIDIFile:
+    @staticmethod
+    def iter_events(path: str) -> EventStream: ...
+    @staticmethod
+    def probe(path: str) -> Probe: ...
+    def to_midicsv(self, path: str) -> None: ...
+    @staticmethod
+    def from_midicsv(path: str) -> MIDIFile: ...
+
+class MidiError(IOError):
+    track: Optional[int]
+    tick: Optional[int]
+    offset: Optional[int]
+class MidiHeaderError(MidiError): ...
+class MidiTrackError(MidiError): ...
+class MidiEventError(MidiError): ...
+class UnsupportedFeatureError(MidiError): ...
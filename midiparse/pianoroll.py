@@ -3,32 +3,61 @@ import numba as nb
 from typing import Optional
 from .midiparse_core import Sequence, Track, TrackTrans
 
+SUSTAIN_PEDAL = 64
+
 
 @nb.njit(nogil=True, cache=True)
-def _to_pianoroll(piano, pitch, start, end):
-    for p, s, e in zip(pitch, start, end):
-        piano[0, p, s:e] = 1
-        piano[1, p, s] = 1
+def _to_pianoroll(piano, pitch, start, end, value):
+    for p, s, e, v in zip(pitch, start, end, value):
+        piano[0, p, s:e] = v
+        piano[1, p, s] = v
+
+
+@nb.njit(nogil=True, cache=True)
+def _to_pedal(piano, time, down):
+    # Each change holds until the next one
+    for i in range(len(time)):
+        e = time[i + 1] if i + 1 < len(time) else piano.shape[2]
+        piano[2, :, time[i]:e] = down[i]
+
 
+def track2pianoroll(track: Track, max_len: Optional[int] = None, quantize: int = 24,
+                    velocity: bool = False, pedal: bool = False) -> np.ndarray:
+    """Frame and onset planes of shape (2, 128, frames) at `quantize` frames per quarter.
 
-def track2pianoroll(track: Track, max_len: Optional[int] = None, quantize: int = 24) -> np.ndarray:
+    With `velocity` the planes hold note velocities instead of ones. With `pedal` a third
+    plane, the same for every pitch, is 1 while the sustain pedal is down, the
+    Onsets-and-Frames layout for transcription labels.
+    """
+    controls = track.controls.get(SUSTAIN_PEDAL, [])
     track: TrackTrans = track.transpose()
     pitch = np.asarray(track.pitch, dtype=np.uint8)
     start = np.asarray(track.start, dtype=np.float32)
     duration = np.asarray(track.duration, dtype=np.float32)
     start = (start * quantize + 0.5).astype(np.uint32)
     end = start + (duration * quantize + 0.5).astype(np.uint32)
-    length = max_len if max_len is not None else end[-1]
-    piano = np.zeros((2, 128, length), dtype=np.uint8)
-    _to_pianoroll(piano, pitch, start, end)
+    value = np.asarray(track.velocity, dtype=np.uint8) if velocity else np.ones(len(pitch), dtype=np.uint8)
+    length = max_len if max_len is not None else int(end.max(initial=0))
+    # The kernels don't check bounds, so cut everything to the roll first
+    keep = start < length
+    pitch, start, end, value = pitch[keep], start[keep], np.minimum(end[keep], length), value[keep]
+    piano = np.zeros((3 if pedal else 2, 128, length), dtype=np.uint8)
+    _to_pianoroll(piano, pitch, start, end, value)
+    if pedal and controls:
+        controls = sorted(controls, key=lambda c: c.time)
+        time = np.asarray([min(int(c.time * quantize + 0.5), length) for c in controls], dtype=np.uint32)
+        down = np.asarray([c.value >= 64 for c in controls], dtype=np.uint8)
+        _to_pedal(piano, time, down)
     return piano
 
 
-def seq2pianoroll(seq: Sequence, max_len: Optional[int] = None, quantize: int = 24) -> np.ndarray:
+def seq2pianoroll(seq: Sequence, max_len: Optional[int] = None, quantize: int = 24,
+                  velocity: bool = False, pedal: bool = False) -> np.ndarray:
+    """`track2pianoroll` of every track, stacked to (tracks, planes, 128, frames)."""
     end = int(max(track.notes[-1].end() for track in seq.tracks) * quantize + 0.5)
     length = max_len if max_len is not None else end
     assert length >= end
     return np.stack([
-        track2pianoroll(track, max_len=length, quantize=quantize)
+        track2pianoroll(track, max_len=length, quantize=quantize, velocity=velocity, pedal=pedal)
         for track in seq.tracks
     ])
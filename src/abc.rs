@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use crate::analysis::key_name;
+use crate::grid::bars;
+use crate::sequence::{Sequence, TimeSignature, Track};
+
+/// Unit note length `L:` in quarters; notes are quantized to it.
+const UNIT: f32 = 0.25;
+const BARS_PER_LINE: usize = 4;
+/// Spellings of the pitch classes, `(letter, alteration)`.
+const SHARP_SPELLINGS: [(char, i8); 12] = [
+    ('C', 0), ('C', 1), ('D', 0), ('D', 1), ('E', 0), ('F', 0),
+    ('F', 1), ('G', 0), ('G', 1), ('A', 0), ('A', 1), ('B', 0),
+];
+const FLAT_SPELLINGS: [(char, i8); 12] = [
+    ('C', 0), ('D', -1), ('D', 0), ('E', -1), ('E', 0), ('F', 0),
+    ('G', -1), ('G', 0), ('A', -1), ('A', 0), ('B', -1), ('B', 0),
+];
+
+/// `K:` field of a `(is_major, sharps)` key, "F#m" or "Bb".
+fn abc_key(key: (bool, i8)) -> String {
+    let name = key_name(key);
+    let tonic = name.split(' ').next().unwrap_or("C");
+    if key.0 { tonic.to_string() } else { format!("{}m", tonic) }
+}
+
+/// Alteration the key signature gives each letter.
+fn key_alterations(sharps: i8) -> HashMap<char, i8> {
+    let order = if sharps >= 0 { "FCGDAEB" } else { "BEADGCF" };
+    let alteration = sharps.signum();
+    order.chars().take(sharps.unsigned_abs().min(7) as usize).map(|c| (c, alteration)).collect()
+}
+
+/// Length suffix of a note or rest of `units` times `UNIT`, none for one unit.
+fn length(units: u32) -> String {
+    if units == 1 { String::new() } else { units.to_string() }
+}
+
+/// Writes pitches of one bar, adding accidentals where the key signature or an earlier
+/// accidental in the bar would otherwise apply.
+struct Speller {
+    key: HashMap<char, i8>,
+    flats: bool,
+    bar: HashMap<(char, i32), i8>,
+}
+
+impl Speller {
+    fn new(key: (bool, i8)) -> Self {
+        Speller { key: key_alterations(key.1), flats: key.1 < 0, bar: HashMap::new() }
+    }
+
+    fn pitch(&mut self, pitch: u8) -> String {
+        let spellings = if self.flats { &FLAT_SPELLINGS } else { &SHARP_SPELLINGS };
+        let (letter, alteration) = spellings[pitch as usize % 12];
+        let octave = pitch as i32 / 12 - 1;
+        let current = self.bar.get(&(letter, octave)).copied()
+            .unwrap_or_else(|| self.key.get(&letter).copied().unwrap_or(0));
+        let mut abc = String::new();
+        if current != alteration {
+            abc.push_str(match alteration { 1 => "^", -1 => "_", _ => "=" });
+            self.bar.insert((letter, octave), alteration);
+        }
+        // Middle C (octave 4) is `C`, the octave above lowercase
+        if octave >= 5 {
+            abc.push(letter.to_ascii_lowercase());
+            abc.push_str(&"'".repeat((octave - 5) as usize));
+        } else {
+            abc.push(letter);
+            abc.push_str(&",".repeat((4 - octave).max(0) as usize));
+        }
+        abc
+    }
+}
+
+impl Track {
+    /// ABC tune body of the notes quantized to sixteenths, with barlines from
+    /// `time_signatures` and accidentals against `key`. Notes sharing an onset form a
+    /// chord that lasts until the next onset at most, and notes crossing a barline are
+    /// tied. Meter changes are written inline.
+    pub fn abc_body(&self, time_signatures: &[TimeSignature], key: (bool, i8)) -> String {
+        let mut onsets: BTreeMap<u32, (Vec<u8>, u32)> = BTreeMap::new();
+        for note in &self.notes {
+            let start = (note.start / UNIT).round().max(0.0) as u32;
+            let end = ((note.start + note.duration) / UNIT).round().max(start as f32 + 1.0) as u32;
+            let (pitches, chord_end) = onsets.entry(start).or_default();
+            pitches.push(note.pitch);
+            *chord_end = (*chord_end).max(end);
+        }
+        let mut events: Vec<(u32, u32, Vec<u8>)> = Vec::with_capacity(onsets.len());
+        for (start, (mut pitches, end)) in onsets {
+            if let Some(prev) = events.last_mut() {
+                prev.1 = prev.1.min(start);
+            }
+            pitches.sort_unstable();
+            pitches.dedup();
+            events.push((start, end, pitches));
+        }
+        let end = events.last().map_or(0, |e| e.1);
+
+        let mut body = String::new();
+        let mut meter = None;
+        let mut speller = Speller::new(key);
+        let mut next = 0;
+        for (idx, bar) in bars(time_signatures, end as f32 * UNIT).iter().enumerate() {
+            if meter != Some((bar.numerator, bar.denominator)) {
+                if meter.is_some() {
+                    write!(body, "[M:{}/{}] ", bar.numerator, bar.denominator).unwrap();
+                }
+                meter = Some((bar.numerator, bar.denominator));
+            }
+            let bar_start = (bar.start / UNIT).round() as u32;
+            let bar_end = (bar.end() / UNIT).round() as u32;
+            speller.bar.clear();
+            let mut cursor = bar_start;
+            while let Some((start, stop, pitches)) = events.get(next) {
+                if *start >= bar_end {
+                    break;
+                }
+                let from = (*start).max(cursor);
+                if from > cursor {
+                    write!(body, "z{} ", length(from - cursor)).unwrap();
+                }
+                let to = (*stop).min(bar_end);
+                let notes: Vec<String> = pitches.iter().map(|&p| speller.pitch(p)).collect();
+                if notes.len() == 1 {
+                    body.push_str(&notes[0]);
+                } else {
+                    write!(body, "[{}]", notes.concat()).unwrap();
+                }
+                body.push_str(&length(to - from));
+                if *stop > bar_end {
+                    body.push('-');
+                } else {
+                    next += 1;
+                }
+                body.push(' ');
+                cursor = to;
+                if to == bar_end {
+                    break;
+                }
+            }
+            if cursor < bar_end {
+                write!(body, "z{} ", length(bar_end - cursor)).unwrap();
+            }
+            body.push_str(if (idx + 1) % BARS_PER_LINE == 0 { "|\n" } else { "| " });
+        }
+        let mut body = body.trim_end().trim_end_matches('|').trim_end().to_string();
+        body.push_str(" |]\n");
+        body
+    }
+
+    /// A single-voice ABC tune, see `abc_body`.
+    pub fn to_abc(&self, time_signatures: &[TimeSignature], key: (bool, i8)) -> String {
+        let mut abc = String::from("X:1\n");
+        if !self.name.is_empty() {
+            writeln!(abc, "T:{}", self.name).unwrap();
+        }
+        abc.push_str(&abc_header(time_signatures, None, key));
+        abc.push_str(&self.abc_body(time_signatures, key));
+        abc
+    }
+}
+
+/// `M:`, `L:`, `Q:` and `K:` fields for the first meter, `qpm` and `key`.
+fn abc_header(time_signatures: &[TimeSignature], qpm: Option<f32>, key: (bool, i8)) -> String {
+    let meter = bars(time_signatures, f32::MIN_POSITIVE).first()
+        .map_or((4, 4), |bar| (bar.numerator, bar.denominator));
+    let mut header = format!("M:{}/{}\nL:1/{}\n", meter.0, meter.1, (4.0 / UNIT) as u32);
+    if let Some(qpm) = qpm {
+        writeln!(header, "Q:1/4={}", qpm.round()).unwrap();
+    }
+    writeln!(header, "K:{}", abc_key(key)).unwrap();
+    header
+}
+
+impl Sequence {
+    /// ABC tune with one voice per pitched track that has notes, in the first key
+    /// signature (C major without one) and the first tempo. See `Track::abc_body`.
+    pub fn to_abc(&self) -> String {
+        let key = self.key_signatures.first().map_or((true, 0), |k| k.key);
+        let mut abc = String::from("X:1\n");
+        if let Some(title) = &self.metadata.title {
+            writeln!(abc, "T:{}", title).unwrap();
+        }
+        abc.push_str(&abc_header(&self.time_signatures, self.qpm.first().map(|t| t.qpm), key));
+        let voices = self.tracks.iter().filter(|t| !t.is_drum && !t.notes.is_empty());
+        for (idx, track) in voices.enumerate() {
+            if track.name.is_empty() {
+                writeln!(abc, "V:{}", idx + 1).unwrap();
+            } else {
+                writeln!(abc, "V:{} name=\"{}\"", idx + 1, track.name.replace('"', "'")).unwrap();
+            }
+            abc.push_str(&track.abc_body(&self.time_signatures, key));
+        }
+        abc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{KeySignature, Note, Tempo};
+
+    fn track(notes: &[(u8, f32, f32)]) -> Track {
+        Track {
+            notes: notes.iter()
+                .map(|&(pitch, start, duration)| Note { pitch, start, duration, velocity: 90, tie: false })
+                .collect(),
+            ..Track::default()
+        }
+    }
+
+    #[test]
+    fn test_abc_body() {
+        let melody = track(&[(60, 0.0, 1.0), (66, 1.0, 0.5), (66, 1.5, 0.5), (72, 3.0, 2.0), (48, 5.0, 1.0), (52, 5.0, 1.0)]);
+        assert_eq!(melody.abc_body(&[], (true, 0)), "C4 ^F2 F2 z4 c4- | c4 [C,E,]4 z8 |]\n");
+        // F# is in the key of G, so only the natural needs an accidental
+        let in_g = track(&[(66, 0.0, 1.0), (65, 1.0, 1.0), (77, 2.0, 1.0), (70, 3.0, 1.0)]);
+        assert_eq!(in_g.abc_body(&[], (true, 1)), "F4 =F4 =f4 ^A4 |]\n");
+        let in_f = track(&[(70, 0.0, 1.0), (71, 1.0, 1.0), (71, 2.0, 1.0), (63, 3.0, 1.0)]);
+        assert_eq!(in_f.abc_body(&[], (false, -1)), "B4 =B4 B4 _E4 |]\n");
+        let waltz = [TimeSignature { time: 0.0, numerator: 3, denominator: 4, ..Default::default() },
+                     TimeSignature { time: 3.0, numerator: 2, denominator: 4, ..Default::default() }];
+        assert_eq!(track(&[(62, 0.5, 4.0)]).abc_body(&waltz, (true, 0)), "z2 D10- | [M:2/4] D6 z2 |]\n");
+    }
+
+    #[test]
+    fn test_to_abc() {
+        let mut melody = track(&[(72, 0.0, 4.0)]);
+        melody.name = "Flute".to_string();
+        let seq = Sequence {
+            tracks: vec![melody, track(&[(45, 0.0, 4.0)]), Track { is_drum: true, ..track(&[(36, 0.0, 1.0)]) }],
+            key_signatures: vec![KeySignature { time: 0.0, key: (false, 0) }],
+            qpm: vec![Tempo { time: 0.0, qpm: 96.0 }],
+            ..Sequence::default()
+        };
+        assert_eq!(seq.to_abc(), "X:1\nM:4/4\nL:1/16\nQ:1/4=96\nK:Am\n\
+                                  V:1 name=\"Flute\"\nc16 |]\nV:2\nA,,16 |]\n");
+        assert_eq!(seq.tracks[0].to_abc(&[], (true, -3)), "X:1\nT:Flute\nM:4/4\nL:1/16\nK:Eb\nc16 |]\n");
+    }
+}
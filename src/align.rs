@@ -0,0 +1,192 @@
+use crate::sequence::Sequence;
+
+/// Onsets closer than this many quarters to the first note of a chord join it, so
+/// spread chords of a performance match the block chords of a score.
+const CHORD_SPREAD: f32 = 0.05;
+
+/// Pairs of `(time in self, time in other)` in quarters; the first increase, the second
+/// never decrease.
+pub type TimeMapping = Vec<(f32, f32)>;
+
+/// Onset times of the pitched notes with the pitches struck at each, as a bit set.
+fn onsets(seq: &Sequence) -> Vec<(f32, u128)> {
+    let mut notes: Vec<(f32, u8)> = seq.tracks.iter()
+        .filter(|t| !t.is_drum)
+        .flat_map(|t| t.notes.iter())
+        .map(|n| (n.start, n.pitch))
+        .collect();
+    notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut onsets: Vec<(f32, u128)> = Vec::new();
+    for (start, pitch) in notes {
+        match onsets.last_mut() {
+            Some((time, pitches)) if start - *time <= CHORD_SPREAD => *pitches |= 1 << (pitch & 0x7f),
+            _ => onsets.push((start, 1 << (pitch & 0x7f))),
+        }
+    }
+    onsets
+}
+
+/// `time` through the piecewise-linear `mapping`, shifted along with the nearest end
+/// point outside it.
+fn warp(mapping: &[(f32, f32)], time: f32) -> f32 {
+    let idx = mapping.partition_point(|&(x, _)| x <= time);
+    let mapped = match (idx.checked_sub(1).map(|i| mapping[i]), mapping.get(idx)) {
+        (Some((x0, y0)), Some(&(x1, y1))) => y0 + (time - x0) * (y1 - y0) / (x1 - x0),
+        (Some((x, y)), None) | (None, Some(&(x, y))) => y + time - x,
+        (None, None) => time,
+    };
+    mapped.max(0.0)
+}
+
+/// Steps of the warping path into a cell, two bits each in `align_to`.
+const DIAGONAL: u8 = 0;
+const FROM_ABOVE: u8 = 1;
+const FROM_LEFT: u8 = 2;
+
+/// One minus the Jaccard similarity of two pitch sets.
+#[inline(always)]
+fn distance(a: u128, b: u128) -> f32 {
+    1.0 - (a & b).count_ones() as f32 / (a | b).count_ones() as f32
+}
+
+impl Sequence {
+    /// Dynamic time warping of the pitched onsets of `self` against those of `other`,
+    /// e.g. a score against a performance of it. Onsets are compared by the pitches they
+    /// strike. Each onset of `self` maps to the mean time of the closest onsets of `other`
+    /// it is matched with; the result feeds `warp_time`. Empty if either has no notes.
+    /// Time grows with the product of the onset counts, memory by two bits per pair.
+    pub fn align_to(&self, other: &Sequence) -> TimeMapping {
+        let (a, b) = (onsets(self), onsets(other));
+        if a.is_empty() || b.is_empty() {
+            return Vec::new();
+        }
+        let (n, m) = (a.len(), b.len());
+        // Accumulated cost one row at a time, with a border of infinity, keeping only the
+        // step taken into each cell, preferring diagonal steps on ties
+        let mut steps = vec![0_u8; (n * m).div_ceil(4)];
+        let mut above = vec![f32::INFINITY; m + 1];
+        let mut row = vec![f32::INFINITY; m + 1];
+        above[0] = 0.0;
+        for (i, &(_, pitches)) in a.iter().enumerate() {
+            row[0] = f32::INFINITY;
+            for (j, &(_, other)) in b.iter().enumerate() {
+                let (diagonal, up, left) = (above[j], above[j + 1], row[j]);
+                let (best, step) = if diagonal <= up && diagonal <= left {
+                    (diagonal, DIAGONAL)
+                } else if up <= left {
+                    (up, FROM_ABOVE)
+                } else {
+                    (left, FROM_LEFT)
+                };
+                row[j + 1] = distance(pitches, other) + best;
+                let cell = i * m + j;
+                steps[cell / 4] |= step << (cell % 4 * 2);
+            }
+            std::mem::swap(&mut above, &mut row);
+        }
+        // Walk back from the end, keeping for each onset of `self` the distance of its
+        // closest matches, their summed times and count
+        let mut matched: Vec<(f32, f32, usize)> = vec![(f32::INFINITY, 0.0, 0); n];
+        let (mut i, mut j) = (n, m);
+        while i > 0 && j > 0 {
+            let d = distance(a[i - 1].1, b[j - 1].1);
+            let (closest, sum, count) = &mut matched[i - 1];
+            if d < *closest {
+                (*closest, *sum, *count) = (d, 0.0, 0);
+            }
+            if d == *closest {
+                *sum += b[j - 1].0;
+                *count += 1;
+            }
+            let cell = (i - 1) * m + j - 1;
+            match steps[cell / 4] >> (cell % 4 * 2) & 3 {
+                DIAGONAL => {
+                    i -= 1;
+                    j -= 1;
+                }
+                FROM_ABOVE => i -= 1,
+                _ => j -= 1,
+            }
+        }
+        a.iter().zip(matched).map(|(&(time, _), (_, sum, count))| (time, sum / count as f32)).collect()
+    }
+
+    /// Move every event through the piecewise-linear `mapping`, e.g. from `align_to`
+    /// against a performance so a score takes on its timing. Notes keep their mapped
+    /// start and end; tempo changes move like any event, so take over the performance's
+    /// `qpm` to hear the result at its tempo. Fails unless all times are finite, the
+    /// first increase and the second never decrease; an empty mapping changes nothing.
+    pub fn warp_time(&mut self, mapping: &[(f32, f32)]) -> Result<(), String> {
+        if mapping.iter().any(|&(x, y)| !(x.is_finite() && y.is_finite())) {
+            return Err("Time mapping must be finite".to_string());
+        }
+        if mapping.windows(2).any(|w| w[1].0 <= w[0].0 || w[1].1 < w[0].1) {
+            return Err("Time mapping must increase in its first and not decrease in its second times".to_string());
+        }
+        let warp = |time: f32| warp(mapping, time);
+        for track in &mut self.tracks {
+            for note in &mut track.notes {
+                let start = warp(note.start);
+                note.duration = (warp(note.start + note.duration) - start).max(0.0);
+                note.start = start;
+            }
+            let controls = track.controls.values_mut()
+                .chain(track.poly_pressure.values_mut())
+                .chain(std::iter::once(&mut track.channel_pressure));
+            controls.flatten().for_each(|c| c.time = warp(c.time));
+            track.fine_controls.values_mut().flatten().for_each(|c| c.time = warp(c.time));
+            track.sysex.iter_mut().for_each(|s| s.time = warp(s.time));
+        }
+        self.time_signatures.iter_mut().for_each(|x| x.time = warp(x.time));
+        self.key_signatures.iter_mut().for_each(|x| x.time = warp(x.time));
+        self.qpm.iter_mut().for_each(|x| x.time = warp(x.time));
+        self.texts.iter_mut().for_each(|x| x.time = warp(x.time));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{ControlChange, Note, Tempo, Track};
+
+    fn seq(notes: &[(u8, f32)]) -> Sequence {
+        let notes = notes.iter()
+            .map(|&(pitch, start)| Note { pitch, start, duration: 0.5, velocity: 90, tie: false })
+            .collect();
+        Sequence::from_tracks(vec![Track { notes, ..Track::default() }], 120.0)
+    }
+
+    #[test]
+    fn test_align_to() {
+        let score = seq(&[(60, 0.0), (64, 0.0), (67, 1.0), (72, 2.0), (71, 3.0)]);
+        // Slower, with a spread chord and an extra grace note before the third onset
+        let performance = seq(&[(60, 0.0), (64, 0.03), (67, 1.5), (74, 2.9), (72, 3.0), (71, 4.5)]);
+        assert_eq!(score.align_to(&performance), vec![(0.0, 0.0), (1.0, 1.5), (2.0, 3.0), (3.0, 4.5)]);
+        // The unmatched grace note lands on a neighbour
+        let reversed = performance.align_to(&score);
+        assert_eq!(reversed, vec![(0.0, 0.0), (1.5, 1.0), (2.9, 1.0), (3.0, 2.0), (4.5, 3.0)]);
+        assert!(score.align_to(&Sequence::default()).is_empty());
+    }
+
+    #[test]
+    fn test_warp_time() {
+        let mut score = seq(&[(60, 0.0), (62, 1.0), (64, 2.0), (65, 4.0)]);
+        score.tracks[0].controls.insert(64, vec![ControlChange { time: 3.0, value: 127 }]);
+        score.qpm.push(Tempo { time: 2.0, qpm: 90.0 });
+        let mapping = [(0.0, 0.5), (1.0, 1.0), (2.0, 3.0)];
+        score.warp_time(&mapping).unwrap();
+        let notes: Vec<(f32, f32)> = score.tracks[0].notes.iter().map(|n| (n.start, n.duration)).collect();
+        // Squeezed to half, stretched to double, then shifted past the last point
+        assert_eq!(notes, vec![(0.5, 0.25), (1.0, 1.0), (3.0, 0.5), (5.0, 0.5)]);
+        assert_eq!(score.tracks[0].controls[&64][0].time, 4.0);
+        assert_eq!(score.qpm, vec![Tempo { time: 0.5, qpm: 120.0 }, Tempo { time: 3.0, qpm: 90.0 }]);
+        assert!(score.warp_time(&[(1.0, 0.0), (1.0, 2.0)]).is_err());
+        assert!(score.warp_time(&[(0.0, 2.0), (1.0, 1.0)]).is_err());
+        assert!(score.warp_time(&[(0.0, 0.0), (f32::NAN, 1.0)]).is_err());
+        assert!(score.warp_time(&[(0.0, f32::INFINITY)]).is_err());
+        let before = score.clone();
+        score.warp_time(&[]).unwrap();
+        assert_eq!(score, before);
+    }
+}
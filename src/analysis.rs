@@ -0,0 +1,560 @@
+use crate::sequence::{KeySignature, Sequence, Track};
+use crate::timing::{TempoMap, TICKS_PER_QUARTER};
+
+// Krumhansl-Kessler key profiles, starting from the tonic.
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+// Tonics of the keys with -7 to 7 sharps, spelled as in their key signature.
+const MAJOR_KEY_NAMES: [&str; 15] = ["Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#"];
+const MINOR_KEY_NAMES: [&str; 15] = ["Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#", "G#", "D#", "A#"];
+
+/// Onsets needed before `detect_quantization` guesses a grid.
+const MIN_ONSETS: usize = 8;
+/// Strength at which a grid is taken even if a finer one fits better.
+const FIT_STRENGTH: f32 = 0.9;
+
+/// Frame-level targets of `Sequence::frame_labels`, each `frames` x `pitches` values
+/// stored row by row, so pitch `p` of frame `f` is at `f * pitches + p`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameLabels {
+    pub frames: usize,
+    pub pitches: usize,
+    pub activation: Vec<u8>,
+    pub onset: Vec<u8>,
+    pub velocity: Option<Vec<u8>>,
+}
+
+/// Onset grids told apart by `Track::detect_quantization`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Grid {
+    Eighth,
+    SwingEighth,
+    Sixteenth,
+    Triplet,
+}
+
+impl Grid {
+    /// Simplest first: a finer grid only wins when the coarser ones don't fit.
+    pub const ALL: [Grid; 4] = [Grid::Eighth, Grid::SwingEighth, Grid::Sixteenth, Grid::Triplet];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Grid::Eighth => "eighth",
+            Grid::SwingEighth => "swing_eighth",
+            Grid::Sixteenth => "sixteenth",
+            Grid::Triplet => "triplet",
+        }
+    }
+
+    /// Grid positions within a quarter.
+    fn points(&self) -> &'static [f32] {
+        match self {
+            Grid::Eighth => &[0.0, 0.5],
+            Grid::SwingEighth => &[0.0, 2.0 / 3.0],
+            Grid::Sixteenth => &[0.0, 0.25, 0.5, 0.75],
+            Grid::Triplet => &[0.0, 1.0 / 3.0, 2.0 / 3.0],
+        }
+    }
+
+    /// Distance from a phase in `[0, 1)` to the nearest grid point, the next downbeat included.
+    fn distance(&self, phase: f32) -> f32 {
+        self.points().iter().chain(&[1.0]).map(|p| (phase - p).abs()).fold(f32::MAX, f32::min)
+    }
+
+    /// Mean `distance` of uniformly spread phases: a quarter of the squared gaps.
+    fn random_distance(&self) -> f32 {
+        let points = self.points();
+        let gaps = points.windows(2).map(|w| w[1] - w[0]).chain([1.0 - points[points.len() - 1]]);
+        gaps.map(|gap| gap * gap / 4.0).sum()
+    }
+}
+
+/// Span `Sequence::note_density` counts onsets over.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DensityUnit {
+    Bar,
+    Second,
+}
+
+impl std::str::FromStr for DensityUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bar" => Ok(DensityUnit::Bar),
+            "second" => Ok(DensityUnit::Second),
+            _ => Err(format!("Unknown density unit {:?}, expected \"bar\" or \"second\"", s)),
+        }
+    }
+}
+
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / n, b.iter().sum::<f32>() / n);
+    let mut cov = 0.0;
+    let (mut var_a, mut var_b) = (0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a) * (x - mean_a);
+        var_b += (y - mean_b) * (y - mean_b);
+    }
+    if var_a == 0.0 || var_b == 0.0 { 0.0 } else { cov / (var_a * var_b).sqrt() }
+}
+
+/// Convert a tonic pitch class and mode into the `(is_major, sharps)` form used by `KeySignature`.
+pub fn tonic2key(tonic: u8, is_major: bool) -> (bool, i8) {
+    let major_tonic = if is_major { tonic % 12 } else { (tonic + 3) % 12 };
+    let fifths = (major_tonic as i8 * 7) % 12;
+    (is_major, if fifths > 6 { fifths - 12 } else { fifths })
+}
+
+/// Tonic pitch class of a `(is_major, sharps)` key, the inverse of `tonic2key`.
+pub fn key2tonic(key: (bool, i8)) -> u8 {
+    let major_tonic = (key.1 as i32 * 7).rem_euclid(12) as u8;
+    if key.0 { major_tonic } else { (major_tonic + 9) % 12 }
+}
+
+/// Name of a `(is_major, sharps)` key such as "F# minor", the tonic spelled as in the
+/// key signature.
+pub fn key_name(key: (bool, i8)) -> String {
+    let idx = (key.1.clamp(-7, 7) + 7) as usize;
+    let (tonic, mode) = if key.0 { (MAJOR_KEY_NAMES[idx], "major") } else { (MINOR_KEY_NAMES[idx], "minor") };
+    format!("{} {}", tonic, mode)
+}
+
+/// Parse "F# minor", "Bb major", "Ebm" or "D" into `(is_major, sharps)`. Tonics without
+/// a key signature of their own, like "D# major", take the enharmonic one.
+pub fn key_from_name(name: &str) -> Result<(bool, i8), String> {
+    let invalid = || format!("Invalid key name: {}", name);
+    let mut chars = name.trim().chars();
+    let letter = chars.next().map(|c| c.to_ascii_uppercase()).ok_or_else(invalid)?;
+    let natural = match letter {
+        'C' => 0, 'D' => 2, 'E' => 4, 'F' => 5, 'G' => 7, 'A' => 9, 'B' => 11,
+        _ => return Err(invalid()),
+    };
+    let rest = chars.as_str();
+    let (alter, rest) = if let Some(rest) = rest.strip_prefix(['#', '♯']) {
+        (1, rest)
+    } else if let Some(rest) = rest.strip_prefix(['b', '♭']) {
+        (-1, rest)
+    } else {
+        (0, rest)
+    };
+    let is_major = match rest.trim() {
+        "" | "M" => true,
+        "m" => false,
+        mode => match mode.to_lowercase().as_str() {
+            "major" | "maj" => true,
+            "minor" | "min" => false,
+            _ => return Err(invalid()),
+        },
+    };
+    let names = if is_major { &MAJOR_KEY_NAMES } else { &MINOR_KEY_NAMES };
+    let spelled = letter.to_string() + if alter > 0 { "#" } else if alter < 0 { "b" } else { "" };
+    match names.iter().position(|n| *n == spelled) {
+        Some(idx) => Ok((is_major, idx as i8 - 7)),
+        None => Ok(tonic2key(((natural + alter + 12) % 12) as u8, is_major)),
+    }
+}
+
+impl KeySignature {
+    pub fn tonic_pitch_class(&self) -> u8 {
+        key2tonic(self.key)
+    }
+
+    /// e.g. "F# minor".
+    pub fn name(&self) -> String {
+        key_name(self.key)
+    }
+
+    /// Key signature at `time` from a name that `key_from_name` reads.
+    pub fn from_name(name: &str, time: f32) -> Result<Self, String> {
+        Ok(KeySignature { time, key: key_from_name(name)? })
+    }
+}
+
+impl Track {
+    /// Duration weighted pitch class distribution, normalized to sum to 1.
+    pub fn pitch_class_histogram(&self) -> Vec<f32> {
+        let mut hist = vec![0_f32; 12];
+        for note in &self.notes {
+            hist[(note.pitch % 12) as usize] += note.duration;
+        }
+        let total: f32 = hist.iter().sum();
+        if total > 0.0 {
+            hist.iter_mut().for_each(|v| *v /= total);
+        }
+        hist
+    }
+
+    /// Guess the grid onsets were written or quantized to, and its strength: 1 when
+    /// every onset sits on the grid, 0 when they are no closer than random. Onsets fold
+    /// into one quarter, so compound meters such as 6/8 read as triplets. None with
+    /// fewer than 8 distinct onsets.
+    pub fn detect_quantization(&self) -> Option<(Grid, f32)> {
+        let mut onsets: Vec<f32> = self.notes.iter().map(|n| n.start).collect();
+        onsets.sort_by(f32::total_cmp);
+        onsets.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+        if onsets.len() < MIN_ONSETS {
+            return None;
+        }
+        let fits = Grid::ALL.map(|grid| {
+            let distance: f32 = onsets.iter().map(|t| grid.distance(t.rem_euclid(1.0))).sum::<f32>() / onsets.len() as f32;
+            (grid, (1.0 - distance / grid.random_distance()).clamp(0.0, 1.0))
+        });
+        fits.iter().find(|(_, strength)| *strength >= FIT_STRENGTH)
+            .or_else(|| fits.iter().max_by(|a, b| a.1.total_cmp(&b.1)))
+            .copied()
+    }
+}
+
+impl Sequence {
+    /// Note counts per velocity 0-127 over all tracks.
+    pub fn velocity_histogram(&self) -> Vec<usize> {
+        let mut hist = vec![0; 128];
+        for note in self.tracks.iter().flat_map(|t| t.notes.iter()) {
+            hist[(note.velocity & 0x7F) as usize] += 1;
+        }
+        hist
+    }
+
+    /// Estimate a tempo in QPM from inter-onset-interval statistics, for files whose
+    /// tempo map can't be trusted. Intervals are folded into the 60-180 QPM octave and
+    /// the most common one wins. Returns None with fewer than two distinct onsets.
+    pub fn estimate_tempo(&self) -> Option<f32> {
+        let tempo_map = TempoMap::new(&self.qpm, TICKS_PER_QUARTER);
+        let mut onsets: Vec<f64> = self.tracks.iter()
+            .flat_map(|t| t.notes.iter())
+            .map(|n| tempo_map.quarter_to_second(n.start as f64))
+            .collect();
+        onsets.sort_by(|a, b| a.total_cmp(b));
+        onsets.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+
+        // 1 QPM bins over [60, 180), holding (weight, weighted qpm sum)
+        let mut hist = [(0_f64, 0_f64); 120];
+        for (i, &onset) in onsets.iter().enumerate() {
+            // Also look a few onsets ahead so beats spanning several notes still vote.
+            for &next in onsets.iter().skip(i + 1).take(4) {
+                let ioi = next - onset;
+                if !(0.05..=4.0).contains(&ioi) {
+                    continue;
+                }
+                let mut bpm = 60.0 / ioi;
+                while bpm < 60.0 { bpm *= 2.0; }
+                while bpm >= 180.0 { bpm /= 2.0; }
+                let weight = 1.0 / ioi.max(0.25);
+                let bin = &mut hist[(bpm - 60.0) as usize];
+                bin.0 += weight;
+                bin.1 += weight * bpm;
+            }
+        }
+        // Pick the densest +-2 QPM window to absorb timing noise, then average inside it.
+        let window = |i: usize| i.saturating_sub(2)..(i + 3).min(hist.len());
+        let best = (0..hist.len())
+            .map(|i| (i, window(i).map(|j| hist[j].0).sum::<f64>()))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        if best.1 == 0.0 {
+            return None;
+        }
+        let qpm_sum: f64 = window(best.0).map(|j| hist[j].1).sum();
+        Some((qpm_sum / best.1) as f32)
+    }
+
+    /// `Track::detect_quantization` of every track.
+    pub fn detect_quantization(&self) -> Vec<Option<(Grid, f32)>> {
+        self.tracks.iter().map(|t| t.detect_quantization()).collect()
+    }
+
+    /// Krumhansl-Schmuckler key finding over all non-drum notes.
+    /// Returns `(is_major, sharps)` like `KeySignature.key`, or None without pitched notes.
+    pub fn estimate_key(&self) -> Option<(bool, i8)> {
+        let mut hist = [0_f32; 12];
+        for track in self.tracks.iter().filter(|t| !t.is_drum) {
+            for note in &track.notes {
+                hist[(note.pitch % 12) as usize] += note.duration;
+            }
+        }
+        if hist.iter().all(|&v| v == 0.0) {
+            return None;
+        }
+
+        let mut best = (f32::MIN, 0_u8, true);
+        for tonic in 0..12 {
+            let rotated: Vec<f32> = (0..12).map(|i| hist[(i + tonic) % 12]).collect();
+            for (profile, is_major) in [(&MAJOR_PROFILE, true), (&MINOR_PROFILE, false)] {
+                let r = correlation(&rotated, profile);
+                if r > best.0 {
+                    best = (r, tonic as u8, is_major);
+                }
+            }
+        }
+        Some(tonic2key(best.1, best.2))
+    }
+
+    /// 12 x frames chromagram sampled at `fps` frames per second. Each sounding note adds
+    /// velocity / 127 to its pitch class for every frame it covers. Drum tracks are skipped.
+    pub fn chroma(&self, fps: f32) -> Vec<Vec<f32>> {
+        assert!(fps > 0.0 && fps.is_finite(), "fps must be positive, got {}", fps);
+        let tempo_map = TempoMap::new(&self.qpm, TICKS_PER_QUARTER);
+        let to_frame = |q: f32| tempo_map.quarter_to_second(q as f64) * fps as f64;
+
+        let n_frames = self.tracks.iter()
+            .filter(|t| !t.is_drum)
+            .flat_map(|t| t.notes.iter())
+            .map(|n| to_frame(n.start + n.duration).ceil() as usize)
+            .max()
+            .unwrap_or(0);
+        let mut chroma = vec![vec![0_f32; n_frames]; 12];
+        for track in self.tracks.iter().filter(|t| !t.is_drum) {
+            for note in &track.notes {
+                let start = to_frame(note.start).round() as usize;
+                let end = (to_frame(note.start + note.duration).round() as usize)
+                    .max(start + 1)
+                    .min(n_frames);
+                let row = &mut chroma[(note.pitch % 12) as usize];
+                for frame in row.iter_mut().take(end).skip(start) {
+                    *frame += note.velocity as f32 / 127.0;
+                }
+            }
+        }
+        chroma
+    }
+
+    /// Number of notes sounding in each frame at `fps` frames per second, drums included.
+    pub fn polyphony(&self, fps: f32) -> Vec<u32> {
+        assert!(fps > 0.0 && fps.is_finite(), "fps must be positive, got {}", fps);
+        let tempo_map = TempoMap::new(&self.qpm, TICKS_PER_QUARTER);
+        let to_frame = |q: f32| tempo_map.quarter_to_second(q as f64) * fps as f64;
+
+        let notes = self.tracks.iter().flat_map(|t| t.notes.iter());
+        let n_frames = notes.clone()
+            .map(|n| to_frame(n.start + n.duration).ceil() as usize)
+            .max()
+            .unwrap_or(0);
+        // Note ons and offs as +1 and -1 steps, summed up into counts
+        let mut steps = vec![0_i64; n_frames + 1];
+        for note in notes {
+            let start = to_frame(note.start).round() as usize;
+            let end = (to_frame(note.start + note.duration).round() as usize)
+                .max(start + 1)
+                .min(n_frames);
+            if start < end {
+                steps[start] += 1;
+                steps[end] -= 1;
+            }
+        }
+        steps.truncate(n_frames);
+        let mut active = 0;
+        steps.iter().map(|step| {
+            active += step;
+            active as u32
+        }).collect()
+    }
+
+    /// Transcription targets at `fps` frames per second, each a frame by pitch matrix over
+    /// the inclusive `pitch_range`: activation is 1 while a note sounds, onset 1 in its
+    /// first frame, and velocity, if asked for, the loudest velocity sounding. Drums and
+    /// pitches outside the range are left out; every note lasts at least one frame.
+    pub fn frame_labels(&self, fps: f32, pitch_range: (u8, u8), include_velocity: bool) -> FrameLabels {
+        assert!(fps > 0.0 && fps.is_finite(), "fps must be positive, got {}", fps);
+        let (low, high) = pitch_range;
+        let tempo_map = TempoMap::new(&self.qpm, TICKS_PER_QUARTER);
+        let to_frame = |q: f32| tempo_map.quarter_to_second(q as f64) * fps as f64;
+
+        let notes = self.tracks.iter()
+            .filter(|t| !t.is_drum)
+            .flat_map(|t| t.notes.iter())
+            .filter(|n| (low..=high).contains(&n.pitch));
+        let n_frames = notes.clone()
+            .map(|n| to_frame(n.start + n.duration).ceil() as usize)
+            .max()
+            .unwrap_or(0);
+        let n_pitches = (high as usize + 1).saturating_sub(low as usize);
+        let mut labels = FrameLabels {
+            frames: n_frames,
+            pitches: n_pitches,
+            activation: vec![0; n_frames * n_pitches],
+            onset: vec![0; n_frames * n_pitches],
+            velocity: include_velocity.then(|| vec![0; n_frames * n_pitches]),
+        };
+        for note in notes {
+            let pitch = (note.pitch - low) as usize;
+            let start = to_frame(note.start).round() as usize;
+            let end = (to_frame(note.start + note.duration).round() as usize)
+                .max(start + 1)
+                .min(n_frames);
+            if start >= end {
+                continue;
+            }
+            labels.onset[start * n_pitches + pitch] = 1;
+            for idx in (start..end).map(|frame| frame * n_pitches + pitch) {
+                labels.activation[idx] = 1;
+                if let Some(velocity) = &mut labels.velocity {
+                    velocity[idx] = velocity[idx].max(note.velocity);
+                }
+            }
+        }
+        labels
+    }
+
+    /// Note onsets in each bar, or in each second of the tempo map, over all tracks.
+    pub fn note_density(&self, per: DensityUnit) -> Vec<usize> {
+        let onsets: Vec<f32> = self.tracks.iter().flat_map(|t| t.notes.iter()).map(|n| n.start).collect();
+        if onsets.is_empty() {
+            return Vec::new();
+        }
+        let end = self.tracks.iter().map(|t| t.end_time()).fold(0_f32, f32::max);
+        let (starts, onsets): (Vec<f32>, Vec<f32>) = match per {
+            DensityUnit::Bar => (self.bars(end).iter().map(|b| b.start).collect(), onsets),
+            DensityUnit::Second => {
+                let tempo_map = TempoMap::new(&self.qpm, TICKS_PER_QUARTER);
+                let seconds = tempo_map.quarter_to_second(end as f64).ceil().max(1.0) as usize;
+                let onsets = onsets.iter().map(|&q| tempo_map.quarter_to_second(q as f64) as f32).collect();
+                ((0..seconds).map(|s| s as f32).collect(), onsets)
+            }
+        };
+        let mut density = vec![0; starts.len()];
+        for onset in onsets {
+            let idx = starts.partition_point(|&s| s <= onset).max(1) - 1;
+            density[idx] += 1;
+        }
+        density
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{Note, Tempo};
+
+    #[test]
+    fn test_pitch_class_features() {
+        let track = Track {
+            notes: vec![
+                Note { pitch: 60, start: 0.0, duration: 1.0, velocity: 127, tie: false },
+                Note { pitch: 72, start: 1.0, duration: 1.0, velocity: 127, tie: false },
+                Note { pitch: 67, start: 0.0, duration: 2.0, velocity: 127, tie: false },
+            ],
+            ..Track::default()
+        };
+        let hist = track.pitch_class_histogram();
+        assert_eq!(hist[0], 0.5);
+        assert_eq!(hist[7], 0.5);
+
+        let seq = Sequence {
+            tracks: vec![track],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }],
+            ..Sequence::default()
+        };
+        let chroma = seq.chroma(10.0);
+        assert_eq!(chroma[0].len(), 10);
+        assert!(chroma[0].iter().all(|&v| v == 1.0));
+        assert_eq!(chroma[1].iter().sum::<f32>(), 0.0);
+        let velocities = seq.velocity_histogram();
+        assert_eq!((velocities.len(), velocities[127]), (128, 3));
+    }
+
+    #[test]
+    fn test_texture() {
+        let note = |pitch: u8, start: f32, duration: f32| Note { pitch, start, duration, velocity: 100, tie: false };
+        let seq = Sequence {
+            tracks: vec![
+                Track { notes: vec![note(60, 0.0, 4.0), note(64, 4.0, 2.0), note(67, 4.5, 0.5)], ..Track::default() },
+                Track { notes: vec![note(36, 0.0, 1.0), note(36, 2.0, 1.0), note(38, 5.0, 1.0)], is_drum: true, ..Track::default() },
+            ],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }],
+            ..Sequence::default()
+        };
+        // One frame per quarter at 120 QPM
+        assert_eq!(seq.polyphony(2.0), vec![2, 1, 2, 1, 1, 3]);
+        assert_eq!(seq.note_density(DensityUnit::Bar), vec![3, 3]);
+        assert_eq!(seq.note_density(DensityUnit::Second), vec![2, 1, 3]);
+        assert!("beat".parse::<DensityUnit>().is_err());
+        assert!(Sequence::default().note_density(DensityUnit::Bar).is_empty());
+    }
+
+    #[test]
+    fn test_frame_labels() {
+        let note = |pitch: u8, start: f32, duration: f32, velocity: u8| Note { pitch, start, duration, velocity, tie: false };
+        let seq = Sequence {
+            tracks: vec![
+                Track { notes: vec![note(60, 0.0, 1.0, 100), note(60, 1.0, 0.5, 70), note(62, 0.5, 0.1, 50), note(90, 0.0, 4.0, 90)], ..Track::default() },
+                Track { notes: vec![note(61, 0.0, 1.0, 100)], is_drum: true, ..Track::default() },
+            ],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }],
+            ..Sequence::default()
+        };
+        // Four frames per quarter at 120 QPM, pitches 60 to 62
+        let labels = seq.frame_labels(8.0, (60, 62), true);
+        let column = |values: &[u8], pitch: usize| values.iter().skip(pitch).step_by(3).copied().collect::<Vec<_>>();
+        assert_eq!((labels.frames, labels.pitches), (6, 3));
+        assert_eq!(column(&labels.activation, 0), vec![1, 1, 1, 1, 1, 1]);
+        assert_eq!(column(&labels.onset, 0), vec![1, 0, 0, 0, 1, 0]);
+        assert_eq!(column(labels.velocity.as_ref().unwrap(), 0), vec![100, 100, 100, 100, 70, 70]);
+        // The short note still lasts a frame, the drum track is left out
+        assert_eq!(column(&labels.activation, 2), vec![0, 0, 1, 0, 0, 0]);
+        assert_eq!(column(&labels.activation, 1), vec![0; 6]);
+        assert!(seq.frame_labels(8.0, (60, 62), false).velocity.is_none());
+        assert_eq!(seq.frame_labels(8.0, (0, 59), false), FrameLabels { pitches: 60, ..FrameLabels::default() });
+    }
+
+    #[test]
+    fn test_estimate_tempo() {
+        // Quarter notes at 100 QPM expressed under the default 120 QPM map
+        let notes = (0..16).map(|i| Note {
+            pitch: 60, start: i as f32 * 1.2, duration: 0.5, velocity: 100, tie: false,
+        }).collect();
+        let seq = Sequence {
+            tracks: vec![Track { notes, ..Track::default() }],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }],
+            ..Sequence::default()
+        };
+        let qpm = seq.estimate_tempo().unwrap();
+        assert!((qpm - 100.0).abs() <= 1.0, "{}", qpm);
+        // A NaN onset is left out rather than panicking
+        let mut seq = seq;
+        seq.tracks[0].notes[0].start = f32::NAN;
+        assert!((seq.estimate_tempo().unwrap() - 100.0).abs() <= 1.0);
+    }
+
+    #[test]
+    fn test_estimate_key() {
+        assert_eq!(tonic2key(7, true), (true, 1));
+        assert_eq!(tonic2key(2, false), (false, -1));
+        for sharps in -7..=7 {
+            for is_major in [true, false] {
+                assert_eq!(key_from_name(&key_name((is_major, sharps))), Ok((is_major, sharps)));
+            }
+        }
+        let ks = KeySignature::from_name("F#m", 2.0).unwrap();
+        assert_eq!((ks.key, ks.name(), ks.tonic_pitch_class()), ((false, 3), "F# minor".to_string(), 6));
+        assert_eq!(key_from_name("bb Major"), Ok((true, -2)));
+        assert_eq!(key_from_name("D# major"), Ok((true, -3)));
+        assert_eq!(key_from_name("E♭ min"), Ok((false, -6)));
+        assert!(key_from_name("H major").is_err() && key_from_name("C dorian").is_err() && key_from_name("").is_err());
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        assert!(seq.estimate_key().is_some());
+    }
+
+    #[test]
+    fn test_detect_quantization() {
+        let track = |starts: Vec<f32>| Track {
+            notes: starts.into_iter().map(|start| Note { pitch: 60, start, duration: 0.1, velocity: 100, tie: false }).collect(),
+            ..Track::default()
+        };
+        let pattern = |offsets: &[f32]| -> Vec<f32> {
+            (0..8).flat_map(|beat| offsets.iter().map(move |o| beat as f32 + o)).collect()
+        };
+        let detect = |offsets: &[f32]| track(pattern(offsets)).detect_quantization().unwrap();
+        assert_eq!(detect(&[0.0, 0.5]), (Grid::Eighth, 1.0));
+        assert_eq!(detect(&[0.0, 0.6667]).0, Grid::SwingEighth);
+        assert_eq!(detect(&[0.0, 0.25, 0.5, 0.75]).0, Grid::Sixteenth);
+        assert_eq!(detect(&[0.0, 0.3333, 0.6667]).0, Grid::Triplet);
+        // Loose onsets still find their grid, with a lower strength
+        let (grid, strength) = detect(&[0.02, 0.23, 0.52, 0.77]);
+        assert_eq!(grid, Grid::Sixteenth);
+        assert!(strength > 0.5 && strength < 0.9, "{}", strength);
+        assert!(track(vec![0.0, 1.0]).detect_quantization().is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use std::f32::consts::PI;
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, prelude::*};
+use serde::{Serialize, Deserialize};
+use crate::pymethods::pymethods_with;
+use crate::sequence::{ControlChange, Track};
+#[cfg(feature = "python")]
+use crate::sequence::positive;
+
+/// A generated controller curve, ready to be merged into `Track.controls`.
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ControlLane {
+    pub number: u8,
+    pub controls: Vec<ControlChange>,
+}
+
+/// Highest MSB controller with an LSB partner 32 numbers above it.
+const LAST_MSB_CONTROLLER: u8 = 31;
+const MAX_14BIT: u16 = 0x3FFF;
+
+/// A controller value with MSB/LSB resolution, from a merged controller pair.
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FineControlChange {
+    pub time: f32,
+    pub value: u16,
+}
+
+impl FineControlChange {
+    /// Value scaled to `[0, 1]`.
+    #[inline(always)]
+    pub fn normalized(&self) -> f32 {
+        self.value.min(MAX_14BIT) as f32 / MAX_14BIT as f32
+    }
+
+    #[inline(always)]
+    pub fn msb(&self) -> u8 { (self.value.min(MAX_14BIT) >> 7) as u8 }
+
+    #[inline(always)]
+    pub fn lsb(&self) -> u8 { (self.value.min(MAX_14BIT) & 0x7F) as u8 }
+}
+
+pymethods_with! { [pickle] #[pymethods] impl FineControlChange {
+    #[new]
+    fn py_new(time: f32, value: u16) -> Self {
+        Self { time, value }
+    }
+
+    #[getter]
+    #[pyo3(name="normalized")]
+    fn py_normalized(&self) -> f32 { self.normalized() }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
+
+#[inline(always)]
+fn to_value(v: f32) -> u8 {
+    v.round().clamp(0.0, 127.0) as u8
+}
+
+impl ControlLane {
+    /// Sample `curve` every `resolution` quarters over `[start, end]`, only emitting
+    /// an event when the quantized value changes. The end point is always kept so the
+    /// lane span stays explicit.
+    fn sample<F: Fn(f32) -> f32>(number: u8, start: f32, end: f32, resolution: f32, curve: F) -> Self {
+        assert!(resolution > 0.0, "Resolution must be positive, got {}", resolution);
+        let mut controls: Vec<ControlChange> = Vec::new();
+        let steps = ((end - start) / resolution).ceil().max(0.0) as usize;
+        for i in 0..=steps {
+            let time = (start + i as f32 * resolution).min(end);
+            let value = to_value(curve(time));
+            if i == steps || controls.last().is_none_or(|c| c.value != value) {
+                controls.push(ControlChange { time, value });
+            }
+        }
+        ControlLane { number, controls }
+    }
+
+    /// Piecewise linear interpolation between `(time, value)` breakpoints.
+    pub fn from_breakpoints(number: u8, breakpoints: &[(f32, f32)], resolution: f32) -> Self {
+        let mut points = breakpoints.to_vec();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (Some(first), Some(last)) = (points.first().copied(), points.last().copied()) else {
+            return ControlLane { number, controls: Vec::new() };
+        };
+        Self::sample(number, first.0, last.0, resolution, |t| {
+            if points.len() == 1 {
+                return first.1;
+            }
+            let idx = points.partition_point(|p| p.0 <= t).clamp(1, points.len() - 1);
+            let (a, b) = (points[idx - 1], points[idx]);
+            if b.0 <= a.0 { b.1 } else { a.1 + (b.1 - a.1) * (t - a.0) / (b.0 - a.0) }
+        })
+    }
+
+    pub fn ramp(number: u8, start: f32, end: f32, from_value: f32, to_value: f32, resolution: f32) -> Self {
+        Self::from_breakpoints(number, &[(start, from_value), (end, to_value)], resolution)
+    }
+
+    /// Sine LFO with `rate` cycles per quarter around `center`.
+    pub fn lfo(number: u8, start: f32, end: f32, rate: f32, center: f32, depth: f32, resolution: f32) -> Self {
+        Self::sample(number, start, end, resolution, |t| {
+            center + depth * (2.0 * PI * rate * (t - start)).sin()
+        })
+    }
+}
+
+impl Track {
+    /// Merge a lane into `controls`, replacing existing events of that controller
+    /// inside the lane's time span.
+    pub fn add_control_lane(&mut self, lane: &ControlLane) {
+        let (Some(first), Some(last)) = (lane.controls.first(), lane.controls.last()) else {
+            return;
+        };
+        let (start, end) = (first.time, last.time);
+        let entry = self.controls.entry(lane.number).or_default();
+        entry.retain(|c| c.time < start || c.time > end);
+        entry.extend_from_slice(&lane.controls);
+        entry.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    }
+}
+
+impl Track {
+    /// Move every controller pair with both an MSB (1-31) and an LSB (33-63) curve from
+    /// `controls` into `fine_controls`, keyed by the MSB number. An MSB resets the LSB to
+    /// 0 as the spec requires, and an MSB/LSB couple at the same time yields one value.
+    pub fn merge_14bit_controls(&mut self) {
+        for msb in 1..=LAST_MSB_CONTROLLER {
+            if !self.controls.contains_key(&msb) || !self.controls.contains_key(&(msb + 32)) {
+                continue;
+            }
+            let mut events: Vec<(f32, bool, u8)> = self.controls.remove(&msb).unwrap().into_iter()
+                .map(|c| (c.time, false, c.value))
+                .chain(self.controls.remove(&(msb + 32)).unwrap().into_iter().map(|c| (c.time, true, c.value)))
+                .collect();
+            // MSB before LSB at equal times
+            events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then(a.1.cmp(&b.1)));
+            let mut fine: Vec<FineControlChange> = Vec::with_capacity(events.len());
+            let mut high = 0_u16;
+            for (time, is_lsb, value) in events {
+                let low = if is_lsb { value as u16 } else { high = value as u16; 0 };
+                let value = (high << 7 | low).min(MAX_14BIT);
+                match fine.last_mut() {
+                    Some(last) if last.time == time => last.value = value,
+                    _ => fine.push(FineControlChange { time, value }),
+                }
+            }
+            self.fine_controls.insert(msb, fine);
+        }
+    }
+}
+
+/// Fail unless `resolution` is positive and the span it is sampled over finite, as
+/// `ControlLane::sample` needs.
+#[cfg(feature = "python")]
+fn check_span(start: f32, end: f32, resolution: f32) -> PyResult<()> {
+    positive("resolution", resolution)?;
+    if !(start.is_finite() && end.is_finite()) {
+        return Err(PyValueError::new_err("Lane start and end must be finite"));
+    }
+    Ok(())
+}
+
+pymethods_with! { [pickle] #[pymethods] impl ControlLane {
+    #[new]
+    fn py_new(number: u8, controls: Vec<ControlChange>) -> Self {
+        Self { number, controls }
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_breakpoints")]
+    fn py_from_breakpoints(number: u8, breakpoints: Vec<(f32, f32)>, resolution: f32) -> PyResult<Self> {
+        for &(time, _) in &breakpoints {
+            check_span(time, time, resolution)?;
+        }
+        Ok(Self::from_breakpoints(number, &breakpoints, resolution))
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "ramp")]
+    fn py_ramp(number: u8, start: f32, end: f32, from_value: f32, to_value: f32, resolution: f32) -> PyResult<Self> {
+        check_span(start, end, resolution)?;
+        Ok(Self::ramp(number, start, end, from_value, to_value, resolution))
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "lfo")]
+    fn py_lfo(number: u8, start: f32, end: f32, rate: f32, center: f32, depth: f32, resolution: f32) -> PyResult<Self> {
+        check_span(start, end, resolution)?;
+        Ok(Self::lfo(number, start, end, rate, center, depth, resolution))
+    }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_lanes() {
+        let lane = ControlLane::from_breakpoints(7, &[(0.0, 0.0), (1.0, 100.0), (2.0, 100.0)], 0.25);
+        let values: Vec<u8> = lane.controls.iter().map(|c| c.value).collect();
+        assert_eq!(values, vec![0, 25, 50, 75, 100, 100]);
+
+        let lfo = ControlLane::lfo(1, 0.0, 1.0, 1.0, 64.0, 10.0, 0.25);
+        assert_eq!(lfo.controls[1].value, 74);
+
+        let mut track = Track::default();
+        track.add_control_lane(&lane);
+        track.add_control_lane(&ControlLane::ramp(7, 0.5, 1.0, 0.0, 0.0, 0.5));
+        let times: Vec<f32> = track.controls[&7].iter().map(|c| c.time).collect();
+        assert_eq!(times, vec![0.0, 0.25, 0.5, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_merge_14bit_controls() {
+        let lane = |values: &[(f32, u8)]| values.iter().map(|&(time, value)| ControlChange { time, value }).collect();
+        let mut track = Track::default();
+        track.controls.insert(7, lane(&[(0.0, 100), (1.0, 64)]));
+        track.controls.insert(39, lane(&[(0.0, 32), (0.5, 127)]));
+        track.controls.insert(10, lane(&[(0.0, 64)]));
+        track.merge_14bit_controls();
+        let fine = &track.fine_controls[&7];
+        let values: Vec<(f32, u16)> = fine.iter().map(|c| (c.time, c.value)).collect();
+        assert_eq!(values, vec![(0.0, 100 << 7 | 32), (0.5, 100 << 7 | 127), (1.0, 64 << 7)]);
+        assert!((fine[2].normalized() - 8192.0 / 16383.0).abs() < 1e-6);
+        assert!(!track.controls.contains_key(&7) && !track.controls.contains_key(&39));
+        assert!(track.controls.contains_key(&10));
+        // Out of range values split as the largest one
+        let over = FineControlChange { time: 0.0, value: u16::MAX };
+        assert_eq!((over.msb(), over.lsb()), (127, 127));
+    }
+}
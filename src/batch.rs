@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::error::MidiError;
+use crate::loader::load_file;
+use crate::sequence::Sequence;
+
+/// File extensions `load_dir` picks up, case-insensitively and also with ".gz" appended.
+const MIDI_EXTENSIONS: [&str; 5] = ["mid", "midi", "kar", "rmi", "smf"];
+
+/// `(path, sequence or error)` of each file read by `load_dir`.
+pub type BatchResults = Vec<(String, Result<Sequence, MidiError>)>;
+
+/// `(path, sequence)` of the files read and `(path, message)` of those that failed.
+#[cfg(feature = "python")]
+pub(crate) type PyBatchResults = (Vec<(String, Sequence)>, Vec<(String, String)>);
+
+/// Stops a running batch between two files. Clones share the flag, so one can be handed
+/// to another thread.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl CancelToken {
+    #[new]
+    fn py_new() -> Self { Self::default() }
+
+    #[pyo3(name = "cancel")]
+    fn py_cancel(&self) { self.cancel() }
+
+    #[getter]
+    fn cancelled(&self) -> bool { self.is_cancelled() }
+}
+
+fn is_midi(path: &Path) -> bool {
+    let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().to_lowercase());
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    Path::new(name).extension().is_some_and(|ext| MIDI_EXTENSIONS.iter().any(|e| ext == *e))
+}
+
+fn collect_paths(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), MidiError> {
+    let entries = fs::read_dir(dir).map_err(|e| MidiError::Io(format!("Can not read {}: {}", dir.display(), e)))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // The entry's own type, so a symlink to a directory is not descended into
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            collect_paths(&path, paths)?;
+        } else if is_midi(&path) && path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// MIDI files under `dir` and its subdirectories, sorted. Symlinked files are read but
+/// symlinked directories are not followed, so links can't loop.
+pub fn midi_paths(dir: &str) -> Result<Vec<PathBuf>, MidiError> {
+    let mut paths = Vec::new();
+    collect_paths(Path::new(dir), &mut paths)?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parse every MIDI file under `dir`, calling `on_progress(done, total, path)` after each
+/// one. Files that fail keep their error, and the batch goes on. Returns early with the
+/// files read so far once `cancel` is cancelled or `on_progress` returns false.
+pub fn load_dir(
+    dir: &str,
+    strict: bool,
+    cancel: &CancelToken,
+    mut on_progress: impl FnMut(usize, usize, &str) -> bool,
+) -> Result<BatchResults, MidiError> {
+    let paths = midi_paths(dir)?;
+    let mut results = Vec::with_capacity(paths.len());
+    for (done, path) in paths.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let path = path.to_string_lossy().into_owned();
+        let seq = load_file(&path, strict).map(|(seq, _, _)| seq);
+        results.push((path, seq));
+        if !on_progress(done + 1, paths.len(), &results[done].0) {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// `Sequence.from_dir()`: the sequences read and `(path, message)` of the files that
+/// failed. The GIL is released while a file is parsed, and Ctrl-C stops the batch.
+#[cfg(feature = "python")]
+pub(crate) fn py_load_dir(
+    py: Python<'_>,
+    dir: &str,
+    strict: bool,
+    on_progress: Option<PyObject>,
+    cancel: Option<CancelToken>,
+) -> PyResult<PyBatchResults> {
+    let cancel = cancel.unwrap_or_default();
+    // The first error of the callback or a signal, which stops the batch
+    let mut error = None;
+    let results = py.allow_threads(|| load_dir(dir, strict, &cancel, |done, total, path| {
+        Python::with_gil(|py| {
+            let called = match &on_progress {
+                Some(on_progress) => on_progress.call1(py, (done, total, path)).map(drop),
+                None => Ok(()),
+            };
+            called.and_then(|_| py.check_signals()).map_err(|e| error = Some(e)).is_ok()
+        })
+    }))?;
+    if let Some(error) = error {
+        return Err(error);
+    }
+    let (mut loaded, mut failed) = (Vec::new(), Vec::new());
+    for (path, seq) in results {
+        match seq {
+            Ok(seq) => loaded.push((path, seq)),
+            Err(error) => failed.push((path, error.to_string())),
+        }
+    }
+    Ok((loaded, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_dir() {
+        assert!(is_midi(Path::new("a/B.MID")) && is_midi(Path::new("x.kar.gz")));
+        assert!(!is_midi(Path::new("notes.txt")) && !is_midi(Path::new("mid")));
+
+        let mut seen = Vec::new();
+        let results = load_dir("tests", true, &CancelToken::default(), |done, total, path| {
+            seen.push((done, total, path.to_string()));
+            true
+        }).unwrap();
+        assert_eq!(results.len(), seen.len());
+        assert_eq!(seen.last().map(|s| s.0), Some(seen[0].1));
+        assert!(results.iter().any(|(path, seq)| path.ends_with("tiny.mid") && seq.is_ok()));
+
+        let cancel = CancelToken::default();
+        let results = load_dir("tests", true, &cancel, |_, _, _| {
+            cancel.cancel();
+            true
+        }).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(load_dir("tests", true, &cancel, |_, _, _| true).unwrap().len(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop() {
+        let dir = std::env::temp_dir().join("midiparse_test_symlink_loop");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::copy("tests/tiny.mid", dir.join("sub/tiny.mid")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("sub/tiny.mid"), dir.join("linked.mid")).unwrap();
+        let paths = midi_paths(dir.to_str().unwrap()).unwrap();
+        assert_eq!(paths, vec![dir.join("linked.mid"), dir.join("sub/tiny.mid")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
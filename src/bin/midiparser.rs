@@ -0,0 +1,167 @@
+//! `midiparser`, the parsing core from the shell. Build with `--features cli`.
+
+use std::fs;
+use std::process::ExitCode;
+use midiparse_core::{aggregate_statistics, load, load_file, MIDIFile, Sequence, SourceFormat, TimeUnit};
+
+const USAGE: &str = "\
+Usage: midiparser <command> [--lenient] <file>...
+
+Commands:
+  info <file>...              Summarize each file
+  to-json <file> [-o <out>]   Convert to JSON, on stdout without -o
+  to-yaml <file> [-o <out>]   Convert to YAML, on stdout without -o
+  validate <file>...          Check the chunk structure, exit 1 on problems
+  stats <file>...             Corpus statistics as JSON
+
+Options:
+  --lenient   Recover from malformed tracks instead of failing
+  -o <out>    Output path for to-json and to-yaml
+
+Files may be MIDI, RMID, karaoke, or sequences cached as JSON, YAML or MessagePack,
+optionally gzipped.";
+
+struct Args {
+    command: String,
+    files: Vec<String>,
+    output: Option<String>,
+    strict: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let command = args.next().ok_or("No command given.")?;
+    let mut parsed = Args { command, files: Vec::new(), output: None, strict: true };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lenient" => parsed.strict = false,
+            "-o" | "--output" => parsed.output = Some(args.next().ok_or("-o needs a path.")?),
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}.", flag)),
+            _ => parsed.files.push(arg),
+        }
+    }
+    if parsed.files.is_empty() {
+        return Err("No input files given.".to_string());
+    }
+    Ok(parsed)
+}
+
+fn info(path: &str, strict: bool) -> Result<(), String> {
+    let (seq, format, compressed) = load_file(path, strict).map_err(|e| e.to_string())?;
+    let notes: usize = seq.tracks.iter().map(|t| t.notes.len()).sum();
+    println!("{}", path);
+    if let Some(title) = &seq.metadata.title {
+        println!("  title:           {}", title);
+    }
+    if let Some(copyright) = &seq.metadata.copyright {
+        println!("  copyright:       {}", copyright);
+    }
+    println!("  format:          {}{}", format.name(), if compressed { ".gz" } else { "" });
+    if let Some(tpq) = seq.tpq {
+        println!("  resolution:      {} ticks per quarter", tpq);
+    }
+    println!("  tracks:          {}", seq.tracks.len());
+    println!("  notes:           {}", notes);
+    println!("  duration:        {:.2} s ({:.2} quarters)",
+             seq.end_time(TimeUnit::Second), seq.end_time(TimeUnit::Quarter));
+    if let Some(tempo) = seq.qpm.first() {
+        println!("  tempo:           {:.2} qpm{}", tempo.qpm, changes(seq.qpm.len()));
+    }
+    if let Some(ts) = seq.time_signatures.first() {
+        println!("  time signature:  {}/{}{}", ts.numerator, ts.denominator, changes(seq.time_signatures.len()));
+    }
+    if let Some(ks) = seq.key_signatures.first() {
+        println!("  key signature:   {:+} {}{}", ks.key.1, if ks.key.0 { "major" } else { "minor" },
+                 changes(seq.key_signatures.len()));
+    }
+    for message in &seq.warnings.messages {
+        println!("  warning:         {}", message);
+    }
+    Ok(())
+}
+
+fn changes(count: usize) -> String {
+    match count {
+        0 | 1 => String::new(),
+        n => format!(", {} changes", n - 1),
+    }
+}
+
+fn convert(path: &str, args: &Args, to_text: fn(&Sequence) -> Result<String, String>) -> Result<(), String> {
+    let (seq, _, _) = load_file(path, args.strict).map_err(|e| e.to_string())?;
+    let text = to_text(&seq)?;
+    match &args.output {
+        Some(output) => fs::write(output, text).map_err(|e| format!("Can not write {}: {}", output, e)),
+        None => {
+            println!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/// Prints the problems found and returns whether there were none.
+fn validate(path: &str, strict: bool) -> Result<bool, String> {
+    let data = fs::read(path).map_err(|e| format!("Can not read {}: {}", path, e))?;
+    let mut problems = Vec::new();
+    if let Ok(SourceFormat::Midi | SourceFormat::Rmid | SourceFormat::Karaoke) = SourceFormat::sniff(&data) {
+        let midi = MIDIFile::from_bytes(&data).map_err(|e| e.to_string())?;
+        problems.extend(midi.validate().iter().map(|d| d.to_string()));
+    }
+    if problems.is_empty() {
+        if let Err(error) = load(&data, strict) {
+            problems.push(error.to_string());
+        }
+    }
+    if problems.is_empty() {
+        println!("{}: ok", path);
+    }
+    problems.iter().for_each(|problem| println!("{}: {}", path, problem));
+    Ok(problems.is_empty())
+}
+
+fn stats(files: &[String], strict: bool) -> Result<(), String> {
+    let sequences = files.iter()
+        .map(|path| load_file(path, strict).map(|(seq, _, _)| seq).map_err(|e| format!("{}: {}", path, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let statistics = aggregate_statistics(&sequences);
+    println!("{}", serde_json::to_string_pretty(&statistics).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+fn run(args: &Args) -> Result<bool, String> {
+    let single = || match args.files.as_slice() {
+        [path] => Ok(path.as_str()),
+        _ => Err(format!("{} takes exactly one file.", args.command)),
+    };
+    match args.command.as_str() {
+        "info" => args.files.iter().try_for_each(|path| info(path, args.strict)).map(|_| true),
+        "to-json" => convert(single()?, args, Sequence::to_json).map(|_| true),
+        "to-yaml" => convert(single()?, args, Sequence::to_yaml).map(|_| true),
+        "validate" => args.files.iter()
+            .try_fold(true, |valid, path| validate(path, args.strict).map(|ok| valid && ok)),
+        "stats" => stats(&args.files, args.strict).map(|_| true),
+        command => Err(format!("Unknown command {}.", command)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let args = match parse_args(args.into_iter()) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("midiparser: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("midiparser: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
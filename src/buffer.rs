@@ -0,0 +1,273 @@
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use pyo3::exceptions::{PyBufferError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::{ffi, AsPyPointer};
+use crate::sequence::TrackTrans;
+
+/// Number of live buffer exports of a `TrackTrans`. While it is non-zero the columns
+/// must not be reallocated, so the column setters refuse to run. Clones start unexported.
+#[derive(Default)]
+pub struct BufferExports(usize);
+
+impl BufferExports {
+    #[inline(always)]
+    pub fn is_exported(&self) -> bool { self.0 > 0 }
+}
+
+impl Clone for BufferExports {
+    fn clone(&self) -> Self { BufferExports(0) }
+}
+
+impl fmt::Debug for BufferExports {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.0) }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Column {
+    Pitch,
+    Start,
+    Duration,
+    Velocity,
+}
+
+impl std::str::FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pitch" => Ok(Column::Pitch),
+            "start" => Ok(Column::Start),
+            "duration" => Ok(Column::Duration),
+            "velocity" => Ok(Column::Velocity),
+            _ => Err(format!("Unknown note column {:?}", s)),
+        }
+    }
+}
+
+/// Read-only, zero-copy view of one `TrackTrans` column implementing the buffer protocol,
+/// so `numpy.asarray(track.column("start"))` wraps the Rust storage directly.
+#[pyclass]
+pub struct NoteArray {
+    owner: Py<TrackTrans>,
+    column: Column,
+}
+
+impl NoteArray {
+    pub fn new(owner: Py<TrackTrans>, column: &str) -> PyResult<Self> {
+        let column = column.parse().map_err(PyValueError::new_err)?;
+        Ok(NoteArray { owner, column })
+    }
+}
+
+#[pymethods]
+impl NoteArray {
+    unsafe fn __getbuffer__(slf: &PyCell<Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Note arrays are read-only"));
+        }
+        let array = slf.borrow();
+        let mut owner = array.owner.try_borrow_mut(slf.py())
+            .map_err(|_| PyBufferError::new_err("Track is in use"))?;
+        let (buf, len, itemsize, format): (*const c_void, usize, usize, &'static [u8]) = match array.column {
+            Column::Pitch => (owner.pitch.as_ptr() as _, owner.pitch.len(), 1, b"B\0"),
+            Column::Velocity => (owner.velocity.as_ptr() as _, owner.velocity.len(), 1, b"B\0"),
+            Column::Start => (owner.start.as_ptr() as _, owner.start.len(), 4, b"f\0"),
+            Column::Duration => (owner.duration.as_ptr() as _, owner.duration.len(), 4, b"f\0"),
+        };
+        owner.exports.0 += 1;
+
+        // shape and strides live until __releasebuffer__
+        let dims = Box::into_raw(Box::new([len as isize, itemsize as isize]));
+        (*view).obj = ffi::_Py_NewRef(slf.as_ptr());
+        (*view).buf = buf as *mut c_void;
+        (*view).len = (len * itemsize) as isize;
+        (*view).readonly = 1;
+        (*view).itemsize = itemsize as isize;
+        (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+            format.as_ptr() as *mut c_char
+        } else {
+            ptr::null_mut()
+        };
+        (*view).ndim = 1;
+        (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+            &mut (*dims)[0]
+        } else {
+            ptr::null_mut()
+        };
+        (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+            &mut (*dims)[1]
+        } else {
+            ptr::null_mut()
+        };
+        (*view).suboffsets = ptr::null_mut();
+        (*view).internal = dims as *mut c_void;
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(slf: &PyCell<Self>, view: *mut ffi::Py_buffer) {
+        drop(Box::from_raw((*view).internal as *mut [isize; 2]));
+        // Can't raise here; if the track is in use the count stays up and the columns
+        // stay fixed, which is safe
+        let array = slf.borrow();
+        if let Ok(mut owner) = array.owner.try_borrow_mut(slf.py()) {
+            owner.exports.0 -= 1;
+        };
+    }
+
+    fn __len__(&self, py: Python<'_>) -> usize {
+        let owner = self.owner.borrow(py);
+        match self.column {
+            Column::Pitch => owner.pitch.len(),
+            Column::Start => owner.start.len(),
+            Column::Duration => owner.duration.len(),
+            Column::Velocity => owner.velocity.len(),
+        }
+    }
+
+    fn __repr__(&self) -> String { format!("NoteArray({:?})", self.column) }
+}
+
+/// Frame-level labels implementing the buffer protocol as a read-only uint8
+/// `(frames, pitches)` array, so `numpy.asarray(onset)` wraps it without a copy.
+const UINT8_FORMAT: &[u8] = b"B\0";
+
+#[pyclass]
+pub struct FrameArray {
+    data: Vec<u8>,
+    /// `(frames, pitches)`.
+    #[pyo3(get)]
+    shape: (usize, usize),
+}
+
+impl FrameArray {
+    pub fn new(data: Vec<u8>, frames: usize, pitches: usize) -> Self {
+        FrameArray { data, shape: (frames, pitches) }
+    }
+}
+
+#[pymethods]
+impl FrameArray {
+    unsafe fn __getbuffer__(slf: &PyCell<Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Frame arrays are read-only"));
+        }
+        let array = slf.borrow();
+        let (frames, pitches) = array.shape;
+        // shape then strides, live until __releasebuffer__
+        let dims = Box::into_raw(Box::new([frames as isize, pitches as isize, pitches as isize, 1]));
+        (*view).obj = ffi::_Py_NewRef(slf.as_ptr());
+        (*view).buf = array.data.as_ptr() as *mut c_void;
+        (*view).len = array.data.len() as isize;
+        (*view).readonly = 1;
+        (*view).itemsize = 1;
+        (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+            UINT8_FORMAT.as_ptr() as *mut c_char
+        } else {
+            ptr::null_mut()
+        };
+        (*view).ndim = 2;
+        (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+            &mut (*dims)[0]
+        } else {
+            ptr::null_mut()
+        };
+        (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+            &mut (*dims)[2]
+        } else {
+            ptr::null_mut()
+        };
+        (*view).suboffsets = ptr::null_mut();
+        (*view).internal = dims as *mut c_void;
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(_slf: &PyCell<Self>, view: *mut ffi::Py_buffer) {
+        drop(Box::from_raw((*view).internal as *mut [isize; 4]));
+    }
+
+    /// Number of frames.
+    fn __len__(&self) -> usize { self.shape.0 }
+
+    fn __repr__(&self) -> String {
+        format!("FrameArray(frames={}, pitches={})", self.shape.0, self.shape.1)
+    }
+}
+
+/// Interleaved stereo audio implementing the buffer protocol as a read-only float32
+/// `(frames, 2)` array, so `numpy.asarray(seq.synthesize(...))` wraps it without a copy.
+#[cfg(feature = "synth")]
+const FLOAT32_FORMAT: &[u8] = b"f\0";
+
+#[cfg(feature = "synth")]
+#[pyclass]
+pub struct Samples {
+    data: Vec<f32>,
+    #[pyo3(get)]
+    sample_rate: u32,
+}
+
+#[cfg(feature = "synth")]
+impl Samples {
+    pub fn new(data: Vec<f32>, sample_rate: u32) -> Self {
+        Samples { data, sample_rate }
+    }
+}
+
+#[cfg(feature = "synth")]
+#[pymethods]
+impl Samples {
+    unsafe fn __getbuffer__(slf: &PyCell<Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Samples are read-only"));
+        }
+        let samples = slf.borrow();
+        // shape then strides, live until __releasebuffer__
+        let dims = Box::into_raw(Box::new([(samples.data.len() / 2) as isize, 2, 8, 4]));
+        (*view).obj = ffi::_Py_NewRef(slf.as_ptr());
+        (*view).buf = samples.data.as_ptr() as *mut c_void;
+        (*view).len = (samples.data.len() * 4) as isize;
+        (*view).readonly = 1;
+        (*view).itemsize = 4;
+        (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+            FLOAT32_FORMAT.as_ptr() as *mut c_char
+        } else {
+            ptr::null_mut()
+        };
+        (*view).ndim = 2;
+        (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+            &mut (*dims)[0]
+        } else {
+            ptr::null_mut()
+        };
+        (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+            &mut (*dims)[2]
+        } else {
+            ptr::null_mut()
+        };
+        (*view).suboffsets = ptr::null_mut();
+        (*view).internal = dims as *mut c_void;
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(_slf: &PyCell<Self>, view: *mut ffi::Py_buffer) {
+        drop(Box::from_raw((*view).internal as *mut [isize; 4]));
+    }
+
+    /// Number of frames.
+    fn __len__(&self) -> usize { self.data.len() / 2 }
+
+    fn __repr__(&self) -> String {
+        format!("Samples(frames={}, sample_rate={})", self.data.len() / 2, self.sample_rate)
+    }
+}
@@ -0,0 +1,241 @@
+use crate::sequence::Sequence;
+
+pub(crate) const PITCH_NAMES: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+/// Chord templates as (suffix, intervals above the root).
+const TEMPLATES: [(&str, &[u8]); 8] = [
+    ("maj", &[0, 4, 7]),
+    ("min", &[0, 3, 7]),
+    ("dim", &[0, 3, 6]),
+    ("aug", &[0, 4, 8]),
+    ("sus4", &[0, 5, 7]),
+    ("7", &[0, 4, 7, 10]),
+    ("maj7", &[0, 4, 7, 11]),
+    ("min7", &[0, 3, 7, 10]),
+];
+
+pub const NO_CHORD: &str = "N";
+
+/// Role of a note against the chord sounding at its onset.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneRole {
+    /// No chord was detected, or the note is on a drum track.
+    NoChord = 0,
+    ChordTone = 1,
+    /// Non-chord tone approached and left by step in the same direction.
+    Passing = 2,
+    /// Non-chord tone approached and left by step in opposite directions.
+    Neighbor = 3,
+    /// Any other non-chord tone.
+    Other = 4,
+}
+
+impl ToneRole {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToneRole::NoChord => "none",
+            ToneRole::ChordTone => "chord",
+            ToneRole::Passing => "passing",
+            ToneRole::Neighbor => "neighbor",
+            ToneRole::Other => "other",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Chord {
+    pub root: u8,
+    template: usize,
+}
+
+impl Chord {
+    pub fn quality(&self) -> &'static str { TEMPLATES[self.template].0 }
+
+    pub fn intervals(&self) -> &'static [u8] { TEMPLATES[self.template].1 }
+
+    /// Pitch classes of the chord tones.
+    pub fn pitch_classes(&self) -> Vec<u8> {
+        self.intervals().iter().map(|i| (self.root + i) % 12).collect()
+    }
+
+    pub fn contains(&self, pitch: u8) -> bool {
+        self.intervals().contains(&((pitch % 12 + 12 - self.root) % 12))
+    }
+
+    /// MIREX style label such as `A:min`.
+    pub fn label(&self) -> String {
+        format!("{}:{}", PITCH_NAMES[self.root as usize], self.quality())
+    }
+
+    /// Best matching template for a pitch class weight vector.
+    pub fn from_weights(weights: &[f32; 12]) -> Option<Chord> {
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut best: Option<(f32, Chord)> = None;
+        for root in 0..12_u8 {
+            for (template, (_, intervals)) in TEMPLATES.iter().enumerate() {
+                let covered: f32 = intervals.iter()
+                    .map(|i| weights[((root + i) % 12) as usize])
+                    .sum();
+                // Reward covered weight, penalize weight outside the template and
+                // template tones that are missing, preferring simpler chords on ties.
+                let missing = intervals.iter()
+                    .filter(|&&i| weights[((root + i) % 12) as usize] == 0.0)
+                    .count() as f32;
+                let score = 2.0 * covered - total - missing * total / intervals.len() as f32
+                    + weights[root as usize] * 0.1;
+                if best.is_none_or(|(s, _)| score > s) {
+                    best = Some((score, Chord { root, template }));
+                }
+            }
+        }
+        best.map(|(_, chord)| chord)
+    }
+}
+
+impl Sequence {
+    /// Pitch class weights (overlap duration times velocity) of non-drum notes in `[start, end)`.
+    pub fn pitch_class_weights(&self, start: f32, end: f32) -> [f32; 12] {
+        let mut weights = [0_f32; 12];
+        for track in self.tracks.iter().filter(|t| !t.is_drum) {
+            for note in &track.notes {
+                let overlap = (note.start + note.duration).min(end) - note.start.max(start);
+                if overlap > 0.0 {
+                    weights[(note.pitch % 12) as usize] += overlap * note.velocity as f32;
+                }
+            }
+        }
+        weights
+    }
+
+    /// Chords over consecutive windows of `window` quarters as `(start, end, chord)`,
+    /// with identical neighbouring windows merged.
+    pub fn chord_segments(&self, window: f32) -> Vec<(f32, f32, Option<Chord>)> {
+        assert!(window > 0.0 && window.is_finite(), "Window must be positive, got {}", window);
+        let end_time = self.tracks.iter()
+            .flat_map(|t| t.notes.iter())
+            .map(|n| n.start + n.duration)
+            .fold(0_f32, f32::max);
+        let mut segments: Vec<(f32, f32, Option<Chord>)> = Vec::new();
+        let mut idx = 0;
+        while (idx as f32) * window < end_time {
+            let start = idx as f32 * window;
+            let end = start + window;
+            let chord = Chord::from_weights(&self.pitch_class_weights(start, end));
+            match segments.last_mut() {
+                Some(last) if last.2 == chord => last.1 = end,
+                _ => segments.push((start, end, chord)),
+            }
+            idx += 1;
+        }
+        segments
+    }
+
+    /// Role of every note against the chords detected over `window` quarter windows, one
+    /// list per track in note order. Steps are judged against the previous and next
+    /// onsets of the same track.
+    pub fn tone_roles(&self, window: f32) -> Vec<Vec<ToneRole>> {
+        let segments = self.chord_segments(window);
+        let chord_at = |time: f32| {
+            let idx = segments.partition_point(|s| s.0 <= time);
+            segments.get(idx.max(1) - 1).and_then(|s| s.2)
+        };
+        self.tracks.iter().map(|track| {
+            let mut roles = vec![ToneRole::NoChord; track.notes.len()];
+            if track.is_drum {
+                return roles;
+            }
+            let mut order: Vec<usize> = (0..track.notes.len()).collect();
+            order.sort_by(|&a, &b| track.notes[a].start.partial_cmp(&track.notes[b].start).unwrap());
+            for (pos, &idx) in order.iter().enumerate() {
+                let note = &track.notes[idx];
+                let Some(chord) = chord_at(note.start) else { continue };
+                roles[idx] = if chord.contains(note.pitch) {
+                    ToneRole::ChordTone
+                } else {
+                    let step = |from: usize, to: usize| track.notes[to].pitch as i32 - track.notes[from].pitch as i32;
+                    let is_step = |d: i32| (1..=2).contains(&d.abs());
+                    match (pos.checked_sub(1).map(|p| order[p]), order.get(pos + 1)) {
+                        (Some(prev), Some(&next)) => {
+                            let (into, out) = (step(prev, idx), step(idx, next));
+                            if !is_step(into) || !is_step(out) {
+                                ToneRole::Other
+                            } else if into.signum() == out.signum() {
+                                ToneRole::Passing
+                            } else {
+                                ToneRole::Neighbor
+                            }
+                        }
+                        _ => ToneRole::Other,
+                    }
+                };
+            }
+            roles
+        }).collect()
+    }
+
+    /// Per note feature rows `[pitch, start, duration, velocity, tone role]`, one matrix
+    /// per track, with the role coded as in `ToneRole`.
+    pub fn note_features(&self, window: f32) -> Vec<Vec<[f32; 5]>> {
+        self.tracks.iter().zip(self.tone_roles(window)).map(|(track, roles)| {
+            track.notes.iter().zip(roles).map(|(n, role)| {
+                [n.pitch as f32, n.start, n.duration, n.velocity as f32, role as u8 as f32]
+            }).collect()
+        }).collect()
+    }
+
+    /// Detected chord changes as `(time, label)`; `N` marks windows without pitched notes.
+    pub fn detect_chords(&self, window: f32) -> Vec<(f32, String)> {
+        self.chord_segments(window)
+            .into_iter()
+            .map(|(start, _, chord)| (start, chord.map_or(NO_CHORD.to_string(), |c| c.label())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{Note, Track};
+
+    #[test]
+    fn test_detect_chords() {
+        let chord = |pitches: &[u8], start: f32| -> Vec<Note> {
+            pitches.iter().map(|&pitch| Note { pitch, start, duration: 2.0, velocity: 80, tie: false }).collect()
+        };
+        let mut notes = chord(&[60, 64, 67], 0.0);
+        notes.extend(chord(&[57, 60, 64], 2.0));
+        notes.extend(chord(&[55, 59, 62, 65], 4.0));
+        let seq = Sequence {
+            tracks: vec![Track { notes, ..Track::default() }],
+            ..Sequence::default()
+        };
+        let labels: Vec<String> = seq.detect_chords(1.0).into_iter().map(|c| c.1).collect();
+        assert_eq!(labels, vec!["C:maj", "A:min", "G:7"]);
+    }
+
+    #[test]
+    fn test_tone_roles() {
+        let note = |pitch: u8, start: f32, duration: f32| Note { pitch, start, duration, velocity: 80, tie: false };
+        let seq = Sequence {
+            tracks: vec![
+                Track { notes: vec![note(48, 0.0, 4.0), note(52, 0.0, 4.0), note(55, 0.0, 4.0)], ..Track::default() },
+                Track {
+                    notes: vec![note(64, 0.0, 1.0), note(65, 1.0, 0.5), note(67, 1.5, 0.5), note(65, 2.0, 0.5),
+                                note(67, 2.5, 1.0), note(61, 3.5, 0.5)],
+                    ..Track::default()
+                },
+            ],
+            ..Sequence::default()
+        };
+        let roles = seq.tone_roles(4.0);
+        assert!(roles[0].iter().all(|&r| r == ToneRole::ChordTone));
+        assert_eq!(roles[1], vec![
+            ToneRole::ChordTone, ToneRole::Passing, ToneRole::ChordTone, ToneRole::Neighbor,
+            ToneRole::ChordTone, ToneRole::Other,
+        ]);
+        assert_eq!(seq.note_features(4.0)[1][1], [65.0, 1.0, 0.5, 80.0, 2.0]);
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use crate::automation::FineControlChange;
+use crate::sequence::{ControlChange, KeySignature, Note, Sequence, SysEx, Tempo, TimeSignature, Track};
+use crate::text::TextEvent;
+
+/// Default tolerance of `approx_eq` from Python, in quarters, well below a tick.
+pub const DEFAULT_TOLERANCE: f32 = 1e-4;
+
+/// Equality of musical content with float times and tempi allowed to differ by
+/// `tolerance`. Everything else, pitches, velocities and counts included, must match.
+/// The parse warnings of a `Sequence` are not content and are left out.
+pub trait ApproxEq {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool;
+}
+
+fn close(a: f32, b: f32, tolerance: f32) -> bool {
+    (a - b).abs() <= tolerance
+}
+
+fn all_close<T: ApproxEq>(a: &[T], b: &[T], tolerance: f32) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.approx_eq(b, tolerance))
+}
+
+fn map_close<T: ApproxEq>(a: &HashMap<u8, Vec<T>>, b: &HashMap<u8, Vec<T>>, tolerance: f32) -> bool {
+    a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| all_close(a, b, tolerance)))
+}
+
+impl ApproxEq for Note {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.pitch == other.pitch && self.velocity == other.velocity && self.tie == other.tie
+            && close(self.start, other.start, tolerance) && close(self.duration, other.duration, tolerance)
+    }
+}
+
+impl ApproxEq for ControlChange {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.value == other.value && close(self.time, other.time, tolerance)
+    }
+}
+
+impl ApproxEq for FineControlChange {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.value == other.value && close(self.time, other.time, tolerance)
+    }
+}
+
+impl ApproxEq for SysEx {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.data == other.data && self.escape == other.escape && close(self.time, other.time, tolerance)
+    }
+}
+
+impl ApproxEq for Tempo {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        close(self.qpm, other.qpm, tolerance) && close(self.time, other.time, tolerance)
+    }
+}
+
+impl ApproxEq for TimeSignature {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        (self.numerator, self.denominator) == (other.numerator, other.denominator)
+            && (self.clocks_per_click, self.thirty_seconds_per_quarter)
+                == (other.clocks_per_click, other.thirty_seconds_per_quarter)
+            && close(self.time, other.time, tolerance)
+    }
+}
+
+impl ApproxEq for KeySignature {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.key == other.key && close(self.time, other.time, tolerance)
+    }
+}
+
+impl ApproxEq for TextEvent {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.kind == other.kind && self.text == other.text && close(self.time, other.time, tolerance)
+    }
+}
+
+impl ApproxEq for Track {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.name == other.name && self.instrument() == other.instrument() && self.is_drum == other.is_drum
+            && self.channel == other.channel && self.midi_track_index == other.midi_track_index
+            && (self.port, self.channel_prefix) == (other.port, other.channel_prefix)
+            && all_close(&self.notes, &other.notes, tolerance)
+            && map_close(&self.controls, &other.controls, tolerance)
+            && map_close(&self.fine_controls, &other.fine_controls, tolerance)
+            && all_close(&self.channel_pressure, &other.channel_pressure, tolerance)
+            && map_close(&self.poly_pressure, &other.poly_pressure, tolerance)
+            && all_close(&self.sysex, &other.sysex, tolerance)
+    }
+}
+
+impl ApproxEq for Sequence {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        all_close(&self.tracks, &other.tracks, tolerance)
+            && all_close(&self.time_signatures, &other.time_signatures, tolerance)
+            && all_close(&self.key_signatures, &other.key_signatures, tolerance)
+            && all_close(&self.qpm, &other.qpm, tolerance)
+            && all_close(&self.texts, &other.texts, tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approx_eq() {
+        let note = Note { pitch: 60, start: 1.0, duration: 0.5, velocity: 90, tie: false };
+        let nudged = Note { start: 1.00005, ..note };
+        assert_ne!(note, nudged);
+        assert!(note.approx_eq(&nudged, DEFAULT_TOLERANCE));
+        assert!(!note.approx_eq(&Note { velocity: 91, ..note }, DEFAULT_TOLERANCE));
+
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        let mut other = seq.clone();
+        other.warnings = Default::default();
+        assert!(seq.approx_eq(&other, 0.0));
+        other.tracks[0].notes[3].start += 0.001;
+        assert!(!seq.approx_eq(&other, DEFAULT_TOLERANCE));
+        assert!(seq.approx_eq(&other, 0.01));
+    }
+}
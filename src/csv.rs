@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use crate::sequence::{ControlChange, Note, Sequence, Tempo, Track};
+
+const NOTE_HEADER: &str = "track,channel,program,pitch,start,duration,velocity,is_drum";
+const TEMPO_HEADER: &str = "time,qpm";
+const CONTROL_HEADER: &str = "track,channel,number,time,value";
+/// Track indices go up to the most tracks a MIDI file can hold.
+const MAX_TRACK: usize = u16::MAX as usize - 1;
+
+/// Rows of a CSV text with a header line, each as a lookup by column name.
+fn rows(text: &str) -> Result<Vec<HashMap<&str, &str>>, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<&str> = lines.next().ok_or("CSV has no header")?.split(',').map(str::trim).collect();
+    lines.enumerate().map(|(idx, line)| {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != header.len() {
+            return Err(format!("CSV row {} has {} fields, the header {}", idx + 1, fields.len(), header.len()));
+        }
+        Ok(header.iter().copied().zip(fields).collect())
+    }).collect()
+}
+
+/// Column `name` of `row`, `None` if the column or the value is missing.
+fn field<T: std::str::FromStr>(row: &HashMap<&str, &str>, name: &str) -> Result<Option<T>, String> {
+    match row.get(name).filter(|v| !v.is_empty()) {
+        Some(value) => value.parse().map(Some).map_err(|_| format!("Invalid {} {:?} in CSV", name, value)),
+        None => Ok(None),
+    }
+}
+
+fn required<T: std::str::FromStr>(row: &HashMap<&str, &str>, name: &str) -> Result<T, String> {
+    field(row, name)?.ok_or_else(|| format!("CSV row without {}", name))
+}
+
+/// The `track` column, at most `MAX_TRACK`.
+fn track_index(row: &HashMap<&str, &str>) -> Result<Option<usize>, String> {
+    match field::<usize>(row, "track")? {
+        Some(idx) if idx > MAX_TRACK => Err(format!("CSV track {} is beyond the {} tracks of a MIDI file", idx, MAX_TRACK + 1)),
+        idx => Ok(idx),
+    }
+}
+
+impl Sequence {
+    /// One row per note with its track index, channel, program and drum flag; times in
+    /// quarters. The channel is empty for tracks without one.
+    pub fn notes_csv(&self) -> String {
+        let mut csv = format!("{}\n", NOTE_HEADER);
+        for (idx, track) in self.tracks.iter().enumerate() {
+            let channel = track.channel.map(|c| c.to_string()).unwrap_or_default();
+            for note in &track.notes {
+                writeln!(csv, "{},{},{},{},{},{},{},{}", idx, channel, track.program, note.pitch,
+                         note.start, note.duration, note.velocity, track.is_drum).unwrap();
+            }
+        }
+        csv
+    }
+
+    /// One row per tempo change, times in quarters.
+    pub fn tempo_csv(&self) -> String {
+        let mut csv = format!("{}\n", TEMPO_HEADER);
+        self.qpm.iter().for_each(|t| writeln!(csv, "{},{}", t.time, t.qpm).unwrap());
+        csv
+    }
+
+    /// One row per control change, ordered by track, controller number and time as in
+    /// `control_table`.
+    pub fn controls_csv(&self) -> String {
+        let table = self.control_table();
+        let mut csv = format!("{}\n", CONTROL_HEADER);
+        for i in 0..table.track.len() {
+            let channel = self.tracks[table.track[i] as usize].channel.map(|c| c.to_string()).unwrap_or_default();
+            writeln!(csv, "{},{},{},{},{}", table.track[i], channel, table.number[i], table.time[i], table.value[i]).unwrap();
+        }
+        csv
+    }
+
+    pub fn to_csv(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.notes_csv()).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn tempo_to_csv(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.tempo_csv()).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn controls_to_csv(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.controls_csv()).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Sequence from `notes_csv` text and optionally `tempo_csv` text, 120 QPM without
+    /// it. Columns may come in any order; only pitch, start, duration and velocity are
+    /// required, notes without a track go to track 0, and a track is a drum track if
+    /// `is_drum` says so or it is on channel 9. Notes are sorted by start.
+    pub fn from_csv_str(notes: &str, tempo: Option<&str>) -> Result<Sequence, String> {
+        let mut tracks: Vec<Track> = Vec::new();
+        for row in rows(notes)? {
+            let idx = track_index(&row)?.unwrap_or(0);
+            if tracks.len() <= idx {
+                tracks.resize_with(idx + 1, Track::default);
+            }
+            let track = &mut tracks[idx];
+            if track.notes.is_empty() {
+                track.channel = field(&row, "channel")?;
+                track.program = field(&row, "program")?.unwrap_or(0);
+                track.is_drum = field(&row, "is_drum")?.unwrap_or(false) || track.channel == Some(9);
+            }
+            track.notes.push(Note {
+                pitch: required(&row, "pitch")?,
+                start: required(&row, "start")?,
+                duration: required(&row, "duration")?,
+                velocity: required(&row, "velocity")?,
+                tie: false,
+            });
+        }
+        for track in &mut tracks {
+            track.notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+        }
+        let mut qpm = match tempo {
+            Some(tempo) => rows(tempo)?.iter()
+                .map(|row| Ok(Tempo { time: required(row, "time")?, qpm: required(row, "qpm")? }))
+                .collect::<Result<Vec<_>, String>>()?,
+            None => Vec::new(),
+        };
+        if qpm.first().is_none_or(|t| t.time > 0.0) {
+            qpm.insert(0, Tempo { time: 0.0, qpm: 120.0 });
+        }
+        Ok(Sequence { tracks, qpm, ..Sequence::default() })
+    }
+
+    /// `from_csv_str` on files, then `add_controls_csv` if a controls file is given.
+    pub fn from_csv(path: &str, tempo_path: Option<&str>, controls_path: Option<&str>) -> Result<Sequence, String> {
+        let read = |path: &str| fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e));
+        let tempo = tempo_path.map(read).transpose()?;
+        let mut seq = Self::from_csv_str(&read(path)?, tempo.as_deref())?;
+        if let Some(controls) = controls_path {
+            seq.add_controls_csv(&read(controls)?)?;
+        }
+        Ok(seq)
+    }
+
+    /// Add the control changes of `controls_csv` text to the tracks they name, keeping
+    /// each controller sorted by time.
+    pub fn add_controls_csv(&mut self, controls: &str) -> Result<(), String> {
+        let mut touched = Vec::new();
+        for row in rows(controls)? {
+            let idx = track_index(&row)?.ok_or("CSV row without track")?;
+            let track = self.tracks.get_mut(idx).ok_or_else(|| format!("CSV control for missing track {}", idx))?;
+            let number = required(&row, "number")?;
+            track.controls.entry(number).or_default().push(ControlChange {
+                time: required(&row, "time")?,
+                value: required(&row, "value")?,
+            });
+            touched.push((idx, number));
+        }
+        touched.sort_unstable();
+        touched.dedup();
+        for (idx, number) in touched {
+            if let Some(controls) = self.tracks[idx].controls.get_mut(&number) {
+                controls.sort_by(|a, b| a.time.total_cmp(&b.time));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_round_trip() {
+        let mut seq = Sequence::from_tracks(vec![
+            Track {
+                program: 40,
+                channel: Some(2),
+                notes: vec![Note { pitch: 67, start: 0.5, duration: 1.25, velocity: 80, tie: false }],
+                ..Track::default()
+            },
+            Track {
+                is_drum: true,
+                notes: vec![Note { pitch: 36, start: 0.0, duration: 0.25, velocity: 100, tie: false }],
+                ..Track::default()
+            },
+        ], 90.0);
+        seq.tracks[0].controls.insert(7, vec![ControlChange { time: 1.0, value: 64 }]);
+        assert_eq!(seq.notes_csv(), format!("{}\n0,2,40,67,0.5,1.25,80,false\n1,,0,36,0,0.25,100,true\n", NOTE_HEADER));
+        assert_eq!(seq.controls_csv(), format!("{}\n0,2,7,1,64\n", CONTROL_HEADER));
+
+        let mut back = Sequence::from_csv_str(&seq.notes_csv(), Some(&seq.tempo_csv())).unwrap();
+        back.add_controls_csv(&seq.controls_csv()).unwrap();
+        assert_eq!(back, seq);
+    }
+
+    #[test]
+    fn test_from_csv_str() {
+        let seq = Sequence::from_csv_str("pitch,start,duration,velocity,channel,track\n38,1,0.5,90,9,1\n", None).unwrap();
+        assert_eq!(seq.tracks.len(), 2);
+        assert!(seq.tracks[1].is_drum);
+        assert_eq!(seq.qpm, vec![Tempo { time: 0.0, qpm: 120.0 }]);
+        assert!(Sequence::from_csv_str("pitch,start\n60,x\n", None).is_err());
+        assert!(Sequence::from_csv_str("pitch,start,duration\n60,0,1\n", None).is_err());
+        assert!(Sequence::from_csv_str("track,pitch,start,duration,velocity\n4000000000,60,0,1,90\n", None).is_err());
+
+        let mut seq = Sequence::from_csv_str("pitch,start,duration,velocity\n62,1,1,90\n60,0,1,90\n", None).unwrap();
+        assert_eq!(seq.tracks[0].notes[0].pitch, 60);
+        seq.add_controls_csv("track,number,time,value\n0,7,2,10\n0,7,1,20\n").unwrap();
+        assert_eq!(seq.tracks[0].controls[&7].iter().map(|c| c.value).collect::<Vec<_>>(), vec![20, 10]);
+        assert!(seq.add_controls_csv("track,number,time,value\n1,7,0,0\n").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::compare::ApproxEq;
+use crate::sequence::{Note, Sequence};
+
+/// Differences between two sequences, as `Sequence::diff` of `self` against `other`.
+/// Notes carry the index of their track; meta events are `(time, description)`.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SequenceDiff {
+    /// Notes only in `other`.
+    pub added_notes: Vec<(usize, Note)>,
+    /// Notes only in `self`.
+    pub removed_notes: Vec<(usize, Note)>,
+    /// Notes at the same pitch and onset whose duration, velocity or tie differ, as
+    /// `(track, old, new)`.
+    pub changed_notes: Vec<(usize, Note, Note)>,
+    pub added_meta: Vec<(f32, String)>,
+    pub removed_meta: Vec<(f32, String)>,
+}
+
+impl SequenceDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == SequenceDiff::default()
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SequenceDiff {
+    #[pyo3(name="is_empty")]
+    fn py_is_empty(&self) -> bool { self.is_empty() }
+
+    fn __bool__(&self) -> bool { !self.is_empty() }
+
+    fn __repr__(&self) -> String {
+        format!("SequenceDiff(+{} -{} ~{} notes, +{} -{} meta)", self.added_notes.len(),
+                self.removed_notes.len(), self.changed_notes.len(), self.added_meta.len(), self.removed_meta.len())
+    }
+}
+
+/// Tempo, signature and text events of a sequence, described for comparison.
+fn meta_events(seq: &Sequence) -> Vec<(f32, String)> {
+    let tempi = seq.qpm.iter().map(|t| (t.time, format!("tempo {:.2} qpm", t.qpm)));
+    let time_signatures = seq.time_signatures.iter()
+        .map(|t| (t.time, format!("time signature {}/{}", t.numerator, t.denominator)));
+    let key_signatures = seq.key_signatures.iter().map(|k| {
+        (k.time, format!("key signature {:+} {}", k.key.1, if k.key.0 { "major" } else { "minor" }))
+    });
+    let texts = seq.texts.iter().map(|t| (t.time, format!("{} {:?}", t.kind.name(), t.text)));
+    tempi.chain(time_signatures).chain(key_signatures).chain(texts).collect()
+}
+
+/// Pair up notes of the same pitch whose onsets are within `tolerance`, nearest first in
+/// time order. Returns for every note of `a` the index of its partner in `b`.
+fn pair_notes(a: &[Note], b: &[Note], tolerance: f32) -> Vec<Option<usize>> {
+    let mut by_pitch = HashMap::<u8, Vec<(f32, usize)>>::new();
+    for (idx, note) in b.iter().enumerate() {
+        by_pitch.entry(note.pitch).or_default().push((note.start, idx));
+    }
+    by_pitch.values_mut().for_each(|starts| starts.sort_by(|x, y| x.0.total_cmp(&y.0)));
+    let mut taken = vec![false; b.len()];
+    a.iter().map(|note| {
+        let starts = by_pitch.get(&note.pitch)?;
+        let first = starts.partition_point(|&(start, _)| start < note.start - tolerance);
+        let (_, idx) = starts[first..].iter()
+            .take_while(|&&(start, _)| start <= note.start + tolerance)
+            .find(|&&(_, idx)| !taken[idx])?;
+        taken[*idx] = true;
+        Some(*idx)
+    }).collect()
+}
+
+impl Sequence {
+    /// What changed from `self` to `other`, with times compared up to `tolerance`
+    /// quarters. Tracks are compared by position.
+    pub fn diff(&self, other: &Sequence, tolerance: f32) -> SequenceDiff {
+        let mut diff = SequenceDiff::default();
+        let empty = Vec::new();
+        for idx in 0..self.tracks.len().max(other.tracks.len()) {
+            let old = self.tracks.get(idx).map_or(&empty, |t| &t.notes);
+            let new = other.tracks.get(idx).map_or(&empty, |t| &t.notes);
+            let pairs = pair_notes(old, new, tolerance);
+            let mut matched = vec![false; new.len()];
+            for (note, pair) in old.iter().zip(pairs) {
+                match pair {
+                    Some(j) => {
+                        matched[j] = true;
+                        if !note.approx_eq(&new[j], tolerance) {
+                            diff.changed_notes.push((idx, *note, new[j]));
+                        }
+                    }
+                    None => diff.removed_notes.push((idx, *note)),
+                }
+            }
+            diff.added_notes.extend(new.iter().zip(matched).filter(|(_, m)| !m).map(|(n, _)| (idx, *n)));
+        }
+
+        let old = meta_events(self);
+        let mut new: Vec<Option<(f32, String)>> = meta_events(other).into_iter().map(Some).collect();
+        for (time, description) in old {
+            let partner = new.iter_mut().find(|event| {
+                event.as_ref().is_some_and(|(t, d)| *d == description && (t - time).abs() <= tolerance)
+            });
+            match partner {
+                Some(event) => *event = None,
+                None => diff.removed_meta.push((time, description)),
+            }
+        }
+        diff.added_meta.extend(new.into_iter().flatten());
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::DEFAULT_TOLERANCE;
+    use crate::sequence::Tempo;
+
+    #[test]
+    fn test_diff() {
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        assert!(seq.diff(&seq.clone(), DEFAULT_TOLERANCE).is_empty());
+
+        let mut other = seq.clone();
+        let removed = other.tracks[0].notes.remove(0);
+        other.tracks[0].notes[0].velocity = 1;
+        other.tracks[0].notes[1].start += 0.00001;
+        let added = Note { pitch: 30, start: 2.0, duration: 1.0, velocity: 80, tie: false };
+        other.tracks[0].notes.push(added);
+        other.qpm.push(Tempo { time: 4.0, qpm: 90.0 });
+
+        let diff = seq.diff(&other, DEFAULT_TOLERANCE);
+        assert_eq!(diff.removed_notes, vec![(0, removed)]);
+        assert_eq!(diff.added_notes, vec![(0, added)]);
+        assert_eq!(diff.changed_notes.len(), 1);
+        assert_eq!(diff.changed_notes[0].2.velocity, 1);
+        assert_eq!(diff.added_meta, vec![(4.0, "tempo 90.00 qpm".to_string())]);
+        assert!(diff.removed_meta.is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+#[cfg(feature = "python")]
+use pyo3::exceptions::PyIOError;
+#[cfg(feature = "python")]
+use pyo3::{create_exception, PyErr, Python};
+
+/// Where in a file a problem was found, as far as it is known.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    /// Index of the MTrk chunk.
+    pub track: Option<usize>,
+    /// Absolute tick within the track.
+    pub tick: Option<u32>,
+    /// Byte offset from the start of the SMF data, after unwrapping gzip or RMID.
+    pub offset: Option<usize>,
+}
+
+impl Location {
+    pub fn is_empty(&self) -> bool {
+        *self == Location::default()
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = [
+            self.track.map(|track| format!("track {}", track)),
+            self.tick.map(|tick| format!("tick {}", tick)),
+            self.offset.map(|offset| format!("byte {}", offset)),
+        ].into_iter().flatten().collect();
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Why a MIDI file could not be read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MidiError {
+    /// The file or archive member could not be read.
+    Io(String),
+    /// Not a MIDI file, or a broken header or container.
+    Header(String, Location),
+    /// Broken chunk structure of a track.
+    Track(String, Location),
+    /// A message inside a track could not be decoded.
+    Event(String, Location),
+    /// Valid MIDI this library does not handle, such as SMPTE timing.
+    Unsupported(String, Location),
+}
+
+impl MidiError {
+    /// The message without its location.
+    pub fn message(&self) -> &str {
+        match self {
+            MidiError::Io(message) | MidiError::Header(message, _) | MidiError::Track(message, _)
+            | MidiError::Event(message, _) | MidiError::Unsupported(message, _) => message,
+        }
+    }
+
+    pub fn location(&self) -> Location {
+        match self {
+            MidiError::Io(_) => Location::default(),
+            MidiError::Header(_, location) | MidiError::Track(_, location)
+            | MidiError::Event(_, location) | MidiError::Unsupported(_, location) => *location,
+        }
+    }
+}
+
+impl fmt::Display for MidiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = self.location();
+        if location.is_empty() {
+            f.write_str(self.message())
+        } else {
+            write!(f, "{} ({})", self.message(), location)
+        }
+    }
+}
+
+impl std::error::Error for MidiError {}
+
+/// Python exception classes, `MidiError` being the base of the others.
+#[cfg(feature = "python")]
+// create_exception! of pyo3 0.18 checks a cfg of its own crate
+#[allow(unexpected_cfgs)]
+pub mod exceptions {
+    use super::*;
+
+    create_exception!(midiparse_core, MidiError, PyIOError, "Base class of MIDI parsing errors; an `IOError`, so existing handlers still catch them.");
+    create_exception!(midiparse_core, MidiHeaderError, MidiError, "Not a MIDI file, or a broken header.");
+    create_exception!(midiparse_core, MidiTrackError, MidiError, "Broken chunk structure of a track.");
+    create_exception!(midiparse_core, MidiEventError, MidiError, "A message could not be decoded.");
+    create_exception!(midiparse_core, UnsupportedFeatureError, MidiError, "Valid MIDI that is not supported.");
+}
+
+/// The location is also set as `track`, `tick` and `offset` attributes of the exception.
+#[cfg(feature = "python")]
+impl From<MidiError> for PyErr {
+    fn from(error: MidiError) -> PyErr {
+        let message = error.to_string();
+        let location = error.location();
+        let err = match error {
+            MidiError::Io(_) => PyIOError::new_err(message),
+            MidiError::Header(..) => exceptions::MidiHeaderError::new_err(message),
+            MidiError::Track(..) => exceptions::MidiTrackError::new_err(message),
+            MidiError::Event(..) => exceptions::MidiEventError::new_err(message),
+            MidiError::Unsupported(..) => exceptions::UnsupportedFeatureError::new_err(message),
+        };
+        Python::with_gil(|py| {
+            let value = err.value(py);
+            let attributes = value.setattr("track", location.track)
+                .and_then(|_| value.setattr("tick", location.tick))
+                .and_then(|_| value.setattr("offset", location.offset));
+            attributes.map(|_| err).unwrap_or_else(|e| e)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MIDIFile;
+    use crate::sequence::Sequence;
+
+    #[test]
+    fn test_error_kinds() {
+        assert!(matches!(MIDIFile::from_bytes(b"not a midi file"), Err(MidiError::Header(..))));
+        assert!(matches!(MIDIFile::from_file("tests/missing.mid"), Err(MidiError::Io(_))));
+        let mut data = std::fs::read("tests/tiny.mid").unwrap();
+        data[9] = 3; // format 3
+        assert!(matches!(MIDIFile::from_bytes(&data), Err(MidiError::Unsupported(..))));
+        data[9] = 1;
+        data[12] |= 0x80; // SMPTE division
+        assert!(matches!(Sequence::from_bytes(&data), Err(MidiError::Unsupported(..))));
+        data[12] &= 0x7F;
+        data[11] += 1; // a missing track
+        assert!(matches!(Sequence::from_bytes(&data), Err(MidiError::Track(..))));
+        data[11] -= 1;
+        data.truncate(data.len() - 7);
+        let error = Sequence::from_midi(&MIDIFile { warnings: Vec::new(), ..MIDIFile::from_bytes(&data).unwrap() });
+        let Err(MidiError::Event(_, location)) = &error else { panic!("expected an event error") };
+        assert_eq!(location.track, Some(1));
+        assert!(location.tick.is_some());
+        // Within the second MTrk chunk, whose data starts at byte 50
+        assert!(location.offset.is_some_and(|offset| (50..data.len()).contains(&offset)));
+        assert!(error.unwrap_err().to_string().contains("track 1, tick"));
+
+        // A key signature with 8 sharps, at the start of the track data
+        let mut data = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60MTrk\x00\x00\x00\x0A".to_vec();
+        data.extend_from_slice(&[0x00, 0xFF, 0x59, 0x02, 0x08, 0x00, 0x00, 0xFF, 0x2F, 0x00]);
+        let Err(MidiError::Event(_, location)) = Sequence::from_bytes(&data) else { panic!("expected an event error") };
+        assert_eq!(location, Location { track: Some(0), tick: Some(0), offset: Some(22) });
+    }
+}
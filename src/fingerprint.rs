@@ -0,0 +1,68 @@
+use crate::sequence::Sequence;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// 64-bit FNV-1a, spelled out so fingerprints stay the same across Rust versions and
+/// platforms, which `DefaultHasher` does not promise.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+impl Sequence {
+    /// Stable hash of the note content for duplicate detection in corpora. Onsets are
+    /// rounded to `steps_per_quarter`, and notes of all tracks are pooled and sorted, so
+    /// track order, durations, velocities and small timing noise don't matter. With
+    /// `transpose_invariant` pitched notes count relative to the lowest one; drums are
+    /// never transposed.
+    pub fn fingerprint(&self, steps_per_quarter: u32, transpose_invariant: bool) -> u64 {
+        let lowest = self.tracks.iter()
+            .filter(|t| !t.is_drum)
+            .flat_map(|t| t.notes.iter().map(|n| n.pitch))
+            .min()
+            .filter(|_| transpose_invariant)
+            .unwrap_or(0);
+        let mut onsets: Vec<(u32, bool, u8)> = self.tracks.iter().flat_map(|track| {
+            track.notes.iter().map(move |note| {
+                let step = (note.start * steps_per_quarter as f32).round().max(0.0) as u32;
+                let pitch = if track.is_drum { note.pitch } else { note.pitch - lowest };
+                (step, track.is_drum, pitch)
+            })
+        }).collect();
+        onsets.sort_unstable();
+        onsets.dedup();
+        fnv1a(onsets.into_iter().flat_map(|(step, is_drum, pitch)| {
+            step.to_le_bytes().into_iter().chain([is_drum as u8, pitch])
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{Note, Track};
+
+    #[test]
+    fn test_fingerprint() {
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        let print = seq.fingerprint(4, false);
+        assert_eq!(print, seq.fingerprint(4, false));
+
+        let mut noisy = seq.clone();
+        for note in &mut noisy.tracks[0].notes {
+            note.start += 0.02;
+            note.velocity = 64;
+        }
+        noisy.tracks.insert(0, Track::default());
+        assert_eq!(noisy.fingerprint(4, false), print);
+
+        let mut transposed = seq.clone();
+        transposed.tracks[0].notes.iter_mut().for_each(|n| n.pitch += 2);
+        assert_ne!(transposed.fingerprint(4, false), print);
+        assert_eq!(transposed.fingerprint(4, true), seq.fingerprint(4, true));
+
+        let mut other = seq.clone();
+        other.tracks[0].notes.push(Note { pitch: 60, start: 40.0, duration: 1.0, velocity: 80, tie: false });
+        assert_ne!(other.fingerprint(4, false), print);
+    }
+}
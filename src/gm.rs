@@ -0,0 +1,237 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::sequence::{Sequence, Track};
+
+/// Minimum voice count a GM level 1 module guarantees.
+pub const GM_POLYPHONY: usize = 24;
+/// Melodic channels available besides the percussion channel.
+pub const GM_MELODIC_CHANNELS: usize = 15;
+/// Controllers a GM level 1 module is required to respond to.
+pub const GM_CONTROLLERS: [u8; 11] = [1, 6, 7, 10, 11, 38, 64, 100, 101, 121, 123];
+/// Key range of the GM level 1 percussion map.
+pub const GM_DRUM_RANGE: (u8, u8) = (35, 81);
+
+/// General MIDI level 1 instrument names by program number.
+pub const GM_PROGRAM_NAMES: [&str; 128] = [
+    "Acoustic Grand Piano", "Bright Acoustic Piano", "Electric Grand Piano", "Honky-tonk Piano",
+    "Electric Piano 1", "Electric Piano 2", "Harpsichord", "Clavinet",
+    "Celesta", "Glockenspiel", "Music Box", "Vibraphone",
+    "Marimba", "Xylophone", "Tubular Bells", "Dulcimer",
+    "Drawbar Organ", "Percussive Organ", "Rock Organ", "Church Organ",
+    "Reed Organ", "Accordion", "Harmonica", "Tango Accordion",
+    "Acoustic Guitar (nylon)", "Acoustic Guitar (steel)", "Electric Guitar (jazz)", "Electric Guitar (clean)",
+    "Electric Guitar (muted)", "Overdriven Guitar", "Distortion Guitar", "Guitar Harmonics",
+    "Acoustic Bass", "Electric Bass (finger)", "Electric Bass (pick)", "Fretless Bass",
+    "Slap Bass 1", "Slap Bass 2", "Synth Bass 1", "Synth Bass 2",
+    "Violin", "Viola", "Cello", "Contrabass",
+    "Tremolo Strings", "Pizzicato Strings", "Orchestral Harp", "Timpani",
+    "String Ensemble 1", "String Ensemble 2", "Synth Strings 1", "Synth Strings 2",
+    "Choir Aahs", "Voice Oohs", "Synth Voice", "Orchestra Hit",
+    "Trumpet", "Trombone", "Tuba", "Muted Trumpet",
+    "French Horn", "Brass Section", "Synth Brass 1", "Synth Brass 2",
+    "Soprano Sax", "Alto Sax", "Tenor Sax", "Baritone Sax",
+    "Oboe", "English Horn", "Bassoon", "Clarinet",
+    "Piccolo", "Flute", "Recorder", "Pan Flute",
+    "Blown Bottle", "Shakuhachi", "Whistle", "Ocarina",
+    "Lead 1 (square)", "Lead 2 (sawtooth)", "Lead 3 (calliope)", "Lead 4 (chiff)",
+    "Lead 5 (charang)", "Lead 6 (voice)", "Lead 7 (fifths)", "Lead 8 (bass + lead)",
+    "Pad 1 (new age)", "Pad 2 (warm)", "Pad 3 (polysynth)", "Pad 4 (choir)",
+    "Pad 5 (bowed)", "Pad 6 (metallic)", "Pad 7 (halo)", "Pad 8 (sweep)",
+    "FX 1 (rain)", "FX 2 (soundtrack)", "FX 3 (crystal)", "FX 4 (atmosphere)",
+    "FX 5 (brightness)", "FX 6 (goblins)", "FX 7 (echoes)", "FX 8 (sci-fi)",
+    "Sitar", "Banjo", "Shamisen", "Koto",
+    "Kalimba", "Bagpipe", "Fiddle", "Shanai",
+    "Tinkle Bell", "Agogo", "Steel Drums", "Woodblock",
+    "Taiko Drum", "Melodic Tom", "Synth Drum", "Reverse Cymbal",
+    "Guitar Fret Noise", "Breath Noise", "Seashore", "Bird Tweet",
+    "Telephone Ring", "Helicopter", "Applause", "Gunshot",
+];
+
+/// General MIDI instrument families, one per eight programs.
+pub const GM_FAMILIES: [&str; 16] = [
+    "Piano", "Chromatic Percussion", "Organ", "Guitar",
+    "Bass", "Strings", "Ensemble", "Brass",
+    "Reed", "Pipe", "Synth Lead", "Synth Pad",
+    "Synth Effects", "Ethnic", "Percussive", "Sound Effects",
+];
+
+/// Names of the GM level 1 percussion map, starting at `GM_DRUM_RANGE.0`.
+pub const GM_DRUM_NAMES: [&str; 47] = [
+    "Acoustic Bass Drum", "Bass Drum 1", "Side Stick", "Acoustic Snare",
+    "Hand Clap", "Electric Snare", "Low Floor Tom", "Closed Hi-Hat",
+    "High Floor Tom", "Pedal Hi-Hat", "Low Tom", "Open Hi-Hat",
+    "Low-Mid Tom", "Hi-Mid Tom", "Crash Cymbal 1", "High Tom",
+    "Ride Cymbal 1", "Chinese Cymbal", "Ride Bell", "Tambourine",
+    "Splash Cymbal", "Cowbell", "Crash Cymbal 2", "Vibraslap",
+    "Ride Cymbal 2", "Hi Bongo", "Low Bongo", "Mute Hi Conga",
+    "Open Hi Conga", "Low Conga", "High Timbale", "Low Timbale",
+    "High Agogo", "Low Agogo", "Cabasa", "Maracas",
+    "Short Whistle", "Long Whistle", "Short Guiro", "Long Guiro",
+    "Claves", "Hi Wood Block", "Low Wood Block", "Mute Cuica",
+    "Open Cuica", "Mute Triangle", "Open Triangle",
+];
+
+/// GS drum kits by program number; programs in between select the kit below them.
+const GS_DRUM_KITS: [(u8, &str); 9] = [
+    (0, "Standard Kit"), (8, "Room Kit"), (16, "Power Kit"), (24, "Electronic Kit"), (25, "TR-808 Kit"),
+    (32, "Jazz Kit"), (40, "Brush Kit"), (48, "Orchestra Kit"), (56, "SFX Kit"),
+];
+
+/// Name of a key in the GM percussion map, `None` outside of it.
+pub fn drum_name(pitch: u8) -> Option<&'static str> {
+    pitch.checked_sub(GM_DRUM_RANGE.0).and_then(|idx| GM_DRUM_NAMES.get(idx as usize).copied())
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name="drum_name")]
+pub fn py_drum_name(pitch: u8) -> Option<&'static str> { drum_name(pitch) }
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GmViolation {
+    pub time: f32,
+    pub track: Option<usize>,
+    pub message: String,
+}
+
+impl GmViolation {
+    fn new(time: f32, track: Option<usize>, message: String) -> Self {
+        GmViolation { time, track, message }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl GmViolation {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+/// GM instrument name of `program`, or the drum kit name for drums.
+pub(crate) fn program_name(program: u8, is_drum: bool) -> &'static str {
+    if is_drum {
+        let kit = GS_DRUM_KITS.iter().rev().find(|(kit, _)| *kit <= program);
+        return kit.map_or(GS_DRUM_KITS[0].1, |(_, name)| name);
+    }
+    GM_PROGRAM_NAMES[(program & 0x7F) as usize]
+}
+
+impl Track {
+    /// GM instrument name of the program, or the drum kit name on a drum track.
+    pub fn program_name(&self) -> &'static str {
+        program_name(self.program, self.is_drum)
+    }
+
+    /// GM family of the program, "Drums" on a drum track.
+    pub fn instrument_family(&self) -> &'static str {
+        if self.is_drum { "Drums" } else { GM_FAMILIES[(self.program & 0x7F) as usize / 8] }
+    }
+}
+
+impl Sequence {
+    /// Check the sequence against General MIDI level 1 limits: voice count, channel count,
+    /// program and key ranges, percussion map and the supported controller list.
+    pub fn check_gm_compliance(&self) -> Vec<GmViolation> {
+        let mut violations = Vec::new();
+
+        let melodic = self.tracks.iter().filter(|t| !t.is_drum).count();
+        if melodic > GM_MELODIC_CHANNELS {
+            violations.push(GmViolation::new(0.0, None, format!(
+                "{} melodic tracks exceed the {} melodic channels", melodic, GM_MELODIC_CHANNELS)));
+        }
+
+        for (idx, track) in self.tracks.iter().enumerate() {
+            if track.program > 127 {
+                violations.push(GmViolation::new(0.0, Some(idx), format!(
+                    "Program {} out of range", track.program)));
+            }
+            for note in &track.notes {
+                if note.pitch > 127 || note.velocity > 127 {
+                    violations.push(GmViolation::new(note.start, Some(idx), format!(
+                        "Note pitch {} / velocity {} out of range", note.pitch, note.velocity)));
+                } else if track.is_drum && !(GM_DRUM_RANGE.0..=GM_DRUM_RANGE.1).contains(&note.pitch) {
+                    violations.push(GmViolation::new(note.start, Some(idx), format!(
+                        "Drum key {} is not in the GM percussion map", note.pitch)));
+                }
+            }
+            let mut numbers: Vec<&u8> = track.controls.keys().collect();
+            numbers.sort();
+            for &number in numbers {
+                if !GM_CONTROLLERS.contains(&number) {
+                    let time = track.controls[&number].first().map_or(0.0, |c| c.time);
+                    violations.push(GmViolation::new(time, Some(idx), format!(
+                        "Controller {} is not supported by GM level 1", number)));
+                }
+            }
+        }
+
+        // Sweep over note boundaries to find where the voice count first exceeds the limit.
+        let mut boundaries: Vec<(f32, i32)> = self.tracks.iter()
+            .flat_map(|t| t.notes.iter())
+            .flat_map(|n| [(n.start, 1), (n.start + n.duration, -1)])
+            .collect();
+        boundaries.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then(a.1.cmp(&b.1)));
+        let mut voices = 0;
+        let mut exceeded = false;
+        for (time, delta) in boundaries {
+            voices += delta;
+            if voices as usize > GM_POLYPHONY && !exceeded {
+                violations.push(GmViolation::new(time, None, format!(
+                    "Polyphony exceeds {} voices", GM_POLYPHONY)));
+            }
+            exceeded = voices as usize > GM_POLYPHONY;
+        }
+
+        violations.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::sequence::{ControlChange, Note, Track};
+
+    #[test]
+    fn test_gm_compliance() {
+        let chord: Vec<Note> = (40..66)
+            .map(|pitch| Note { pitch, start: 1.0, duration: 1.0, velocity: 90, tie: false })
+            .collect();
+        let seq = Sequence {
+            tracks: vec![
+                Track {
+                    notes: chord,
+                    controls: HashMap::from([(74, vec![ControlChange { time: 0.5, value: 3 }])]),
+                    ..Track::default()
+                },
+                Track {
+                    is_drum: true,
+                    notes: vec![Note { pitch: 20, start: 0.0, duration: 0.1, velocity: 90, tie: false }],
+                    ..Track::default()
+                },
+            ],
+            ..Sequence::default()
+        };
+        let violations = seq.check_gm_compliance();
+        assert_eq!(violations.len(), 3);
+        assert_eq!(violations[0].track, Some(1));
+        assert_eq!(violations[1].time, 0.5);
+        assert_eq!(violations[2].time, 1.0);
+        assert!(Sequence::from_file("tests/tiny.mid").unwrap().check_gm_compliance().is_empty());
+    }
+
+    #[test]
+    fn test_gm_names() {
+        let mut track = Track { program: 33, ..Track::default() };
+        assert_eq!((track.program_name(), track.instrument_family()), ("Electric Bass (finger)", "Bass"));
+        track.is_drum = true;
+        assert_eq!((track.program_name(), track.instrument_family()), ("Jazz Kit", "Drums"));
+        assert_eq!(GM_PROGRAM_NAMES[127], "Gunshot");
+        assert_eq!(drum_name(35), Some("Acoustic Bass Drum"));
+        assert_eq!(drum_name(42), Some("Closed Hi-Hat"));
+        assert_eq!(drum_name(81), Some("Open Triangle"));
+        assert_eq!((drum_name(34), drum_name(82)), (None, None));
+    }
+}
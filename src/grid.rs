@@ -0,0 +1,347 @@
+use crate::sequence::{Note, Sequence, TimeSignature, Track};
+use crate::timing::{TempoMap, TimeUnit, TICKS_PER_QUARTER};
+
+/// One measure of the metrical grid, in quarters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bar {
+    pub start: f32,
+    pub numerator: u8,
+    pub denominator: u8,
+    /// From the time signature, see `TimeSignature`.
+    pub clocks_per_click: Option<u8>,
+    pub thirty_seconds_per_quarter: Option<u8>,
+}
+
+/// 32nds per quarter honored by `Bar::beat_length`; others, which no real file uses,
+/// count as the standard 8.
+const THIRTY_SECONDS_RANGE: std::ops::RangeInclusive<u8> = 4..=32;
+
+impl Bar {
+    /// Beat length in quarters, following the denominator (an eighth in 6/8). A time
+    /// signature that notates 4 to 32 32nds per quarter other than 8 scales it.
+    #[inline(always)]
+    pub fn beat_length(&self) -> f32 {
+        let thirty_seconds = self.thirty_seconds_per_quarter.filter(|n| THIRTY_SECONDS_RANGE.contains(n)).unwrap_or(8);
+        4.0 / self.denominator as f32 * 8.0 / thirty_seconds as f32
+    }
+
+    /// Metronome click length in quarters, the beat unless the time signature gives
+    /// its clocks per click (36 for dotted quarters in 6/8).
+    #[inline(always)]
+    pub fn click_length(&self) -> f32 {
+        self.clocks_per_click.filter(|&n| n > 0).map_or(self.beat_length(), |n| n as f32 / 24.0)
+    }
+
+    #[inline(always)]
+    pub fn length(&self) -> f32 {
+        self.numerator as f32 * self.beat_length()
+    }
+
+    #[inline(always)]
+    pub fn end(&self) -> f32 {
+        self.start + self.length()
+    }
+}
+
+/// Bars covering `[0, end)`. A time signature change always starts a new bar,
+/// and 4/4 is assumed before the first time signature.
+pub fn bars(time_signatures: &[TimeSignature], end: f32) -> Vec<Bar> {
+    let mut signatures: Vec<TimeSignature> = time_signatures.iter()
+        .filter(|ts| ts.numerator > 0 && ts.denominator > 0)
+        .copied()
+        .collect();
+    signatures.sort_by(|a, b| a.time.total_cmp(&b.time));
+    if signatures.first().is_none_or(|ts| ts.time > 0.0) {
+        signatures.insert(0, TimeSignature { time: 0.0, numerator: 4, denominator: 4, ..Default::default() });
+    }
+
+    let mut bars = Vec::new();
+    for (idx, ts) in signatures.iter().enumerate() {
+        let next = signatures.get(idx + 1).map_or(f32::MAX, |n| n.time);
+        let mut bar = Bar {
+            start: ts.time,
+            numerator: ts.numerator,
+            denominator: ts.denominator,
+            clocks_per_click: ts.clocks_per_click,
+            thirty_seconds_per_quarter: ts.thirty_seconds_per_quarter,
+        };
+        while bar.start < next && bar.start < end {
+            bars.push(bar);
+            let start = bar.start + bar.length();
+            // Far out, a short bar no longer moves an f32 forward
+            if start <= bar.start {
+                break;
+            }
+            bar.start = start;
+        }
+    }
+    bars
+}
+
+impl Track {
+    /// Break notes that cross a barline into one segment per bar. Every segment after
+    /// the first has `tie` set, so bar-wise consumers can tell continuations apart.
+    pub fn split_notes_at_barlines(&mut self, time_signatures: &[TimeSignature]) {
+        let end = self.notes.iter().map(|n| n.start + n.duration).fold(0_f32, f32::max);
+        let starts: Vec<f32> = bars(time_signatures, end).iter().skip(1).map(|b| b.start).collect();
+        let mut notes = Vec::with_capacity(self.notes.len());
+        for note in &self.notes {
+            let note_end = note.start + note.duration;
+            let mut segment = *note;
+            let first = starts.partition_point(|&s| s <= note.start);
+            for &barline in starts[first..].iter().take_while(|&&s| s < note_end) {
+                notes.push(Note { duration: barline - segment.start, ..segment });
+                segment = Note { start: barline, duration: note_end - barline, tie: true, ..segment };
+            }
+            notes.push(segment);
+        }
+        self.notes = notes;
+    }
+}
+
+impl Track {
+    /// Latest note end, controller, aftertouch or SysEx time in quarters.
+    pub fn end_time(&self) -> f32 {
+        let notes = self.notes.iter().map(|n| n.start + n.duration);
+        let controls = self.controls.values().flatten().map(|c| c.time);
+        let fine = self.fine_controls.values().flatten().map(|c| c.time);
+        let pressure = self.channel_pressure.iter().chain(self.poly_pressure.values().flatten()).map(|p| p.time);
+        let sysex = self.sysex.iter().map(|s| s.time);
+        notes.chain(controls).chain(fine).chain(pressure).chain(sysex).fold(0_f32, f32::max)
+    }
+}
+
+impl Sequence {
+    /// Latest `Track::end_time` over all tracks.
+    pub fn end_time(&self, unit: TimeUnit) -> f32 {
+        let end = self.tracks.iter().map(|t| t.end_time()).fold(0_f32, f32::max);
+        TempoMap::new(&self.qpm, TICKS_PER_QUARTER).convert(end, unit)
+    }
+
+    /// Whether no track has any notes.
+    pub fn is_empty(&self) -> bool {
+        self.tracks.iter().all(|t| t.notes.is_empty())
+    }
+
+    pub(crate) fn notes_end(&self) -> f32 {
+        self.tracks.iter()
+            .flat_map(|t| t.notes.iter())
+            .map(|n| n.start + n.duration)
+            .fold(0_f32, f32::max)
+    }
+
+    /// Bars covering `[0, end)` under this sequence's time signatures.
+    pub fn bars(&self, end: f32) -> Vec<Bar> {
+        bars(&self.time_signatures, end)
+    }
+
+    /// Split notes of every track at barlines, see `Track::split_notes_at_barlines`.
+    pub fn split_notes_at_barlines(&mut self) {
+        let signatures = self.time_signatures.clone();
+        for track in &mut self.tracks {
+            track.split_notes_at_barlines(&signatures);
+        }
+    }
+
+    fn convert_times(&self, times: Vec<f32>, unit: TimeUnit) -> Vec<f32> {
+        let tempo_map = TempoMap::new(&self.qpm, TICKS_PER_QUARTER);
+        times.into_iter().map(|t| tempo_map.convert(t, unit)).collect()
+    }
+
+    /// Beat positions up to the last note end. Beats follow the time signature
+    /// denominator, and a beat cut short by a time signature change is dropped.
+    pub fn beats(&self, unit: TimeUnit) -> Vec<f32> {
+        let end = self.notes_end();
+        let mut beats = Vec::new();
+        for bar in self.bars(end) {
+            for i in 0..bar.numerator {
+                let beat = bar.start + i as f32 * bar.beat_length();
+                if beat < end {
+                    beats.push(beat);
+                }
+            }
+        }
+        self.convert_times(beats, unit)
+    }
+
+    /// Metronome click positions up to the last note end, see `Bar::click_length`.
+    /// Clicks restart on every downbeat.
+    pub fn clicks(&self, unit: TimeUnit) -> Vec<f32> {
+        let end = self.notes_end();
+        let mut clicks = Vec::new();
+        for bar in self.bars(end) {
+            let mut click = bar.start;
+            while click < bar.end().min(end) - 1e-4 {
+                clicks.push(click);
+                click += bar.click_length();
+            }
+        }
+        self.convert_times(clicks, unit)
+    }
+
+    /// Drum track with a hit on every click up to the last note end, `strong_pitch` on
+    /// downbeats and `weak_pitch` elsewhere. Hits last a sixteenth or the click, if shorter.
+    pub fn make_click_track(&self, strong_pitch: u8, weak_pitch: u8) -> Track {
+        let bars = self.bars(self.notes_end());
+        let notes = self.clicks(TimeUnit::Quarter).into_iter().map(|click| {
+            let bar = &bars[bars.partition_point(|b| b.start <= click).max(1) - 1];
+            let downbeat = click == bar.start;
+            Note {
+                pitch: if downbeat { strong_pitch } else { weak_pitch },
+                start: click,
+                duration: bar.click_length().min(0.25),
+                velocity: if downbeat { 110 } else { 80 },
+                tie: false,
+            }
+        }).collect();
+        Track {
+            name: "Click".to_string(),
+            is_drum: true,
+            channel: Some(9),
+            notes,
+            ..Track::default()
+        }
+    }
+
+    /// Metrical position of every note as `(bar_index, beat_in_bar, tick_in_beat)`, one
+    /// list per track. Beats follow the time signature denominator and ticks use the
+    /// resolution of the source file, or `TICKS_PER_QUARTER` without one.
+    pub fn note_positions(&self) -> Vec<Vec<(u32, u32, u32)>> {
+        let bars = self.bars(self.notes_end().max(f32::MIN_POSITIVE));
+        let tpq = self.tpq.map_or(TICKS_PER_QUARTER, u32::from);
+        let locate = |time: f32| {
+            let bar_idx = bars.partition_point(|b| b.start <= time).max(1) - 1;
+            let bar = &bars[bar_idx];
+            let offset = ((time - bar.start).max(0.0) as f64 * tpq as f64).round() as u32;
+            let beat_ticks = ((bar.beat_length() * tpq as f32).round() as u32).max(1);
+            // Rounding can push a note onto the next beat or bar
+            let beat = (offset / beat_ticks).min((bar.numerator as u32).max(1) - 1);
+            (bar_idx as u32, beat, offset - beat * beat_ticks)
+        };
+        self.tracks.iter()
+            .map(|t| t.notes.iter().map(|n| locate(n.start)).collect())
+            .collect()
+    }
+
+    /// Bar start positions up to the last note end.
+    pub fn downbeats(&self, unit: TimeUnit) -> Vec<f32> {
+        let starts = self.bars(self.notes_end()).iter().map(|b| b.start).collect();
+        self.convert_times(starts, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Tempo;
+
+    #[test]
+    fn test_beat_grid() {
+        let seq = Sequence {
+            tracks: vec![Track {
+                notes: vec![Note { pitch: 60, start: 0.0, duration: 10.0, velocity: 100, tie: false }],
+                ..Track::default()
+            }],
+            time_signatures: vec![
+                TimeSignature { time: 0.0, numerator: 2, denominator: 4, ..Default::default() },
+                TimeSignature { time: 4.0, numerator: 6, denominator: 8, ..Default::default() },
+            ],
+            qpm: vec![Tempo { time: 0.0, qpm: 60.0 }],
+            ..Sequence::default()
+        };
+        assert_eq!(seq.downbeats(TimeUnit::Quarter), vec![0.0, 2.0, 4.0, 7.0]);
+        let beats = seq.beats(TimeUnit::Second);
+        assert_eq!(&beats[..6], &[0.0, 1.0, 2.0, 3.0, 4.0, 4.5]);
+        assert_eq!(beats.len(), 4 + 12);
+        // Without clocks per click the metronome follows the beat
+        assert_eq!(seq.clicks(TimeUnit::Quarter), seq.beats(TimeUnit::Quarter));
+    }
+
+    #[test]
+    fn test_clicks() {
+        let seq = Sequence {
+            tracks: vec![Track {
+                notes: vec![Note { pitch: 60, start: 0.0, duration: 6.0, velocity: 100, tie: false }],
+                ..Track::default()
+            }],
+            time_signatures: vec![
+                TimeSignature { time: 0.0, numerator: 6, denominator: 8, clocks_per_click: Some(36), ..Default::default() },
+                TimeSignature { time: 3.0, numerator: 2, denominator: 4, thirty_seconds_per_quarter: Some(16), ..Default::default() },
+            ],
+            ..Sequence::default()
+        };
+        assert_eq!(seq.clicks(TimeUnit::Quarter), vec![0.0, 1.5, 3.0, 3.5, 4.0, 4.5, 5.0, 5.5]);
+        assert_eq!(seq.downbeats(TimeUnit::Quarter), vec![0.0, 3.0, 4.0, 5.0]);
+        // Implausible 32nds per quarter are taken as 8
+        let hostile = TimeSignature { time: 0.0, numerator: 1, denominator: 128, thirty_seconds_per_quarter: Some(255), ..Default::default() };
+        assert_eq!(bars(&[hostile], 1.0).len(), 32);
+
+        let click = seq.make_click_track(76, 77);
+        assert!(click.is_drum);
+        let hits: Vec<(u8, f32, f32)> = click.notes.iter().map(|n| (n.pitch, n.start, n.duration)).collect();
+        assert_eq!(hits, vec![
+            (76, 0.0, 0.25), (77, 1.5, 0.25), (76, 3.0, 0.25), (77, 3.5, 0.25),
+            (76, 4.0, 0.25), (77, 4.5, 0.25), (76, 5.0, 0.25), (77, 5.5, 0.25),
+        ]);
+    }
+
+    #[test]
+    fn test_end_time() {
+        let mut seq = Sequence {
+            tracks: vec![Track::default()],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }],
+            ..Sequence::default()
+        };
+        assert!(seq.is_empty());
+        assert_eq!(seq.end_time(TimeUnit::Quarter), 0.0);
+        seq.tracks[0].notes.push(Note { pitch: 60, start: 1.0, duration: 2.0, velocity: 100, tie: false });
+        seq.tracks[0].controls.insert(64, vec![crate::sequence::ControlChange { time: 5.0, value: 0 }]);
+        assert!(!seq.is_empty());
+        assert_eq!(seq.tracks[0].end_time(), 5.0);
+        assert_eq!(seq.end_time(TimeUnit::Second), 2.5);
+    }
+
+    #[test]
+    fn test_note_positions() {
+        let notes = [0.0, 2.5, 4.0, 5.75, 7.5].iter()
+            .map(|&start| Note { pitch: 60, start, duration: 0.5, velocity: 100, tie: false })
+            .collect();
+        let seq = Sequence {
+            tracks: vec![Track { notes, ..Track::default() }],
+            time_signatures: vec![
+                TimeSignature { time: 0.0, numerator: 4, denominator: 4, ..Default::default() },
+                TimeSignature { time: 4.0, numerator: 6, denominator: 8, ..Default::default() },
+            ],
+            ..Sequence::default()
+        };
+        assert_eq!(seq.note_positions()[0], vec![
+            (0, 0, 0), (0, 2, 480), (1, 0, 0), (1, 3, 240), (2, 1, 0),
+        ]);
+        // Beats shorter than a tick still get a position
+        let degenerate = Sequence {
+            tpq: Some(1),
+            time_signatures: vec![TimeSignature { time: 0.0, numerator: 3, denominator: 64, ..Default::default() }],
+            ..seq
+        };
+        assert_eq!(degenerate.note_positions()[0].len(), 5);
+    }
+
+    #[test]
+    fn test_split_notes_at_barlines() {
+        let mut track = Track {
+            notes: vec![
+                Note { pitch: 60, start: 1.0, duration: 6.0, velocity: 100, tie: false },
+                Note { pitch: 64, start: 0.0, duration: 2.0, velocity: 100, tie: false },
+            ],
+            ..Track::default()
+        };
+        track.split_notes_at_barlines(&[TimeSignature { time: 0.0, numerator: 2, denominator: 4, ..Default::default() }]);
+        let segments: Vec<(u8, f32, f32, bool)> = track.notes.iter()
+            .map(|n| (n.pitch, n.start, n.duration, n.tie))
+            .collect();
+        assert_eq!(segments, vec![
+            (60, 1.0, 1.0, false), (60, 2.0, 2.0, true), (60, 4.0, 2.0, true), (60, 6.0, 1.0, true),
+            (64, 0.0, 2.0, false),
+        ]);
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use crate::sequence::Track;
+
+/// Microtiming of one onset against a regular grid, as in Groove MIDI and GrooVAE.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GrooveHit {
+    pub pitch: u8,
+    /// Index of the nearest grid position.
+    pub step: u32,
+    /// Distance from that position in steps, within -0.5 to 0.5; negative is early.
+    pub offset: f32,
+    pub velocity: u8,
+    /// Velocity minus the mean velocity of the notes of the same pitch.
+    pub velocity_deviation: f32,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl GrooveHit {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+impl Track {
+    /// Each onset against a grid of `grid` quarters, in onset order.
+    pub fn groove(&self, grid: f32) -> Result<Vec<GrooveHit>, &'static str> {
+        if grid <= 0.0 {
+            return Err("grid must be positive");
+        }
+        let mut velocities: HashMap<u8, (f32, usize)> = HashMap::new();
+        for note in &self.notes {
+            let (sum, count) = velocities.entry(note.pitch).or_default();
+            *sum += note.velocity as f32;
+            *count += 1;
+        }
+        let mut notes = self.notes.clone();
+        notes.sort_by(|a, b| (a.start, a.pitch).partial_cmp(&(b.start, b.pitch)).unwrap());
+        Ok(notes.iter().map(|note| {
+            let position = note.start / grid;
+            let step = position.round().max(0.0);
+            let (sum, count) = velocities[&note.pitch];
+            GrooveHit {
+                pitch: note.pitch,
+                step: step as u32,
+                offset: position - step,
+                velocity: note.velocity,
+                velocity_deviation: note.velocity as f32 - sum / count as f32,
+            }
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Note;
+
+    #[test]
+    fn test_groove() {
+        let note = |pitch: u8, start: f32, velocity: u8| Note { pitch, start, duration: 0.1, velocity, tie: false };
+        let track = Track {
+            notes: vec![note(42, 0.51, 60), note(36, 0.0, 110), note(42, 0.0, 100), note(42, 0.95, 80)],
+            is_drum: true,
+            ..Track::default()
+        };
+        let hits: Vec<(u8, u32, i32, f32)> = track.groove(0.5).unwrap().iter()
+            .map(|h| (h.pitch, h.step, (h.offset * 100.0).round() as i32, h.velocity_deviation))
+            .collect();
+        assert_eq!(hits, vec![(36, 0, 0, 0.0), (42, 0, 0, 20.0), (42, 1, 2, -20.0), (42, 2, -10, 0.0)]);
+        assert!(track.groove(0.0).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::Write;
+use crate::sequence::Sequence;
+use crate::timing::TimeUnit;
+
+/// Size of the inline piano roll in pixels.
+const ROLL_WIDTH: f32 = 640.0;
+const ROLL_HEIGHT: f32 = 120.0;
+/// Notes drawn at most, the rest is thinned out by `Sequence::preview`.
+const ROLL_NOTES: usize = 4000;
+/// Track colours, reused when there are more tracks.
+const COLORS: [&str; 8] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#17becf"];
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl Sequence {
+    /// Compact HTML for notebooks: a summary line, a table with the instrument, note
+    /// count and length of every track, and an SVG piano roll if `piano_roll` is set.
+    pub fn repr_html(&self, piano_roll: bool) -> String {
+        let notes: usize = self.tracks.iter().map(|t| t.notes.len()).sum();
+        let mut html = String::from("<div>");
+        write!(html, "<p><b>Sequence</b>: {} tracks, {} notes, {:.1} quarters ({:.1} s)</p>",
+               self.tracks.len(), notes, self.end_time(TimeUnit::Quarter), self.end_time(TimeUnit::Second)).unwrap();
+        html.push_str("<table><tr><th>#</th><th>Name</th><th>Instrument</th><th>Channel</th>\
+                       <th>Notes</th><th>Pitches</th><th>End</th></tr>");
+        for (idx, track) in self.tracks.iter().enumerate() {
+            let pitches = track.notes.iter().map(|n| n.pitch);
+            let range = match (pitches.clone().min(), pitches.max()) {
+                (Some(low), Some(high)) => format!("{}–{}", low, high),
+                _ => String::new(),
+            };
+            let channel = track.channel.map(|c| c.to_string()).unwrap_or_default();
+            write!(html, "<tr><td style=\"color:{}\">■ {}</td><td>{}</td><td>{}</td><td>{}</td>\
+                          <td>{}</td><td>{}</td><td>{:.1}</td></tr>",
+                   COLORS[idx % COLORS.len()], idx, escape(&track.name), track.program_name(), channel,
+                   track.notes.len(), range, track.end_time()).unwrap();
+        }
+        html.push_str("</table>");
+        if piano_roll && notes > 0 {
+            html.push_str(&self.piano_roll_svg());
+        }
+        html.push_str("</div>");
+        html
+    }
+
+    fn piano_roll_svg(&self) -> String {
+        let preview = self.preview(ROLL_NOTES);
+        let notes = || preview.tracks.iter().enumerate().flat_map(|(idx, t)| t.notes.iter().map(move |n| (idx, n)));
+        let end = notes().map(|(_, n)| n.start + n.duration).fold(f32::EPSILON, f32::max);
+        let low = notes().map(|(_, n)| n.pitch).min().unwrap_or(0) as f32;
+        let high = notes().map(|(_, n)| n.pitch).max().unwrap_or(127) as f32;
+        let x_scale = ROLL_WIDTH / end;
+        let row = ROLL_HEIGHT / (high - low + 1.0);
+        let mut svg = format!("<svg width=\"{}\" height=\"{}\" style=\"background:#f8f8f8\">", ROLL_WIDTH, ROLL_HEIGHT);
+        for (idx, note) in notes() {
+            write!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+                   note.start * x_scale, (high - note.pitch as f32) * row,
+                   (note.duration * x_scale).max(1.0), row.max(1.0), COLORS[idx % COLORS.len()]).unwrap();
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repr_html() {
+        let mut seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        seq.tracks[0].name = "<Lead>".to_string();
+        let html = seq.repr_html(true);
+        assert!(html.contains("&lt;Lead&gt;"));
+        assert_eq!(html.matches("<rect").count(), seq.tracks[0].notes.len());
+        assert!(!seq.repr_html(false).contains("<svg"));
+        assert!(!Sequence::default().repr_html(true).contains("<svg"));
+    }
+}
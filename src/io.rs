@@ -1,141 +1,478 @@
+use std::borrow::Cow;
 use std::fs;
+use std::io::Read;
+use std::ops::{Deref, Range};
 use std::str;
+use std::sync::Arc;
+use flate2::read::GzDecoder;
+use memmap2::Mmap;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::error::{Location, MidiError};
 use crate::message::{MIDIFormat, EventStatus, MIDIMessage, MetaStatus};
+#[cfg(feature = "python")]
+use crate::{probe::Probe, stream::EventStream};
 use crate::util::read_variable_length;
 
+/// Largest zip member `from_zip` or gzip stream `from_bytes` inflates, far beyond any
+/// real MIDI file.
+pub const MAX_ZIP_MEMBER: u64 = 64 << 20;
+
+/// Iterator error of a track whose delta times add up past the largest `u32` tick.
+pub const TICK_OVERFLOW: &str = "Delta times run past the largest tick";
+
+#[cfg_attr(feature = "python", pyclass)]
 #[derive(Clone)]
 pub struct MIDIFile {
     pub format: MIDIFormat,
     pub division: u16,
     pub tracks: Vec<MidiTrack>,
+    /// Track count from the MThd header, which may disagree with `tracks`.
+    pub declared_tracks: u16,
+    /// Chunk level problems that were worked around while reading.
+    pub warnings: Vec<MidiError>,
+}
+
+/// Payload of a track chunk, owned or borrowed from a memory mapped file that all its
+/// tracks share.
+#[derive(Clone)]
+enum TrackData {
+    Owned(Vec<u8>),
+    Mapped(Arc<Mmap>, Range<usize>),
+}
+
+impl Deref for TrackData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            TrackData::Owned(data) => data,
+            TrackData::Mapped(map, range) => &map[range.clone()],
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct MidiTrack {
-    track_idx: u16,
-    data: Vec<u8>,
+    pub track_idx: u16,
+    data: TrackData,
+    /// Length field of the MTrk chunk header.
+    pub(crate) declared_len: usize,
+    /// Position of the chunk payload in the file, for error locations.
+    pub(crate) offset: usize,
 }
 
 pub struct MidiTrackIter<'a> {
-    data: &'a [u8],
+    data: Cow<'a, TrackData>,
 
     byte_offset: usize,
     tick_offset: u32,
 
-    last_status_code: u8,
-    last_event_len: usize,
+    /// Last channel voice status, which data bytes in status position run on. SysEx and
+    /// meta messages cancel it as the spec says, system common messages leave it, and
+    /// nothing runs before the first.
+    running_status: Option<u8>,
+
+    /// Why the iterator stopped early, if the track data is malformed.
+    pub error: Option<&'static str>,
+    /// Byte offset just past the last complete message.
+    pub message_end: usize,
+    pub end_of_track: bool,
+    /// Bytes left over after the EndOfTrack meta, which are not read.
+    pub trailing_bytes: usize,
+    /// Runs of data bytes that relied on a running status a SysEx or meta cancelled,
+    /// skipped like other stray data bytes.
+    pub cancelled_running_status: usize,
+    /// Data bytes met before any channel status, skipped up to the next status byte.
+    pub skipped_bytes: usize,
+    /// A SysEx or meta cancelled the running status since the last status byte.
+    cancelled: bool,
 }
 
 impl MidiTrack {
-    pub fn iter(&self) -> MidiTrackIter {
+    pub fn new(track_idx: u16, data: Vec<u8>) -> Self {
+        MidiTrack { track_idx, declared_len: data.len(), data: TrackData::Owned(data), offset: 0 }
+    }
+
+    /// Raw MTrk chunk payload.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Position of the chunk payload in the file it was read from.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn iter(&self) -> MidiTrackIter<'_> {
+        MidiTrackIter::new(Cow::Borrowed(&self.data))
+    }
+}
+
+/// Iterate over the messages of a track that is no longer needed, without copying it.
+impl IntoIterator for MidiTrack {
+    type Item = MIDIMessage;
+    type IntoIter = MidiTrackIter<'static>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MidiTrackIter::new(Cow::Owned(self.data))
+    }
+}
+
+impl<'a> MidiTrackIter<'a> {
+    fn new(data: Cow<'a, TrackData>) -> Self {
         MidiTrackIter {
-            data: &self.data,
+            data,
             byte_offset: 0,
             tick_offset: 0,
-            last_event_len: 0,
-            last_status_code: 0,
+            running_status: None,
+            error: None,
+            message_end: 0,
+            end_of_track: false,
+            trailing_bytes: 0,
+            cancelled_running_status: 0,
+            skipped_bytes: 0,
+            cancelled: false,
         }
     }
 }
 
 impl MIDIFile {
-    pub fn from_file(path: &str) -> Result<MIDIFile, &'static str> {
-        let data = fs::read(path)
-            .expect(concat!("Can not read file ", stringify!(path)));
-        assert!(&data.starts_with(b"MThd"), "Invalid midi file. MThd expected.");
-        let (format, track_num, division) = Self::parse_mthd(&data[8..14]);
+    pub fn from_file(path: &str) -> Result<MIDIFile, MidiError> {
+        let data = fs::read(path).map_err(|e| MidiError::Io(format!("Can not read {}: {}", path, e)))?;
+        Self::from_bytes(&data)
+    }
+
+    /// Like `from_file`, but the tracks borrow their bytes from a memory map of the file,
+    /// paged in on demand, instead of copies of it. Gzipped files are inflated as usual.
+    /// Like any mapping, the file must not be truncated while the tracks are alive.
+    pub fn from_file_mapped(path: &str) -> Result<MIDIFile, MidiError> {
+        let io = |e: std::io::Error| MidiError::Io(format!("Can not read {}: {}", path, e));
+        let file = fs::File::open(path).map_err(io)?;
+        // Read-only and private; only truncation by another process can invalidate it
+        let map = Arc::new(unsafe { Mmap::map(&file) }.map_err(io)?);
+        if map.starts_with(&[0x1F, 0x8B]) {
+            return Self::from_bytes(&map);
+        }
+        Self::parse(&map, Some(&map))
+    }
+
+    /// Parse an SMF, an RMID container or either gzipped once. Gzip streams inflating
+    /// past `MAX_ZIP_MEMBER` bytes are refused.
+    pub fn from_bytes(data: &[u8]) -> Result<MIDIFile, MidiError> {
+        if data.starts_with(&[0x1F, 0x8B]) {
+            let inflated = Self::inflate(data, MAX_ZIP_MEMBER)?;
+            if inflated.starts_with(&[0x1F, 0x8B]) {
+                return Err(MidiError::Unsupported("Nested gzip is not supported.".to_string(), Location::default()));
+            }
+            return Self::parse(&inflated, None);
+        }
+        Self::parse(data, None)
+    }
+
+    /// The gzip stream `data` inflated, failing past `limit` bytes.
+    fn inflate(data: &[u8], limit: u64) -> Result<Vec<u8>, MidiError> {
+        let mut inflated = Vec::new();
+        GzDecoder::new(data).take(limit + 1).read_to_end(&mut inflated)
+            .map_err(|_| MidiError::Header("Invalid gzip stream.".to_string(), Location::default()))?;
+        if inflated.len() as u64 > limit {
+            return Err(MidiError::Header(
+                format!("Gzip stream inflates past {} bytes.", limit), Location::default()));
+        }
+        Ok(inflated)
+    }
+
+    /// Split `data` into tracks, which borrow from `map` when `data` is all of it.
+    fn parse(data: &[u8], map: Option<&Arc<Mmap>>) -> Result<MIDIFile, MidiError> {
+        let smf = if data.starts_with(b"RIFF") { Self::unwrap_rmid(data)? } else { 0..data.len() };
+        let base = smf.start;
+        let data = &data[smf];
+        if !data.starts_with(b"MThd") || data.len() < 14 {
+            return Err(MidiError::Header("Invalid midi file. MThd expected.".to_string(), Location {
+                offset: Some(0),
+                ..Location::default()
+            }));
+        }
+        let (format, track_num, division) = Self::parse_mthd(&data[8..14])?;
         let mut midi = MIDIFile {
             format,
             division,
             tracks: Vec::new(),
+            declared_tracks: track_num,
+            warnings: Vec::new(),
         };
-        let mut byte_offset = 14;
+        let header_len = u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize;
+        let mut byte_offset = 8 + header_len.max(6);
 
         for track_idx in 0..track_num {
-            let mut chunk_len = u32::from_be_bytes(
-                data[byte_offset + 4..byte_offset + 8]
-                    .try_into().expect("Invalid chunk!")
-            );
             // Skip unknown chunks
-            while !data[byte_offset..].starts_with(b"MTrk") {
-                byte_offset += 8 + chunk_len as usize;
-                chunk_len = u32::from_be_bytes(
-                    data[byte_offset + 4..byte_offset + 8]
-                        .try_into().expect("Invalid chunk!")
-                )
+            let mut chunk = Self::chunk_at(data, byte_offset);
+            while let Some((id, len)) = chunk {
+                if id == b"MTrk" {
+                    break;
+                }
+                byte_offset += 8 + len;
+                chunk = Self::chunk_at(data, byte_offset);
             }
+            let Some((_, chunk_len)) = chunk else {
+                midi.warnings.push(MidiError::Track(format!(
+                    "Header declares {} tracks but only {} were found", track_num, track_idx), Location {
+                    track: Some(track_idx as usize),
+                    offset: Some(byte_offset),
+                    ..Location::default()
+                }));
+                break;
+            };
             let start = byte_offset + 8;
-            let end = start + chunk_len as usize;
+            let end = if start + chunk_len > data.len() {
+                midi.warnings.push(MidiError::Track(format!(
+                    "Chunk length {} exceeds the file, truncated to {} bytes",
+                    chunk_len, data.len() - start), Location {
+                    track: Some(track_idx as usize),
+                    offset: Some(byte_offset),
+                    ..Location::default()
+                }));
+                data.len()
+            } else {
+                start + chunk_len
+            };
             byte_offset = end;
+            let track_data = match map {
+                Some(map) => TrackData::Mapped(map.clone(), base + start..base + end),
+                None => TrackData::Owned(data[start..end].to_vec()),
+            };
             midi.tracks.push(MidiTrack {
                 track_idx,
-                data: data[start..end].to_vec(),
+                data: track_data,
+                declared_len: chunk_len,
+                offset: start,
             });
         }
         Ok(midi)
     }
 
-    fn parse_mthd(data: &[u8]) -> (MIDIFormat, u16, u16) {
-        let to_u16 = |s: &[u8]|
-            u16::from_be_bytes(s.try_into().expect("Error reading midi file."));
+    /// Id and declared length of the chunk header at `offset`, if a whole header is there.
+    fn chunk_at(data: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+        let header = data.get(offset..offset.checked_add(8)?)?;
+        Some((&header[..4], u32::from_be_bytes(header[4..].try_into().unwrap()) as usize))
+    }
+
+    /// Read `member` from a zip archive such as the Lakh MIDI dataset. Members inflating
+    /// past `MAX_ZIP_MEMBER` bytes are refused rather than read into memory.
+    pub fn from_zip(archive: &str, member: &str) -> Result<MIDIFile, MidiError> {
+        Self::from_zip_limited(archive, member, MAX_ZIP_MEMBER)
+    }
+
+    fn from_zip_limited(archive: &str, member: &str, limit: u64) -> Result<MIDIFile, MidiError> {
+        let io = |message: &str| MidiError::Io(format!("{} {}", message, archive));
+        let file = fs::File::open(archive).map_err(|_| io("Can not read"))?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|_| io("Invalid zip archive"))?;
+        let entry = zip.by_name(member).map_err(|_| MidiError::Io(format!("No member {} in {}", member, archive)))?;
+        let too_large = || MidiError::Unsupported(
+            format!("{} in {} is larger than {} bytes", member, archive, limit), Location::default());
+        // The declared size may lie, so the reader is capped as well
+        if entry.size() > limit {
+            return Err(too_large());
+        }
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.take(limit + 1).read_to_end(&mut data)
+            .map_err(|_| MidiError::Io(format!("Can not read {} in {}", member, archive)))?;
+        if data.len() as u64 > limit {
+            return Err(too_large());
+        }
+        Self::from_bytes(&data)
+    }
+
+    /// Locate the SMF embedded in the `data` chunk of a RIFF `RMID` container.
+    fn unwrap_rmid(data: &[u8]) -> Result<Range<usize>, MidiError> {
+        if data.len() < 12 || &data[8..12] != b"RMID" {
+            return Err(MidiError::Header("RIFF file is not an RMID container.".to_string(), Location {
+                offset: Some(8),
+                ..Location::default()
+            }));
+        }
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            let start = offset + 8;
+            if &data[offset..offset + 4] == b"data" {
+                return data.get(start..start + size).map(|_| start..start + size)
+                    .ok_or_else(|| MidiError::Header("RMID data chunk is truncated.".to_string(), Location {
+                        offset: Some(offset),
+                        ..Location::default()
+                    }));
+            }
+            // RIFF chunks are padded to even sizes
+            offset = start + size + (size & 1);
+        }
+        Err(MidiError::Header("RMID container has no data chunk.".to_string(), Location::default()))
+    }
+
+    /// Split a format 2 file into one single-track file per song. Other formats hold a
+    /// single song and are returned as is.
+    pub fn songs(&self) -> Vec<MIDIFile> {
+        match self.format {
+            MIDIFormat::MultiSong => self.tracks.iter().map(|track| MIDIFile {
+                format: MIDIFormat::SingleTrack,
+                division: self.division,
+                tracks: vec![MidiTrack { track_idx: 0, ..track.clone() }],
+                declared_tracks: 1,
+                warnings: self.warnings.clone(),
+            }).collect(),
+            _ => vec![self.clone()],
+        }
+    }
+
+    /// Serialize into a Standard MIDI File.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            14 + self.tracks.iter().map(|t| t.data.len() + 8).sum::<usize>());
+        bytes.extend_from_slice(b"MThd");
+        bytes.extend_from_slice(&6_u32.to_be_bytes());
+        bytes.extend_from_slice(&(self.format as u16).to_be_bytes());
+        bytes.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.division.to_be_bytes());
+        for track in &self.tracks {
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(track.data.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&track.data);
+        }
+        bytes
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), &'static str> {
+        fs::write(path, self.to_bytes()).map_err(|_| "Can not write file.")
+    }
+
+    fn parse_mthd(data: &[u8]) -> Result<(MIDIFormat, u16, u16), MidiError> {
+        let to_u16 = |s: &[u8]| u16::from_be_bytes(s.try_into().unwrap());
         let format = match to_u16(&data[0..2]) {
             0 => MIDIFormat::SingleTrack,
             1 => MIDIFormat::MultiTrack,
             2 => MIDIFormat::MultiSong,
-            x => panic!("MIDI format {} is not supported.", x),
+            format => return Err(MidiError::Unsupported(format!("MIDI format {} is not supported.", format), Location {
+                offset: Some(8),
+                ..Location::default()
+            })),
         };
-        (format, to_u16(&data[2..4]), to_u16(&data[4..6]))
+        Ok((format, to_u16(&data[2..4]), to_u16(&data[4..6])))
     }
 }
 
-impl<'a> Iterator for MidiTrackIter<'a> {
-    type Item = MIDIMessage;
+#[cfg(feature = "python")]
+#[pymethods]
+impl MIDIFile {
+    /// Lazily yield `(track, tick, message bytes)` for every message, track by track,
+    /// without building notes or Python objects for the whole file.
+    #[staticmethod]
+    #[pyo3(name = "iter_events")]
+    fn py_iter_events(path: &str) -> PyResult<EventStream> {
+        Ok(EventStream::new(MIDIFile::from_file(path)?))
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.byte_offset >= self.data.len() { return None; }
-        let (bytes, value) = read_variable_length(
-            &self.data[self.byte_offset..self.byte_offset + 4]
-                .try_into()
-                .expect("Reading variable length error.")
-        );
-        self.byte_offset += bytes as usize;
-        self.tick_offset += value as u32;
-
-        let this_status: u8 = self.data[self.byte_offset];
+    /// Write the file as midicsv text, see `MIDIFile::midicsv`.
+    #[pyo3(name = "to_midicsv")]
+    fn py_to_midicsv(&self, path: &str) -> PyResult<()> {
+        Ok(self.to_midicsv(path)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_midicsv")]
+    fn py_from_midicsv(path: &str) -> PyResult<MIDIFile> {
+        Ok(MIDIFile::from_midicsv(path)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "probe")]
+    fn py_probe(path: &str) -> PyResult<Probe> {
+        Ok(MIDIFile::probe(path)?)
+    }
+}
+
+impl MidiTrackIter<'_> {
+    /// Absolute tick of the last message read, or of the one that failed.
+    pub fn tick(&self) -> u32 {
+        self.tick_offset
+    }
+
+    /// `error` located in track `track_idx`, whose data starts at byte `base` of the file:
+    /// a `Track` error when the delta times overflow, an `Event` error otherwise.
+    pub fn located_error(&self, track_idx: usize, base: usize) -> Option<MidiError> {
+        let location = Location {
+            track: Some(track_idx),
+            tick: Some(self.tick_offset),
+            offset: Some(base + self.message_end),
+        };
+        self.error.map(|error| match error {
+            TICK_OVERFLOW => MidiError::Track(error.to_string(), location),
+            _ => MidiError::Event(error.to_string(), location),
+        })
+    }
+
+    /// The VLQ at `offset`, which may be past the end of the track.
+    fn read_vlq(&self, offset: usize) -> Result<(usize, u32), &'static str> {
+        read_variable_length(self.data.get(offset..).unwrap_or_default())
+    }
+
+    fn cancel_running_status(&mut self) {
+        self.cancelled |= self.running_status.take().is_some();
+    }
+
+    fn read_message(&mut self) -> Result<Option<MIDIMessage>, &'static str> {
+        if self.end_of_track { return Ok(None); }
+        self.message_end = self.byte_offset.min(self.data.len());
+        if self.byte_offset >= self.data.len() { return Ok(None); }
+        let (bytes, value) = self.read_vlq(self.byte_offset).map_err(|_| "Invalid delta time")?;
+        self.byte_offset += bytes;
+        self.tick_offset = self.tick_offset.checked_add(value).ok_or(TICK_OVERFLOW)?;
+
+        if self.data.get(self.byte_offset).is_some_and(|&b| b < 0x80) && self.running_status.is_none() {
+            // Running status with nothing to run on, drop the stray data bytes
+            let skipped = self.data[self.byte_offset..].iter().take_while(|&&b| b < 0x80).count();
+            self.skipped_bytes += skipped;
+            if self.cancelled {
+                self.cancelled_running_status += 1;
+            }
+            self.byte_offset += skipped;
+        }
+        let this_status: u8 = *self.data.get(self.byte_offset).ok_or("Track ends after a delta time")?;
         let start = self.byte_offset;
         let msg = match this_status {
-            // Just ignore and pass the SysEx Message
             0xF0 | 0xF7 => {
-                let (bytes, mut event_len) = read_variable_length(
-                    match self.data.get(start + 1..start + 5) {
-                        Some(res) => res.try_into().unwrap(),
-                        None => &[0u8; 4]
-                    }
-                );
-                event_len += bytes as usize + 1;
+                // The declared length is authoritative: a packet without a final F7 is
+                // continued by later F7 packets, which `Sequence` joins back together.
+                let (bytes, len) = self.read_vlq(start + 1).map_err(|_| "Invalid SysEx length")?;
+                let event_len = len as usize + bytes + 1;
+                if start + event_len > self.data.len() {
+                    return Err("Truncated SysEx message");
+                }
                 self.byte_offset += event_len;
-                self.last_event_len = event_len;
-                self.last_status_code = this_status;
-                let end_byte = self.data[self.byte_offset - 1];
-                assert_eq!(end_byte, 0xf7_u8);
-                return self.next();
+                self.cancel_running_status();
+                MIDIMessage::new_sysex(self.tick_offset, &self.data[start..self.byte_offset])
             }
             // Reuse last status code
             0x00..=0x7F => {
-                assert_ne!(self.last_status_code, 0xFF, "Last status can't be meta");
-                self.byte_offset += self.last_event_len - 1;
-                MIDIMessage::new_event(
-                    self.tick_offset,
-                    self.last_status_code,
-                    &self.data[start..self.byte_offset],
-                )
+                let status = self.running_status.ok_or("Running status without a status")?;
+                let data_len = EventStatus::from_status_code(status).1 as usize - 1;
+                if start + data_len > self.data.len() {
+                    return Err("Truncated channel message");
+                }
+                self.byte_offset += data_len;
+                MIDIMessage::new_event(self.tick_offset, status, &self.data[start..self.byte_offset])
             }
             // MIDI Messages has determinate length.
             0x80..=0xFE => {
-                self.last_status_code = this_status;
                 let event_len = EventStatus::from_status_code(this_status).1 as usize;
+                if start + event_len > self.data.len() {
+                    return Err("Truncated channel message");
+                }
+                // Only channel messages set the running status; system common
+                // messages in between leave it untouched.
+                if this_status < 0xF0 {
+                    self.running_status = Some(this_status);
+                    self.cancelled = false;
+                }
                 self.byte_offset += event_len;
-                self.last_event_len = event_len;
                 MIDIMessage::new_event(
                     self.tick_offset,
                     this_status,
@@ -144,28 +481,50 @@ impl<'a> Iterator for MidiTrackIter<'a> {
             }
             // Meta Messages has variable length.
             0xFF => {
-                let (bytes, mut meta_len) = read_variable_length(
-                    match self.data.get(start + 2..start + 6) {
-                        Some(res) => res.try_into().unwrap(),
-                        None => &[0u8; 4]
-                    }
-                );
-                meta_len += bytes as usize + 2;
+                let (bytes, len) = self.read_vlq(start + 2).map_err(|_| "Truncated meta message")?;
+                let meta_len = len as usize + bytes + 2;
+                if start + meta_len > self.data.len() {
+                    return Err("Truncated meta message");
+                }
                 self.byte_offset += meta_len;
-                MIDIMessage::new_meta(
+                self.cancel_running_status();
+                let msg = MIDIMessage::new_meta(
                     self.tick_offset,
                     this_status,
                     &self.data[start + 1..self.byte_offset],
-                )
+                );
+                if self.data[start + 1] == MetaStatus::EndOfTrack as u8 {
+                    self.end_of_track = true;
+                    self.message_end = self.byte_offset;
+                    self.trailing_bytes = self.data.len() - self.byte_offset;
+                }
+                msg
             }
         };
-        Some(msg)
+        Ok(Some(msg))
+    }
+}
+
+impl<'a> Iterator for MidiTrackIter<'a> {
+    type Item = MIDIMessage;
+
+    /// Stops at EndOfTrack or at the first malformed message, recording it in `error`.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() { return None; }
+        match self.read_message() {
+            Ok(msg) => msg,
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::MetaStatus;
 
     #[test]
     fn test_read_midi_head() {
@@ -184,8 +543,198 @@ mod tests {
                             println!("tempo {:?}", meta.tempo().unwrap());
                         }
                     }
+                    MIDIMessage::SysEx(_) => {}
                 }
             }
         }
     }
+
+    #[test]
+    fn test_system_messages() {
+        let data = vec![
+            0x00, 0x90, 0x3C, 0x40,
+            0x00, 0xF1, 0x21,       // MTC quarter frame
+            0x00, 0xF4,             // undefined system commons
+            0x00, 0xF5,
+            0x00, 0xF9,
+            0x00, 0x3E, 0x40,       // running status survives them
+            0x10, 0xFF, 0x2F, 0x00,
+        ];
+        let track = MidiTrack::new(0, data);
+        let mut messages = track.iter();
+        let statuses: Vec<EventStatus> = messages.by_ref().filter_map(|m| match m {
+            MIDIMessage::Event(event) => Some(event.status),
+            _ => None,
+        }).collect();
+        assert!(messages.error.is_none() && messages.end_of_track);
+        assert_eq!(statuses, vec![
+            EventStatus::NoteOn, EventStatus::MTCQuarterFrame, EventStatus::UndefinedSystem,
+            EventStatus::UndefinedSystem, EventStatus::UndefinedSystem, EventStatus::NoteOn,
+        ]);
+    }
+
+    #[test]
+    fn test_running_status() {
+        // Each case: track bytes before EndOfTrack, then the expected (tick, status, data)
+        // of its channel messages and the runs on a cancelled status counted.
+        type Case = (Vec<u8>, Vec<(u32, u8, Vec<u8>)>, usize);
+        let cases: Vec<Case> = vec![
+            // Plain running status, a note off by velocity 0 included
+            (vec![0x00, 0x92, 0x3C, 0x40, 0x08, 0x3E, 0x40, 0x08, 0x3C, 0x00],
+             vec![(0, 0x92, vec![0x3C, 0x40]), (8, 0x92, vec![0x3E, 0x40]), (16, 0x92, vec![0x3C, 0x00])], 0),
+            // Two-byte messages run with their own length
+            (vec![0x00, 0xC1, 0x05, 0x00, 0x06, 0x00, 0xD1, 0x40, 0x04, 0x41],
+             vec![(0, 0xC1, vec![0x05]), (0, 0xC1, vec![0x06]), (0, 0xD1, vec![0x40]), (4, 0xD1, vec![0x41])], 0),
+            // Meta messages cancel the status, the data bytes after one are skipped
+            (vec![0x00, 0x90, 0x3C, 0x40, 0x00, 0xFF, 0x01, 0x02, 0x68, 0x69, 0x00, 0x3C, 0x00],
+             vec![(0, 0x90, vec![0x3C, 0x40])], 1),
+            // So does a SysEx
+            (vec![0x00, 0xB0, 0x07, 0x64, 0x00, 0xF0, 0x03, 0x7E, 0x01, 0xF7, 0x00, 0x0A, 0x40],
+             vec![(0, 0xB0, vec![0x07, 0x64])], 1),
+            // A new status after the SysEx clears the count
+            (vec![0x00, 0xF0, 0x01, 0xF7, 0x00, 0x80, 0x3C, 0x00, 0x00, 0x3E, 0x00],
+             vec![(0, 0x80, vec![0x3C, 0x00]), (0, 0x80, vec![0x3E, 0x00])], 0),
+            // System common messages neither set nor end it
+            (vec![0x00, 0xE0, 0x00, 0x40, 0x00, 0xF5, 0x00, 0xF1, 0x11, 0x00, 0x7F, 0x7F],
+             vec![(0, 0xE0, vec![0x00, 0x40]), (0, 0xF5, vec![]), (0, 0xF1, vec![0x11]), (0, 0xE0, vec![0x7F, 0x7F])], 0),
+        ];
+        for (mut data, expected, cancelled) in cases {
+            data.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+            let track = MidiTrack::new(0, data);
+            let mut messages = track.iter();
+            let events: Vec<(u32, u8, Vec<u8>)> = messages.by_ref().filter_map(|m| match m {
+                MIDIMessage::Event(event) => {
+                    let len = EventStatus::from_status_code(event.data[0]).1 as usize;
+                    Some((event.time, event.data[0], event.data[1..len].to_vec()))
+                }
+                _ => None,
+            }).collect();
+            assert!(messages.error.is_none() && messages.end_of_track);
+            assert_eq!(events, expected);
+            assert_eq!(messages.cancelled_running_status, cancelled);
+        }
+
+        let truncated = MidiTrack::new(0, vec![0x00, 0x90, 0x3C, 0x40, 0x00, 0x3E]);
+        let mut messages = truncated.iter();
+        assert_eq!(messages.by_ref().count(), 1);
+        assert_eq!(messages.error, Some("Truncated channel message"));
+    }
+
+    #[test]
+    fn test_tick_overflow() {
+        // Sixteen of the longest delta times fit in a u32 tick, the seventeenth doesn't
+        let data: Vec<u8> = (0..17).flat_map(|_| [0xFF, 0xFF, 0xFF, 0x7F, 0x90, 0x3C, 0x00]).collect();
+        let track = MidiTrack::new(0, data);
+        let mut messages = track.iter();
+        assert_eq!(messages.by_ref().count(), 16);
+        assert_eq!(messages.error, Some(TICK_OVERFLOW));
+        let Some(MidiError::Track(_, location)) = messages.located_error(0, 22) else { panic!("expected a track error") };
+        assert_eq!(location, Location { track: Some(0), tick: Some(16 * 0x0FFF_FFFF), offset: Some(22 + 16 * 7) });
+    }
+
+    #[test]
+    fn test_data_bytes_at_track_start() {
+        let data = vec![
+            0x00, 0x3C, 0x40,       // running status before any status byte
+            0x90, 0x3C, 0x40,
+            0x10, 0x80, 0x3C, 0x00,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let track = MidiTrack::new(0, data);
+        let mut messages = track.iter();
+        let events: Vec<(u32, EventStatus)> = messages.by_ref().filter_map(|m| match m {
+            MIDIMessage::Event(event) => Some((event.time, event.status)),
+            _ => None,
+        }).collect();
+        assert_eq!(events, vec![(0, EventStatus::NoteOn), (0x10, EventStatus::NoteOff)]);
+        assert_eq!(messages.skipped_bytes, 2);
+        assert!(messages.error.is_none() && messages.end_of_track);
+    }
+
+    #[test]
+    fn test_rmid() {
+        let smf = fs::read("tests/tiny.mid").unwrap();
+        let mut rmid = b"RIFF\0\0\0\0RMIDLIST\x03\0\0\0abc\0data".to_vec();
+        rmid.extend_from_slice(&(smf.len() as u32).to_le_bytes());
+        rmid.extend_from_slice(&smf);
+        let size = (rmid.len() - 8) as u32;
+        rmid[4..8].copy_from_slice(&size.to_le_bytes());
+        let mf = MIDIFile::from_bytes(&rmid).unwrap();
+        assert_eq!(mf.tracks.len(), 2);
+        assert!(MIDIFile::from_bytes(b"RIFF\0\0\0\0WAVE").is_err());
+    }
+
+    #[test]
+    fn test_mapped() {
+        let midi = MIDIFile::from_file_mapped("tests/tiny.mid").unwrap();
+        assert!(midi.tracks.iter().all(|t| matches!(t.data, TrackData::Mapped(..))));
+        assert_eq!(midi.to_bytes(), MIDIFile::from_file("tests/tiny.mid").unwrap().to_bytes());
+        let iterated: Vec<MIDIMessage> = midi.tracks[0].clone().into_iter().collect();
+        assert_eq!(iterated.len(), midi.tracks[0].iter().count());
+        // Tracks of an RMID container borrow from inside its data chunk
+        let smf = fs::read("tests/tiny.mid").unwrap();
+        let mut rmid = b"RIFF\0\0\0\0RMIDdata".to_vec();
+        rmid.extend_from_slice(&(smf.len() as u32).to_le_bytes());
+        rmid.extend_from_slice(&smf);
+        let path = std::env::temp_dir().join("midiparse_test_mapped.rmi");
+        fs::write(&path, &rmid).unwrap();
+        let mapped = MIDIFile::from_file_mapped(path.to_str().unwrap()).unwrap();
+        assert_eq!(mapped.to_bytes(), midi.to_bytes());
+        assert!(matches!(MIDIFile::from_file_mapped("tests/missing.mid"), Err(MidiError::Io(_))));
+    }
+
+    #[test]
+    fn test_zip() {
+        use std::io::Write;
+        let smf = fs::read("tests/tiny.mid").unwrap();
+        let path = std::env::temp_dir().join("midiparse_test_member.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("tiny.mid", options).unwrap();
+        zip.write_all(&smf).unwrap();
+        zip.finish().unwrap();
+        let archive = path.to_str().unwrap();
+        let midi = MIDIFile::from_zip(archive, "tiny.mid").unwrap();
+        assert_eq!(midi.to_bytes(), MIDIFile::from_bytes(&smf).unwrap().to_bytes());
+        assert!(matches!(MIDIFile::from_zip(archive, "missing.mid"), Err(MidiError::Io(_))));
+        assert!(matches!(MIDIFile::from_zip_limited(archive, "tiny.mid", 100), Err(MidiError::Unsupported(..))));
+        assert!(matches!(MIDIFile::from_zip("tests/tiny.mid", "tiny.mid"), Err(MidiError::Io(_))));
+    }
+
+    #[test]
+    fn test_gzip_limit() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+        let smf = fs::read("tests/tiny.mid").unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&smf).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        assert_eq!(MIDIFile::inflate(&gzipped, smf.len() as u64).unwrap(), smf);
+        assert!(matches!(MIDIFile::inflate(&gzipped, smf.len() as u64 - 1), Err(MidiError::Header(..))));
+    }
+
+    #[test]
+    fn test_gzip() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+        let smf = fs::read("tests/tiny.mid").unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&smf).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let mf = MIDIFile::from_bytes(&gzipped).unwrap();
+        assert_eq!(mf.tracks.len(), 2);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&gzipped).unwrap();
+        assert!(matches!(MIDIFile::from_bytes(&encoder.finish().unwrap()), Err(MidiError::Unsupported(..))));
+    }
+
+    #[test]
+    fn test_songs() {
+        let mut mf = MIDIFile::from_file("tests/tiny.mid").expect("Read midi failed.");
+        assert_eq!(mf.songs().len(), 1);
+        mf.format = MIDIFormat::MultiSong;
+        let songs = mf.songs();
+        assert_eq!(songs.len(), 2);
+        assert!(songs.iter().all(|s| s.tracks.len() == 1 && s.format == MIDIFormat::SingleTrack));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use crate::sequence::{Note, Sequence};
+
+/// Next unread note of one track, ordered by start time and then track index.
+struct Head {
+    start: f32,
+    track: usize,
+    pos: usize,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.start.total_cmp(&other.start).then(self.track.cmp(&other.track))
+    }
+}
+
+/// Notes of all tracks as `(track_index, note)` in start order, merged from the
+/// individually sorted tracks.
+pub struct NoteMerge<'a> {
+    seq: &'a Sequence,
+    /// Note indices of every track in start order.
+    order: Vec<Vec<usize>>,
+    heap: BinaryHeap<Reverse<Head>>,
+}
+
+impl<'a> NoteMerge<'a> {
+    fn new(seq: &'a Sequence) -> Self {
+        let order: Vec<Vec<usize>> = seq.tracks.iter().map(|track| {
+            let mut idx: Vec<usize> = (0..track.notes.len()).collect();
+            idx.sort_by(|&a, &b| track.notes[a].start.total_cmp(&track.notes[b].start));
+            idx
+        }).collect();
+        let heap = order.iter().enumerate()
+            .filter(|(_, idx)| !idx.is_empty())
+            .map(|(track, idx)| Reverse(Head { start: seq.tracks[track].notes[idx[0]].start, track, pos: 0 }))
+            .collect();
+        NoteMerge { seq, order, heap }
+    }
+}
+
+impl<'a> Iterator for NoteMerge<'a> {
+    type Item = (usize, &'a Note);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(head) = self.heap.pop()?;
+        let notes = &self.seq.tracks[head.track].notes;
+        let note = &notes[self.order[head.track][head.pos]];
+        if let Some(&next) = self.order[head.track].get(head.pos + 1) {
+            self.heap.push(Reverse(Head { start: notes[next].start, track: head.track, pos: head.pos + 1 }));
+        }
+        Some((head.track, note))
+    }
+}
+
+impl Sequence {
+    /// All notes with their track index, sorted by start time. Notes starting together
+    /// come in track order.
+    pub fn iter_notes(&self) -> NoteMerge<'_> {
+        NoteMerge::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Track;
+
+    #[test]
+    fn test_iter_notes() {
+        let track = |starts: &[f32]| Track {
+            notes: starts.iter().map(|&start| Note { pitch: 60, start, duration: 1.0, velocity: 80, tie: false }).collect(),
+            ..Track::default()
+        };
+        let seq = Sequence {
+            tracks: vec![track(&[0.0, 2.0, 1.0]), Track::default(), track(&[1.0, 0.5])],
+            ..Sequence::default()
+        };
+        let merged: Vec<(usize, f32)> = seq.iter_notes().map(|(t, n)| (t, n.start)).collect();
+        assert_eq!(merged, vec![(0, 0.0), (2, 0.5), (0, 1.0), (2, 1.0), (0, 2.0)]);
+    }
+}
@@ -0,0 +1,191 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::sequence::{Note, Sequence};
+use crate::text::TextKind;
+
+/// One lyric event, its text without line break markers.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Syllable {
+    pub time: f32,
+    pub text: String,
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LyricLine {
+    /// The line starts a new paragraph, a cleared screen in Soft Karaoke.
+    pub paragraph: bool,
+    pub syllables: Vec<Syllable>,
+}
+
+/// Lyrics of a Soft Karaoke (.kar) file or of plain lyric metas, broken into lines.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Karaoke {
+    /// `@T` headers: title, then usually artist and sequencer credits.
+    pub title: Vec<String>,
+    /// `@L` header, e.g. "ENGL".
+    pub language: Option<String>,
+    /// `@I` headers.
+    pub info: Vec<String>,
+    pub lines: Vec<LyricLine>,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Syllable {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl LyricLine {
+    /// The syllables joined.
+    #[pyo3(name = "text")]
+    fn py_text(&self) -> String { self.text() }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Karaoke {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+impl LyricLine {
+    pub fn text(&self) -> String {
+        self.syllables.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    pub fn start(&self) -> Option<f32> {
+        self.syllables.first().map(|s| s.time)
+    }
+}
+
+impl Karaoke {
+    /// Syllables of all lines in order.
+    pub fn syllables(&self) -> impl Iterator<Item = &Syllable> {
+        self.lines.iter().flat_map(|line| line.syllables.iter())
+    }
+
+    fn push(&mut self, time: f32, text: &str) {
+        // "\" clears the screen and "/" breaks the line before a syllable, while lyric
+        // metas end a line with a trailing CR or LF
+        let (new_line, paragraph, text) = match text.chars().next() {
+            Some('\\') => (true, true, &text[1..]),
+            Some('/') => (true, false, &text[1..]),
+            _ => (false, false, text),
+        };
+        let ends_line = text.ends_with(['\r', '\n']);
+        let text = text.trim_end_matches(['\r', '\n']);
+        if new_line || self.lines.is_empty() {
+            self.lines.push(LyricLine { paragraph: paragraph || self.lines.is_empty(), syllables: Vec::new() });
+        }
+        if !text.is_empty() {
+            self.lines.last_mut().unwrap().syllables.push(Syllable { time, text: text.to_string() });
+        }
+        if ends_line {
+            self.lines.push(LyricLine { paragraph: false, syllables: Vec::new() });
+        }
+    }
+}
+
+impl Sequence {
+    /// Karaoke lyrics. Text events of a file with Soft Karaoke "@" headers are read as
+    /// syllables, otherwise the lyric metas are. `None` without any lyrics.
+    pub fn karaoke(&self) -> Option<Karaoke> {
+        let soft_karaoke = self.texts.iter().any(|t| t.kind == TextKind::Text && t.text.starts_with('@'));
+        let lyric_kind = if soft_karaoke { TextKind::Text } else { TextKind::Lyric };
+        let mut karaoke = Karaoke::default();
+        for event in self.texts.iter().filter(|t| t.kind == lyric_kind) {
+            match event.text.strip_prefix('@') {
+                Some(header) if soft_karaoke => {
+                    let (tag, value) = header.split_at(header.chars().next().map_or(0, char::len_utf8));
+                    let value = value.trim().to_string();
+                    match tag {
+                        "T" => karaoke.title.push(value),
+                        "L" => karaoke.language = Some(value),
+                        "I" => karaoke.info.push(value),
+                        _ => {} // @K file id and @V version
+                    }
+                }
+                _ => karaoke.push(event.time, &event.text),
+            }
+        }
+        karaoke.lines.retain(|line| !line.syllables.is_empty());
+        (!karaoke.lines.is_empty()).then_some(karaoke)
+    }
+
+    /// Each syllable of `karaoke` with the note of track `track_index` whose onset is
+    /// nearest, for singing voice datasets. Ties go to the earlier note.
+    pub fn align_lyrics(&self, track_index: usize) -> Result<Vec<(Note, String)>, &'static str> {
+        let track = self.tracks.get(track_index).ok_or("Track index out of range")?;
+        let mut notes = track.notes.clone();
+        notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+        if notes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let Some(karaoke) = self.karaoke() else { return Ok(Vec::new()) };
+        Ok(karaoke.syllables().map(|syllable| {
+            let idx = notes.partition_point(|n| n.start < syllable.time);
+            let nearest = match (idx.checked_sub(1).map(|i| &notes[i]), notes.get(idx)) {
+                (Some(before), Some(after)) if after.start - syllable.time < syllable.time - before.start => after,
+                (Some(before), _) => before,
+                (None, after) => after.unwrap(),
+            };
+            (*nearest, syllable.text.clone())
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextEvent;
+
+    fn texts(kind: TextKind, events: &[(f32, &str)]) -> Sequence {
+        let texts = events.iter()
+            .map(|&(time, text)| TextEvent { time, text: text.to_string(), kind })
+            .collect();
+        Sequence { texts, ..Sequence::default() }
+    }
+
+    #[test]
+    fn test_karaoke() {
+        let seq = texts(TextKind::Text, &[
+            (0.0, "@KMIDI KARAOKE FILE"), (0.0, "@LENGL"), (0.0, "@TTwinkle"), (0.0, "@TTraditional"),
+            (1.0, "\\Twin"), (1.5, "kle "), (2.0, "twin"), (2.5, "kle"),
+            (3.0, "/Lit"), (3.5, "tle "), (4.0, "star"),
+            (5.0, "\\How "), (6.0, "I"),
+        ]);
+        let karaoke = seq.karaoke().unwrap();
+        assert_eq!(karaoke.title, vec!["Twinkle", "Traditional"]);
+        assert_eq!(karaoke.language.as_deref(), Some("ENGL"));
+        let lines: Vec<(bool, String, Option<f32>)> = karaoke.lines.iter()
+            .map(|l| (l.paragraph, l.text(), l.start()))
+            .collect();
+        assert_eq!(lines, vec![
+            (true, "Twinkle twinkle".to_string(), Some(1.0)),
+            (false, "Little star".to_string(), Some(3.0)),
+            (true, "How I".to_string(), Some(5.0)),
+        ]);
+        assert_eq!(karaoke.syllables().count(), 9);
+
+        let seq = texts(TextKind::Lyric, &[(0.0, "Hel"), (0.5, "lo\r"), (1.0, "world\n")]);
+        let lines: Vec<String> = seq.karaoke().unwrap().lines.iter().map(LyricLine::text).collect();
+        assert_eq!(lines, vec!["Hello", "world"]);
+        assert!(Sequence::default().karaoke().is_none());
+    }
+
+    #[test]
+    fn test_align_lyrics() {
+        let mut seq = texts(TextKind::Lyric, &[(0.1, "Hel"), (0.9, "lo "), (2.6, "world")]);
+        let note = |start: f32| Note { pitch: 60, start, duration: 0.5, velocity: 80, tie: false };
+        seq.tracks.push(crate::sequence::Track { notes: vec![note(2.0), note(0.0), note(1.0), note(3.0)], ..Default::default() });
+        let aligned: Vec<(f32, String)> = seq.align_lyrics(0).unwrap().into_iter().map(|(n, s)| (n.start, s)).collect();
+        assert_eq!(aligned, vec![(0.0, "Hel".to_string()), (1.0, "lo ".to_string()), (3.0, "world".to_string())]);
+        assert!(seq.align_lyrics(1).is_err());
+    }
+}
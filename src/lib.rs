@@ -1,22 +1,163 @@
+mod abc;
+mod align;
+mod analysis;
+mod automation;
+mod batch;
+#[cfg(feature = "python")]
+mod buffer;
+mod chords;
+mod compare;
+mod csv;
+mod diff;
+mod error;
+mod fingerprint;
+mod gm;
+mod grid;
+mod groove;
+mod html;
 mod io;
+mod iter;
+mod karaoke;
+mod loader;
 mod message;
+mod midicsv;
+mod options;
+mod pattern;
+mod pitch;
+#[cfg(feature = "playback")]
+mod playback;
+mod probe;
+mod pymethods;
+mod record;
+mod report;
+mod roles;
+mod scheduler;
+mod serial;
 mod util;
 mod sequence;
+mod stats;
+mod stream;
+mod structure;
+#[cfg(feature = "synth")]
+mod synth;
+mod table;
+mod text;
+mod timing;
+mod transform;
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod writer;
 
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-pub use crate::io::{MIDIFile};
-pub use crate::message::{EventStatus, MIDIMessage, MIDIFormat, MetaStatus};
-pub use crate::util::{read_variable_length};
+pub use crate::align::TimeMapping;
+pub use crate::analysis::{key_from_name, key_name, DensityUnit, FrameLabels, Grid};
+pub use crate::automation::{ControlLane, FineControlChange};
+pub use crate::batch::{load_dir, midi_paths, BatchResults, CancelToken};
+#[cfg(feature = "python")]
+pub use crate::buffer::{FrameArray, NoteArray};
+#[cfg(all(feature = "python", feature = "synth"))]
+pub use crate::buffer::Samples;
+pub use crate::chords::{Chord, ToneRole};
+pub use crate::compare::{ApproxEq, DEFAULT_TOLERANCE};
+pub use crate::diff::SequenceDiff;
+pub use crate::error::MidiError;
+pub use crate::pattern::{DrumMatrix, DRUM_GROUPS};
+pub use crate::groove::GrooveHit;
+pub use crate::roles::TrackRole;
+pub use crate::gm::{drum_name, GmViolation, GM_DRUM_NAMES, GM_FAMILIES, GM_PROGRAM_NAMES};
+pub use crate::grid::Bar;
+pub use crate::io::{MIDIFile, MidiTrack, MAX_ZIP_MEMBER};
+pub use crate::iter::NoteMerge;
+pub use crate::karaoke::{Karaoke, LyricLine, Syllable};
+pub use crate::loader::{load, load_file, SourceFormat};
+pub use crate::message::{Event, EventStatus, MIDIMessage, MIDIFormat, Meta, MetaStatus, SysExJoiner, SysExMessage};
+pub use crate::options::{Overlap, ParseOptions, TextEncoding};
+#[cfg(feature = "playback")]
+pub use crate::playback::Player;
+pub use crate::pitch::{octave, pitch_class, pitch_from_name, pitch_name, MIDDLE_C_OCTAVE};
+pub use crate::probe::Probe;
+#[cfg(feature = "record")]
+pub use crate::record::Recorder;
+pub use crate::report::ParseReport;
+pub use crate::scheduler::Scheduler;
+pub use crate::serial::Format;
+pub use crate::util::{read_variable_length, write_delta_time, write_variable_length, MAX_VARIABLE_LENGTH};
 pub use crate::sequence::*;
+pub use crate::stats::{aggregate_statistics, CorpusStatistics};
+pub use crate::stream::EventStream;
+pub use crate::table::{Column, ControlTable, MetaTable, NoteTable};
+pub use crate::text::{Metadata, TextEvent, TextKind};
+pub use crate::timing::{EventTimes, TempoMap, TimeUnit, TrackTimes, TICKS_PER_QUARTER};
+pub use crate::transform::{MergeKey, Monophony, VoiceStealing};
+pub use crate::validate::Diagnostic;
+#[cfg(feature = "wasm")]
+pub use crate::wasm::{analyze_json, parse_json};
+pub use crate::writer::{Rounding, WriteOptions};
 
+#[cfg(feature = "python")]
 #[pymodule]
-fn midiparse_core(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+fn midiparse_core(py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<Sequence>()?;
     m.add_class::<Track>()?;
     m.add_class::<TrackTrans>()?;
+    m.add_class::<SequenceTrans>()?;
+    m.add_class::<NoteArray>()?;
+    m.add_class::<FrameArray>()?;
+    #[cfg(feature = "synth")]
+    m.add_class::<Samples>()?;
     m.add_class::<Note>()?;
+    m.add_class::<Instrument>()?;
+    m.add_class::<ControlChange>()?;
+    m.add_class::<SysEx>()?;
+    m.add_class::<ControlLane>()?;
+    m.add_class::<FineControlChange>()?;
+    m.add_class::<GmViolation>()?;
+    m.add_class::<ParseReport>()?;
+    m.add_class::<WriteOptions>()?;
+    m.add_class::<ParseOptions>()?;
     m.add_class::<TimeSignature>()?;
     m.add_class::<KeySignature>()?;
     m.add_class::<Tempo>()?;
+    m.add_class::<EventTimes>()?;
+    m.add_class::<TrackTimes>()?;
+    m.add_class::<TextEvent>()?;
+    m.add_class::<Metadata>()?;
+    m.add_class::<Karaoke>()?;
+    m.add_class::<LyricLine>()?;
+    m.add_class::<Syllable>()?;
+    m.add_class::<GrooveHit>()?;
+    m.add_class::<CorpusStatistics>()?;
+    m.add_class::<SequenceDiff>()?;
+    m.add_class::<MIDIFile>()?;
+    m.add_class::<EventStream>()?;
+    m.add_class::<Probe>()?;
+    #[cfg(feature = "playback")]
+    m.add_class::<Player>()?;
+    #[cfg(feature = "record")]
+    m.add_class::<Recorder>()?;
+    m.add_class::<CancelToken>()?;
+    m.add_function(wrap_pyfunction!(gm::py_drum_name, m)?)?;
+    m.add_function(wrap_pyfunction!(pitch::py_pitch_name, m)?)?;
+    m.add_function(wrap_pyfunction!(pitch::py_pitch_from_name, m)?)?;
+    m.add("MidiError", py.get_type::<error::exceptions::MidiError>())?;
+    m.add("MidiHeaderError", py.get_type::<error::exceptions::MidiHeaderError>())?;
+    m.add("MidiTrackError", py.get_type::<error::exceptions::MidiTrackError>())?;
+    m.add("MidiEventError", py.get_type::<error::exceptions::MidiEventError>())?;
+    m.add("UnsupportedFeatureError", py.get_type::<error::exceptions::UnsupportedFeatureError>())?;
+    m.add_function(wrap_pyfunction!(stats::py_aggregate_statistics, m)?)?;
+    m.add_function(wrap_pyfunction!(serial::py_unpickle, m)?)?;
+    m.add_function(wrap_pyfunction!(loader::py_load, m)?)?;
+    // Classes would otherwise claim to be builtins; `__reduce__` looks up `_unpickle`
+    // in the module they name
+    let name = m.name()?;
+    for (_, value) in m.dict() {
+        if let Ok(class) = value.downcast::<pyo3::types::PyType>() {
+            if class.getattr("__module__")?.extract::<&str>()? == "builtins" {
+                class.setattr("__module__", name)?;
+            }
+        }
+    }
     Ok(())
 }
\ No newline at end of file
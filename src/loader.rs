@@ -0,0 +1,151 @@
+use std::fs;
+use std::io::Read;
+use flate2::read::GzDecoder;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyBytes;
+use crate::error::{Location, MidiError};
+use crate::io::MIDIFile;
+use crate::sequence::Sequence;
+use crate::serial::MSGPACK_MAGIC;
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+
+/// What `load` found a file to be.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SourceFormat {
+    Midi,
+    /// An SMF wrapped in a RIFF `RMID` container.
+    Rmid,
+    /// An SMF with `@K` karaoke text events, as in `.kar` files.
+    Karaoke,
+    Json,
+    Yaml,
+    MessagePack,
+}
+
+impl SourceFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceFormat::Midi => "midi",
+            SourceFormat::Rmid => "rmid",
+            SourceFormat::Karaoke => "kar",
+            SourceFormat::Json => "json",
+            SourceFormat::Yaml => "yaml",
+            SourceFormat::MessagePack => "msgpack",
+        }
+    }
+
+    /// Tell the format from the leading bytes. Gzip is unwrapped by `load` beforehand.
+    pub fn sniff(data: &[u8]) -> Result<SourceFormat, MidiError> {
+        let unsupported = |what: &str| Err(MidiError::Unsupported(format!("{} is not supported.", what), Location::default()));
+        let text = std::str::from_utf8(data).map(|t| t.trim_start_matches('\u{FEFF}').trim_start());
+        match data {
+            [b'M', b'T', b'h', b'd', ..] if contains(data, b"@KMIDI") => Ok(SourceFormat::Karaoke),
+            [b'M', b'T', b'h', b'd', ..] => Ok(SourceFormat::Midi),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'R', b'M', b'I', b'D', ..] => Ok(SourceFormat::Rmid),
+            [b'P', b'K', 3, 4, ..] => unsupported("A zip archive, such as compressed MusicXML,"),
+            _ if data.starts_with(MSGPACK_MAGIC) => Ok(SourceFormat::MessagePack),
+            _ => match text {
+                Ok(text) if text.starts_with('{') => Ok(SourceFormat::Json),
+                Ok(text) if text.starts_with("<?xml") || text.starts_with("<score-") => unsupported("MusicXML"),
+                Ok(text) if text.starts_with("tracks:") || text.contains("\ntracks:") => Ok(SourceFormat::Yaml),
+                _ => Err(MidiError::Header("Unrecognized file format.".to_string(), Location { offset: Some(0), ..Location::default() })),
+            },
+        }
+    }
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
+
+/// One entry point for heterogeneous corpora: sniff MIDI, RMID, karaoke, or a sequence
+/// cached as JSON, YAML or MessagePack, any of them possibly gzipped, and parse it.
+/// Returns the format found and whether it was compressed. Only one level of gzip is
+/// unwrapped.
+pub fn load(data: &[u8], strict: bool) -> Result<(Sequence, SourceFormat, bool), MidiError> {
+    if data.starts_with(GZIP_MAGIC) {
+        let mut inflated = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut inflated)
+            .map_err(|_| MidiError::Header("Invalid gzip stream.".to_string(), Location::default()))?;
+        if inflated.starts_with(GZIP_MAGIC) {
+            return Err(MidiError::Unsupported("Nested gzip is not supported.".to_string(), Location::default()));
+        }
+        let (seq, format) = load_uncompressed(&inflated, strict)?;
+        return Ok((seq, format, true));
+    }
+    let (seq, format) = load_uncompressed(data, strict)?;
+    Ok((seq, format, false))
+}
+
+fn load_uncompressed(data: &[u8], strict: bool) -> Result<(Sequence, SourceFormat), MidiError> {
+    let format = SourceFormat::sniff(data)?;
+    let cached = |result: Result<Sequence, String>| result.map_err(|e| MidiError::Header(e, Location::default()));
+    let text = || std::str::from_utf8(data).map_err(|e| e.to_string());
+    let seq = match format {
+        SourceFormat::Midi | SourceFormat::Rmid | SourceFormat::Karaoke => {
+            Sequence::from_midi_with(&MIDIFile::from_bytes(data)?, strict)?
+        }
+        SourceFormat::Json => cached(text().and_then(Sequence::from_json))?,
+        SourceFormat::Yaml => cached(text().and_then(Sequence::from_yaml))?,
+        SourceFormat::MessagePack => cached(Sequence::from_msgpack(data))?,
+    };
+    Ok((seq, format))
+}
+
+pub fn load_file(path: &str, strict: bool) -> Result<(Sequence, SourceFormat, bool), MidiError> {
+    let data = fs::read(path).map_err(|e| MidiError::Io(format!("Can not read {}: {}", path, e)))?;
+    load(&data, strict)
+}
+
+/// Load a path or bytes of any supported format. The tag is the format name, with
+/// ".gz" appended for gzipped input.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "load", signature = (source, strict = true))]
+pub fn py_load(source: &PyAny, strict: bool) -> PyResult<(Sequence, String)> {
+    let (seq, format, compressed) = match source.downcast::<PyBytes>() {
+        Ok(bytes) => load(bytes.as_bytes(), strict)?,
+        Err(_) => load_file(&source.str()?.to_string(), strict)?,
+    };
+    Ok((seq, format!("{}{}", format.name(), if compressed { ".gz" } else { "" })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::{write::GzEncoder, Compression};
+
+    fn gzip_bytes(data: &[u8]) -> Vec<u8> {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::fast());
+        gzip.write_all(data).unwrap();
+        gzip.finish().unwrap()
+    }
+
+    #[test]
+    fn test_load() {
+        let data = fs::read("tests/tiny.mid").unwrap();
+        let (seq, format, compressed) = load(&data, true).unwrap();
+        assert_eq!((format, compressed), (SourceFormat::Midi, false));
+
+        let (cached, format, compressed) = load(&gzip_bytes(&seq.to_msgpack().unwrap()), true).unwrap();
+        assert_eq!((format, compressed), (SourceFormat::MessagePack, true));
+        assert_eq!(cached, seq);
+
+        assert_eq!(load(seq.to_json().unwrap().as_bytes(), true).unwrap().1, SourceFormat::Json);
+        assert_eq!(load(seq.to_yaml().unwrap().as_bytes(), true).unwrap().1, SourceFormat::Yaml);
+        let mut kar = data.clone();
+        kar.extend_from_slice(b"@KMIDI KARAOKE FILE");
+        assert_eq!(SourceFormat::sniff(&kar).unwrap(), SourceFormat::Karaoke);
+        assert!(matches!(SourceFormat::sniff(b"<?xml version=\"1.0\"?>"), Err(MidiError::Unsupported(..))));
+        assert!(matches!(load(b"hello", true), Err(MidiError::Header(..))));
+        // Bare MessagePack arrays are not taken for a cache
+        assert!(load(&[0x91; 64], true).is_err());
+        let mut twice = GzEncoder::new(Vec::new(), Compression::fast());
+        twice.write_all(&gzip_bytes(&data)).unwrap();
+        assert!(matches!(load(&twice.finish().unwrap(), true), Err(MidiError::Unsupported(..))));
+    }
+}
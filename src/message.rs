@@ -1,9 +1,14 @@
+use crate::text::TextKind;
+use crate::timing::qpm2tempo;
+use crate::util::{read_variable_length, write_variable_length, MAX_VARIABLE_LENGTH};
+
 #[derive(Debug, Clone)]
 pub enum MIDIMessage {
     // 二者栈上内存占用一致，且都存在enum的成员
     // 经过编译优化后，这层enum应该不会增加内存开销
     Event(Event),
     Meta(Meta),
+    SysEx(SysExMessage),
 }
 
 const EVENT_DATA_LEN: usize = 8;
@@ -22,6 +27,13 @@ pub struct Meta {
     pub data: Box<[u8]>, // Box为胖指针，大小为16字节
 } // Box，存在堆上空间，无法Copy, 实现Clone, 支持深拷贝，默认move
 
+/// A SysEx (F0) or escape (F7) message, stored as in the file: status, length, payload.
+#[derive(Debug, Clone)]
+pub struct SysExMessage {
+    pub time: u32,
+    pub data: Box<[u8]>,
+}
+
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum MIDIFormat {
     SingleTrack = 0,
@@ -43,8 +55,11 @@ pub enum EventStatus {
 
     // System Common Messages
     SysExStart = 0xF0,
+    MTCQuarterFrame = 0xF1,
     SongPositionPointer = 0xF2,
     SongSelect = 0xF3,
+    /// 0xF4, 0xF5, 0xF9 and 0xFD are reserved and carry no data.
+    UndefinedSystem = 0xF4,
     TuneRequest = 0xF6,
     SysExEnd = 0xF7,
     TimingClock = 0xF8,
@@ -67,6 +82,7 @@ pub enum MetaStatus {
     Marker = 0x06,
     CuePoint = 0x07,
     MIDIChannelPrefix = 0x20,
+    MIDIPort = 0x21,
     EndOfTrack = 0x2F,
     SetTempo = 0x51,
     SMPTEOffset = 0x54,
@@ -87,8 +103,10 @@ impl EventStatus {
             0xD0..=0xDF => (EventStatus::ChannelAfterTouch, 2),
             0xE0..=0xEF => (EventStatus::PitchBend, 3),
             0xF0 => (EventStatus::SysExStart, -1),
+            0xF1 => (EventStatus::MTCQuarterFrame, 2),
             0xF2 => (EventStatus::SongPositionPointer, 3),
             0xF3 => (EventStatus::SongSelect, 2),
+            0xF4 | 0xF5 | 0xF9 | 0xFD => (EventStatus::UndefinedSystem, 1),
             0xF6 => (EventStatus::TuneRequest, 1),
             0xF7 => (EventStatus::SysExEnd, 1),
             0xF8 => (EventStatus::TimingClock, 1),
@@ -97,7 +115,7 @@ impl EventStatus {
             0xFC => (EventStatus::StopSequence, 1),
             0xFE => (EventStatus::ActiveSensing, 1),
             0xFF => (EventStatus::Meta, -1),
-            _ => panic!("Event status code {:?} not implemented!", status),
+            _ => panic!("{:#04X} is a data byte, not a status code", status),
         }
     }
 }
@@ -114,6 +132,7 @@ impl MetaStatus {
             0x06 => MetaStatus::Marker,
             0x07 => MetaStatus::CuePoint,
             0x20 => MetaStatus::MIDIChannelPrefix,
+            0x21 => MetaStatus::MIDIPort,
             0x2F => MetaStatus::EndOfTrack,
             0x51 => MetaStatus::SetTempo,
             0x54 => MetaStatus::SMPTEOffset,
@@ -138,6 +157,11 @@ impl MIDIMessage {
         Self::Event(Event { time, status, data: arr })
     }
 
+    #[inline(always)]
+    pub fn new_sysex(time: u32, data: &[u8]) -> Self {
+        Self::SysEx(SysExMessage { time, data: data.into() })
+    }
+
     #[inline(always)]
     pub fn new_meta(time: u32, status_code: u8, data: &[u8]) -> Self {
         assert_eq!(status_code, 0xFF, "Can't take other msg as a meta msg!");
@@ -147,9 +171,154 @@ impl MIDIMessage {
         arr[1..].copy_from_slice(data);
         Self::Meta(Meta { time, status, data: arr.into() })
     }
+
+    /// Absolute tick within the track.
+    #[inline(always)]
+    pub fn time(&self) -> u32 {
+        match self {
+            MIDIMessage::Event(event) => event.time,
+            MIDIMessage::Meta(meta) => meta.time,
+            MIDIMessage::SysEx(sysex) => sysex.time,
+        }
+    }
+
+    /// The message as it is stored in a file, without the delta time.
+    #[inline(always)]
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            MIDIMessage::Event(event) => event.bytes(),
+            MIDIMessage::Meta(meta) => &meta.data,
+            MIDIMessage::SysEx(sysex) => &sysex.data,
+        }
+    }
+}
+
+/// Joins a SysEx split into an F0 packet and F7 continuation packets back into one
+/// message. Escape packets, F7 outside a SysEx, carry raw bytes and pass through as they are.
+#[derive(Debug, Default)]
+pub struct SysExJoiner {
+    pending: Option<(u32, Vec<u8>)>,
+}
+
+impl SysExJoiner {
+    /// Feed the next SysEx packet of a track, appending the messages it completes to
+    /// `joined`. The declared lengths are followed, so an F7 inside a payload is data.
+    pub fn push(&mut self, packet: &SysExMessage, joined: &mut Vec<SysExMessage>) {
+        let payload = packet.payload();
+        let payload = payload.strip_suffix(&[0xF7]).unwrap_or(payload);
+        match (packet.status(), self.pending.as_mut()) {
+            (0xF0, _) => {
+                joined.extend(self.finish());
+                self.pending = Some((packet.time, payload.to_vec()));
+            }
+            (_, Some((_, data))) => data.extend_from_slice(payload),
+            (_, None) => {
+                joined.push(packet.clone());
+                return;
+            }
+        }
+        if packet.is_terminated() {
+            joined.extend(self.pending.take().map(|(time, data)| SysExMessage::new(time, &data)));
+        }
+    }
+
+    /// The SysEx still waiting for its final F7 at the end of the track, as a single
+    /// unterminated packet.
+    pub fn finish(&mut self) -> Option<SysExMessage> {
+        self.pending.take().map(|(time, data)| SysExMessage::encode(time, &data, false))
+    }
+}
+
+impl SysExMessage {
+    /// A complete SysEx in one F0 packet; `payload` excludes the framing F0 and F7 and
+    /// is cut to the `MAX_VARIABLE_LENGTH` bytes a packet holds.
+    pub fn new(time: u32, payload: &[u8]) -> Self {
+        Self::encode(time, payload, true)
+    }
+
+    fn encode(time: u32, payload: &[u8], terminated: bool) -> Self {
+        let payload = &payload[..payload.len().min((MAX_VARIABLE_LENGTH - terminated as u32) as usize)];
+        let mut data = vec![0xF0];
+        write_variable_length(&mut data, payload.len() as u32 + terminated as u32).unwrap();
+        data.extend_from_slice(payload);
+        if terminated {
+            data.push(0xF7);
+        }
+        SysExMessage { time, data: data.into() }
+    }
+
+    #[inline(always)]
+    pub fn status(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// Bytes after the length, including the terminating F7 if present.
+    #[inline(always)]
+    pub fn payload(&self) -> &[u8] {
+        let bytes = read_variable_length(&self.data[1..]).map_or(0, |(bytes, _)| bytes);
+        &self.data[1 + bytes..]
+    }
+
+    /// Whether the packet ends the SysEx, otherwise F7 continuation packets follow.
+    #[inline(always)]
+    pub fn is_terminated(&self) -> bool {
+        self.payload().last() == Some(&0xF7)
+    }
 }
 
 impl Event {
+    /// A channel voice message; the channel and data bytes must fit 4 and 7 bits.
+    pub fn new_channel(time: u32, status: EventStatus, channel: u8, data1: u8, data2: u8) -> Result<Self, &'static str> {
+        if !(0x80..0xF0).contains(&(status as u8)) {
+            return Err("Not a channel voice status");
+        }
+        if channel > 0x0F {
+            return Err("Channel out of range 0-15");
+        }
+        if data1 > 0x7F || data2 > 0x7F {
+            return Err("Data byte out of range 0-127");
+        }
+        let mut data = [0; EVENT_DATA_LEN];
+        data[0] = status as u8 | channel;
+        data[1] = data1;
+        // Two byte messages keep the unused byte zeroed
+        if EventStatus::from_status_code(status as u8).1 == 3 {
+            data[2] = data2;
+        }
+        Ok(Event { time, status, data })
+    }
+
+    pub fn new_note_on(time: u32, channel: u8, pitch: u8, velocity: u8) -> Result<Self, &'static str> {
+        Self::new_channel(time, EventStatus::NoteOn, channel, pitch, velocity)
+    }
+
+    pub fn new_note_off(time: u32, channel: u8, pitch: u8, velocity: u8) -> Result<Self, &'static str> {
+        Self::new_channel(time, EventStatus::NoteOff, channel, pitch, velocity)
+    }
+
+    pub fn new_control_change(time: u32, channel: u8, number: u8, value: u8) -> Result<Self, &'static str> {
+        Self::new_channel(time, EventStatus::ControlChange, channel, number, value)
+    }
+
+    pub fn new_program_change(time: u32, channel: u8, program: u8) -> Result<Self, &'static str> {
+        Self::new_channel(time, EventStatus::ProgramChange, channel, program, 0)
+    }
+
+    /// Pitch bend from -8192 to 8191, 0 being the center.
+    pub fn new_pitch_bend(time: u32, channel: u8, value: i16) -> Result<Self, &'static str> {
+        if !(-8192..=8191).contains(&value) {
+            return Err("Pitch bend out of range -8192-8191");
+        }
+        let raw = (value + 8192) as u16;
+        Self::new_channel(time, EventStatus::PitchBend, channel, (raw & 0x7F) as u8, (raw >> 7) as u8)
+    }
+
+    /// Status and data bytes as they are written to a file.
+    #[inline(always)]
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..EventStatus::from_status_code(self.data[0]).1.max(1) as usize]
+    }
+
     #[inline(always)]
     pub fn channel(&self) -> Option<u8> {
         match self.status {
@@ -184,6 +353,16 @@ impl Event {
         }
     }
 
+    /// Pressure of a channel or polyphonic aftertouch.
+    #[inline(always)]
+    pub fn pressure(&self) -> Option<u8> {
+        match self.status {
+            EventStatus::ChannelAfterTouch => Some(self.data[1]),
+            EventStatus::PolyphonicAfterTouch => Some(self.data[2]),
+            _ => None
+        }
+    }
+
     #[inline(always)]
     pub fn control_change(&self) -> Option<(u8, u8)> {
         match self.status {
@@ -199,12 +378,66 @@ impl Event {
             _ => None
         }
     }
+
+    #[inline(always)]
+    pub fn pitch_bend(&self) -> Option<i16> {
+        match self.status {
+            EventStatus::PitchBend => Some(((self.data[2] as i16) << 7 | self.data[1] as i16) - 8192),
+            _ => None
+        }
+    }
 }
 
 impl Meta {
+    /// Encode a meta message of type `meta_type` with its length prefix, cutting `payload`
+    /// to the `MAX_VARIABLE_LENGTH` bytes the prefix holds.
+    pub fn new(time: u32, meta_type: u8, payload: &[u8]) -> Self {
+        let payload = &payload[..payload.len().min(MAX_VARIABLE_LENGTH as usize)];
+        let mut data = vec![0xFF, meta_type];
+        write_variable_length(&mut data, payload.len() as u32).unwrap();
+        data.extend_from_slice(payload);
+        Meta { time, status: MetaStatus::from_status_code(meta_type), data: data.into() }
+    }
+
+    /// SetTempo for `qpm` quarters per minute, clamped to the 24 bit microsecond range.
+    pub fn new_tempo(time: u32, qpm: f32) -> Self {
+        let us = qpm2tempo(qpm).clamp(1, 0xFFFFFF) as u32;
+        Self::new(time, MetaStatus::SetTempo as u8, &us.to_be_bytes()[1..])
+    }
+
+    /// TimeSignature; `denominator` must be a power of two.
+    pub fn new_time_signature(
+        time: u32, numerator: u8, denominator: u8, clocks_per_click: u8, thirty_seconds_per_quarter: u8,
+    ) -> Result<Self, &'static str> {
+        if numerator == 0 || !denominator.is_power_of_two() {
+            return Err("Time signature needs a numerator and a power of two denominator");
+        }
+        let payload = [numerator, denominator.trailing_zeros() as u8, clocks_per_click, thirty_seconds_per_quarter];
+        Ok(Self::new(time, MetaStatus::TimeSignature as u8, &payload))
+    }
+
+    /// KeySignature with `sharps` from -7 (flats) to 7.
+    pub fn new_key_signature(time: u32, major: bool, sharps: i8) -> Result<Self, &'static str> {
+        if !(-7..=7).contains(&sharps) {
+            return Err("Key signature needs -7 to 7 sharps");
+        }
+        Ok(Self::new(time, MetaStatus::KeySignature as u8, &[sharps as u8, !major as u8]))
+    }
+
+    pub fn new_text(time: u32, kind: TextKind, text: &str) -> Self {
+        Self::new(time, kind.meta_type(), text.as_bytes())
+    }
+
+    pub fn new_end_of_track(time: u32) -> Self {
+        Self::new(time, MetaStatus::EndOfTrack as u8, &[])
+    }
+
+    /// Payload after the type and length.
     #[inline(always)]
     pub fn meta_value(&self) -> &[u8] {
-        &self.data[3..]
+        let rest = self.data.get(2..).unwrap_or_default();
+        let bytes = read_variable_length(rest).map_or(0, |(bytes, _)| bytes);
+        &rest[bytes..]
     }
 
     #[inline(always)]
@@ -212,32 +445,121 @@ impl Meta {
         match self.status {
             MetaStatus::SetTempo => {
                 let mut tempo = [0; 4];
-                tempo[1..].copy_from_slice(&self.data[3..6]);
+                tempo[1..].copy_from_slice(self.data.get(3..6)?);
                 Some(u32::from_be_bytes(tempo))
             }
             _ => None
         }
     }
 
+    /// Port of a MIDIPort meta, which addresses more than 16 channels.
+    #[inline(always)]
+    pub fn port(&self) -> Option<u8> {
+        match self.status {
+            MetaStatus::MIDIPort => self.meta_value().first().copied(),
+            _ => None,
+        }
+    }
+
+    /// Channel of a MIDIChannelPrefix meta, which the metas and SysEx after it refer to.
+    #[inline(always)]
+    pub fn channel_prefix(&self) -> Option<u8> {
+        match self.status {
+            MetaStatus::MIDIChannelPrefix => self.meta_value().first().copied().filter(|&c| c < 16),
+            _ => None,
+        }
+    }
+
+    /// `None` for other metas, and for key signatures that are truncated or out of range.
     #[inline(always)]
     pub fn key_signature(&self) -> Option<(bool, i8)> {
         // bC bG bD bA bE bB F C G D A E B #F #C
         // bc bg bd ba be bb f c g d a e b #f #c
         if let MetaStatus::KeySignature = self.status {
-            let key = self.data[3] as i8;
-            assert!(key >= -7 && key <= 7, "KeySignature: {} is invalid", key);
-            Some((self.data[4] == 0, key as i8))
+            let key = *self.data.get(3)? as i8;
+            if !(-7..=7).contains(&key) {
+                return None;
+            }
+            Some((*self.data.get(4)? == 0, key))
         } else { None }
     }
     #[inline(always)]
     pub fn time_signature(&self) -> Option<(u8, u8, u8, u8)> {
         match self.status {
-            MetaStatus::TimeSignature => Some((
-                self.data[3],
-                1 << self.data[4],
-                self.data[5],
-                self.data[6])),
+            MetaStatus::TimeSignature => {
+                let ts = self.data.get(3..7)?;
+                Some((ts[0], 1_u8.checked_shl(ts[1] as u32)?, ts[2], ts[3]))
+            }
             _ => None,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meta_builders() {
+        let tempo = Meta::new_tempo(0, 120.0);
+        assert_eq!(&tempo.data[..], &[0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]);
+        assert_eq!(tempo.tempo(), Some(500000));
+        let ts = Meta::new_time_signature(0, 6, 8, 36, 8).unwrap();
+        assert_eq!(ts.time_signature(), Some((6, 8, 36, 8)));
+        assert!(Meta::new_time_signature(0, 3, 6, 24, 8).is_err());
+        let ks = Meta::new_key_signature(0, false, -3).unwrap();
+        assert_eq!(ks.key_signature(), Some((false, -3)));
+        assert!(Meta::new_key_signature(0, true, 8).is_err());
+        // Long texts take a two byte length
+        let lyric = "la ".repeat(50);
+        let text = Meta::new_text(0, TextKind::Lyric, &lyric);
+        assert_eq!((text.status, &text.data[2..4]), (MetaStatus::Lyric, &[0x81, 0x16][..]));
+        assert_eq!(text.meta_value(), lyric.as_bytes());
+        assert_eq!(&Meta::new_end_of_track(0).data[..], &[0xFF, 0x2F, 0x00]);
+    }
+
+    #[test]
+    fn test_sysex_joiner() {
+        let packets = [
+            SysExMessage { time: 0, data: vec![0xF0, 0x03, 0x43, 0x10, 0x4C].into() },
+            SysExMessage { time: 4, data: vec![0xF7, 0x02, 0x00, 0x7E].into() },
+            SysExMessage { time: 8, data: vec![0xF7, 0x02, 0x00, 0xF7].into() },
+            SysExMessage { time: 8, data: vec![0xF7, 0x01, 0xF8].into() },
+            SysExMessage { time: 9, data: vec![0xF0, 0x02, 0x7E, 0x7F].into() },
+            SysExMessage { time: 9, data: vec![0xF0, 0x02, 0x41, 0xF7].into() },
+            SysExMessage { time: 10, data: vec![0xF0, 0x01, 0x43].into() },
+        ];
+        let mut joiner = SysExJoiner::default();
+        let mut joined = Vec::new();
+        for packet in &packets {
+            joiner.push(packet, &mut joined);
+        }
+        joined.extend(joiner.finish());
+        let joined: Vec<(u32, &[u8], bool)> = joined.iter().map(|m| (m.time, &m.data[..], m.is_terminated())).collect();
+        assert_eq!(joined, vec![
+            (0, &[0xF0, 0x07, 0x43, 0x10, 0x4C, 0x00, 0x7E, 0x00, 0xF7][..], true),
+            (8, &[0xF7, 0x01, 0xF8][..], false),
+            (9, &[0xF0, 0x02, 0x7E, 0x7F][..], false),
+            (9, &[0xF0, 0x02, 0x41, 0xF7][..], true),
+            (10, &[0xF0, 0x01, 0x43][..], false),
+        ]);
+    }
+
+    #[test]
+    fn test_event_builders() {
+        let on = Event::new_note_on(10, 3, 60, 100).unwrap();
+        assert_eq!((on.bytes(), on.channel(), on.key()), (&[0x93, 60, 100][..], Some(3), Some(60)));
+        let program = Event::new_program_change(0, 9, 25).unwrap();
+        assert_eq!((program.bytes(), program.program()), (&[0xC9, 25][..], Some(25)));
+        assert_eq!(Event::new_control_change(0, 0, 7, 90).unwrap().control_change(), Some((7, 90)));
+        assert_eq!(Event::new_note_off(0, 0, 60, 0).unwrap().status, EventStatus::NoteOff);
+        for value in [-8192, -1, 0, 8191] {
+            assert_eq!(Event::new_pitch_bend(0, 0, value).unwrap().pitch_bend(), Some(value));
+        }
+        assert_eq!(Event::new_pitch_bend(0, 0, 0).unwrap().bytes(), &[0xE0, 0x00, 0x40]);
+        assert!(Event::new_note_on(0, 16, 60, 100).is_err());
+        assert!(Event::new_note_on(0, 0, 128, 100).is_err());
+        assert!(Event::new_pitch_bend(0, 0, 8192).is_err());
+        assert!(Event::new_channel(0, EventStatus::TimingClock, 0, 0, 0).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt::Write;
+use std::fs;
+use crate::error::{Location, MidiError};
+use crate::io::{MIDIFile, MidiTrack};
+use crate::message::{EventStatus, MIDIFormat, MIDIMessage, Meta, MetaStatus};
+use crate::util::{write_delta_time, write_variable_length};
+
+/// midicsv record names of the text metas, by meta type.
+const TEXT_RECORDS: [(u8, &str); 7] = [
+    (0x01, "Text_t"), (0x02, "Copyright_t"), (0x03, "Title_t"), (0x04, "Instrument_name_t"),
+    (0x05, "Lyric_t"), (0x06, "Marker_t"), (0x07, "Cue_point_t"),
+];
+
+/// A midicsv string: quotes doubled, backslashes doubled, control characters as octal
+/// escapes, and other bytes as Latin-1.
+fn quote(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' => text.push_str("\"\""),
+            b'\\' => text.push_str("\\\\"),
+            0..=31 | 127..=159 => write!(text, "\\{:03o}", b).unwrap(),
+            _ => text.push(b as char),
+        }
+    }
+    text.push('"');
+    text
+}
+
+fn unquote(field: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut chars = field.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'\\') => {
+                chars.next();
+                bytes.push(b'\\');
+            }
+            '\\' => {
+                let octal: String = (0..3).map_while(|_| chars.next_if(|c| c.is_digit(8))).collect();
+                bytes.push(u8::from_str_radix(&octal, 8).unwrap_or(b'\\'));
+            }
+            c if (c as u32) < 256 => bytes.push(c as u8),
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+/// Comma separated fields of a record, trimmed, with quoted strings unquoted as they are.
+fn split_record(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    // None outside quotes, Some(closed) within a quoted field
+    let mut quoted: Option<bool> = None;
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', Some(false)) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', Some(false)) => quoted = Some(true),
+            ('"', None) => {
+                field.clear();
+                quoted = Some(false);
+            }
+            (',', Some(true) | None) => {
+                let field = std::mem::take(&mut field);
+                fields.push(if quoted.is_some() { field } else { field.trim().to_string() });
+                quoted = None;
+            }
+            (_, Some(true)) => {}
+            (c, _) => field.push(c),
+        }
+    }
+    fields.push(if quoted.is_some() { field } else { field.trim().to_string() });
+    fields
+}
+
+fn data_list(data: &[u8]) -> String {
+    let mut text = data.len().to_string();
+    data.iter().for_each(|b| write!(text, ", {}", b).unwrap());
+    text
+}
+
+impl MIDIFile {
+    /// The file in the text format of John Walker's midicsv: one `track, tick, record,
+    /// parameters` line per message, tracks numbered from 1 after a track 0 header.
+    /// System common and real-time messages have no record and are left out.
+    pub fn midicsv(&self) -> String {
+        let mut csv = String::new();
+        writeln!(csv, "0, 0, Header, {}, {}, {}", self.format as u8, self.tracks.len(), self.division).unwrap();
+        for (idx, track) in self.tracks.iter().enumerate() {
+            let number = idx + 1;
+            writeln!(csv, "{}, 0, Start_track", number).unwrap();
+            let mut end = 0;
+            for message in track.iter() {
+                let time = message.time();
+                end = time;
+                let record = match message {
+                    MIDIMessage::Event(event) => {
+                        let channel = event.channel().unwrap_or(0);
+                        let d = &event.data;
+                        match event.status {
+                            EventStatus::NoteOn => format!("Note_on_c, {}, {}, {}", channel, d[1], d[2]),
+                            EventStatus::NoteOff => format!("Note_off_c, {}, {}, {}", channel, d[1], d[2]),
+                            EventStatus::PolyphonicAfterTouch => format!("Poly_aftertouch_c, {}, {}, {}", channel, d[1], d[2]),
+                            EventStatus::ControlChange => format!("Control_c, {}, {}, {}", channel, d[1], d[2]),
+                            EventStatus::ProgramChange => format!("Program_c, {}, {}", channel, d[1]),
+                            EventStatus::ChannelAfterTouch => format!("Channel_aftertouch_c, {}, {}", channel, d[1]),
+                            EventStatus::PitchBend => format!("Pitch_bend_c, {}, {}", channel, d[1] as u16 | (d[2] as u16) << 7),
+                            _ => continue,
+                        }
+                    }
+                    MIDIMessage::SysEx(sysex) => {
+                        let name = if sysex.status() == 0xF0 { "System_exclusive" } else { "System_exclusive_packet" };
+                        format!("{}, {}", name, data_list(sysex.payload()))
+                    }
+                    MIDIMessage::Meta(meta) => {
+                        let value = meta.meta_value();
+                        let byte = |i: usize| value.get(i).copied().unwrap_or(0);
+                        match meta.status {
+                            MetaStatus::EndOfTrack => break,
+                            MetaStatus::SequenceNumber => format!("Sequence_number, {}", (byte(0) as u16) << 8 | byte(1) as u16),
+                            MetaStatus::MIDIChannelPrefix => format!("Channel_prefix, {}", byte(0)),
+                            MetaStatus::MIDIPort => format!("MIDI_port, {}", byte(0)),
+                            MetaStatus::SetTempo => format!("Tempo, {}", meta.tempo().unwrap_or(0)),
+                            MetaStatus::SMPTEOffset => format!("SMPTE_offset, {}, {}, {}, {}, {}", byte(0), byte(1), byte(2), byte(3), byte(4)),
+                            MetaStatus::TimeSignature => format!("Time_signature, {}, {}, {}, {}", byte(0), byte(1), byte(2), byte(3)),
+                            MetaStatus::KeySignature => format!("Key_signature, {}, \"{}\"", byte(0) as i8, if byte(1) == 0 { "major" } else { "minor" }),
+                            MetaStatus::SequencerSpecificMeta => format!("Sequencer_specific, {}", data_list(value)),
+                            _ => match TEXT_RECORDS.iter().find(|(t, _)| *t == meta.data[1]) {
+                                Some((_, name)) => format!("{}, {}", name, quote(value)),
+                                None => format!("Unknown_meta_event, {}, {}", meta.data[1], data_list(value)),
+                            },
+                        }
+                    }
+                };
+                writeln!(csv, "{}, {}, {}", number, time, record).unwrap();
+            }
+            writeln!(csv, "{}, {}, End_track", number, end).unwrap();
+        }
+        csv.push_str("0, 0, End_of_file\n");
+        csv
+    }
+
+    pub fn to_midicsv(&self, path: &str) -> Result<(), MidiError> {
+        fs::write(path, self.midicsv()).map_err(|e| MidiError::Io(format!("{}: {}", path, e)))
+    }
+
+    /// Read midicsv text back into a file. Blank lines and lines starting with `#` or `;`
+    /// are skipped, records of a track must be in time order.
+    pub fn from_midicsv_str(text: &str) -> Result<MIDIFile, MidiError> {
+        let mut header = None;
+        let mut tracks: Vec<(Vec<u8>, u32)> = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            let fields = split_record(line);
+            let number = |i: usize| fields.get(i).and_then(|f| f.parse::<i64>().ok());
+            let (Some(track), Some(tick)) = (number(0), number(1)) else {
+                return Err(MidiError::Header(format!("Invalid midicsv record: {}", line), Location::default()));
+            };
+            // Tracks count from 1 and must be within the count the header declares
+            let declared = header.map_or(0, |(_, count, _)| count);
+            if !(0..=declared).contains(&track) {
+                return Err(MidiError::Track(format!(
+                    "midicsv track {} outside the {} tracks of the header: {}", track, declared, line), Location::default()));
+            }
+            let location = Location { track: Some(track.max(1) as usize - 1), tick: u32::try_from(tick).ok(), offset: None };
+            let invalid = || MidiError::Event(format!("Invalid midicsv record: {}", line), location);
+            let record = fields.get(2).ok_or_else(invalid)?.as_str();
+            if track == 0 {
+                match record {
+                    "Header" => header = Some((
+                        number(3).ok_or_else(invalid)?,
+                        number(4).filter(|n| (0..=u16::MAX as i64).contains(n)).ok_or_else(invalid)?,
+                        number(5).ok_or_else(invalid)?,
+                    )),
+                    "End_of_file" => break,
+                    _ => return Err(invalid()),
+                }
+                continue;
+            }
+            let track = track as usize;
+            if tracks.len() < track {
+                tracks.resize_with(track, Default::default);
+            }
+            let (data, last) = &mut tracks[track - 1];
+            let tick = u32::try_from(tick).map_err(|_| invalid())?;
+            if tick < *last {
+                return Err(MidiError::Event(format!("midicsv record before the previous one: {}", line), location));
+            }
+            let byte = |i: usize| number(i).filter(|n| (0..=255).contains(n)).map(|n| n as u8).ok_or_else(invalid);
+            let data_bytes = |i: usize| -> Result<Vec<u8>, MidiError> {
+                let len = number(i).ok_or_else(invalid)? as usize;
+                let bytes = (0..len).map(|j| byte(i + 1 + j)).collect::<Result<Vec<u8>, _>>()?;
+                Ok(bytes)
+            };
+            let channel = |status: u8| Ok::<u8, MidiError>(status | byte(3)? & 0x0F);
+            let bytes: Vec<u8> = match record {
+                "Start_track" => continue,
+                "End_track" => Meta::new_end_of_track(tick).data.into(),
+                "Note_on_c" => vec![channel(0x90)?, byte(4)?, byte(5)?],
+                "Note_off_c" => vec![channel(0x80)?, byte(4)?, byte(5)?],
+                "Poly_aftertouch_c" => vec![channel(0xA0)?, byte(4)?, byte(5)?],
+                "Control_c" => vec![channel(0xB0)?, byte(4)?, byte(5)?],
+                "Program_c" => vec![channel(0xC0)?, byte(4)?],
+                "Channel_aftertouch_c" => vec![channel(0xD0)?, byte(4)?],
+                "Pitch_bend_c" => {
+                    let value = number(4).filter(|v| (0..16384).contains(v)).ok_or_else(invalid)?;
+                    vec![channel(0xE0)?, (value & 0x7F) as u8, (value >> 7) as u8]
+                }
+                "System_exclusive" | "System_exclusive_packet" => {
+                    let payload = data_bytes(3)?;
+                    let mut bytes = vec![if record == "System_exclusive" { 0xF0 } else { 0xF7 }];
+                    write_variable_length(&mut bytes, payload.len() as u32).map_err(|_| invalid())?;
+                    bytes.extend(payload);
+                    bytes
+                }
+                "Sequence_number" => {
+                    let value = number(3).filter(|v| (0..65536).contains(v)).ok_or_else(invalid)? as u16;
+                    Meta::new(tick, MetaStatus::SequenceNumber as u8, &value.to_be_bytes()).data.into()
+                }
+                "Channel_prefix" => Meta::new(tick, MetaStatus::MIDIChannelPrefix as u8, &[byte(3)?]).data.into(),
+                "MIDI_port" => Meta::new(tick, MetaStatus::MIDIPort as u8, &[byte(3)?]).data.into(),
+                "Tempo" => {
+                    let tempo = number(3).filter(|v| (0..1 << 24).contains(v)).ok_or_else(invalid)? as u32;
+                    Meta::new(tick, MetaStatus::SetTempo as u8, &tempo.to_be_bytes()[1..]).data.into()
+                }
+                "SMPTE_offset" => {
+                    let payload = (3..8).map(byte).collect::<Result<Vec<u8>, _>>()?;
+                    Meta::new(tick, MetaStatus::SMPTEOffset as u8, &payload).data.into()
+                }
+                "Time_signature" => {
+                    let payload = (3..7).map(byte).collect::<Result<Vec<u8>, _>>()?;
+                    Meta::new(tick, MetaStatus::TimeSignature as u8, &payload).data.into()
+                }
+                "Key_signature" => {
+                    let sharps = number(3).filter(|v| (-7..=7).contains(v)).ok_or_else(invalid)? as i8;
+                    let minor = match fields.get(4).map(|f| f.to_lowercase()).as_deref() {
+                        Some("major") => 0,
+                        Some("minor") => 1,
+                        _ => return Err(invalid()),
+                    };
+                    Meta::new(tick, MetaStatus::KeySignature as u8, &[sharps as u8, minor]).data.into()
+                }
+                "Sequencer_specific" => Meta::new(tick, MetaStatus::SequencerSpecificMeta as u8, &data_bytes(3)?).data.into(),
+                "Unknown_meta_event" => Meta::new(tick, byte(3)?, &data_bytes(4)?).data.into(),
+                name => match TEXT_RECORDS.iter().find(|(_, n)| *n == name) {
+                    Some(&(meta_type, _)) => {
+                        let text = unquote(fields.get(3).ok_or_else(invalid)?);
+                        Meta::new(tick, meta_type, &text).data.into()
+                    }
+                    None => return Err(MidiError::Event(format!("Unknown midicsv record {}", name), location)),
+                },
+            };
+            write_delta_time(data, (tick - *last) as u64);
+            data.extend_from_slice(&bytes);
+            *last = tick;
+        }
+
+        let (format, _, division) = header.ok_or_else(|| MidiError::Header("midicsv text has no Header record".to_string(), Location::default()))?;
+        let format = match format {
+            0 => MIDIFormat::SingleTrack,
+            1 => MIDIFormat::MultiTrack,
+            2 => MIDIFormat::MultiSong,
+            _ => return Err(MidiError::Unsupported(format!("MIDI format {} is not supported.", format), Location::default())),
+        };
+        Ok(MIDIFile {
+            format,
+            division: u16::try_from(division).map_err(|_| MidiError::Header(format!("Invalid division {}", division), Location::default()))?,
+            declared_tracks: tracks.len() as u16,
+            tracks: tracks.into_iter().enumerate().map(|(idx, (data, _))| MidiTrack::new(idx as u16, data)).collect(),
+            warnings: Vec::new(),
+        })
+    }
+
+    pub fn from_midicsv(path: &str) -> Result<MIDIFile, MidiError> {
+        let text = fs::read_to_string(path).map_err(|e| MidiError::Io(format!("{}: {}", path, e)))?;
+        Self::from_midicsv_str(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{Note, Sequence, Track};
+    use crate::writer::WriteOptions;
+
+    const CSV: &str = "\
+0, 0, Header, 1, 1, 480
+1, 0, Start_track
+1, 0, Title_t, \"Say \"\"hi\"\", \\\\ \\011 \"
+1, 0, Tempo, 500000
+1, 0, Time_signature, 6, 3, 36, 8
+1, 0, Key_signature, -2, \"minor\"
+1, 0, Program_c, 1, 40
+1, 0, System_exclusive, 3, 65, 16, 247
+1, 240, Note_on_c, 1, 60, 100
+1, 480, Pitch_bend_c, 1, 8192
+1, 720, Note_off_c, 1, 60, 0
+1, 960, End_track
+0, 0, End_of_file
+";
+
+    #[test]
+    fn test_midicsv_round_trip() {
+        let midi = MIDIFile::from_midicsv_str(CSV).unwrap();
+        assert_eq!(midi.midicsv(), CSV);
+        let seq = Sequence::from_midi(&midi).unwrap();
+        assert_eq!(seq.tracks[0].name, "Say \"hi\", \\ \t ");
+        assert_eq!(seq.tracks[0].notes, vec![Note { pitch: 60, start: 0.5, duration: 1.0, velocity: 100, tie: false }]);
+        assert_eq!(seq.key_signatures[0].key, (false, -2));
+    }
+
+    #[test]
+    fn test_midicsv_from_sequence() {
+        let seq = Sequence::from_tracks(vec![Track {
+            notes: vec![Note { pitch: 64, start: 0.0, duration: 1.0, velocity: 90, tie: false }],
+            ..Track::default()
+        }], 120.0);
+        let midi = seq.to_midi(&WriteOptions::default()).unwrap();
+        let back = MIDIFile::from_midicsv_str(&midi.midicsv()).unwrap();
+        assert_eq!(back.to_bytes(), midi.to_bytes());
+    }
+
+    #[test]
+    fn test_midicsv_errors() {
+        assert!(MIDIFile::from_midicsv_str("1, 0, Start_track\n").is_err());
+        let unknown = MIDIFile::from_midicsv_str("0, 0, Header, 0, 1, 96\n1, 0, Bogus, 1\n");
+        assert!(matches!(unknown, Err(MidiError::Event(_, Location { track: Some(0), .. }))));
+        let backwards = "0, 0, Header, 0, 1, 96\n1, 10, Tempo, 500000\n1, 5, End_track\n";
+        assert!(MIDIFile::from_midicsv_str(backwards).is_err());
+        for track in ["-1", "2", "4000000000"] {
+            let text = format!("0, 0, Header, 1, 1, 96\n{}, 0, Start_track\n", track);
+            assert!(matches!(MIDIFile::from_midicsv_str(&text), Err(MidiError::Track(..))), "{}", track);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// What a note on does to a note of the same pitch and channel that is still sounding.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Overlap {
+    /// The sounding note is discarded and the new one takes its note off.
+    Replace,
+    /// The sounding note ends where the new one starts.
+    Close,
+    /// The new note on is ignored and the sounding note runs to the next note off.
+    Keep,
+}
+
+impl std::str::FromStr for Overlap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(Overlap::Replace),
+            "close" => Ok(Overlap::Close),
+            "keep" => Ok(Overlap::Keep),
+            _ => Err(format!("Unknown overlap {:?}, expected \"replace\", \"close\" or \"keep\"", s)),
+        }
+    }
+}
+
+impl Overlap {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overlap::Replace => "replace",
+            Overlap::Close => "close",
+            Overlap::Keep => "keep",
+        }
+    }
+}
+
+/// How the bytes of track names and text metas are decoded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextEncoding {
+    /// Invalid sequences become U+FFFD.
+    Utf8,
+    /// Every byte is one character, as older files written on Windows and Macs expect.
+    Latin1,
+}
+
+impl std::str::FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(TextEncoding::Latin1),
+            _ => Err(format!("Unknown encoding {:?}, expected \"utf-8\" or \"latin-1\"", s)),
+        }
+    }
+}
+
+impl TextEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Latin1 => "latin-1",
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            TextEncoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        }
+    }
+}
+
+/// Controls how a MIDI file is read into a `Sequence`.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// Fail on malformed data instead of recovering with warnings.
+    pub strict: bool,
+    /// Memory map the file instead of reading it.
+    pub mmap: bool,
+    /// Hold notes through the sustain pedal, see `Track::apply_sustain`.
+    pub sustain: bool,
+    pub overlap: Overlap,
+    pub encoding: TextEncoding,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions { strict: true, mmap: false, sustain: false, overlap: Overlap::Replace, encoding: TextEncoding::Utf8 }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ParseOptions {
+    #[new]
+    #[pyo3(signature = (strict = true, mmap = false, sustain = false, overlap = "replace", encoding = "utf-8"))]
+    fn py_new(strict: bool, mmap: bool, sustain: bool, overlap: &str, encoding: &str) -> PyResult<Self> {
+        let mut options = ParseOptions { strict, mmap, sustain, ..ParseOptions::default() };
+        options.set_overlap(overlap)?;
+        options.set_encoding(encoding)?;
+        Ok(options)
+    }
+
+    #[getter]
+    fn get_strict(&self) -> bool { self.strict }
+
+    #[setter]
+    fn set_strict(&mut self, strict: bool) { self.strict = strict; }
+
+    #[getter]
+    fn get_mmap(&self) -> bool { self.mmap }
+
+    #[setter]
+    fn set_mmap(&mut self, mmap: bool) { self.mmap = mmap; }
+
+    #[getter]
+    fn get_sustain(&self) -> bool { self.sustain }
+
+    #[setter]
+    fn set_sustain(&mut self, sustain: bool) { self.sustain = sustain; }
+
+    #[getter]
+    fn get_overlap(&self) -> &'static str { self.overlap.name() }
+
+    #[setter]
+    fn set_overlap(&mut self, overlap: &str) -> PyResult<()> {
+        self.overlap = overlap.parse().map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn get_encoding(&self) -> &'static str { self.encoding.name() }
+
+    #[setter]
+    fn set_encoding(&mut self, encoding: &str) -> PyResult<()> {
+        self.encoding = encoding.parse().map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MIDIFile;
+    use crate::sequence::Sequence;
+
+    /// A format 0 file, division 960, with one track of `events` (delta times of one or two bytes).
+    fn smf(events: &[u8]) -> MIDIFile {
+        let mut bytes = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x03\xC0MTrk".to_vec();
+        bytes.extend_from_slice(&(events.len() as u32 + 4).to_be_bytes());
+        bytes.extend_from_slice(events);
+        bytes.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+        MIDIFile::from_bytes(&bytes).unwrap()
+    }
+
+    fn parse(midi: &MIDIFile, options: ParseOptions) -> Vec<(f32, f32)> {
+        let seq = Sequence::from_midi_with_options(midi, &options).unwrap();
+        let mut notes: Vec<(f32, f32)> = seq.tracks.iter().flat_map(|t| &t.notes).map(|n| (n.start, n.duration)).collect();
+        notes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        notes
+    }
+
+    #[test]
+    fn test_parse_options() {
+        // Two note ons of C4 a quarter apart, then a single note off a quarter later
+        let midi = smf(&[0x00, 0x90, 60, 100, 0x87, 0x40, 0x90, 60, 100, 0x87, 0x40, 0x80, 60, 0]);
+        assert_eq!(parse(&midi, ParseOptions::default()), vec![(1.0, 1.0)]);
+        let close = ParseOptions { overlap: "close".parse().unwrap(), ..ParseOptions::default() };
+        assert_eq!(parse(&midi, close), vec![(0.0, 1.0), (1.0, 1.0)]);
+        let keep = ParseOptions { overlap: Overlap::Keep, ..ParseOptions::default() };
+        assert_eq!(parse(&midi, keep), vec![(0.0, 2.0)]);
+        assert!("merge".parse::<Overlap>().is_err());
+
+        let midi = smf(&[0x00, 0xFF, 0x03, 0x04, b'C', b'a', b'f', 0xE9]);
+        let name = |options: &ParseOptions| Sequence::from_midi_with_options(&midi, options).unwrap().metadata.title;
+        assert_eq!(name(&ParseOptions::default()).as_deref(), Some("Caf\u{FFFD}"));
+        let latin1 = ParseOptions { encoding: "ISO-8859-1".parse().unwrap(), ..ParseOptions::default() };
+        assert_eq!(name(&latin1).as_deref(), Some("Café"));
+
+        // Pedal down before the note, released a quarter after its note off
+        let midi = smf(&[0x00, 0xB0, 64, 127, 0x00, 0x90, 60, 100, 0x87, 0x40, 0x80, 60, 0, 0x87, 0x40, 0xB0, 64, 0]);
+        assert_eq!(parse(&midi, ParseOptions::default()), vec![(0.0, 1.0)]);
+        assert_eq!(parse(&midi, ParseOptions { sustain: true, ..ParseOptions::default() }), vec![(0.0, 2.0)]);
+    }
+}
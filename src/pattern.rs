@@ -0,0 +1,168 @@
+use crate::grid::bars;
+use crate::sequence::{Note, Sequence, TimeSignature, Track};
+
+const HIT_VELOCITY: u8 = 100;
+const ACCENT_VELOCITY: u8 = 127;
+
+/// The nine drum classes of the Groove MIDI dataset as GM keys: kick, snare, closed
+/// hi-hat, open hi-hat, low, mid and high tom, crash and ride.
+pub const DRUM_GROUPS: [&[u8]; 9] = [
+    &[36, 35],
+    &[38, 27, 28, 31, 32, 33, 34, 37, 39, 40, 56, 65, 66, 75, 85],
+    &[42, 44, 54, 68, 69, 70, 71, 73, 78, 80],
+    &[46, 67, 72, 74, 79, 81],
+    &[45, 29, 41, 61, 64, 84],
+    &[48, 47, 60, 63, 77, 86, 87],
+    &[50, 30, 43, 62, 76, 83],
+    &[49, 55, 57, 58],
+    &[51, 52, 53, 59, 82],
+];
+
+/// Velocities indexed `[drum][step][bar]`, 0 where nothing is hit.
+pub type DrumMatrix = Vec<Vec<Vec<u8>>>;
+
+impl Track {
+    /// Build a track from a drum-machine style step string.
+    ///
+    /// `x` is a hit, `X` an accented hit, `.` or `-` a rest; whitespace and `|` are ignored
+    /// so steps can be grouped visually. Every step lasts `1 / steps_per_beat` quarters.
+    pub fn from_step_pattern(pattern: &str, pitch: u8, steps_per_beat: u32) -> Result<Track, String> {
+        let notes = parse_steps(pattern, pitch, steps_per_beat)?;
+        Ok(Track { notes, ..Track::default() })
+    }
+
+    /// Build a drum track from several `pitch: steps` lines, e.g.
+    /// ```text
+    /// 36: x... x... x... x...
+    /// 38: .... x... .... x...
+    /// ```
+    pub fn from_kit_pattern(pattern: &str, steps_per_beat: u32) -> Result<Track, String> {
+        let mut notes = Vec::new();
+        for (line_idx, line) in pattern.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pitch, steps) = line.split_once(':')
+                .ok_or(format!("Line {}: expected `pitch: steps`", line_idx + 1))?;
+            let pitch = pitch.trim().parse::<u8>().ok().filter(|&p| p < 128)
+                .ok_or(format!("Line {}: invalid pitch {:?}", line_idx + 1, pitch.trim()))?;
+            notes.extend(parse_steps(steps, pitch, steps_per_beat)
+                .map_err(|e| format!("Line {}: {}", line_idx + 1, e))?);
+        }
+        notes.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+        Ok(Track { is_drum: true, notes, ..Track::default() })
+    }
+}
+
+impl Track {
+    /// Hits of a drum track on a grid of `steps_per_bar` equal steps per bar, one row per
+    /// group of keys in `groups` (see `DRUM_GROUPS`). Onsets snap to the nearest step and
+    /// a cell keeps its loudest hit. Keys outside every group are left out.
+    pub fn drum_matrix(&self, time_signatures: &[TimeSignature], steps_per_bar: usize, groups: &[&[u8]]) -> Result<DrumMatrix, String> {
+        if !self.is_drum {
+            return Err("drum_matrix needs a drum track".to_string());
+        }
+        if steps_per_bar == 0 {
+            return Err("steps_per_bar must be positive".to_string());
+        }
+        let end = self.notes.iter().map(|n| n.start + n.duration).fold(0_f32, f32::max);
+        let bars = bars(time_signatures, end);
+        let mut matrix = vec![vec![vec![0_u8; bars.len()]; steps_per_bar]; groups.len()];
+        // Nothing but zero-length notes at 0 spans no bar
+        if bars.is_empty() {
+            return Ok(matrix);
+        }
+        for note in &self.notes {
+            let Some(row) = groups.iter().position(|g| g.contains(&note.pitch)) else { continue };
+            let bar = bars.partition_point(|b| b.start <= note.start).max(1) - 1;
+            let step_length = bars[bar].length() / steps_per_bar as f32;
+            let step = ((note.start - bars[bar].start) / step_length).round() as usize;
+            // Late hits near the next downbeat belong to it
+            let (bar, step) = if step >= steps_per_bar { (bar + 1, 0) } else { (bar, step) };
+            if let Some(cell) = matrix[row][step].get_mut(bar) {
+                *cell = (*cell).max(note.velocity);
+            }
+        }
+        Ok(matrix)
+    }
+}
+
+impl Sequence {
+    /// `Track::drum_matrix` of track `track_index` over the bars of the sequence's own
+    /// time signatures.
+    pub fn drum_matrix(&self, track_index: usize, steps_per_bar: usize, groups: &[&[u8]]) -> Result<DrumMatrix, String> {
+        let track = self.tracks.get(track_index).ok_or("Track index out of range")?;
+        track.drum_matrix(&self.time_signatures, steps_per_bar, groups)
+    }
+}
+
+fn parse_steps(pattern: &str, pitch: u8, steps_per_beat: u32) -> Result<Vec<Note>, String> {
+    if steps_per_beat == 0 {
+        return Err("steps_per_beat must be positive".to_string());
+    }
+    if pitch > 127 {
+        return Err(format!("Pitch {} is above 127", pitch));
+    }
+    let step = 1.0 / steps_per_beat as f32;
+    let mut notes = Vec::new();
+    let mut idx = 0;
+    for c in pattern.chars() {
+        let velocity = match c {
+            'x' => HIT_VELOCITY,
+            'X' => ACCENT_VELOCITY,
+            '.' | '-' => 0,
+            c if c.is_whitespace() || c == '|' => continue,
+            c => return Err(format!("Unexpected step character {:?}", c)),
+        };
+        if velocity > 0 {
+            notes.push(Note { pitch, start: idx as f32 * step, duration: step, velocity, tie: false });
+        }
+        idx += 1;
+    }
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_patterns() {
+        let track = Track::from_step_pattern("x..X x...", 42, 4).unwrap();
+        let starts: Vec<f32> = track.notes.iter().map(|n| n.start).collect();
+        assert_eq!(starts, vec![0.0, 0.75, 1.0]);
+        assert_eq!(track.notes[1].velocity, ACCENT_VELOCITY);
+
+        let kit = Track::from_kit_pattern("36: x.x.\n38: .x.x", 2).unwrap();
+        assert!(kit.is_drum);
+        assert_eq!(kit.notes.len(), 4);
+        assert_eq!(kit.notes[1].pitch, 38);
+        assert!(Track::from_kit_pattern("36 x.x.", 2).is_err());
+        assert!(Track::from_step_pattern("x...", 128, 4).is_err());
+    }
+
+    #[test]
+    fn test_drum_matrix() {
+        let mut kit = Track::from_kit_pattern("36: x... x... x... x...\n38: .... X... .... x...\n42: x.x. x.x. x.x. x.x.", 4).unwrap();
+        // A rimshot slightly early on the second downbeat, and a key outside the groups
+        kit.notes.push(Note { pitch: 37, start: 3.98, duration: 0.1, velocity: 90, tie: false });
+        kit.notes.push(Note { pitch: 100, start: 0.0, duration: 0.1, velocity: 90, tie: false });
+        let matrix = kit.drum_matrix(&[], 8, &DRUM_GROUPS).unwrap();
+        assert_eq!((matrix.len(), matrix[0].len(), matrix[0][0].len()), (9, 8, 2));
+        let hits = |row: usize, bar: usize| -> Vec<u8> { matrix[row].iter().map(|steps| steps[bar]).collect() };
+        assert_eq!(hits(0, 0), vec![100, 0, 100, 0, 100, 0, 100, 0]);
+        assert_eq!(hits(1, 0), vec![0, 0, 127, 0, 0, 0, 100, 0]);
+        assert_eq!(hits(1, 1), vec![90, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(hits(2, 0), vec![100; 8]);
+        assert!(matrix[3..].iter().flatten().flatten().all(|&v| v == 0));
+        assert!(Track::default().drum_matrix(&[], 8, &DRUM_GROUPS).is_err());
+        let silent = Track { is_drum: true, notes: vec![Note { pitch: 36, start: 0.0, duration: 0.0, velocity: 90, tie: false }], ..Track::default() };
+        assert_eq!(silent.drum_matrix(&[], 4, &DRUM_GROUPS).unwrap(), vec![vec![Vec::<u8>::new(); 4]; 9]);
+
+        let waltz = TimeSignature { time: 0.0, numerator: 3, denominator: 4, ..Default::default() };
+        let seq = Sequence { tracks: vec![kit], time_signatures: vec![waltz], ..Sequence::default() };
+        assert_eq!(seq.drum_matrix(0, 3, &DRUM_GROUPS).unwrap()[0][0].len(), 2);
+        assert!(seq.drum_matrix(1, 3, &DRUM_GROUPS).is_err());
+    }
+}
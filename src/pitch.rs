@@ -0,0 +1,99 @@
+#[cfg(feature = "python")]
+use pyo3::exceptions::PyValueError;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::sequence::Note;
+
+/// Scientific pitch notation: middle C (60) is C4. Yamaha and some DAWs use C3.
+pub const MIDDLE_C_OCTAVE: i8 = 4;
+const SHARP_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+#[inline(always)]
+pub fn pitch_class(pitch: u8) -> u8 {
+    pitch % 12
+}
+
+/// Octave number of `pitch` when middle C is in octave `middle_c_octave`.
+#[inline(always)]
+pub fn octave(pitch: u8, middle_c_octave: i8) -> i8 {
+    (pitch / 12) as i8 - 5 + middle_c_octave
+}
+
+/// Name such as "C#4" or "A-1", spelled with sharps.
+pub fn pitch_name(pitch: u8, middle_c_octave: i8) -> String {
+    format!("{}{}", SHARP_NAMES[pitch_class(pitch) as usize], octave(pitch, middle_c_octave))
+}
+
+/// Parse "Bb3", "C#-1", "E♭5" or "Fx2" (double sharp), any number of `#`/`b`
+/// accidentals allowed. Fails if the pitch falls outside 0 to 127.
+pub fn pitch_from_name(name: &str, middle_c_octave: i8) -> Result<u8, String> {
+    let invalid = || format!("Invalid pitch name: {}", name);
+    let mut chars = name.trim().chars().peekable();
+    let natural: i32 = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('C') => 0, Some('D') => 2, Some('E') => 4, Some('F') => 5,
+        Some('G') => 7, Some('A') => 9, Some('B') => 11,
+        _ => return Err(invalid()),
+    };
+    let mut alteration = 0;
+    while let Some(c) = chars.next_if(|c| matches!(c, '#' | '♯' | 'b' | '♭' | 'x' | '𝄪')) {
+        alteration += match c { '#' | '♯' => 1, 'x' | '𝄪' => 2, _ => -1 };
+    }
+    let octave: i32 = chars.collect::<String>().parse().map_err(|_| invalid())?;
+    let pitch = (octave - middle_c_octave as i32 + 5) * 12 + natural + alteration;
+    u8::try_from(pitch).ok().filter(|&p| p < 128)
+        .ok_or_else(|| format!("Pitch name {} is outside the MIDI range", name))
+}
+
+impl Note {
+    /// See `pitch_name`.
+    pub fn name(&self, middle_c_octave: i8) -> String {
+        pitch_name(self.pitch, middle_c_octave)
+    }
+
+    pub fn pitch_class(&self) -> u8 {
+        pitch_class(self.pitch)
+    }
+
+    pub fn octave(&self, middle_c_octave: i8) -> i8 {
+        octave(self.pitch, middle_c_octave)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name="pitch_name", signature = (pitch, middle_c_octave = MIDDLE_C_OCTAVE))]
+pub fn py_pitch_name(pitch: u8, middle_c_octave: i8) -> String { pitch_name(pitch, middle_c_octave) }
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name="pitch_from_name", signature = (name, middle_c_octave = MIDDLE_C_OCTAVE))]
+pub fn py_pitch_from_name(name: &str, middle_c_octave: i8) -> PyResult<u8> {
+    pitch_from_name(name, middle_c_octave).map_err(PyValueError::new_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pitch_names() {
+        assert_eq!(pitch_name(60, MIDDLE_C_OCTAVE), "C4");
+        assert_eq!(pitch_name(61, 3), "C#3");
+        assert_eq!(pitch_name(0, MIDDLE_C_OCTAVE), "C-1");
+        assert_eq!(pitch_name(127, MIDDLE_C_OCTAVE), "G9");
+        for pitch in 0..128 {
+            assert_eq!(pitch_from_name(&pitch_name(pitch, 3), 3), Ok(pitch));
+        }
+        assert_eq!(pitch_from_name("Bb3", MIDDLE_C_OCTAVE), Ok(58));
+        assert_eq!(pitch_from_name("e♭5", MIDDLE_C_OCTAVE), Ok(75));
+        assert_eq!(pitch_from_name("Fx2", MIDDLE_C_OCTAVE), Ok(43));
+        assert_eq!(pitch_from_name("Cb4", MIDDLE_C_OCTAVE), Ok(59));
+        assert!(pitch_from_name("H2", MIDDLE_C_OCTAVE).is_err());
+        assert!(pitch_from_name("C", MIDDLE_C_OCTAVE).is_err());
+        assert!(pitch_from_name("Cb-1", MIDDLE_C_OCTAVE).is_err());
+        assert!(pitch_from_name("G#9", MIDDLE_C_OCTAVE).is_err());
+
+        let note = Note { pitch: 70, start: 0.0, duration: 1.0, velocity: 100, tie: false };
+        assert_eq!((note.name(MIDDLE_C_OCTAVE), note.pitch_class(), note.octave(MIDDLE_C_OCTAVE)), ("A#4".to_string(), 10, 4));
+    }
+}
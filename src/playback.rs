@@ -0,0 +1,231 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use midir::{MidiOutput, MidiOutputConnection};
+use crate::scheduler::Scheduler;
+use crate::sequence::Sequence;
+
+/// Scheduler resolution: one "sample" per microsecond, one block per millisecond.
+const MICROS: u32 = 1_000_000;
+const BLOCK_MICROS: usize = 1000;
+/// All Sound Off, sent on every channel when playback stops.
+const ALL_SOUND_OFF: u8 = 120;
+
+#[derive(Default)]
+struct State {
+    playing: bool,
+    closed: bool,
+    /// Microseconds into the sequence when playback was last started or sought.
+    position: u64,
+    started: Option<Instant>,
+    seek: Option<u64>,
+}
+
+impl State {
+    fn current(&self) -> u64 {
+        self.position + self.started.map_or(0, |s| s.elapsed().as_micros() as u64)
+    }
+}
+
+/// Plays a `Sequence` to a MIDI output port on a background thread, following its tempo
+/// map. Playback begins stopped; the thread lives until `close` or drop.
+#[cfg_attr(feature = "python", pyclass)]
+pub struct Player {
+    state: Arc<(Mutex<State>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Output port whose name contains `port_name`, the first one if it is empty.
+fn connect(port_name: &str) -> Result<MidiOutputConnection, String> {
+    let output = MidiOutput::new("midiparse").map_err(|e| e.to_string())?;
+    let port = output.ports().into_iter()
+        .find(|port| output.port_name(port).is_ok_and(|name| name.contains(port_name)))
+        .ok_or_else(|| format!("No MIDI output port matching {:?}", port_name))?;
+    output.connect(&port, "midiparse").map_err(|e| e.to_string())
+}
+
+fn silence(send: &mut impl FnMut(&[u8])) {
+    for channel in 0..16 {
+        send(&[0xB0 | channel, ALL_SOUND_OFF, 0]);
+    }
+}
+
+/// The playback thread, passing every message to `send`. After a seek the scheduler
+/// restores the program and controller state before the first notes.
+fn run(mut scheduler: Scheduler, mut send: impl FnMut(&[u8]), state: Arc<(Mutex<State>, Condvar)>) {
+    let (lock, signal) = &*state;
+    let mut sounding = false;
+    loop {
+        let mut guard = lock.lock().unwrap();
+        if let Some(position) = guard.seek.take() {
+            silence(&mut send);
+            scheduler.seek(position);
+        }
+        if guard.closed {
+            break;
+        }
+        if scheduler.is_finished() && guard.playing {
+            guard.position = guard.current();
+            guard.started = None;
+            guard.playing = false;
+        }
+        if !guard.playing {
+            if sounding {
+                silence(&mut send);
+                sounding = false;
+            }
+            drop(signal.wait(guard).unwrap());
+            continue;
+        }
+        let now = guard.current();
+        drop(guard);
+        sounding = true;
+        while !scheduler.is_finished() && scheduler.position() <= now {
+            for (_, event) in scheduler.next_block() {
+                send(event.bytes());
+            }
+        }
+        thread::sleep(Duration::from_micros(BLOCK_MICROS as u64));
+    }
+    silence(&mut send);
+}
+
+impl Player {
+    fn spawn(seq: &Sequence, send: impl FnMut(&[u8]) + Send + 'static) -> Player {
+        let scheduler = Scheduler::new(seq, MICROS, BLOCK_MICROS);
+        let state = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let thread_state = state.clone();
+        let thread = thread::spawn(move || run(scheduler, send, thread_state));
+        Player { state, thread: Some(thread) }
+    }
+
+    pub fn start(&self) {
+        let (lock, signal) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if !state.playing {
+            state.playing = true;
+            state.started = Some(Instant::now());
+            signal.notify_one();
+        }
+    }
+
+    /// Pause, silencing the port. `start` resumes from the same position.
+    pub fn stop(&self) {
+        let (lock, signal) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.position = state.current();
+        state.started = None;
+        state.playing = false;
+        signal.notify_one();
+    }
+
+    /// Continue from `seconds` into the sequence, playing or not.
+    pub fn seek(&self, seconds: f64) {
+        let (lock, signal) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        let position = (seconds.max(0.0) * MICROS as f64) as u64;
+        state.position = position;
+        state.seek = Some(position);
+        if state.started.is_some() {
+            state.started = Some(Instant::now());
+        }
+        signal.notify_one();
+    }
+
+    /// Seconds into the sequence.
+    pub fn position(&self) -> f64 {
+        self.state.0.lock().unwrap().current() as f64 / MICROS as f64
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state.0.lock().unwrap().playing
+    }
+
+    /// Stop the thread and release the port.
+    pub fn close(&mut self) {
+        let (lock, signal) = &*self.state;
+        lock.lock().unwrap().closed = true;
+        signal.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Sequence {
+    /// A `Player` on the first MIDI output port whose name contains `port_name`.
+    pub fn play(&self, port_name: &str) -> Result<Player, String> {
+        let mut connection = connect(port_name)?;
+        // The connection closes when the thread drops it
+        Ok(Player::spawn(self, move |message| { let _ = connection.send(message); }))
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Player {
+    #[pyo3(name="start")]
+    fn py_start(&self) {self.start()}
+
+    #[pyo3(name="stop")]
+    fn py_stop(&self) {self.stop()}
+
+    #[pyo3(name="seek")]
+    fn py_seek(&self, seconds: f64) {self.seek(seconds)}
+
+    #[getter]
+    fn get_position(&self) -> f64 {self.position()}
+
+    #[getter]
+    fn get_is_playing(&self) -> bool {self.is_playing()}
+
+    #[pyo3(name="close")]
+    fn py_close(&mut self, py: Python<'_>) {py.allow_threads(|| self.close())}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{Note, Track};
+
+    /// A player sending to a shared list instead of a port.
+    fn player(seq: &Sequence) -> (Player, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sink = sent.clone();
+        (Player::spawn(seq, move |message| sink.lock().unwrap().push(message.to_vec())), sent)
+    }
+
+    #[test]
+    fn test_player_seek() {
+        let notes = vec![
+            Note { pitch: 60, start: 0.0, duration: 1.0, velocity: 100, tie: false },
+            Note { pitch: 62, start: 8.0, duration: 1.0, velocity: 100, tie: false },
+        ];
+        let seq = Sequence::from_tracks(vec![Track { program: 40, channel: Some(0), notes, ..Track::default() }], 120.0);
+        let (mut player, sent) = player(&seq);
+        // Seeking between the notes while stopped, then playing briefly
+        player.seek(2.0);
+        player.start();
+        thread::sleep(Duration::from_millis(50));
+        player.stop();
+        assert!(!player.is_playing());
+        let position = player.position();
+        assert!((2.0..3.0).contains(&position), "{}", position);
+        player.close();
+
+        let sent = sent.lock().unwrap();
+        let after_seek = sent.iter().skip_while(|m| m[1] == ALL_SOUND_OFF).collect::<Vec<_>>();
+        assert_eq!(after_seek[0], &vec![0xC0, 40], "{:?}", sent);
+        assert!(!sent.iter().any(|m| m[0] == 0x90), "{:?}", sent);
+        assert_eq!(sent.last(), Some(&vec![0xBF, ALL_SOUND_OFF, 0]));
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeSet;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::error::MidiError;
+use crate::gm::program_name;
+use crate::io::MIDIFile;
+use crate::message::{EventStatus, MIDIMessage, MetaStatus};
+use crate::sequence::Tempo;
+use crate::timing::{TempoMap, TICKS_PER_QUARTER};
+use crate::util::tempo2qpm;
+
+/// What `MIDIFile::probe` finds out about a file without building notes.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Probe {
+    /// SMF format, 0, 1 or 2.
+    pub format: u16,
+    pub tracks: usize,
+    /// Ticks per quarter, or the raw SMPTE division with the top bit set.
+    pub division: u16,
+    /// Seconds until the last message of the longest track, 0 for an SMPTE division
+    /// with a frame rate other than 24, 25, 29.97 (given as 29) or 30.
+    pub duration: f64,
+    /// GM instrument or drum kit names of the channels that play notes, sorted.
+    pub instruments: Vec<String>,
+    pub tempos: usize,
+    pub time_signatures: usize,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Probe {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+impl MIDIFile {
+    /// Scan the metas, program changes and note ons of a file for corpus indexing,
+    /// without pairing notes or building tracks. The file is memory mapped.
+    pub fn probe(path: &str) -> Result<Probe, MidiError> {
+        Ok(MIDIFile::from_file_mapped(path)?.summary())
+    }
+
+    /// `probe` of a file already read.
+    pub fn summary(&self) -> Probe {
+        let mut tempo_ticks = Vec::new();
+        let mut time_signatures = 0;
+        let mut instruments = BTreeSet::new();
+        let mut end = 0_u32;
+        for track in &self.tracks {
+            let mut programs = [0_u8; 16];
+            for message in track.iter() {
+                end = end.max(message.time());
+                match message {
+                    MIDIMessage::Meta(meta) => match meta.status {
+                        MetaStatus::SetTempo => tempo_ticks.extend(meta.tempo().map(|t| (meta.time, t))),
+                        MetaStatus::TimeSignature => time_signatures += 1,
+                        _ => {}
+                    },
+                    MIDIMessage::Event(event) => {
+                        let channel = event.channel().unwrap_or(0) as usize;
+                        match event.status {
+                            EventStatus::ProgramChange => programs[channel] = event.program().unwrap_or(0),
+                            EventStatus::NoteOn if event.velocity().unwrap_or(0) > 0 => {
+                                instruments.insert(program_name(programs[channel], channel == 9));
+                            }
+                            _ => {}
+                        }
+                    }
+                    MIDIMessage::SysEx(_) => {}
+                }
+            }
+        }
+
+        // Tracks are scanned one after another, the tempo map wants time order
+        tempo_ticks.sort_by_key(|&(tick, _)| tick);
+        let duration = if self.division >> 15 == 1 {
+            // SMPTE: frames per second in the high byte, negated, ticks per frame in the low
+            let fps = match -((self.division >> 8) as i8 as i16) {
+                29 => 29.97,
+                fps @ (24 | 25 | 30) => fps as f64,
+                _ => 0.0,
+            };
+            let ticks_per_second = fps * (self.division & 0xFF) as f64;
+            if ticks_per_second > 0.0 { end as f64 / ticks_per_second } else { 0.0 }
+        } else {
+            let tpq = self.division.max(1) as f32;
+            let tempos: Vec<Tempo> = tempo_ticks.iter()
+                .map(|&(tick, tempo)| Tempo { time: tick as f32 / tpq, qpm: tempo2qpm(tempo) })
+                .collect();
+            TempoMap::new(&tempos, TICKS_PER_QUARTER).quarter_to_second(end as f64 / tpq as f64)
+        };
+        Probe {
+            format: self.format as u16,
+            tracks: self.tracks.len(),
+            division: self.division,
+            duration,
+            instruments: instruments.into_iter().map(str::to_string).collect(),
+            tempos: tempo_ticks.len(),
+            time_signatures,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Sequence;
+    use crate::timing::TimeUnit;
+
+    #[test]
+    fn test_probe() {
+        let probe = MIDIFile::probe("tests/tiny.mid").unwrap();
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        assert_eq!(probe.tracks, 2);
+        assert_eq!(probe.tempos, seq.qpm.len());
+        assert_eq!(probe.time_signatures, seq.time_signatures.len());
+        assert_eq!(probe.instruments, vec![seq.tracks[0].program_name().to_string()]);
+        assert!((probe.duration - seq.end_time(TimeUnit::Second) as f64).abs() < 0.1);
+
+        // 25 fps at 40 ticks per frame, and the out of range -128
+        let mut midi = MIDIFile::from_file("tests/tiny.mid").unwrap();
+        let end = midi.tracks.iter().flat_map(|t| t.iter()).map(|m| m.time()).max().unwrap();
+        midi.division = 0xE728;
+        assert!((midi.summary().duration - end as f64 / 1000.0).abs() < 1e-9);
+        midi.division = 0x8028;
+        assert_eq!(midi.summary().duration, 0.0);
+    }
+}
@@ -0,0 +1,56 @@
+/// A `#[pymethods]` block, `pymethods_with! { [...] #[pymethods] impl Type { ... }}`, with
+/// the shared methods listed in brackets added to the ones written out:
+///
+/// - `pickle`: `__reduce__` through `serial::reduce`, `__copy__` and `__deepcopy__`.
+/// - `approx_eq("doc")`: `==` and `!=` by exact `ApproxEq`, and `approx_eq` with a
+///   tolerance, documented by the string.
+///
+/// The attribute comes from the call site rather than the macro so that the wrappers
+/// pyo3 generates for `slf: &PyCell<Self>` methods resolve their own locals.
+macro_rules! pymethods_with {
+    ([] $pound:tt $pymethods:tt impl $ty:ident { $($body:tt)* }) => {
+        #[cfg(feature = "python")]
+        $pound $pymethods
+        impl $ty { $($body)* }
+    };
+    ([pickle $(, $($rest:tt)*)?] $pound:tt $pymethods:tt impl $ty:ident { $($body:tt)* }) => {
+        $crate::pymethods::pymethods_with! { [$($($rest)*)?] $pound $pymethods impl $ty {
+            $($body)*
+
+            fn __reduce__(&self, py: pyo3::Python<'_>) -> pyo3::PyResult<$crate::serial::Reduced> {
+                $crate::serial::reduce(py, self)
+            }
+
+            fn __copy__(&self) -> Self {
+                Clone::clone(self)
+            }
+
+            fn __deepcopy__(&self, _memo: &pyo3::PyAny) -> Self {
+                Clone::clone(self)
+            }
+        }}
+    };
+    ([approx_eq($doc:literal) $(, $($rest:tt)*)?] $pound:tt $pymethods:tt impl $ty:ident { $($body:tt)* }) => {
+        $crate::pymethods::pymethods_with! { [$($($rest)*)?] $pound $pymethods impl $ty {
+            $($body)*
+
+            fn __richcmp__(&self, other: &Self, op: pyo3::basic::CompareOp, py: pyo3::Python<'_>) -> pyo3::PyObject {
+                use pyo3::IntoPy;
+                let equal = $crate::compare::ApproxEq::approx_eq(self, other, 0.0);
+                match op {
+                    pyo3::basic::CompareOp::Eq => equal.into_py(py),
+                    pyo3::basic::CompareOp::Ne => (!equal).into_py(py),
+                    _ => py.NotImplemented(),
+                }
+            }
+
+            #[doc = $doc]
+            #[pyo3(name = "approx_eq", signature = (other, tolerance = crate::compare::DEFAULT_TOLERANCE))]
+            fn py_approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+                $crate::compare::ApproxEq::approx_eq(self, other, tolerance)
+            }
+        }}
+    };
+}
+
+pub(crate) use pymethods_with;
@@ -0,0 +1,143 @@
+#[cfg(all(feature = "record", feature = "python"))]
+use pyo3::exceptions::{PyIOError, PyRuntimeError};
+#[cfg(all(feature = "record", feature = "python"))]
+use pyo3::prelude::*;
+#[cfg(feature = "record")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "record")]
+use std::time::Instant;
+#[cfg(feature = "record")]
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use crate::error::MidiError;
+use crate::io::{MIDIFile, MidiTrack};
+use crate::message::{MIDIFormat, Meta};
+use crate::sequence::Sequence;
+use crate::timing::{qpm2tempo, TICKS_PER_QUARTER};
+use crate::util::write_delta_time;
+
+impl Sequence {
+    /// Sequence from channel messages stamped with microseconds since the start, as
+    /// captured from a live input, laid on a grid of `qpm`. Messages are paired into notes
+    /// as `from_midi` does, and notes still held at `end` microseconds end there. Other
+    /// than channel messages are skipped.
+    pub fn from_timed_messages(messages: &[(u64, Vec<u8>)], end: u64, qpm: f32) -> Result<Sequence, MidiError> {
+        let us_per_quarter = qpm2tempo(qpm).max(1);
+        let to_tick = |us: u64| us * TICKS_PER_QUARTER as u64 / us_per_quarter;
+        let mut data = vec![0x00];
+        data.extend_from_slice(&Meta::new_tempo(0, qpm).data);
+        let mut tick = 0;
+        for (time, bytes) in messages {
+            if !matches!(bytes.first(), Some(0x80..=0xEF)) {
+                continue;
+            }
+            let time = to_tick(*time).max(tick);
+            write_delta_time(&mut data, time - tick);
+            data.extend_from_slice(bytes);
+            tick = time;
+        }
+        write_delta_time(&mut data, to_tick(end).max(tick) - tick);
+        data.extend_from_slice(&Meta::new_end_of_track(0).data);
+        let midi = MIDIFile {
+            format: MIDIFormat::SingleTrack,
+            division: TICKS_PER_QUARTER as u16,
+            tracks: vec![MidiTrack::new(0, data)],
+            declared_tracks: 1,
+            warnings: Vec::new(),
+        };
+        Sequence::from_midi_with(&midi, false)
+    }
+}
+
+#[cfg(feature = "record")]
+type Captured = Arc<Mutex<Vec<(u64, Vec<u8>)>>>;
+
+/// Captures channel messages from a MIDI input port until `stop`.
+#[cfg(feature = "record")]
+#[cfg_attr(feature = "python", pyclass(unsendable))]
+pub struct Recorder {
+    connection: Option<MidiInputConnection<()>>,
+    captured: Captured,
+    started: Instant,
+    qpm: f32,
+}
+
+#[cfg(feature = "record")]
+impl Recorder {
+    /// Record from the first input port whose name contains `port_name`, placing the
+    /// captured messages on a grid of `qpm` quarters per minute.
+    pub fn open(port_name: &str, qpm: f32) -> Result<Recorder, String> {
+        let mut input = MidiInput::new("midiparse").map_err(|e| e.to_string())?;
+        input.ignore(Ignore::All);
+        let port = input.ports().into_iter()
+            .find(|port| input.port_name(port).is_ok_and(|name| name.contains(port_name)))
+            .ok_or_else(|| format!("No MIDI input port matching {:?}", port_name))?;
+        let started = Instant::now();
+        let captured = Captured::default();
+        let sink = captured.clone();
+        let connection = input.connect(&port, "midiparse", move |_, bytes, _| {
+            sink.lock().unwrap().push((started.elapsed().as_micros() as u64, bytes.to_vec()));
+        }, ()).map_err(|e| e.to_string())?;
+        Ok(Recorder { connection: Some(connection), captured, started, qpm })
+    }
+
+    /// Number of messages captured so far.
+    pub fn len(&self) -> usize {
+        self.captured.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Close the port and build the recording, see `Sequence::from_timed_messages`.
+    pub fn stop(&mut self) -> Result<Sequence, String> {
+        let end = self.started.elapsed().as_micros() as u64;
+        self.connection.take().ok_or("Recorder is already stopped")?.close();
+        let messages = std::mem::take(&mut *self.captured.lock().unwrap());
+        Sequence::from_timed_messages(&messages, end, self.qpm).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(all(feature = "record", feature = "python"))]
+#[pymethods]
+impl Recorder {
+    #[staticmethod]
+    #[pyo3(name="open", signature = (port_name = "", qpm = 120.0))]
+    fn py_open(port_name: &str, qpm: f32) -> PyResult<Self> {
+        Self::open(port_name, qpm).map_err(PyIOError::new_err)
+    }
+
+    fn __len__(&self) -> usize {self.len()}
+
+    #[pyo3(name="stop")]
+    fn py_stop(&mut self) -> PyResult<Sequence> {
+        self.stop().map_err(PyRuntimeError::new_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Note;
+
+    #[test]
+    fn test_from_timed_messages() {
+        let messages = vec![
+            (250_000, vec![0x90, 60, 100]),
+            (500_000, vec![0xF8]),
+            (750_000, vec![0x80, 60, 0]),
+            (1_000_000, vec![0x91, 64, 80]),
+        ];
+        let seq = Sequence::from_timed_messages(&messages, 2_000_000, 120.0).unwrap();
+        assert_eq!(seq.qpm[0].qpm, 120.0);
+        let mut notes: Vec<(Option<u8>, Note)> = seq.tracks.iter()
+            .flat_map(|t| t.notes.iter().map(move |&n| (t.channel, n)))
+            .collect();
+        notes.sort_by_key(|(channel, _)| *channel);
+        assert_eq!(notes, vec![
+            (Some(0), Note { pitch: 60, start: 0.5, duration: 1.0, velocity: 100, tie: false }),
+            (Some(1), Note { pitch: 64, start: 2.0, duration: 2.0, velocity: 80, tie: false }),
+        ]);
+        assert_eq!(seq.warnings.notes_closed_at_eot, 1);
+    }
+}
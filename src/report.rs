@@ -0,0 +1,85 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::{Serialize, Deserialize};
+
+/// Anomalies met while turning a MIDI file into a `Sequence`. Frequent ones are counted,
+/// everything else is described in `messages`.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParseReport {
+    /// Runs of data bytes relying on a running status that a SysEx or meta cancelled,
+    /// skipped.
+    pub cancelled_running_status: usize,
+    /// Notes whose note off is at the same tick as their note on.
+    pub zero_length_notes: usize,
+    /// Notes never released, closed at the end of their track.
+    pub notes_closed_at_eot: usize,
+    /// Meta events of an unknown type, skipped.
+    pub unknown_metas: usize,
+    /// Chunk lengths or track counts that disagree with the data.
+    pub chunk_length_mismatches: usize,
+    /// No SetTempo was found, so 120 QPM was assumed.
+    #[serde(default)]
+    pub default_tempo_assumed: bool,
+    pub messages: Vec<String>,
+}
+
+impl ParseReport {
+    pub fn warn(&mut self, message: String) {
+        self.messages.push(message);
+    }
+
+    pub fn is_clean(&self) -> bool {
+        *self == ParseReport::default()
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ParseReport {
+    #[pyo3(name="is_clean")]
+    fn py_is_clean(&self) -> bool { self.is_clean() }
+
+    fn __bool__(&self) -> bool { !self.is_clean() }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{MIDIFile, MidiTrack};
+    use crate::message::MIDIFormat;
+    use crate::sequence::Sequence;
+
+    #[test]
+    fn test_parse_report() {
+        let data = vec![
+            0x00, 0x90, 0x3C, 0x40,
+            0x00, 0x3C, 0x00,
+            0x00, 0xF0, 0x02, 0x7E, 0xF7,
+            0x00, 0x3E, 0x40,
+            0x00, 0xFF, 0x60, 0x00,
+            0x10, 0xFF, 0x2F, 0x00,
+        ];
+        let midi = MIDIFile {
+            format: MIDIFormat::SingleTrack,
+            division: 96,
+            tracks: vec![MidiTrack::new(0, data)],
+            declared_tracks: 1,
+            warnings: Vec::new(),
+        };
+        let seq = Sequence::from_midi(&midi).unwrap();
+        let report = &seq.warnings;
+        assert_eq!(report.zero_length_notes, 1);
+        assert_eq!(report.cancelled_running_status, 1);
+        assert_eq!(report.unknown_metas, 1);
+        assert_eq!(report.notes_closed_at_eot, 0);
+        assert_eq!(report.chunk_length_mismatches, 0);
+        assert!(report.default_tempo_assumed);
+        assert!(!Sequence::from_file("tests/tiny.mid").unwrap().warnings.default_tempo_assumed);
+        // The note on after the SysEx ran on a cancelled status and is gone
+        let notes = &seq.tracks[0].notes;
+        assert_eq!(notes.len(), 1);
+        assert!(report.messages.iter().any(|m| m.contains("without a running status")));
+    }
+}
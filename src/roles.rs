@@ -0,0 +1,104 @@
+use crate::sequence::{Sequence, Track};
+
+/// Mean notes per onset from which a track counts as chordal.
+const CHORDAL_NOTES_PER_ONSET: f32 = 1.5;
+/// Mean pitch below which a single line counts as a bass line.
+const BASS_MEAN_PITCH: f32 = 50.0;
+/// Mean note length in quarters from which a single line counts as held harmony.
+const HELD_DURATION: f32 = 2.0;
+
+/// Function of a track in the arrangement, guessed by `Sequence::classify_tracks`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrackRole {
+    Melody,
+    Bass,
+    Harmony,
+    Drums,
+}
+
+impl TrackRole {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrackRole::Melody => "melody",
+            TrackRole::Bass => "bass",
+            TrackRole::Harmony => "harmony",
+            TrackRole::Drums => "drums",
+        }
+    }
+}
+
+impl Track {
+    /// See `Sequence::classify_tracks`.
+    pub fn classify(&self) -> Option<TrackRole> {
+        if self.is_drum {
+            return Some(TrackRole::Drums);
+        }
+        if self.notes.is_empty() {
+            return None;
+        }
+        let n = self.notes.len() as f32;
+        let mut onsets: Vec<f32> = self.notes.iter().map(|n| n.start).collect();
+        onsets.sort_by(f32::total_cmp);
+        onsets.dedup();
+        let notes_per_onset = n / onsets.len() as f32;
+        let mean_pitch = self.notes.iter().map(|n| n.pitch as f32).sum::<f32>() / n;
+        let mean_duration = self.notes.iter().map(|n| n.duration).sum::<f32>() / n;
+        // GM bass, string ensemble and pad programs
+        let role = match self.program {
+            32..=39 => TrackRole::Bass,
+            _ if notes_per_onset >= CHORDAL_NOTES_PER_ONSET => TrackRole::Harmony,
+            _ if mean_pitch < BASS_MEAN_PITCH => TrackRole::Bass,
+            48..=51 | 88..=95 => TrackRole::Harmony,
+            _ if mean_duration >= HELD_DURATION => TrackRole::Harmony,
+            _ => TrackRole::Melody,
+        };
+        Some(role)
+    }
+}
+
+impl Sequence {
+    /// Role of each track from its program, register, chords and note lengths: drum
+    /// tracks are drums, GM bass programs and low single lines bass, chordal tracks,
+    /// ensembles, pads and long held lines harmony, and other lines melody. `None` for
+    /// tracks without notes.
+    pub fn classify_tracks(&self) -> Vec<Option<TrackRole>> {
+        self.tracks.iter().map(Track::classify).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Note;
+
+    #[test]
+    fn test_classify_tracks() {
+        let line = |pitches: &[u8], duration: f32, program: u8| Track {
+            program,
+            notes: pitches.iter().enumerate()
+                .map(|(i, &pitch)| Note { pitch, start: i as f32 * duration, duration, velocity: 90, tie: false })
+                .collect(),
+            ..Track::default()
+        };
+        let mut chords = line(&[60, 64, 67, 65], 1.0, 0);
+        chords.notes.extend(line(&[64, 67, 71, 69], 1.0, 0).notes);
+        let seq = Sequence {
+            tracks: vec![
+                line(&[72, 74, 76, 77], 0.5, 0),
+                line(&[36, 43, 41, 38], 1.0, 0),
+                line(&[60, 62], 1.0, 33),
+                chords,
+                line(&[60, 62], 4.0, 0),
+                line(&[67, 69], 0.5, 89),
+                Track { is_drum: true, ..Track::default() },
+                Track::default(),
+            ],
+            ..Sequence::default()
+        };
+        let roles: Vec<Option<&str>> = seq.classify_tracks().iter().map(|r| r.map(|r| r.name())).collect();
+        assert_eq!(roles, vec![
+            Some("melody"), Some("bass"), Some("bass"), Some("harmony"),
+            Some("harmony"), Some("harmony"), Some("drums"), None,
+        ]);
+    }
+}
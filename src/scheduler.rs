@@ -0,0 +1,213 @@
+use crate::message::{Event, EventStatus};
+use crate::sequence::{Sequence, BANK_SELECT_LSB, BANK_SELECT_MSB};
+use crate::timing::{TempoMap, TICKS_PER_QUARTER};
+use crate::util::default_channel;
+
+/// Turns a `Sequence` into sample-accurate channel events for audio hosts.
+///
+/// Every call to `next_block` (or `Iterator::next`) advances by `block_size` samples and
+/// returns the events falling inside the block, as `(sample_offset, event)` pairs where the
+/// offset is relative to the block start.
+pub struct Scheduler {
+    sample_rate: u32,
+    block_size: usize,
+    events: Vec<(u64, Event)>,
+    cursor: usize,
+    position: u64,
+    /// Program and controller state restored at the start of the block after a `seek`.
+    chased: Vec<Event>,
+}
+
+fn make_event(tick: u64, status: EventStatus, channel: u8, data1: u8, data2: u8) -> Event {
+    Event::new_channel(tick as u32, status, channel & 0x0F, data1 & 0x7F, data2 & 0x7F).unwrap()
+}
+
+impl Scheduler {
+    pub fn new(seq: &Sequence, sample_rate: u32, block_size: usize) -> Self {
+        assert!(sample_rate > 0 && block_size > 0, "Sample rate and block size must be positive");
+        let tempo_map = TempoMap::new(&seq.qpm, TICKS_PER_QUARTER);
+        let to_sample = |tick: u64| {
+            (tempo_map.tick_to_us(tick) as u128 * sample_rate as u128 / 1_000_000) as u64
+        };
+
+        // (sample, order, event); note offs sort before everything else at the same sample
+        // so that retriggered notes are not cut by their own release, and bank selects go
+        // before program changes as in the writer.
+        let mut events = Vec::<(u64, u8, Event)>::new();
+        // Channels as the writer gives them on a single port, wrapping around when the
+        // tracks need more
+        let allocation = seq.allocate_channels(Some(1)).ok();
+        for (idx, track) in seq.tracks.iter().enumerate() {
+            let channel = match &allocation {
+                Some(allocation) => allocation[idx].1,
+                None => track.channel.unwrap_or_else(|| default_channel(idx, track.is_drum)),
+            };
+            for (number, value) in [(BANK_SELECT_MSB, track.bank_msb), (BANK_SELECT_LSB, track.bank_lsb)] {
+                if value != 0 && !track.controls.contains_key(&number) {
+                    events.push((0, 1, make_event(0, EventStatus::ControlChange, channel, number, value)));
+                }
+            }
+            events.push((0, 2, make_event(0, EventStatus::ProgramChange, channel, track.program, 0)));
+            for (&number, controls) in &track.controls {
+                let order = if number == BANK_SELECT_MSB || number == BANK_SELECT_LSB { 1 } else { 3 };
+                for control in controls {
+                    let tick = tempo_map.quarter_to_tick(control.time);
+                    events.push((to_sample(tick), order, make_event(
+                        tick, EventStatus::ControlChange, channel, number, control.value)));
+                }
+            }
+            for note in &track.notes {
+                let on = tempo_map.quarter_to_tick(note.start);
+                let off = tempo_map.quarter_to_tick(note.start + note.duration);
+                events.push((to_sample(on), 4, make_event(
+                    on, EventStatus::NoteOn, channel, note.pitch, note.velocity)));
+                events.push((to_sample(off), 0, make_event(
+                    off, EventStatus::NoteOff, channel, note.pitch, 0)));
+            }
+        }
+        events.sort_by_key(|e| (e.0, e.1));
+
+        Scheduler {
+            sample_rate,
+            block_size,
+            events: events.into_iter().map(|(sample, _, event)| (sample, event)).collect(),
+            cursor: 0,
+            position: 0,
+            chased: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 { self.sample_rate }
+
+    pub fn block_size(&self) -> usize { self.block_size }
+
+    /// Absolute sample position of the next block.
+    pub fn position(&self) -> u64 { self.position }
+
+    pub fn is_finished(&self) -> bool { self.cursor >= self.events.len() }
+
+    /// Jump to an absolute sample position. Notes before it are skipped; the last program
+    /// change and value of every controller on each channel are sent at the start of the
+    /// next block, so tracks keep their instruments and controller state.
+    pub fn seek(&mut self, sample: u64) {
+        self.position = sample;
+        self.cursor = self.events.partition_point(|e| e.0 < sample);
+        // The last event per channel, status and controller number, in their original order
+        let mut last = std::collections::HashMap::new();
+        for (idx, (_, event)) in self.events[..self.cursor].iter().enumerate() {
+            match event.status {
+                EventStatus::ProgramChange => last.insert((event.data[0], 0), idx),
+                EventStatus::ControlChange => last.insert((event.data[0], event.data[1]), idx),
+                _ => None,
+            };
+        }
+        let mut chased: Vec<usize> = last.into_values().collect();
+        chased.sort_unstable();
+        self.chased = chased.into_iter().map(|idx| self.events[idx].1).collect();
+    }
+
+    pub fn next_block(&mut self) -> Vec<(usize, Event)> {
+        let end = self.position + self.block_size as u64;
+        let mut block: Vec<(usize, Event)> = self.chased.drain(..).map(|event| (0, event)).collect();
+        while self.cursor < self.events.len() && self.events[self.cursor].0 < end {
+            let (sample, event) = self.events[self.cursor];
+            block.push(((sample - self.position) as usize, event));
+            self.cursor += 1;
+        }
+        self.position = end;
+        block
+    }
+}
+
+impl Iterator for Scheduler {
+    type Item = Vec<(usize, Event)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished() { None } else { Some(self.next_block()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{ControlChange, Note, Tempo, Track};
+
+    #[test]
+    fn test_scheduler_blocks() {
+        let seq = Sequence {
+            tracks: vec![Track {
+                notes: vec![Note { pitch: 60, start: 1.0, duration: 1.0, velocity: 100, tie: false }],
+                ..Track::default()
+            }],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }],
+            ..Sequence::default()
+        };
+        // One quarter is 0.5s = 22050 samples
+        let blocks: Vec<_> = Scheduler::new(&seq, 44100, 512).collect();
+        let events: Vec<_> = blocks.iter().enumerate()
+            .flat_map(|(i, b)| b.iter().map(move |(offset, e)| (i * 512 + offset, e.status)))
+            .collect();
+        assert_eq!(events[0], (0, EventStatus::ProgramChange));
+        assert_eq!(events[1], (22050, EventStatus::NoteOn));
+        assert_eq!(events[2], (44100, EventStatus::NoteOff));
+    }
+
+    #[test]
+    fn test_scheduler_channels() {
+        let track = |program: u8| Track {
+            program,
+            notes: vec![Note { pitch: 60, start: 0.0, duration: 1.0, velocity: 100, tie: false }],
+            ..Track::default()
+        };
+        // Channels follow the writer: the same instrument shares one
+        let seq = Sequence::from_tracks(vec![track(0), track(5), track(0)], 120.0);
+        let channels = |seq: &Sequence| -> Vec<u8> {
+            Scheduler::new(seq, 100, 1000).next_block().iter()
+                .filter(|(_, e)| e.status == EventStatus::ProgramChange)
+                .map(|(_, e)| e.channel().unwrap())
+                .collect()
+        };
+        assert_eq!(channels(&seq), vec![0, 1, 0]);
+        // Tracks beyond one port still play, wrapping around the channels
+        let seq = Sequence::from_tracks((0..20).map(track).collect(), 120.0);
+        assert_eq!(channels(&seq).len(), 20);
+    }
+
+    #[test]
+    fn test_scheduler_seek() {
+        let mut track = Track {
+            program: 40,
+            bank_msb: 8,
+            channel: Some(2),
+            notes: vec![
+                Note { pitch: 60, start: 0.0, duration: 1.0, velocity: 100, tie: false },
+                Note { pitch: 62, start: 2.0, duration: 1.0, velocity: 100, tie: false },
+            ],
+            ..Track::default()
+        };
+        track.controls.insert(7, vec![ControlChange { time: 0.0, value: 100 }, ControlChange { time: 1.0, value: 50 }]);
+        track.controls.insert(64, vec![ControlChange { time: 1.5, value: 127 }]);
+        let seq = Sequence::from_tracks(vec![track], 120.0);
+        // One quarter is 100 samples at 200 samples per second
+        let mut scheduler = Scheduler::new(&seq, 200, 10);
+        let first: Vec<u8> = scheduler.next_block().iter().map(|(_, e)| e.data[1]).collect();
+        assert_eq!(first, vec![BANK_SELECT_MSB, 40, 7, 60]);
+
+        scheduler.seek(195);
+        let chased: Vec<(usize, EventStatus, u8, u8)> = scheduler.next_block().iter()
+            .map(|(offset, e)| (*offset, e.status, e.data[1], e.data[2]))
+            .collect();
+        assert_eq!(chased, vec![
+            (0, EventStatus::ControlChange, BANK_SELECT_MSB, 8),
+            (0, EventStatus::ProgramChange, 40, 0),
+            (0, EventStatus::ControlChange, 7, 50),
+            (0, EventStatus::ControlChange, 64, 127),
+            (5, EventStatus::NoteOn, 62, 100),
+        ]);
+        // Chased state is sent once, and seeking to the start chases nothing
+        assert!(scheduler.next_block().is_empty());
+        scheduler.seek(0);
+        assert_eq!(scheduler.next_block().len(), first.len());
+        assert_eq!(scheduler.position(), 10);
+    }
+}
@@ -1,161 +1,345 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
-use pyo3::exceptions::{PyIOError};
+#[cfg(feature = "python")]
+use pyo3::exceptions::{PyBufferError, PyIOError, PyValueError};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::{PyBytes, PyDict};
+use crate::automation::FineControlChange;
+use crate::error::{Location, MidiError};
 use crate::io::MIDIFile;
-use crate::message::{MIDIMessage, MetaStatus, EventStatus};
+use crate::message::{MIDIFormat, MIDIMessage, MetaStatus, EventStatus, SysExJoiner};
+use crate::options::{Overlap, ParseOptions};
+use crate::pymethods::pymethods_with;
 use crate::util::tempo2qpm;
+use crate::report::ParseReport;
+use crate::text::{Metadata, TextEvent, TextKind};
+#[cfg(feature = "python")]
+use crate::{
+    automation::ControlLane,
+    batch::{py_load_dir, CancelToken, PyBatchResults},
+    buffer::{BufferExports, FrameArray, NoteArray},
+    compare::DEFAULT_TOLERANCE,
+    diff::SequenceDiff,
+    gm::GmViolation,
+    karaoke::Karaoke,
+    pattern::{DrumMatrix, DRUM_GROUPS},
+    pitch::MIDDLE_C_OCTAVE,
+    groove::GrooveHit,
+    text::parse_kind,
+    timing::{EventTimes, TimeUnit},
+    writer::WriteOptions,
+};
 use serde::{Serialize, Deserialize};
-use serde_yaml;
 
 const DEFAULT_QPM: f32 = 120.0;
 const DEFAULT_TEMPO: u32 = 500000;
+pub const BANK_SELECT_MSB: u8 = 0;
+pub const BANK_SELECT_LSB: u8 = 32;
+pub const SUSTAIN_PEDAL: u8 = 64;
+/// XG bank MSB selecting percussion kits on any channel.
+pub const XG_DRUM_BANK: u8 = 127;
 
-#[pyclass]
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Empty track for a channel, with the instrument selected on it so far.
+fn channel_track(channel: u8, program: u8, bank: (u8, u8)) -> Track {
+    Track {
+        program,
+        bank_msb: bank.0,
+        bank_lsb: bank.1,
+        is_drum: channel == 9 || bank.0 == XG_DRUM_BANK,
+        channel: Some(channel),
+        ..Track::default()
+    }
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
 pub struct Sequence {
-    #[pyo3(get, set)]
     pub tracks: Vec<Track>,
-    #[pyo3(get, set)]
     pub time_signatures: Vec<TimeSignature>,
-    #[pyo3(get, set)]
     pub key_signatures: Vec<KeySignature>,
-    #[pyo3(get, set)]
     pub qpm: Vec<Tempo>,
+    #[serde(default)]
+    pub texts: Vec<TextEvent>,
+    #[serde(default)]
+    pub warnings: ParseReport,
+    /// Ticks per quarter of the file the sequence was read from, `None` if it was built
+    /// in code. Writing and tick positions use it unless told otherwise.
+    #[serde(default)]
+    pub tpq: Option<u16>,
+    #[serde(default)]
+    pub metadata: Metadata,
 }
 
-#[pyclass]
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
 pub struct Track {
-    #[pyo3(get, set)]
     pub name: String,
-    #[pyo3(get, set)]
     pub program: u8,
-    #[pyo3(get, set)]
     pub is_drum: bool,
-    #[pyo3(get, set)]
+    #[serde(default)]
+    pub bank_msb: u8,
+    #[serde(default)]
+    pub bank_lsb: u8,
     pub notes: Vec<Note>,
-    #[pyo3(get, set)]
     pub controls: HashMap<u8, Vec<ControlChange>>,
+    /// Merged MSB/LSB controller pairs keyed by the MSB number, see `merge_14bit_controls`.
+    #[serde(default)]
+    pub fine_controls: HashMap<u8, Vec<FineControlChange>>,
+    #[serde(default)]
+    pub channel_pressure: Vec<ControlChange>,
+    /// Polyphonic aftertouch keyed by pitch.
+    #[serde(default)]
+    pub poly_pressure: HashMap<u8, Vec<ControlChange>>,
+    #[serde(default)]
+    pub sysex: Vec<SysEx>,
+    /// MIDI channel the track was read from and is written to, `None` assigns one by position.
+    #[serde(default)]
+    pub channel: Option<u8>,
+    /// Index of the MTrk chunk the track was read from.
+    #[serde(default)]
+    pub midi_track_index: Option<usize>,
+    /// MIDI port of a MIDIPort meta on the track; large templates use ports to address
+    /// more than 16 channels.
+    #[serde(default)]
+    pub port: Option<u8>,
+    /// Channel of a MIDIChannelPrefix meta on the track.
+    #[serde(default)]
+    pub channel_prefix: Option<u8>,
 }
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TrackTrans {
-    #[pyo3(get, set)]
     pub name: String,
-    #[pyo3(get, set)]
     pub program: u8,
-    #[pyo3(get, set)]
     pub is_drum: bool,
-    #[pyo3(get, set)]
     pub pitch: Vec<u8>,
-    #[pyo3(get, set)]
     pub start: Vec<f32>,
-    #[pyo3(get, set)]
     pub duration: Vec<f32>,
-    #[pyo3(get, set)]
     pub velocity: Vec<u8>,
-    #[pyo3(get, set)]
     pub controls: HashMap<u8, Vec<ControlChange>>,
+    #[serde(default)]
+    pub sysex: Vec<SysEx>,
+    #[cfg(feature = "python")]
+    #[serde(skip)]
+    pub(crate) exports: BufferExports,
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-#[pyclass]
+/// Every track of a sequence as `TrackTrans`. Concatenated, the note columns of all
+/// tracks are laid end to end here, `track` holds the index of each note's track, and the
+/// tracks keep only their metadata.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default)]
+pub struct SequenceTrans {
+    pub tracks: Vec<TrackTrans>,
+    pub track: Vec<u32>,
+    pub pitch: Vec<u8>,
+    pub start: Vec<f32>,
+    pub duration: Vec<f32>,
+    pub velocity: Vec<u8>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct Note {
-    #[pyo3(get, set)]
     pub pitch: u8,
-    #[pyo3(get, set)]
     pub start: f32,
-    #[pyo3(get, set)]
     pub duration: f32,
-    #[pyo3(get, set)]
     pub velocity: u8,
+    /// The note continues a previous segment of the same pitch, e.g. after a barline split.
+    #[serde(default)]
+    pub tie: bool,
 }
 
-#[pyclass]
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ControlChange {
-    #[pyo3(get, set)]
     pub time: f32,
-    #[pyo3(get, set)]
     pub value: u8,
 }
 
-#[pyclass]
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+/// A system exclusive message; `data` excludes the framing F0 and F7 bytes. An escape
+/// carries bytes sent as they are in an F7 packet, without framing, e.g. real-time
+/// messages or a SysEx split across events by its author.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SysEx {
+    pub time: f32,
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub escape: bool,
+}
+
+/// Bank select and program change together, so GS/XG variations and kits are told apart.
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Instrument {
+    pub bank_msb: u8,
+    pub bank_lsb: u8,
+    pub program: u8,
+}
+
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct TimeSignature {
-    #[pyo3(get, set)]
     pub time: f32,
-    #[pyo3(get, set)]
     pub numerator: u8,
-    #[pyo3(get, set)]
     pub denominator: u8,
+    /// MIDI clocks, 24 to a quarter, per metronome click; 24 if not given.
+    #[serde(default)]
+    pub clocks_per_click: Option<u8>,
+    /// Notated 32nds per MIDI quarter; 8 if not given.
+    #[serde(default)]
+    pub thirty_seconds_per_quarter: Option<u8>,
 }
 
-#[pyclass]
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct KeySignature {
-    #[pyo3(get, set)]
     pub time: f32,
-    #[pyo3(get, set)]
     pub key: (bool, i8), // bool true 代表大调，false代表小调
 }
 
-#[pyclass]
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Tempo {
-    #[pyo3(get, set)]
     pub time: f32,
-    #[pyo3(get, set)]
     pub qpm: f32,
 }
 
 impl Sequence {
-    pub fn from_file(path: &str) -> Result<Sequence, &'static str> {
+    /// A sequence of `tracks` at a constant `qpm`, e.g. from `TrackTrans::to_track`.
+    pub fn from_tracks(tracks: Vec<Track>, qpm: f32) -> Sequence {
+        Sequence { tracks, qpm: vec![Tempo { time: 0.0, qpm }], ..Sequence::default() }
+    }
+
+    pub fn from_file(path: &str) -> Result<Sequence, MidiError> {
         let midi = MIDIFile::from_file(path)?;
         Self::from_midi(&midi)
     }
-    pub fn from_midi(midi: &MIDIFile) -> Result<Sequence, &'static str> {
+    pub fn from_bytes(data: &[u8]) -> Result<Sequence, MidiError> {
+        let midi = MIDIFile::from_bytes(data)?;
+        Self::from_midi(&midi)
+    }
+    pub fn from_zip(archive: &str, member: &str) -> Result<Sequence, MidiError> {
+        let midi = MIDIFile::from_zip(archive, member)?;
+        Self::from_midi(&midi)
+    }
+
+    /// One independent `Sequence` per song, so format 2 files don't share a tempo map.
+    pub fn from_midi_multi(midi: &MIDIFile, strict: bool) -> Result<Vec<Sequence>, MidiError> {
+        midi.songs().iter().map(|song| Self::from_midi_with(song, strict)).collect()
+    }
+
+    pub fn from_midi(midi: &MIDIFile) -> Result<Sequence, MidiError> {
+        Self::from_midi_with(midi, true)
+    }
+
+    /// With `strict` off, malformed data doesn't fail the parse: chunk problems are worked
+    /// around, a track stops at its first unreadable message, and everything is recorded
+    /// in `warnings`.
+    pub fn from_midi_with(midi: &MIDIFile, strict: bool) -> Result<Sequence, MidiError> {
+        Self::from_midi_with_options(midi, &ParseOptions { strict, ..ParseOptions::default() })
+    }
+
+    /// Read a file as `options` say, memory mapped if `options.mmap` is set.
+    pub fn from_file_with_options(path: &str, options: &ParseOptions) -> Result<Sequence, MidiError> {
+        let midi = if options.mmap { MIDIFile::from_file_mapped(path)? } else { MIDIFile::from_file(path)? };
+        Self::from_midi_with_options(&midi, options)
+    }
+
+    /// `from_midi_with` for every `ParseOptions` setting.
+    pub fn from_midi_with_options(midi: &MIDIFile, options: &ParseOptions) -> Result<Sequence, MidiError> {
+        let strict = options.strict;
+        if let Some(warning) = midi.warnings.first().filter(|_| strict) {
+            return Err(MidiError::Track(format!(
+                "{}, parse with strict=False to recover.", warning.message()), warning.location()));
+        }
         if midi.division >> 15 == 1 {
-            return Err("Division with 1 at high bit is not supported!");
+            return Err(MidiError::Unsupported("SMPTE time division is not supported.".to_string(), Location {
+                offset: Some(12),
+                ..Location::default()
+            }));
         }
         let tpq = midi.division as f32; // ticks per quarter
         let mut qpm = Vec::new();
         let mut time_signatures = Vec::new();
         let mut key_signatures = Vec::new();
+        let mut texts = Vec::new();
         let mut tracks = HashMap::<(u8, u8), Track>::new();
         let mut track_names = vec![String::new(); midi.tracks.len()];
+        let mut ports = vec![None; midi.tracks.len()];
+        let mut channel_prefixes = vec![None; midi.tracks.len()];
+        let mut instrument_names = Vec::new();
+        let mut warnings = ParseReport {
+            chunk_length_mismatches: midi.warnings.len(),
+            messages: midi.warnings.iter().map(ToString::to_string).collect(),
+            ..ParseReport::default()
+        };
         for (track_idx, track) in midi.tracks.iter().enumerate() {
             let mut cur_instr = [0_u8; 16]; // 16 channels
+            let mut cur_bank = [(0_u8, 0_u8); 16]; // (MSB, LSB)
             let mut last_note_on = [[(0_u32, 0_u8); 128]; 16]; // （start, velocity)
-            for msg in track.iter() {
+            let mut end_tick = 0;
+            let mut sysex_messages = Vec::new();
+            let mut sysex_joiner = SysExJoiner::default();
+            let mut messages = track.iter();
+            while let Some(msg) = messages.next() {
                 match msg {
                     MIDIMessage::Event(event) => {
+                        end_tick = event.time;
                         let cur = event.time as f32 / tpq;
                         match event.status {
                             EventStatus::ProgramChange => {
-                                cur_instr[event.channel().unwrap_or(0) as usize]
-                                    = event.program().unwrap_or(0)
+                                let channel = event.channel().unwrap_or(0);
+                                cur_instr[channel as usize] = event.program().unwrap_or(0);
+                                // Controllers may have opened the track before its program was set
+                                if let Some(track) = tracks.get_mut(&(track_idx as u8, channel)) {
+                                    if track.notes.is_empty() {
+                                        *track = Track {
+                                            controls: std::mem::take(&mut track.controls),
+                                            ..channel_track(channel, cur_instr[channel as usize], cur_bank[channel as usize])
+                                        };
+                                    }
+                                }
                             }
                             EventStatus::ControlChange => {
                                 let channel = event.channel().unwrap_or(0);
+                                let (ctrl_k, ctrl_v) = event.control_change().unwrap();
                                 let track_entry = tracks
                                     .entry((track_idx as u8, channel))
-                                    .or_insert(Track {
-                                        program: cur_instr[channel as usize],
-                                        is_drum: channel == 9,
-                                        ..Track::default()
-                                    });
-                                let (ctrl_k, ctrl_v) = event.control_change().unwrap();
+                                    .or_insert_with(|| channel_track(channel, cur_instr[channel as usize], cur_bank[channel as usize]));
+                                if ctrl_k == BANK_SELECT_MSB || ctrl_k == BANK_SELECT_LSB {
+                                    let bank = &mut cur_bank[channel as usize];
+                                    if ctrl_k == BANK_SELECT_MSB { bank.0 = ctrl_v } else { bank.1 = ctrl_v }
+                                    if track_entry.notes.is_empty() {
+                                        *track_entry = Track {
+                                            controls: std::mem::take(&mut track_entry.controls),
+                                            ..channel_track(channel, cur_instr[channel as usize], *bank)
+                                        };
+                                    }
+                                }
                                 let ctrl_entry = track_entry
                                     .controls.entry(ctrl_k)
-                                    .or_insert(Vec::new());
+                                    .or_default();
                                 ctrl_entry.push(ControlChange {
                                     time: cur,
                                     value: ctrl_v,
                                 });
                             }
+                            EventStatus::ChannelAfterTouch | EventStatus::PolyphonicAfterTouch => {
+                                let channel = event.channel().unwrap_or(0);
+                                let track_entry = tracks
+                                    .entry((track_idx as u8, channel))
+                                    .or_insert_with(|| channel_track(channel, cur_instr[channel as usize], cur_bank[channel as usize]));
+                                let pressure = ControlChange { time: cur, value: event.pressure().unwrap() };
+                                match event.key() {
+                                    Some(pitch) => track_entry.poly_pressure.entry(pitch).or_default().push(pressure),
+                                    None => track_entry.channel_pressure.push(pressure),
+                                }
+                            }
                             EventStatus::NoteOn | EventStatus::NoteOff => {
                                 let velocity = event.velocity().unwrap_or(0);
                                 let channel = event.channel().unwrap_or(0);
@@ -164,29 +348,51 @@ impl Sequence {
                                 if velocity == 0 || event.status == EventStatus::NoteOff {
                                     let (start, on_vel) = last_note_on[channel as usize][pitch as usize];
                                     if on_vel != 0 {
+                                        if event.time == start {
+                                            warnings.zero_length_notes += 1;
+                                        }
                                         let track_entry = tracks
                                             .entry((track_idx as u8, channel))
-                                            .or_insert(Track {
-                                                program: cur_instr[channel as usize],
-                                                is_drum: channel == 9,
-                                                ..Track::default()
-                                            });
+                                            .or_insert_with(|| channel_track(channel, cur_instr[channel as usize], cur_bank[channel as usize]));
                                         track_entry.notes.push(Note {
                                             pitch,
                                             velocity: on_vel,
                                             start: start as f32 / tpq,
                                             duration: (event.time - start) as f32 / tpq,
+                                            tie: false,
                                         });
                                         last_note_on[channel as usize][pitch as usize].1 = 0;
                                     }
                                 } else {
+                                    let (start, on_vel) = last_note_on[channel as usize][pitch as usize];
+                                    if on_vel != 0 {
+                                        match options.overlap {
+                                            Overlap::Replace => {}
+                                            Overlap::Close => tracks
+                                                .entry((track_idx as u8, channel))
+                                                .or_insert_with(|| channel_track(channel, cur_instr[channel as usize], cur_bank[channel as usize]))
+                                                .notes.push(Note {
+                                                    pitch,
+                                                    velocity: on_vel,
+                                                    start: start as f32 / tpq,
+                                                    duration: (event.time - start) as f32 / tpq,
+                                                    tie: false,
+                                                }),
+                                            Overlap::Keep => continue,
+                                        }
+                                    }
                                     last_note_on[channel as usize][pitch as usize] = (event.time, velocity);
                                 }
                             }
                             _ => {} // Pass unused event
                         }
                     }
+                    MIDIMessage::SysEx(message) => {
+                        end_tick = message.time;
+                        sysex_joiner.push(&message, &mut sysex_messages);
+                    }
                     MIDIMessage::Meta(meta) => {
+                        end_tick = meta.time;
                         let cur = meta.time as f32 / tpq;
                         match meta.status {
                             MetaStatus::SetTempo => {
@@ -196,73 +402,149 @@ impl Sequence {
                                 })
                             }
                             MetaStatus::TimeSignature => {
-                                let t = meta.time_signature().unwrap_or((4, 4, 0, 0));
+                                let t = meta.time_signature();
+                                let (numerator, denominator, _, _) = t.unwrap_or((4, 4, 0, 0));
                                 time_signatures.push(TimeSignature {
                                     time: cur,
-                                    numerator: t.0,
-                                    denominator: t.1,
+                                    numerator,
+                                    denominator,
+                                    clocks_per_click: t.map(|t| t.2),
+                                    thirty_seconds_per_quarter: t.map(|t| t.3),
                                 })
                             }
-                            MetaStatus::KeySignature => {
-                                key_signatures.push(KeySignature {
-                                    time: cur,
-                                    key: meta.key_signature().unwrap(),
-                                })
+                            MetaStatus::KeySignature => match meta.key_signature() {
+                                Some(key) => key_signatures.push(KeySignature { time: cur, key }),
+                                None if strict => return Err(MidiError::Event(
+                                    "Invalid key signature".to_string(), Location {
+                                        track: Some(track_idx),
+                                        tick: Some(meta.time),
+                                        offset: Some(track.offset() + messages.message_end),
+                                    })),
+                                None => warnings.warn(format!(
+                                    "Track {}: invalid key signature at {} skipped", track_idx, cur)),
                             }
                             MetaStatus::TrackName => {
-                                let name: String = String::from_utf8(
-                                    meta.meta_value().to_vec()
-                                ).unwrap();
-                                track_names[track_idx] = name;
+                                track_names[track_idx] = options.encoding.decode(meta.meta_value());
+                            }
+                            MetaStatus::InstrumentName => {
+                                instrument_names.push(options.encoding.decode(meta.meta_value()));
+                            }
+                            MetaStatus::MIDIPort => {
+                                ports[track_idx] = ports[track_idx].or(meta.port());
                             }
-                            _ => {} // Pass unknown meta
+                            MetaStatus::MIDIChannelPrefix => {
+                                channel_prefixes[track_idx] = channel_prefixes[track_idx].or(meta.channel_prefix());
+                            }
+                            MetaStatus::Unknown => warnings.unknown_metas += 1,
+                            status => if let Some(kind) = TextKind::from_meta(status) {
+                                texts.push(TextEvent {
+                                    time: cur,
+                                    text: options.encoding.decode(meta.meta_value()),
+                                    kind,
+                                })
+                            } // Pass unknown meta
                         }
                     }
                 }
             }
+            if let Some(error) = messages.located_error(track_idx, track.offset()) {
+                if strict {
+                    return Err(error);
+                }
+                warnings.warn(format!("{}, rest of the track skipped", error));
+            }
+            if messages.trailing_bytes > 0 {
+                warnings.warn(format!(
+                    "Track {}: {} bytes after EndOfTrack ignored", track_idx, messages.trailing_bytes));
+            }
+            warnings.cancelled_running_status += messages.cancelled_running_status;
+            if messages.skipped_bytes > 0 {
+                warnings.warn(format!(
+                    "Track {}: {} data bytes without a running status skipped", track_idx, messages.skipped_bytes));
+            }
+            sysex_messages.extend(sysex_joiner.finish());
+            let mut sysex = Vec::with_capacity(sysex_messages.len());
+            for message in sysex_messages {
+                let time = message.time as f32 / tpq;
+                let payload = message.payload();
+                if message.status() == 0xF7 {
+                    sysex.push(SysEx { time, data: payload.to_vec(), escape: true });
+                    continue;
+                }
+                if !message.is_terminated() {
+                    warnings.warn(format!("Track {}: SysEx at {} was never terminated", track_idx, time));
+                }
+                sysex.push(SysEx { time, data: payload.strip_suffix(&[0xF7]).unwrap_or(payload).to_vec(), escape: false });
+            }
+            if !sysex.is_empty() {
+                // Kept on the first channel of the MIDI track, which may only hold SysEx
+                let channel = (0..16_u8).find(|&c| tracks.contains_key(&(track_idx as u8, c)))
+                    .or(channel_prefixes[track_idx])
+                    .unwrap_or(0);
+                tracks.entry((track_idx as u8, channel))
+                    .or_insert_with(|| channel_track(channel, cur_instr[channel as usize], cur_bank[channel as usize]))
+                    .sysex = sysex;
+            }
+            for (channel, notes_on) in last_note_on.iter().enumerate() {
+                for (pitch, &(start, velocity)) in notes_on.iter().enumerate() {
+                    if velocity == 0 {
+                        continue;
+                    }
+                    warnings.notes_closed_at_eot += 1;
+                    tracks.entry((track_idx as u8, channel as u8))
+                        .or_insert_with(|| channel_track(channel as u8, cur_instr[channel], cur_bank[channel]))
+                        .notes.push(Note {
+                            pitch: pitch as u8,
+                            velocity,
+                            start: start as f32 / tpq,
+                            duration: end_tick.saturating_sub(start) as f32 / tpq,
+                            tie: false,
+                        });
+                }
+            }
         }
 
         qpm.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
         time_signatures.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
         key_signatures.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        texts.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        if midi.format == MIDIFormat::MultiSong && midi.tracks.len() > 1 {
+            warnings.warn("Format 2 songs were merged into one sequence, use from_midi_multi to keep them apart".to_string());
+        }
+        warnings.default_tempo_assumed = qpm.is_empty();
         if qpm.is_empty() || qpm[0].time > 0.0 {
             qpm.insert(0, Tempo { time: 0.0, qpm: DEFAULT_QPM });
         }
+        if options.sustain {
+            tracks.values_mut().for_each(Track::apply_sustain);
+        }
+        let metadata = Metadata::collect(track_names.first().map_or("", String::as_str), instrument_names, &texts);
         Ok(Sequence {
             tracks: tracks
                 .into_iter()
                 .map(|(k, mut t)| {
                     t.name = track_names[k.0 as usize].clone();
+                    t.midi_track_index = Some(k.0 as usize);
+                    t.port = ports[k.0 as usize];
+                    t.channel_prefix = channel_prefixes[k.0 as usize];
                     t
                 }) // .filter(|t| !t.notes.is_empty())
                 .collect(),
             time_signatures,
             key_signatures,
             qpm,
+            texts,
+            warnings,
+            tpq: Some(midi.division),
+            metadata,
         })
     }
-}
-
-#[pymethods]
-impl Sequence {
-    #[new]
-    pub fn py_new(path: &str) -> PyResult<Self> {
-        let seq = Self::from_file(path);
-        match seq {
-            Err(info) => Err(PyIOError::new_err(info)),
-            Ok(seq) => Ok(seq)
-        }
-    }
-
-    pub fn __repr__(&self) -> String {
-        serde_yaml::to_string(&self).unwrap()
-    }
-
     pub fn sort(&mut self) {
-        self.time_signatures.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
-        self.qpm.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
-        self.key_signatures.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
-        for mut track in self.tracks.iter_mut() {
+        self.time_signatures.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.qpm.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.key_signatures.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.texts.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for track in self.tracks.iter_mut() {
             track.sort();
         }
     }
@@ -273,14 +555,14 @@ impl Sequence {
         for track in &self.tracks {
             let mut starts = Vec::<f32>::with_capacity(track.notes.len());
 
-            let mut next_timesig_time = if(self.time_signatures.len() > 1) {self.time_signatures[1].time} else {f32::MAX};
+            let mut next_timesig_time = if self.time_signatures.len() > 1 {self.time_signatures[1].time} else {f32::MAX};
             let mut this_timesig = self.time_signatures[0];
             let mut this_timesig_idx = 0;
             for note in &track.notes {
-                if(note.start >= next_timesig_time) {
+                if note.start >= next_timesig_time {
                     this_timesig_idx += 1;
                     this_timesig = self.time_signatures[this_timesig_idx];
-                    next_timesig_time = if(this_timesig_idx < self.time_signatures.len() - 1) {self.time_signatures[1].time} else {f32::MAX};
+                    next_timesig_time = if this_timesig_idx < self.time_signatures.len() - 1 {self.time_signatures[this_timesig_idx + 1].time} else {f32::MAX};
                 }
                 starts.push((note.start - this_timesig.time) % (this_timesig.numerator as f32));
             }
@@ -289,8 +571,549 @@ impl Sequence {
 
         time_in_measure
     }
+
+    /// Merge MSB/LSB controller pairs on every track, see `Track.merge_14bit_controls`.
+    pub fn merge_14bit_controls(&mut self) {
+        for track in &mut self.tracks {
+            track.merge_14bit_controls();
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+fn parse_unit(unit: &str) -> PyResult<TimeUnit> {
+    unit.parse().map_err(PyValueError::new_err)
+}
+
+/// `value` if it is positive and finite, a `ValueError` naming it otherwise.
+#[cfg(feature = "python")]
+pub(crate) fn positive(name: &str, value: f32) -> PyResult<f32> {
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(PyValueError::new_err(format!("{} must be positive, got {}", name, value)))
+    }
+}
+
+/// Drum groups given from Python, `DRUM_GROUPS` if none are.
+#[cfg(feature = "python")]
+fn drum_groups(groups: &Option<Vec<Vec<u8>>>) -> Vec<&[u8]> {
+    match groups {
+        Some(groups) => groups.iter().map(Vec::as_slice).collect(),
+        None => DRUM_GROUPS.to_vec(),
+    }
 }
 
+pymethods_with! { [pickle, approx_eq("Equal up to `tolerance` in event times and tempi, ignoring parse warnings.")] #[pymethods] impl Sequence {
+    /// Keyword arguments override the matching fields of `options`.
+    #[new]
+    #[pyo3(signature = (path, strict = None, mmap = None, *, options = None, sustain = None, overlap = None, encoding = None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn py_new(
+        path: &str,
+        strict: Option<bool>,
+        mmap: Option<bool>,
+        options: Option<ParseOptions>,
+        sustain: Option<bool>,
+        overlap: Option<&str>,
+        encoding: Option<&str>,
+    ) -> PyResult<Self> {
+        let mut options = options.unwrap_or_default();
+        options.strict = strict.unwrap_or(options.strict);
+        options.mmap = mmap.unwrap_or(options.mmap);
+        options.sustain = sustain.unwrap_or(options.sustain);
+        if let Some(overlap) = overlap {
+            options.overlap = overlap.parse().map_err(PyValueError::new_err)?;
+        }
+        if let Some(encoding) = encoding {
+            options.encoding = encoding.parse().map_err(PyValueError::new_err)?;
+        }
+        Ok(Self::from_file_with_options(path, &options)?)
+    }
+
+    pub fn __repr__(&self) -> String {
+        serde_yaml::to_string(&self).unwrap()
+    }
+
+    #[setter]
+    fn set_tracks(&mut self, tracks: Vec<Track>) { self.tracks = tracks; }
+
+    #[setter]
+    fn set_time_signatures(&mut self, time_signatures: Vec<TimeSignature>) { self.time_signatures = time_signatures; }
+
+    #[setter]
+    fn set_key_signatures(&mut self, key_signatures: Vec<KeySignature>) { self.key_signatures = key_signatures; }
+
+    #[setter]
+    fn set_qpm(&mut self, qpm: Vec<Tempo>) { self.qpm = qpm; }
+
+    #[setter]
+    fn set_texts(&mut self, texts: Vec<TextEvent>) { self.texts = texts; }
+
+    #[setter]
+    fn set_metadata(&mut self, metadata: Metadata) { self.metadata = metadata; }
+
+    /// See `Sequence::karaoke`.
+    #[getter]
+    fn get_karaoke(&self) -> Option<Karaoke> { self.karaoke() }
+
+    /// `(note, syllable)` pairs, each syllable with the nearest note onset of a track.
+    #[pyo3(name="align_lyrics")]
+    pub fn py_align_lyrics(&self, track_index: usize) -> PyResult<Vec<(Note, String)>> {
+        self.align_lyrics(track_index).map_err(pyo3::exceptions::PyIndexError::new_err)
+    }
+
+    /// `Track.drum_matrix` of a track over the sequence's time signatures.
+    #[pyo3(name="drum_matrix", signature = (track_index, steps_per_bar = 16, groups = None))]
+    pub fn py_drum_matrix(&self, track_index: usize, steps_per_bar: usize, groups: Option<Vec<Vec<u8>>>) -> PyResult<DrumMatrix> {
+        let groups = drum_groups(&groups);
+        self.drum_matrix(track_index, steps_per_bar, &groups).map_err(PyValueError::new_err)
+    }
+
+    fn _repr_html_(&self) -> String {
+        self.repr_html(true)
+    }
+
+    #[pyo3(name = "to_html", signature = (piano_roll = true))]
+    fn py_to_html(&self, piano_roll: bool) -> String {
+        self.repr_html(piano_roll)
+    }
+
+    fn __len__(&self) -> usize { self.tracks.len() }
+
+    /// Iterate over copies of the tracks.
+    fn __iter__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.tracks.clone().into_py(py).call_method0(py, "__iter__")
+    }
+
+    /// `(track_index, note)` over all tracks in start order.
+    #[pyo3(name="iter_notes")]
+    fn py_iter_notes(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let notes: Vec<(usize, Note)> = self.iter_notes().map(|(t, n)| (t, *n)).collect();
+        notes.into_py(py).call_method0(py, "__iter__")
+    }
+
+    #[staticmethod]
+    #[pyo3(name="from_zip", signature = (archive, member, strict = true))]
+    pub fn py_from_zip(archive: &str, member: &str, strict: bool) -> PyResult<Self> {
+        let midi = MIDIFile::from_zip(archive, member)?;
+        Ok(Self::from_midi_with(&midi, strict)?)
+    }
+
+    /// Read a format 2 file as one sequence per song.
+    #[staticmethod]
+    #[pyo3(name = "from_midi_multi", signature = (path, strict = true))]
+    pub fn py_from_midi_multi(path: &str, strict: bool) -> PyResult<Vec<Sequence>> {
+        let midi = MIDIFile::from_file(path)?;
+        Ok(Self::from_midi_multi(&midi, strict)?)
+    }
+
+    /// Read every MIDI file under a directory as `([(path, sequence)], [(path, error)])`.
+    /// `on_progress(done, total, path)` is called after each file; cancelling `cancel`
+    /// stops the batch with the files read so far.
+    #[staticmethod]
+    #[pyo3(name = "from_dir", signature = (path, strict = true, on_progress = None, cancel = None))]
+    pub fn py_from_dir(
+        py: Python<'_>,
+        path: &str,
+        strict: bool,
+        on_progress: Option<PyObject>,
+        cancel: Option<CancelToken>,
+    ) -> PyResult<PyBatchResults> {
+        py_load_dir(py, path, strict, on_progress, cancel)
+    }
+
+    #[staticmethod]
+    #[pyo3(name="from_bytes", signature = (data, strict = true))]
+    pub fn py_from_bytes(data: &[u8], strict: bool) -> PyResult<Self> {
+        let midi = MIDIFile::from_bytes(data)?;
+        Ok(Self::from_midi_with(&midi, strict)?)
+    }
+
+    #[pyo3(name="to_json")]
+    pub fn py_to_json(&self) -> PyResult<String> {
+        self.to_json().map_err(PyValueError::new_err)
+    }
+
+    /// All tracks as `TrackTrans`, or with `concat` as one set of note columns plus a
+    /// track index column.
+    #[pyo3(name="transpose", signature = (concat = false))]
+    pub fn py_transpose(&self, concat: bool) -> SequenceTrans {self.transpose(concat)}
+
+    #[pyo3(name="to_soa", signature = (concat = false))]
+    pub fn py_to_soa(&self, concat: bool) -> SequenceTrans {self.transpose(concat)}
+
+    /// A one track sequence from note columns, such as a model's output.
+    #[staticmethod]
+    #[pyo3(name="from_arrays", signature = (pitch, start, duration, velocity, program = 0, is_drum = false, qpm = 120.0))]
+    pub fn py_from_arrays(
+        pitch: Vec<u8>,
+        start: Vec<f32>,
+        duration: Vec<f32>,
+        velocity: Vec<u8>,
+        program: u8,
+        is_drum: bool,
+        qpm: f32,
+    ) -> PyResult<Self> {
+        let track = Track { program, is_drum, ..Track::from_arrays(&pitch, &start, &duration, &velocity).map_err(PyValueError::new_err)? };
+        Ok(Self::from_tracks(vec![track], qpm))
+    }
+
+    #[staticmethod]
+    #[pyo3(name="from_json")]
+    pub fn py_from_json(json: &str) -> PyResult<Self> {
+        Self::from_json(json).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name="to_yaml")]
+    pub fn py_to_yaml(&self) -> PyResult<String> {
+        self.to_yaml().map_err(PyValueError::new_err)
+    }
+
+    #[staticmethod]
+    #[pyo3(name="from_yaml")]
+    pub fn py_from_yaml(yaml: &str) -> PyResult<Self> {
+        Self::from_yaml(yaml).map_err(PyValueError::new_err)
+    }
+
+    /// Note columns track, program, is_drum, pitch, start, duration and velocity, for
+    /// `pandas.DataFrame(seq.notes_dataframe())`.
+    #[pyo3(name="notes_dataframe")]
+    pub fn py_notes_dataframe<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        crate::table::columns_to_dict(py, self.note_table().columns())
+    }
+
+    /// Note, control and meta tables as `pyarrow.RecordBatch`es. Needs pyarrow.
+    #[pyo3(name="to_arrow")]
+    pub fn py_to_arrow<'py>(&self, py: Python<'py>) -> PyResult<(&'py PyAny, &'py PyAny, &'py PyAny)> {
+        crate::table::to_arrow(py, self)
+    }
+
+    /// One of the tables, "notes", "controls" or "meta", as a Parquet file. Needs pyarrow.
+    #[pyo3(name="write_parquet", signature = (path, table = "notes"))]
+    pub fn py_write_parquet(&self, py: Python<'_>, path: &str, table: &str) -> PyResult<()> {
+        crate::table::write_parquet(py, self, path, table)
+    }
+
+    #[pyo3(name="to_msgpack")]
+    pub fn py_to_msgpack<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        Ok(PyBytes::new(py, &self.to_msgpack().map_err(PyValueError::new_err)?))
+    }
+
+    #[staticmethod]
+    #[pyo3(name="from_msgpack")]
+    pub fn py_from_msgpack(data: &[u8]) -> PyResult<Self> {
+        Self::from_msgpack(data).map_err(PyValueError::new_err)
+    }
+
+    /// Store as JSON or YAML by file extension, see `load`.
+    #[pyo3(name="save")]
+    pub fn py_save(&self, path: &str) -> PyResult<()> {
+        self.save(path).map_err(PyIOError::new_err)
+    }
+
+    /// Stereo samples rendered through a SoundFont, see `Samples`.
+    #[cfg(feature = "synth")]
+    #[pyo3(name="synthesize", signature = (soundfont, sample_rate = 44100))]
+    pub fn py_synthesize(&self, py: Python<'_>, soundfont: &str, sample_rate: u32) -> PyResult<crate::buffer::Samples> {
+        let samples = py.allow_threads(|| self.synthesize(soundfont, sample_rate)).map_err(PyIOError::new_err)?;
+        Ok(crate::buffer::Samples::new(samples, sample_rate))
+    }
+
+    #[cfg(feature = "synth")]
+    #[pyo3(name="to_wav", signature = (path, soundfont, sample_rate = 44100))]
+    pub fn py_to_wav(&self, py: Python<'_>, path: &str, soundfont: &str, sample_rate: u32) -> PyResult<()> {
+        py.allow_threads(|| self.to_wav(path, soundfont, sample_rate)).map_err(PyIOError::new_err)
+    }
+
+    /// A stopped `Player` on the first output port whose name contains `port_name`.
+    #[cfg(feature = "playback")]
+    #[pyo3(name="play", signature = (port_name = ""))]
+    pub fn py_play(&self, port_name: &str) -> PyResult<crate::playback::Player> {
+        self.play(port_name).map_err(PyIOError::new_err)
+    }
+
+    /// Notes as CSV, one row per note.
+    #[pyo3(name="to_csv")]
+    pub fn py_to_csv(&self, path: &str) -> PyResult<()> {
+        self.to_csv(path).map_err(PyIOError::new_err)
+    }
+
+    #[pyo3(name="tempo_to_csv")]
+    pub fn py_tempo_to_csv(&self, path: &str) -> PyResult<()> {
+        self.tempo_to_csv(path).map_err(PyIOError::new_err)
+    }
+
+    #[pyo3(name="controls_to_csv")]
+    pub fn py_controls_to_csv(&self, path: &str) -> PyResult<()> {
+        self.controls_to_csv(path).map_err(PyIOError::new_err)
+    }
+
+    #[staticmethod]
+    #[pyo3(name="from_csv", signature = (path, tempo_path = None, controls_path = None))]
+    pub fn py_from_csv(path: &str, tempo_path: Option<&str>, controls_path: Option<&str>) -> PyResult<Self> {
+        Self::from_csv(path, tempo_path, controls_path).map_err(PyIOError::new_err)
+    }
+
+    #[staticmethod]
+    #[pyo3(name="load")]
+    pub fn py_load(path: &str) -> PyResult<Self> {
+        Self::load(path).map_err(PyIOError::new_err)
+    }
+
+    #[pyo3(name="to_bytes", signature = (options = None))]
+    pub fn py_to_bytes<'py>(&self, py: Python<'py>, options: Option<WriteOptions>) -> PyResult<&'py PyBytes> {
+        let bytes = self.to_bytes(&options.unwrap_or_else(|| self.write_options())).map_err(PyValueError::new_err)?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    #[pyo3(name="allocate_channels", signature = (max_ports = None))]
+    pub fn py_allocate_channels(&self, max_ports: Option<u8>) -> PyResult<Vec<(u8, u8)>> {
+        self.allocate_channels(max_ports).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name="write", signature = (path, options = None))]
+    pub fn py_write(&self, path: &str, options: Option<WriteOptions>) -> PyResult<()> {
+        self.write(path, &options.unwrap_or_else(|| self.write_options())).map_err(PyIOError::new_err)
+    }
+
+    #[pyo3(name="sort")]
+    pub fn py_sort(&mut self) {self.sort()}
+
+    #[pyo3(name="start_in_measure")]
+    pub fn py_start_in_measure(&self) -> Vec<Vec<f32>> {self.start_in_measure()}
+
+    #[pyo3(name="add_text_event", signature = (time, text, kind = "marker"))]
+    pub fn py_add_text_event(&mut self, time: f32, text: String, kind: &str) -> PyResult<()> {
+        self.add_text_event(TextEvent { time, text, kind: parse_kind(kind)? });
+        Ok(())
+    }
+
+    #[pyo3(name="text_events", signature = (kind = None))]
+    pub fn py_text_events(&self, kind: Option<&str>) -> PyResult<Vec<TextEvent>> {
+        Ok(self.text_events(kind.map(parse_kind).transpose()?))
+    }
+
+    #[pyo3(name="remove_text_events", signature = (kind = None))]
+    pub fn py_remove_text_events(&mut self, kind: Option<&str>) -> PyResult<usize> {
+        Ok(self.remove_text_events(kind.map(parse_kind).transpose()?))
+    }
+
+    #[pyo3(name="markers")]
+    pub fn py_markers(&self) -> Vec<(f32, String)> {self.markers()}
+
+    #[pyo3(name="merge_14bit_controls")]
+    pub fn py_merge_14bit_controls(&mut self) {self.merge_14bit_controls()}
+
+    #[pyo3(name="chroma")]
+    pub fn py_chroma(&self, fps: f32) -> PyResult<Vec<Vec<f32>>> {
+        Ok(self.chroma(positive("fps", fps)?))
+    }
+
+    #[pyo3(name="polyphony")]
+    pub fn py_polyphony(&self, fps: f32) -> PyResult<Vec<u32>> {
+        Ok(self.polyphony(positive("fps", fps)?))
+    }
+
+    /// `(activation, onset, velocity)` as `(frames, pitches)` uint8 buffers, velocity
+    /// `None` unless `include_velocity`.
+    #[pyo3(name="frame_labels", signature = (fps, pitch_range = (21, 108), include_velocity = true))]
+    pub fn py_frame_labels(&self, py: Python<'_>, fps: f32, pitch_range: (u8, u8), include_velocity: bool) -> PyResult<(FrameArray, FrameArray, Option<FrameArray>)> {
+        let fps = positive("fps", fps)?;
+        if pitch_range.0 > pitch_range.1 || pitch_range.1 > 127 {
+            return Err(PyValueError::new_err("pitch_range must be (low, high) within 0 to 127"));
+        }
+        let labels = py.allow_threads(|| self.frame_labels(fps, pitch_range, include_velocity));
+        let array = |data| FrameArray::new(data, labels.frames, labels.pitches);
+        Ok((array(labels.activation), array(labels.onset), labels.velocity.map(array)))
+    }
+
+    #[pyo3(name="note_density", signature = (per = "bar"))]
+    pub fn py_note_density(&self, per: &str) -> PyResult<Vec<usize>> {
+        Ok(self.note_density(per.parse().map_err(PyValueError::new_err)?))
+    }
+
+    #[pyo3(name="beats", signature = (unit = "quarter"))]
+    pub fn py_beats(&self, unit: &str) -> PyResult<Vec<f32>> {
+        Ok(self.beats(parse_unit(unit)?))
+    }
+
+    #[pyo3(name="end_time", signature = (unit = "quarter"))]
+    pub fn py_end_time(&self, unit: &str) -> PyResult<f32> {
+        Ok(self.end_time(parse_unit(unit)?))
+    }
+
+    #[pyo3(name="is_empty")]
+    pub fn py_is_empty(&self) -> bool {self.is_empty()}
+
+    #[pyo3(name="clicks", signature = (unit = "quarter"))]
+    pub fn py_clicks(&self, unit: &str) -> PyResult<Vec<f32>> {
+        Ok(self.clicks(parse_unit(unit)?))
+    }
+
+    /// ABC tune with one voice per pitched track.
+    #[pyo3(name="to_abc")]
+    pub fn py_to_abc(&self) -> String {self.to_abc()}
+
+    /// Drum track of metronome hits, wood blocks by default.
+    #[pyo3(name="make_click_track", signature = (strong_pitch = 76, weak_pitch = 77))]
+    pub fn py_make_click_track(&self, strong_pitch: u8, weak_pitch: u8) -> Track {
+        self.make_click_track(strong_pitch, weak_pitch)
+    }
+
+    #[pyo3(name="downbeats", signature = (unit = "quarter"))]
+    pub fn py_downbeats(&self, unit: &str) -> PyResult<Vec<f32>> {
+        Ok(self.downbeats(parse_unit(unit)?))
+    }
+
+    #[pyo3(name="split_notes_at_barlines")]
+    pub fn py_split_notes_at_barlines(&mut self) {self.split_notes_at_barlines()}
+
+    #[pyo3(name="note_positions")]
+    pub fn py_note_positions(&self) -> Vec<Vec<(u32, u32, u32)>> {self.note_positions()}
+
+    #[pyo3(name="check_gm_compliance")]
+    pub fn py_check_gm_compliance(&self) -> Vec<GmViolation> {self.check_gm_compliance()}
+
+    #[pyo3(name="pitch_range")]
+    pub fn py_pitch_range(&self) -> Option<(u8, u8)> {self.pitch_range()}
+
+    #[pyo3(name="fit_to_range")]
+    pub fn py_fit_to_range(&mut self, low: u8, high: u8) -> Vec<i32> {self.fit_to_range(low, high)}
+
+    #[pyo3(name="remove_doublings", signature = (threshold = 0.9))]
+    pub fn py_remove_doublings(&mut self, threshold: f32) -> Vec<usize> {self.remove_doublings(threshold)}
+
+    #[pyo3(name="velocity_histogram")]
+    pub fn py_velocity_histogram(&self) -> Vec<usize> {self.velocity_histogram()}
+
+    #[pyo3(name="merge_tracks", signature = (by = "program"))]
+    pub fn py_merge_tracks(&mut self, by: &str) -> PyResult<()> {
+        self.merge_tracks(by.parse().map_err(PyValueError::new_err)?);
+        Ok(())
+    }
+
+    #[pyo3(name="remove_empty_tracks")]
+    pub fn py_remove_empty_tracks(&mut self) -> usize {self.remove_empty_tracks()}
+
+    /// Keep melodic tracks with one of `programs`, only drum tracks with `drums_only`,
+    /// and tracks for which `predicate(track)` is true. Returns how many were removed.
+    #[pyo3(name="filter_tracks", signature = (predicate = None, programs = None, drums_only = false))]
+    pub fn py_filter_tracks(
+        &mut self, py: Python<'_>,
+        predicate: Option<PyObject>, programs: Option<Vec<u8>>, drums_only: bool,
+    ) -> PyResult<usize> {
+        let mut keep = Vec::with_capacity(self.tracks.len());
+        for track in &self.tracks {
+            let mut kept = (!drums_only || track.is_drum)
+                && programs.as_ref().is_none_or(|p| !track.is_drum && p.contains(&track.program));
+            if kept {
+                if let Some(predicate) = &predicate {
+                    kept = predicate.call1(py, (track.clone(),))?.is_true(py)?;
+                }
+            }
+            keep.push(kept);
+        }
+        let mut keep = keep.into_iter();
+        Ok(self.filter_tracks(|_| keep.next().unwrap()))
+    }
+
+    #[pyo3(name="split_at")]
+    pub fn py_split_at(&self, times: Vec<f32>) -> Vec<Sequence> {self.split_at(&times)}
+
+    #[pyo3(name="preview")]
+    pub fn py_preview(&self, max_notes: usize) -> Sequence {self.preview(max_notes)}
+
+    #[pyo3(name="diff", signature = (other, tolerance = DEFAULT_TOLERANCE))]
+    pub fn py_diff(&self, other: &Sequence, tolerance: f32) -> SequenceDiff {
+        self.diff(other, tolerance)
+    }
+
+    #[pyo3(name="align_to")]
+    pub fn py_align_to(&self, other: &Sequence, py: Python<'_>) -> Vec<(f32, f32)> {
+        py.allow_threads(|| self.align_to(other))
+    }
+
+    #[pyo3(name="warp_time")]
+    pub fn py_warp_time(&mut self, mapping: Vec<(f32, f32)>) -> PyResult<()> {
+        self.warp_time(&mapping).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name="fingerprint", signature = (steps_per_quarter = 4, transpose_invariant = false))]
+    pub fn py_fingerprint(&self, steps_per_quarter: u32, transpose_invariant: bool) -> u64 {
+        self.fingerprint(steps_per_quarter, transpose_invariant)
+    }
+
+    #[pyo3(name="extract_melody")]
+    pub fn py_extract_melody(&self) -> Track {self.extract_melody()}
+
+    /// Without a `seed` the jitter differs on every call.
+    #[pyo3(name="humanize", signature = (timing_std = 0.01, velocity_std = 5.0, seed = None))]
+    pub fn py_humanize(&mut self, timing_std: f32, velocity_std: f32, seed: Option<u64>) {
+        let seed = seed.unwrap_or_else(|| {
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+        });
+        self.humanize(timing_std, velocity_std, seed)
+    }
+
+    /// `melody`, `bass`, `harmony` or `drums` per track, `None` for tracks without notes.
+    #[pyo3(name="classify_tracks")]
+    pub fn py_classify_tracks(&self) -> Vec<Option<&'static str>> {
+        self.classify_tracks().iter().map(|role| role.map(|r| r.name())).collect()
+    }
+
+    /// Note roles (`chord`, `passing`, `neighbor`, `other` or `none`) per track.
+    #[pyo3(name="tone_roles")]
+    pub fn py_tone_roles(&self, window: f32) -> PyResult<Vec<Vec<&'static str>>> {
+        let roles = self.tone_roles(positive("window", window)?);
+        Ok(roles.iter().map(|roles| roles.iter().map(|r| r.name()).collect()).collect())
+    }
+
+    #[pyo3(name="note_features")]
+    pub fn py_note_features(&self, window: f32) -> PyResult<Vec<Vec<[f32; 5]>>> {
+        Ok(self.note_features(positive("window", window)?))
+    }
+
+    #[pyo3(name="self_similarity")]
+    pub fn py_self_similarity(&self) -> Vec<Vec<f32>> {self.self_similarity()}
+
+    #[pyo3(name="label_sections", signature = (section_bars = None, threshold = 0.9))]
+    pub fn py_label_sections(&self, section_bars: Option<usize>, threshold: f32) -> Vec<(f32, f32, String)> {
+        self.label_sections(section_bars, threshold)
+    }
+
+    #[pyo3(name="mark_sections", signature = (section_bars = None, threshold = 0.9))]
+    pub fn py_mark_sections(&mut self, section_bars: Option<usize>, threshold: f32) {
+        self.mark_sections(section_bars, threshold)
+    }
+
+    #[pyo3(name="detect_cadences", signature = (window = 1.0))]
+    pub fn py_detect_cadences(&self, window: f32) -> PyResult<Vec<(f32, f32)>> {
+        Ok(self.detect_cadences(positive("window", window)?))
+    }
+
+    #[pyo3(name="detect_chords")]
+    pub fn py_detect_chords(&self, window: f32) -> PyResult<Vec<(f32, String)>> {
+        Ok(self.detect_chords(positive("window", window)?))
+    }
+
+    #[pyo3(name="estimate_tempo")]
+    pub fn py_estimate_tempo(&self) -> Option<f32> {self.estimate_tempo()}
+
+    #[pyo3(name="estimate_key")]
+    pub fn py_estimate_key(&self) -> Option<(bool, i8)> {self.estimate_key()}
+
+    #[pyo3(name="detect_quantization")]
+    pub fn py_detect_quantization(&self) -> Vec<Option<(&'static str, f32)>> {
+        self.detect_quantization().into_iter().map(|q| q.map(|(grid, strength)| (grid.name(), strength))).collect()
+    }
+
+    #[pyo3(name="event_times_us")]
+    pub fn py_event_times_us(&self) -> PyResult<EventTimes> {
+        self.event_times_us().map_err(PyValueError::new_err)
+    }
+}}
+
 impl Track {
     pub fn transpose(&self) -> TrackTrans {
         let mut pitch = Vec::with_capacity(self.notes.len());
@@ -307,96 +1130,456 @@ impl Track {
             program: self.program,
             is_drum: self.is_drum,
             name: self.name.clone(),
-            controls: self.controls.clone()
+            controls: self.controls.clone(),
+            sysex: self.sysex.clone(),
+            #[cfg(feature = "python")]
+            exports: BufferExports::default(),
         }
     }
 
+    /// One track of notes from note columns, as the inverse of `transpose`.
+    pub fn from_arrays(pitch: &[u8], start: &[f32], duration: &[f32], velocity: &[u8]) -> Result<Track, &'static str> {
+        let len = pitch.len();
+        if start.len() != len || duration.len() != len || velocity.len() != len {
+            return Err("Note columns differ in length");
+        }
+        let notes = (0..len)
+            .map(|i| Note { pitch: pitch[i], start: start[i], duration: duration[i], velocity: velocity[i], tie: false })
+            .collect();
+        Ok(Track { notes, ..Track::default() })
+    }
+
+    /// Polyphonic aftertouch applying to the `idx`-th note: its key's pressure from the
+    /// note on up to the note off.
+    pub fn note_pressure(&self, idx: usize) -> Vec<ControlChange> {
+        let note = &self.notes[idx];
+        self.poly_pressure.get(&note.pitch).map_or_else(Vec::new, |pressure| {
+            pressure.iter()
+                .filter(|p| p.time >= note.start && p.time <= note.start + note.duration)
+                .copied()
+                .collect()
+        })
+    }
+
+    pub fn instrument(&self) -> Instrument {
+        Instrument { bank_msb: self.bank_msb, bank_lsb: self.bank_lsb, program: self.program }
+    }
+
+    pub fn set_instrument(&mut self, instrument: Instrument) {
+        self.bank_msb = instrument.bank_msb;
+        self.bank_lsb = instrument.bank_lsb;
+        self.program = instrument.program;
+    }
+
     pub fn sort(&mut self) {
-        self.notes.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+        self.notes.sort_by(|a, b| a.start.total_cmp(&b.start));
 
-        for (control_number, control_change) in self.controls.iter_mut() {
-            control_change.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        for control_change in self.controls.values_mut() {
+            control_change.sort_by(|a, b| a.time.total_cmp(&b.time));
+        }
+        for control_change in self.fine_controls.values_mut() {
+            control_change.sort_by(|a, b| a.time.total_cmp(&b.time));
+        }
+        self.channel_pressure.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for pressure in self.poly_pressure.values_mut() {
+            pressure.sort_by(|a, b| a.time.total_cmp(&b.time));
         }
     }
 }
 
-#[pymethods]
-impl Track {
+pymethods_with! { [pickle, approx_eq("Equal up to `tolerance` in the times of its notes and events.")] #[pymethods] impl Track {
     pub fn __repr__(&self) -> String {
         serde_yaml::to_string(&self).unwrap()
     }
 
+    fn __len__(&self) -> usize { self.notes.len() }
+
+    /// Iterate over copies of the notes.
+    fn __iter__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.notes.clone().into_py(py).call_method0(py, "__iter__")
+    }
+
+    #[getter(instrument)]
+    fn py_instrument(&self) -> Instrument {self.instrument()}
+
+    #[setter(instrument)]
+    fn py_set_instrument(&mut self, instrument: Instrument) {self.set_instrument(instrument)}
+
+    #[pyo3(name="program_name")]
+    fn py_program_name(&self) -> &'static str {self.program_name()}
+
+    #[pyo3(name="instrument_family")]
+    fn py_instrument_family(&self) -> &'static str {self.instrument_family()}
+
     #[pyo3(name="transpose")]
     pub fn py_transpose(&self) -> TrackTrans {self.transpose()}
 
+    #[staticmethod]
+    #[pyo3(name="from_step_pattern")]
+    pub fn py_from_step_pattern(pattern: &str, pitch: u8, steps_per_beat: u32) -> PyResult<Self> {
+        Self::from_step_pattern(pattern, pitch, steps_per_beat).map_err(PyValueError::new_err)
+    }
+
+    #[staticmethod]
+    #[pyo3(name="from_kit_pattern")]
+    pub fn py_from_kit_pattern(pattern: &str, steps_per_beat: u32) -> PyResult<Self> {
+        Self::from_kit_pattern(pattern, steps_per_beat).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name="merge_14bit_controls")]
+    pub fn py_merge_14bit_controls(&mut self) {self.merge_14bit_controls()}
+
+    #[pyo3(name="note_pressure")]
+    pub fn py_note_pressure(&self, idx: usize) -> PyResult<Vec<ControlChange>> {
+        if idx >= self.notes.len() {
+            return Err(pyo3::exceptions::PyIndexError::new_err("Note index out of range"));
+        }
+        Ok(self.note_pressure(idx))
+    }
+
+    #[pyo3(name="add_control_lane")]
+    pub fn py_add_control_lane(&mut self, lane: ControlLane) {self.add_control_lane(&lane)}
+
+    #[pyo3(name="filter_notes", signature = (min_pitch = 0, max_pitch = 127, min_velocity = 1, min_duration = 0.0))]
+    pub fn py_filter_notes(&mut self, min_pitch: u8, max_pitch: u8, min_velocity: u8, min_duration: f32) -> usize {
+        self.filter_notes(min_pitch, max_pitch, min_velocity, min_duration)
+    }
+
+    #[pyo3(name="remove_duplicate_notes")]
+    pub fn py_remove_duplicate_notes(&mut self) -> usize {self.remove_duplicate_notes()}
+
+    #[pyo3(name="apply_sustain")]
+    pub fn py_apply_sustain(&mut self) {self.apply_sustain()}
+
+    #[pyo3(name="adjust_durations", signature = (gate = 0.9))]
+    pub fn py_adjust_durations(&mut self, gate: f32) {self.adjust_durations(gate)}
+
+    #[pyo3(name="legato")]
+    pub fn py_legato(&mut self) {self.legato()}
+
+    #[pyo3(name="to_monophonic", signature = (policy = "highest"))]
+    pub fn py_to_monophonic(&self, policy: &str) -> PyResult<Track> {
+        Ok(self.to_monophonic(policy.parse().map_err(PyValueError::new_err)?))
+    }
+
+    #[pyo3(name="limit_polyphony", signature = (max_voices, strategy = "oldest"))]
+    pub fn py_limit_polyphony(&mut self, max_voices: usize, strategy: &str) -> PyResult<()> {
+        self.limit_polyphony(max_voices, strategy.parse().map_err(PyValueError::new_err)?);
+        Ok(())
+    }
+
+    #[pyo3(name="split_notes_at_barlines")]
+    pub fn py_split_notes_at_barlines(&mut self, time_signatures: Vec<TimeSignature>) {
+        self.split_notes_at_barlines(&time_signatures)
+    }
+
+    /// `groups` defaults to the nine Groove MIDI drum classes, see `DRUM_GROUPS`, and
+    /// bars are 4/4 without `time_signatures`. `Sequence.drum_matrix` takes its own.
+    #[pyo3(name="drum_matrix", signature = (steps_per_bar = 16, groups = None, time_signatures = Vec::new()))]
+    pub fn py_drum_matrix(&self, steps_per_bar: usize, groups: Option<Vec<Vec<u8>>>, time_signatures: Vec<TimeSignature>) -> PyResult<DrumMatrix> {
+        let groups = drum_groups(&groups);
+        self.drum_matrix(&time_signatures, steps_per_bar, &groups).map_err(PyValueError::new_err)
+    }
+
+    /// ABC tune of the track in `key`, a name that `key_from_name` reads.
+    #[pyo3(name="to_abc", signature = (time_signatures = Vec::new(), key = "C major"))]
+    pub fn py_to_abc(&self, time_signatures: Vec<TimeSignature>, key: &str) -> PyResult<String> {
+        let key = crate::analysis::key_from_name(key).map_err(PyValueError::new_err)?;
+        Ok(self.to_abc(&time_signatures, key))
+    }
+
+    /// Microtiming against a grid of `grid` quarters, a sixteenth by default.
+    #[pyo3(name="groove", signature = (grid = 0.25))]
+    pub fn py_groove(&self, grid: f32) -> PyResult<Vec<GrooveHit>> {
+        self.groove(grid).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name="pitch_class_histogram")]
+    pub fn py_pitch_class_histogram(&self) -> Vec<f32> {self.pitch_class_histogram()}
+
+    /// Onset grid, "eighth", "swing_eighth", "sixteenth" or "triplet", and its strength.
+    #[pyo3(name="detect_quantization")]
+    pub fn py_detect_quantization(&self) -> Option<(&'static str, f32)> {
+        self.detect_quantization().map(|(grid, strength)| (grid.name(), strength))
+    }
+
+    #[pyo3(name="end_time")]
+    pub fn py_end_time(&self) -> f32 {self.end_time()}
+
+    #[pyo3(name="scale_velocity")]
+    pub fn py_scale_velocity(&mut self, factor: f32) {self.scale_velocity(factor)}
+
+    #[pyo3(name="normalize_velocity")]
+    pub fn py_normalize_velocity(&mut self, target_mean: f32) {self.normalize_velocity(target_mean)}
+
     #[new]
+    #[pyo3(signature = (name, program, is_drum, notes, controls, sysex = Vec::new()))]
     pub fn py_new(
         name: String, program: u8,
         is_drum: bool, notes: Vec<Note>,
-        controls: HashMap<u8, Vec<ControlChange>>
+        controls: HashMap<u8, Vec<ControlChange>>,
+        sysex: Vec<SysEx>,
     ) -> Self {
-        Self{name, program, is_drum, notes, controls}
+        Self{name, program, is_drum, notes, controls, sysex, ..Self::default()}
+    }
+}}
+
+impl Sequence {
+    /// All tracks in note columns at once, in one set of columns with `concat`.
+    pub fn transpose(&self, concat: bool) -> SequenceTrans {
+        let mut trans = SequenceTrans { tracks: self.tracks.iter().map(Track::transpose).collect(), ..SequenceTrans::default() };
+        if concat {
+            let total = self.tracks.iter().map(|t| t.notes.len()).sum();
+            trans.track.reserve(total);
+            trans.pitch.reserve(total);
+            trans.start.reserve(total);
+            trans.duration.reserve(total);
+            trans.velocity.reserve(total);
+            for (idx, track) in trans.tracks.iter_mut().enumerate() {
+                trans.track.extend(std::iter::repeat_n(idx as u32, track.pitch.len()));
+                trans.pitch.append(&mut track.pitch);
+                trans.start.append(&mut track.start);
+                trans.duration.append(&mut track.duration);
+                trans.velocity.append(&mut track.velocity);
+            }
+        }
+        trans
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
+impl SequenceTrans {
+    fn __repr__(&self) -> String {
+        format!("SequenceTrans(tracks={}, notes={})", self.tracks.len(), self.pitch.len())
+    }
+
+    fn __len__(&self) -> usize { self.tracks.len() }
+}
+
+impl TrackTrans {
+    /// The `Track` the columns describe, the inverse of `Track::transpose`.
+    pub fn to_track(&self) -> Result<Track, &'static str> {
+        Ok(Track {
+            name: self.name.clone(),
+            program: self.program,
+            is_drum: self.is_drum,
+            controls: self.controls.clone(),
+            sysex: self.sysex.clone(),
+            ..Track::from_arrays(&self.pitch, &self.start, &self.duration, &self.velocity)?
+        })
+    }
+}
+
+#[cfg(feature = "python")]
 impl TrackTrans {
-    fn __repr__(&self) -> String { return format!("{:?}", self) }
+    fn check_not_exported(&self) -> PyResult<()> {
+        if self.exports.is_exported() {
+            return Err(PyBufferError::new_err("Can't resize note columns while buffers are exported"));
+        }
+        Ok(())
+    }
 }
-#[pymethods]
-impl Note {
+
+pymethods_with! { [pickle] #[pymethods] impl TrackTrans {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+
+    #[pyo3(name = "to_track")]
+    fn py_to_track(&self) -> PyResult<Track> {
+        self.to_track().map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn get_name(&self) -> String { self.name.clone() }
+
+    #[setter]
+    fn set_name(&mut self, name: String) { self.name = name; }
+
+    #[getter]
+    fn get_program(&self) -> u8 { self.program }
+
+    #[setter]
+    fn set_program(&mut self, program: u8) { self.program = program; }
+
+    #[getter]
+    fn get_is_drum(&self) -> bool { self.is_drum }
+
+    #[setter]
+    fn set_is_drum(&mut self, is_drum: bool) { self.is_drum = is_drum; }
+
+    #[getter]
+    fn get_controls(&self) -> HashMap<u8, Vec<ControlChange>> { self.controls.clone() }
+
+    #[setter]
+    fn set_controls(&mut self, controls: HashMap<u8, Vec<ControlChange>>) { self.controls = controls; }
+
+    #[getter]
+    fn get_sysex(&self) -> Vec<SysEx> { self.sysex.clone() }
+
+    #[setter]
+    fn set_sysex(&mut self, sysex: Vec<SysEx>) { self.sysex = sysex; }
+
+    #[getter]
+    fn get_pitch(&self) -> Vec<u8> { self.pitch.clone() }
+
+    #[getter]
+    fn get_start(&self) -> Vec<f32> { self.start.clone() }
+
+    #[getter]
+    fn get_duration(&self) -> Vec<f32> { self.duration.clone() }
+
+    #[getter]
+    fn get_velocity(&self) -> Vec<u8> { self.velocity.clone() }
+
+    /// Zero-copy buffer view of a note column: "pitch", "start", "duration" or "velocity".
+    fn column(slf: &PyCell<Self>, name: &str) -> PyResult<NoteArray> {
+        NoteArray::new(slf.into(), name)
+    }
+
+    #[setter]
+    fn set_pitch(&mut self, pitch: Vec<u8>) -> PyResult<()> {
+        self.check_not_exported()?;
+        self.pitch = pitch;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_start(&mut self, start: Vec<f32>) -> PyResult<()> {
+        self.check_not_exported()?;
+        self.start = start;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_duration(&mut self, duration: Vec<f32>) -> PyResult<()> {
+        self.check_not_exported()?;
+        self.duration = duration;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_velocity(&mut self, velocity: Vec<u8>) -> PyResult<()> {
+        self.check_not_exported()?;
+        self.velocity = velocity;
+        Ok(())
+    }
+}}
+pymethods_with! { [pickle, approx_eq("Equal up to `tolerance` in start and duration.")] #[pymethods] impl Note {
     #[new]
-    fn py_new(pitch: u8, start: f32, duration: f32, velocity: u8) -> Self{
-        Self{pitch, start, duration, velocity}
+    #[pyo3(signature = (pitch, start, duration, velocity, tie = false))]
+    fn py_new(pitch: u8, start: f32, duration: f32, velocity: u8, tie: bool) -> Self{
+        Self{pitch, start, duration, velocity, tie}
     }
-    fn __repr__(&self) -> String { return format!("{:?}", self) }
+    fn __repr__(&self) -> String { format!("{:?}", self) }
 
     fn end(&self) -> f32 { self.start + self.duration }
-}
 
-#[pymethods]
-impl TimeSignature {
+    /// Pitch name such as "C#4"; pass `middle_c_octave=3` for the Yamaha convention.
+    #[pyo3(name="name", signature = (middle_c_octave = MIDDLE_C_OCTAVE))]
+    fn py_name(&self, middle_c_octave: i8) -> String { self.name(middle_c_octave) }
+
+    #[pyo3(name="octave", signature = (middle_c_octave = MIDDLE_C_OCTAVE))]
+    fn py_octave(&self, middle_c_octave: i8) -> i8 { self.octave(middle_c_octave) }
+
+    #[getter]
+    fn get_pitch_class(&self) -> u8 { self.pitch_class() }
+}}
+
+pymethods_with! { [pickle, approx_eq("Equal up to `tolerance` in time.")] #[pymethods] impl TimeSignature {
     #[new]
-    fn py_new(time: f32, numerator: u8, denominator: u8) -> Self {
-        Self{time, numerator, denominator}
+    #[pyo3(signature = (time, numerator, denominator, clocks_per_click = None, thirty_seconds_per_quarter = None))]
+    fn py_new(
+        time: f32,
+        numerator: u8,
+        denominator: u8,
+        clocks_per_click: Option<u8>,
+        thirty_seconds_per_quarter: Option<u8>,
+    ) -> Self {
+        Self{time, numerator, denominator, clocks_per_click, thirty_seconds_per_quarter}
     }
-    fn __repr__(&self) -> String { return format!("{:?}", self) }
-}
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
 
-#[pymethods]
-impl KeySignature {
+pymethods_with! { [pickle, approx_eq("Equal up to `tolerance` in time.")] #[pymethods] impl KeySignature {
     #[new]
     fn py_new(time: f32, key: (bool, i8)) -> Self {
         // bool true代表大调，false 小调
         assert!(key.1 >= -7 && key.1 <= 7, "Key: {:?} is invalid", key);
         Self{time, key}
     }
-    fn __repr__(&self) -> String { return format!("{:?}", self) }
-}
+    fn __repr__(&self) -> String { format!("{:?}", self) }
 
-#[pymethods]
-impl ControlChange {
+    /// Read "F# minor", "Bb", "Ebm" and the like.
+    #[staticmethod]
+    #[pyo3(name = "from_name", signature = (name, time = 0.0))]
+    fn py_from_name(name: &str, time: f32) -> PyResult<Self> {
+        Self::from_name(name, time).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "name")]
+    fn py_name(&self) -> String { self.name() }
+
+    #[pyo3(name = "tonic_pitch_class")]
+    fn py_tonic_pitch_class(&self) -> u8 { self.tonic_pitch_class() }
+}}
+
+pymethods_with! { [pickle, approx_eq("Equal up to `tolerance` in time.")] #[pymethods] impl ControlChange {
     #[new]
     fn py_new(time: f32, value: u8) -> Self{
        Self{time, value}
     }
-    fn __repr__(&self) -> String { return format!("{:?}", self) }
-}
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
 
-#[pymethods]
-impl Tempo {
+pymethods_with! { [pickle] #[pymethods] impl Instrument {
+    #[new]
+    #[pyo3(signature = (program, bank_msb = 0, bank_lsb = 0))]
+    fn py_new(program: u8, bank_msb: u8, bank_lsb: u8) -> Self {
+        Self{bank_msb, bank_lsb, program}
+    }
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
+
+pymethods_with! { [pickle] #[pymethods] impl SysEx {
+    #[new]
+    #[pyo3(signature = (time, data, escape = false))]
+    fn py_new(time: f32, data: Vec<u8>, escape: bool) -> Self {
+        Self{time, data, escape}
+    }
+
+    #[getter]
+    fn get_time(&self) -> f32 { self.time }
+
+    #[setter]
+    fn set_time(&mut self, time: f32) { self.time = time; }
+
+    #[getter]
+    fn get_data<'py>(&self, py: Python<'py>) -> &'py PyBytes { PyBytes::new(py, &self.data) }
+
+    #[setter]
+    fn set_data(&mut self, data: Vec<u8>) { self.data = data; }
+
+    #[getter]
+    fn get_escape(&self) -> bool { self.escape }
+
+    #[setter]
+    fn set_escape(&mut self, escape: bool) { self.escape = escape; }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
+
+pymethods_with! { [pickle, approx_eq("Equal up to `tolerance` in time and tempo.")] #[pymethods] impl Tempo {
     #[new]
     fn py_new(time: f32, qpm: f32) -> Self{
         Self{time, qpm}
     }
-    fn __repr__(&self) -> String { return format!("{:?}", self) }
-}
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde_json;
-    use serde_yaml;
+    use crate::writer::WriteOptions;
     #[test]
     fn test_midi2seq() {
         let seq = Sequence::from_file("tests/tiny.mid").unwrap();
@@ -404,4 +1587,120 @@ mod tests {
         println!("{t}");
         println!("{:?}", seq.start_in_measure());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_track_trans_round_trip() {
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        for track in &seq.tracks {
+            let back = track.transpose().to_track().unwrap();
+            assert_eq!((&back.name, back.program, back.is_drum), (&track.name, track.program, track.is_drum));
+            assert_eq!(back.notes, track.notes);
+        }
+        let mut trans = seq.tracks[0].transpose();
+        trans.velocity.pop();
+        assert!(trans.to_track().is_err());
+
+        let track = Track::from_arrays(&[60, 64], &[0.0, 1.0], &[1.0, 0.5], &[90, 80]).unwrap();
+        let back = Sequence::from_bytes(&Sequence::from_tracks(vec![track.clone()], 90.0).to_bytes(&WriteOptions::default()).unwrap()).unwrap();
+        assert_eq!(back.tracks[0].notes, track.notes);
+        assert_eq!(back.qpm[0].qpm.round(), 90.0);
+    }
+
+    #[test]
+    fn test_sequence_trans() {
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        let trans = seq.transpose(false);
+        assert_eq!(trans.tracks.len(), seq.tracks.len());
+        assert!(trans.pitch.is_empty());
+        let concat = seq.transpose(true);
+        assert_eq!(concat.pitch.len(), 28);
+        assert!(concat.tracks.iter().all(|t| t.pitch.is_empty()));
+        for (idx, track) in seq.tracks.iter().enumerate() {
+            let rows: Vec<usize> = (0..concat.track.len()).filter(|&i| concat.track[i] == idx as u32).collect();
+            assert_eq!(rows.iter().map(|&i| concat.start[i]).collect::<Vec<_>>(), trans.tracks[idx].start);
+            assert_eq!(rows.len(), track.notes.len());
+        }
+    }
+
+    #[test]
+    fn test_lenient_parse() {
+        let mut data = std::fs::read("tests/tiny.mid").unwrap();
+        data.truncate(data.len() - 7);
+        let midi = MIDIFile::from_bytes(&data).unwrap();
+        assert!(Sequence::from_midi(&midi).is_err());
+        let seq = Sequence::from_midi_with(&midi, false).unwrap();
+        assert!(seq.tracks.iter().any(|t| !t.notes.is_empty()));
+        assert_eq!(seq.warnings.chunk_length_mismatches, 1);
+        assert!(seq.warnings.messages.iter().any(|w| w.contains("truncated")));
+    }
+
+    #[test]
+    fn test_bank_select() {
+        let data = vec![
+            0x00, 0xB1, 0x00, 0x01,
+            0x00, 0x20, 0x02,
+            0x00, 0xC1, 0x05,
+            0x00, 0x91, 0x3C, 0x40,
+            0x10, 0x81, 0x3C, 0x00,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let midi = MIDIFile {
+            format: MIDIFormat::SingleTrack,
+            division: 96,
+            tracks: vec![crate::io::MidiTrack::new(0, data)],
+            declared_tracks: 1,
+            warnings: Vec::new(),
+        };
+        let seq = Sequence::from_midi(&midi).unwrap();
+        let instrument = Instrument { bank_msb: 1, bank_lsb: 2, program: 5 };
+        assert_eq!(seq.tracks[0].instrument(), instrument);
+        let back = Sequence::from_bytes(&seq.to_bytes(&WriteOptions::default()).unwrap()).unwrap();
+        assert_eq!(back.tracks[0].instrument(), instrument);
+        // The source channel and chunk are kept, and the channel survives writing
+        assert_eq!((seq.tracks[0].channel, seq.tracks[0].midi_track_index), (Some(1), Some(0)));
+        assert_eq!(back.tracks[0].channel, Some(1));
+    }
+
+    #[test]
+    fn test_sysex_continuation() {
+        let data = vec![
+            // GS reset in one packet
+            0x00, 0xF0, 0x0A, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+            // XG system on split into an F0 packet and an F7 continuation
+            0x00, 0xF0, 0x04, 0x43, 0x10, 0x4C, 0x00,
+            0x10, 0xF7, 0x04, 0x00, 0x7E, 0x00, 0xF7,
+            // Escape packet carrying a timing clock
+            0x00, 0xF7, 0x01, 0xF8,
+            0x00, 0x90, 0x3C, 0x40,
+            0x10, 0x80, 0x3C, 0x00,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let midi = MIDIFile {
+            format: MIDIFormat::SingleTrack,
+            division: 96,
+            tracks: vec![crate::io::MidiTrack::new(0, data)],
+            declared_tracks: 1,
+            warnings: Vec::new(),
+        };
+        let seq = Sequence::from_midi(&midi).unwrap();
+        let sysex: Vec<(&[u8], bool)> = seq.tracks[0].sysex.iter().map(|s| (s.data.as_slice(), s.escape)).collect();
+        assert_eq!(sysex, vec![
+            (&[0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41][..], false),
+            (&[0x43, 0x10, 0x4C, 0x00, 0x00, 0x7E, 0x00][..], false),
+            (&[0xF8][..], true),
+        ]);
+        assert_eq!(seq.tracks[0].notes.len(), 1);
+        assert!(!seq.warnings.messages.iter().any(|w| w.contains("SysEx")));
+    }
+
+    #[test]
+    fn test_sort_nan() {
+        let mut seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        seq.qpm.insert(0, Tempo { time: f32::NAN, qpm: 90.0 });
+        seq.time_signatures[0].time = f32::NAN;
+        // A NaN time sorts last instead of panicking
+        seq.sort();
+        assert!(seq.qpm.last().unwrap().time.is_nan());
+        assert!(seq.time_signatures.last().unwrap().time.is_nan());
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "python")]
+use pyo3::exceptions::PyValueError;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::{PyClass, PyTypeInfo};
+#[cfg(feature = "python")]
+use serde::{de::DeserializeOwned, Serialize};
+use crate::sequence::Sequence;
+#[cfg(feature = "python")]
+use crate::{
+    automation::{ControlLane, FineControlChange},
+    sequence::{ControlChange, Instrument, KeySignature, Note, SysEx, Tempo, TimeSignature, Track, TrackTrans},
+    stats::CorpusStatistics,
+    text::{Metadata, TextEvent},
+};
+
+/// Leads every `to_msgpack` cache so `load` can tell it from other binary input.
+pub const MSGPACK_MAGIC: &[u8; 4] = b"MPSQ";
+
+/// Formats a `Sequence` can be saved in without going through MIDI. MessagePack is the
+/// compact one, for caching parsed corpora.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+    MessagePack,
+}
+
+impl Format {
+    /// Format from a `.json`, `.yaml`, `.yml`, `.msgpack` or `.mpk` extension.
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        match Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("json") => Ok(Format::Json),
+            Some("yaml") | Some("yml") => Ok(Format::Yaml),
+            Some("msgpack") | Some("mpk") => Ok(Format::MessagePack),
+            _ => Err(format!(
+                "Can not tell the format of {:?}, expected a .json, .yaml, .yml, .msgpack or .mpk file", path)),
+        }
+    }
+}
+
+impl Sequence {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(json: &str) -> Result<Sequence, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    pub fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Sequence, String> {
+        serde_yaml::from_str(yaml).map_err(|e| e.to_string())
+    }
+
+    /// `MSGPACK_MAGIC` and MessagePack with structs as arrays of their fields, so fields
+    /// added later must go last and carry `#[serde(default)]`.
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, String> {
+        let mut data = MSGPACK_MAGIC.to_vec();
+        rmp_serde::encode::write(&mut data, self).map_err(|e| e.to_string())?;
+        Ok(data)
+    }
+
+    /// Nesting is bounded by rmp-serde's depth limit, so hostile input fails instead of
+    /// overflowing the stack.
+    pub fn from_msgpack(data: &[u8]) -> Result<Sequence, String> {
+        let data = data.strip_prefix(MSGPACK_MAGIC).ok_or("Not a MessagePack sequence cache")?;
+        rmp_serde::from_slice(data).map_err(|e| e.to_string())
+    }
+
+    /// Store as JSON, YAML or MessagePack, chosen by the file extension.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let data = match Format::from_path(path)? {
+            Format::Json => self.to_json()?.into_bytes(),
+            Format::Yaml => self.to_yaml()?.into_bytes(),
+            Format::MessagePack => self.to_msgpack()?,
+        };
+        fs::write(path, data).map_err(|e| e.to_string())
+    }
+
+    /// Read back a sequence stored by `save`.
+    pub fn load(path: &str) -> Result<Sequence, String> {
+        let format = Format::from_path(path)?;
+        let data = fs::read(path).map_err(|e| e.to_string())?;
+        let text = || std::str::from_utf8(&data).map_err(|e| e.to_string());
+        match format {
+            Format::Json => Self::from_json(text()?),
+            Format::Yaml => Self::from_yaml(text()?),
+            Format::MessagePack => Self::from_msgpack(&data),
+        }
+    }
+}
+
+/// What `__reduce__` of the pyclasses returns: `_unpickle` and its arguments, the class
+/// name and the JSON state. Unlike the constructors this rebuilds any class the same way.
+#[cfg(feature = "python")]
+pub(crate) type Reduced = (PyObject, (&'static str, String));
+
+#[cfg(feature = "python")]
+pub(crate) fn reduce<T: Serialize + PyTypeInfo>(py: Python<'_>, value: &T) -> PyResult<Reduced> {
+    let state = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let module: &str = T::type_object(py).getattr("__module__")?.extract()?;
+    let unpickle = py.import(module)?.getattr("_unpickle")?;
+    Ok((unpickle.into_py(py), (T::NAME, state)))
+}
+
+#[cfg(feature = "python")]
+fn restore<T: DeserializeOwned + PyClass + IntoPy<PyObject>>(py: Python<'_>, state: &str) -> PyResult<PyObject> {
+    let value: T = serde_json::from_str(state).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(value.into_py(py))
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "_unpickle")]
+pub fn py_unpickle(py: Python<'_>, name: &str, state: &str) -> PyResult<PyObject> {
+    match name {
+        "Sequence" => restore::<Sequence>(py, state),
+        "Track" => restore::<Track>(py, state),
+        "TrackTrans" => restore::<TrackTrans>(py, state),
+        "Note" => restore::<Note>(py, state),
+        "ControlChange" => restore::<ControlChange>(py, state),
+        "FineControlChange" => restore::<FineControlChange>(py, state),
+        "ControlLane" => restore::<ControlLane>(py, state),
+        "SysEx" => restore::<SysEx>(py, state),
+        "Instrument" => restore::<Instrument>(py, state),
+        "Tempo" => restore::<Tempo>(py, state),
+        "TimeSignature" => restore::<TimeSignature>(py, state),
+        "KeySignature" => restore::<KeySignature>(py, state),
+        "TextEvent" => restore::<TextEvent>(py, state),
+        "Metadata" => restore::<Metadata>(py, state),
+        "CorpusStatistics" => restore::<CorpusStatistics>(py, state),
+        _ => Err(PyValueError::new_err(format!("Can not unpickle a {}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_yaml_round_trip() {
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        let json = seq.to_json().unwrap();
+        assert_eq!(Sequence::from_json(&json).unwrap().to_json().unwrap(), json);
+        let yaml = seq.to_yaml().unwrap();
+        assert_eq!(Sequence::from_yaml(&yaml).unwrap().to_json().unwrap(), json);
+        let binary = seq.to_msgpack().unwrap();
+        assert!(binary.len() < json.len() / 3, "{} vs {}", binary.len(), json.len());
+        assert_eq!(Sequence::from_msgpack(&binary).unwrap().to_json().unwrap(), json);
+        // Deeply nested arrays hit the depth limit instead of the end of the stack
+        let mut nested = MSGPACK_MAGIC.to_vec();
+        nested.resize(100_000, 0x91);
+        assert!(Sequence::from_msgpack(&nested).is_err());
+        assert!(Sequence::from_msgpack(&binary[MSGPACK_MAGIC.len()..]).is_err());
+
+        let dir = std::env::temp_dir();
+        for name in ["midiparse_round_trip.json", "midiparse_round_trip.yml", "midiparse_round_trip.msgpack"] {
+            let path = dir.join(name);
+            let path = path.to_str().unwrap();
+            seq.save(path).unwrap();
+            assert_eq!(Sequence::load(path).unwrap().to_json().unwrap(), json);
+            fs::remove_file(path).unwrap();
+        }
+        assert!(seq.save("piece.mid").is_err());
+    }
+}
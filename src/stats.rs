@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::thread;
+#[cfg(feature = "python")]
+use pyo3::exceptions::PyValueError;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::analysis::key_name;
+use crate::sequence::Sequence;
+use crate::timing::{TempoMap, TICKS_PER_QUARTER};
+use crate::pymethods::pymethods_with;
+
+/// Histogram bin widths: QPM, seconds and notes per second.
+const TEMPO_BIN: f32 = 10.0;
+const LENGTH_BIN: f32 = 30.0;
+const DENSITY_BIN: f32 = 1.0;
+
+fn bin(value: f32, width: f32) -> u32 {
+    ((value / width).floor() * width).max(0.0) as u32
+}
+
+/// Corpus level distributions for dataset cards. Histograms are keyed by the lower
+/// edge of their bin.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CorpusStatistics {
+    pub sequences: usize,
+    pub notes: usize,
+    /// Total length in seconds.
+    pub duration: f64,
+    /// First key signature, or the estimated key without one, e.g. "Eb major".
+    pub keys: BTreeMap<String, usize>,
+    /// Initial tempo in 10 QPM bins.
+    pub tempi: BTreeMap<u32, usize>,
+    /// Length in 30 second bins.
+    pub lengths: BTreeMap<u32, usize>,
+    /// Tracks per GM program or drum kit name.
+    pub instruments: BTreeMap<String, usize>,
+    /// Notes per second in bins of one.
+    pub densities: BTreeMap<u32, usize>,
+}
+
+impl CorpusStatistics {
+    pub fn add(&mut self, seq: &Sequence) {
+        let tempo_map = TempoMap::new(&seq.qpm, TICKS_PER_QUARTER);
+        let seconds = tempo_map.quarter_to_second(seq.notes_end() as f64) as f32;
+        let notes: usize = seq.tracks.iter().map(|t| t.notes.len()).sum();
+        self.sequences += 1;
+        self.notes += notes;
+        self.duration += seconds as f64;
+        let key = seq.key_signatures.first().map(|ks| ks.key).or_else(|| seq.estimate_key());
+        if let Some(key) = key {
+            *self.keys.entry(key_name(key)).or_default() += 1;
+        }
+        if let Some(tempo) = seq.qpm.first() {
+            *self.tempi.entry(bin(tempo.qpm, TEMPO_BIN)).or_default() += 1;
+        }
+        *self.lengths.entry(bin(seconds, LENGTH_BIN)).or_default() += 1;
+        for track in &seq.tracks {
+            *self.instruments.entry(track.program_name().to_string()).or_default() += 1;
+        }
+        if seconds > 0.0 {
+            *self.densities.entry(bin(notes as f32 / seconds, DENSITY_BIN)).or_default() += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: CorpusStatistics) {
+        self.sequences += other.sequences;
+        self.notes += other.notes;
+        self.duration += other.duration;
+        for (mine, theirs) in [(&mut self.keys, other.keys), (&mut self.instruments, other.instruments)] {
+            theirs.into_iter().for_each(|(k, n)| *mine.entry(k).or_default() += n);
+        }
+        for (mine, theirs) in [
+            (&mut self.tempi, other.tempi), (&mut self.lengths, other.lengths), (&mut self.densities, other.densities),
+        ] {
+            theirs.into_iter().for_each(|(k, n)| *mine.entry(k).or_default() += n);
+        }
+    }
+}
+
+/// Statistics over `sequences`, computed on all available cores.
+pub fn aggregate_statistics(sequences: &[Sequence]) -> CorpusStatistics {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = sequences.len().div_ceil(workers).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = sequences.chunks(chunk).map(|part| scope.spawn(move || {
+            let mut stats = CorpusStatistics::default();
+            part.iter().for_each(|seq| stats.add(seq));
+            stats
+        })).collect();
+        handles.into_iter().fold(CorpusStatistics::default(), |mut total, handle| {
+            total.merge(handle.join().unwrap());
+            total
+        })
+    })
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name="aggregate_statistics")]
+pub fn py_aggregate_statistics(py: Python<'_>, sequences: Vec<Sequence>) -> CorpusStatistics {
+    py.allow_threads(|| aggregate_statistics(&sequences))
+}
+
+pymethods_with! { [pickle] #[pymethods] impl CorpusStatistics {
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(self).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{KeySignature, Note, Tempo, Track};
+
+    #[test]
+    fn test_aggregate_statistics() {
+        let piece = |qpm: f32, key: (bool, i8), notes: usize| Sequence {
+            tracks: vec![Track {
+                notes: (0..notes).map(|i| Note { pitch: 60, start: i as f32, duration: 1.0, velocity: 80, tie: false }).collect(),
+                ..Track::default()
+            }],
+            qpm: vec![Tempo { time: 0.0, qpm }],
+            key_signatures: vec![KeySignature { time: 0.0, key }],
+            ..Sequence::default()
+        };
+        let corpus: Vec<Sequence> = (0..9).map(|i| piece(60.0 + i as f32 * 5.0, (i % 2 == 0, -3), 40)).collect();
+        let stats = aggregate_statistics(&corpus);
+        assert_eq!((stats.sequences, stats.notes), (9, 360));
+        assert_eq!(stats.keys["Eb major"], 5);
+        assert_eq!(stats.keys["C minor"], 4);
+        assert_eq!(stats.tempi[&60], 2);
+        assert_eq!(stats.tempi[&100], 1);
+        assert_eq!(stats.instruments["Acoustic Grand Piano"], 9);
+        assert_eq!(stats.densities.values().sum::<usize>(), 9);
+        // 40 quarters last 30 seconds or more up to 80 QPM
+        assert_eq!((stats.lengths[&30], stats.lengths[&0]), (5, 4));
+
+        let mut sequential = CorpusStatistics::default();
+        corpus.iter().for_each(|seq| sequential.add(seq));
+        assert_eq!(sequential, stats);
+        assert!(serde_json::to_string(&stats).unwrap().contains("\"Eb major\":5"));
+    }
+}
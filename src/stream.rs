@@ -0,0 +1,86 @@
+use std::vec;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyBytes;
+use crate::error::MidiError;
+use crate::io::{MIDIFile, MidiTrack, MidiTrackIter};
+use crate::message::MIDIMessage;
+
+/// Messages of a file track by track as `(track, message)`, for captures too large to
+/// hold as a `Sequence`. Each track's data is dropped once its messages are read.
+#[cfg_attr(feature = "python", pyclass)]
+pub struct EventStream {
+    tracks: vec::IntoIter<MidiTrack>,
+    current: Option<(usize, usize, MidiTrackIter<'static>)>,
+}
+
+impl EventStream {
+    pub fn new(midi: MIDIFile) -> Self {
+        EventStream { tracks: midi.tracks.into_iter(), current: None }
+    }
+}
+
+impl Iterator for EventStream {
+    type Item = Result<(usize, MIDIMessage), MidiError>;
+
+    /// Ends after the first malformed message, which is returned as an `Event` error, or a
+    /// `Track` error when its delta times overflow.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((track, offset, messages)) = &mut self.current {
+                if let Some(message) = messages.next() {
+                    return Some(Ok((*track, message)));
+                }
+                let error = messages.located_error(*track, *offset);
+                self.current = None;
+                if let Some(error) = error {
+                    self.tracks = Vec::new().into_iter();
+                    return Some(Err(error));
+                }
+            }
+            let track = self.tracks.next()?;
+            self.current = Some((track.track_idx as usize, track.offset(), track.into_iter()));
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl EventStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<(usize, u32, PyObject)>> {
+        match self.next() {
+            Some(Ok((track, message))) => {
+                Ok(Some((track, message.time(), PyBytes::new(py, message.bytes()).into_py(py))))
+            }
+            Some(Err(error)) => Err(error.into()),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_stream() {
+        let midi = MIDIFile::from_file("tests/tiny.mid").unwrap();
+        let expected: usize = midi.tracks.iter().map(|t| t.iter().count()).sum();
+        let events: Vec<_> = EventStream::new(midi.clone()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(events.len(), expected);
+        assert!(events.iter().all(|(_, m)| !m.bytes().is_empty()));
+        assert!(events.windows(2).all(|w| w[0].0 <= w[1].0));
+
+        let mut broken = midi;
+        let track = broken.tracks.last_mut().unwrap();
+        let data = track.data()[..track.data().len() - 2].to_vec();
+        *track = MidiTrack::new(track.track_idx, data);
+        let last = EventStream::new(broken).last().unwrap();
+        assert!(matches!(last, Err(MidiError::Event(..))));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::analysis::key2tonic;
+use crate::sequence::Sequence;
+use crate::text::{TextEvent, TextKind};
+
+/// Candidates closer than this many quarters are merged into one phrase ending.
+const MERGE_DISTANCE: f32 = 1.0;
+/// A melodic gap at least this long, in quarters, reads as a breath between phrases.
+const REST_LENGTH: f32 = 0.5;
+/// Onset grid per bar used for the rhythm half of bar features.
+const BAR_STEPS: usize = 16;
+/// Section lengths in bars tried by `label_sections`, longest first.
+const SECTION_LENGTHS: [usize; 3] = [8, 4, 2];
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom > 0.0 { dot / denom } else if norm(a) == norm(b) { 1.0 } else { 0.0 }
+}
+
+/// Section label for the `idx`-th distinct section: A..Z, then A1, B1...
+fn section_label(idx: usize) -> String {
+    let letter = (b'A' + (idx % 26) as u8) as char;
+    if idx < 26 { letter.to_string() } else { format!("{}{}", letter, idx / 26) }
+}
+
+impl Sequence {
+    /// One feature vector per bar: duration weighted pitch classes of the non-drum tracks
+    /// followed by onsets of all tracks on a 16 step grid.
+    fn bar_features(&self) -> Vec<(f32, f32, Vec<f32>)> {
+        self.bars(self.notes_end()).iter().map(|bar| {
+            let (start, end) = (bar.start, bar.end());
+            let mut features = vec![0_f32; 12 + BAR_STEPS];
+            for track in &self.tracks {
+                for note in &track.notes {
+                    let overlap = (note.start + note.duration).min(end) - note.start.max(start);
+                    if overlap > 0.0 && !track.is_drum {
+                        features[(note.pitch % 12) as usize] += overlap;
+                    }
+                    if note.start >= start && note.start < end {
+                        let step = ((note.start - start) / bar.length() * BAR_STEPS as f32) as usize;
+                        features[12 + step.min(BAR_STEPS - 1)] += 1.0;
+                    }
+                }
+            }
+            (start, end, features)
+        }).collect()
+    }
+
+    /// Bar by bar cosine self-similarity of pitch class and onset features.
+    pub fn self_similarity(&self) -> Vec<Vec<f32>> {
+        let features = self.bar_features();
+        features.iter()
+            .map(|(_, _, a)| features.iter().map(|(_, _, b)| cosine(a, b)).collect())
+            .collect()
+    }
+
+    /// Segment the piece into equal sections and label repeats, e.g. A A B A, as
+    /// `(start, end, label)`. A section repeats an earlier one when the mean similarity
+    /// of their aligned bars reaches `threshold`. With `section_bars` unset, the longest
+    /// of 8, 4 and 2 bars that reveals a repeat is used, falling back to 4.
+    pub fn label_sections(&self, section_bars: Option<usize>, threshold: f32) -> Vec<(f32, f32, String)> {
+        let features = self.bar_features();
+        if features.is_empty() {
+            return Vec::new();
+        }
+        let similarity: Vec<Vec<f32>> = features.iter()
+            .map(|(_, _, a)| features.iter().map(|(_, _, b)| cosine(a, b)).collect())
+            .collect();
+        let label = |length: usize| -> Vec<usize> {
+            let chunks = features.len().div_ceil(length);
+            let mut labels: Vec<usize> = Vec::with_capacity(chunks);
+            let mut distinct = 0;
+            for i in 0..chunks {
+                let matched = (0..i).find(|&j| {
+                    let pairs: Vec<f32> = (0..length)
+                        .filter(|k| i * length + k < features.len())
+                        .map(|k| similarity[i * length + k][j * length + k])
+                        .collect();
+                    !pairs.is_empty() && pairs.iter().sum::<f32>() / pairs.len() as f32 >= threshold
+                });
+                labels.push(matched.map_or_else(|| { distinct += 1; distinct - 1 }, |j| labels[j]));
+            }
+            labels
+        };
+        let (length, labels) = match section_bars {
+            Some(length) => (length.max(1), label(length.max(1))),
+            None => SECTION_LENGTHS.iter()
+                .map(|&length| (length, label(length)))
+                .find(|(_, labels)| labels.iter().enumerate().any(|(i, &l)| l != i))
+                .unwrap_or_else(|| (4, label(4))),
+        };
+        labels.iter().enumerate().map(|(i, &l)| {
+            let last = ((i + 1) * length).min(features.len()) - 1;
+            (features[i * length].0, features[last].1, section_label(l))
+        }).collect()
+    }
+
+    /// Add a marker named after its label at the start of every section.
+    pub fn mark_sections(&mut self, section_bars: Option<usize>, threshold: f32) {
+        for (start, _, label) in self.label_sections(section_bars, threshold) {
+            self.add_text_event(TextEvent { time: start, text: label, kind: TextKind::Marker });
+        }
+    }
+
+    /// Heuristic phrase endings as `(time, confidence)`, sorted by time.
+    ///
+    /// Evidence is a dominant to tonic root motion in the chords detected over `window`
+    /// quarters (stronger when it lands on the estimated key), and a rest or an unusually
+    /// long note in the skyline melody. Nearby evidence is combined, so confidences grow
+    /// when several cues agree.
+    pub fn detect_cadences(&self, window: f32) -> Vec<(f32, f32)> {
+        let mut candidates: Vec<(f32, f32)> = Vec::new();
+
+        let tonic = self.estimate_key().map(key2tonic);
+        let segments = self.chord_segments(window);
+        for pair in segments.windows(2) {
+            let (Some(dominant), Some(tonic_chord)) = (pair[0].2, pair[1].2) else { continue };
+            let is_dominant = matches!(dominant.quality(), "maj" | "7");
+            if is_dominant && (tonic_chord.root + 7) % 12 == dominant.root {
+                let confidence = if tonic == Some(tonic_chord.root) { 0.8 } else { 0.5 };
+                candidates.push((pair[1].0, confidence));
+            }
+        }
+
+        let melody = self.extract_melody().notes;
+        let mut durations: Vec<f32> = melody.iter().map(|n| n.duration).collect();
+        durations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let median = durations.get(durations.len() / 2).copied().unwrap_or(0.0);
+        for (idx, note) in melody.iter().enumerate() {
+            let end = note.start + note.duration;
+            let rest = melody.get(idx + 1).map_or(0.0, |next| next.start - end);
+            if rest >= REST_LENGTH {
+                candidates.push((end, 0.3));
+            }
+            if median > 0.0 && note.duration >= 2.0 * median {
+                candidates.push((note.start, 0.3));
+            }
+        }
+
+        candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut cadences: Vec<(f32, f32)> = Vec::new();
+        for (time, confidence) in candidates {
+            match cadences.last_mut() {
+                Some(last) if time - last.0 < MERGE_DISTANCE => {
+                    last.1 = 1.0 - (1.0 - last.1) * (1.0 - confidence);
+                }
+                _ => cadences.push((time, confidence)),
+            }
+        }
+        cadences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequence::{Note, Sequence, Track};
+
+    #[test]
+    fn test_label_sections() {
+        let note = |pitch: u8, start: f32| Note { pitch, start, duration: 1.0, velocity: 80, tie: false };
+        // Two bar A and B phrases laid out as A A B A
+        let a: [u8; 8] = [60, 64, 67, 64, 62, 65, 69, 65];
+        let b: [u8; 8] = [57, 57, 61, 61, 59, 59, 63, 63];
+        let notes = [a, a, b, a].iter().enumerate()
+            .flat_map(|(section, pitches)| pitches.iter().enumerate()
+                .map(move |(i, &p)| note(p, (section * 8 + i) as f32)))
+            .collect();
+        let mut seq = Sequence { tracks: vec![Track { notes, ..Track::default() }], ..Sequence::default() };
+        let sections = seq.label_sections(None, 0.95);
+        let labels: Vec<&str> = sections.iter().map(|s| s.2.as_str()).collect();
+        assert_eq!(labels, vec!["A", "A", "B", "A"]);
+        assert_eq!((sections[2].0, sections[2].1), (16.0, 24.0));
+        seq.mark_sections(Some(2), 0.95);
+        assert_eq!(seq.markers().len(), 4);
+    }
+
+    #[test]
+    fn test_detect_cadences() {
+        let note = |pitch: u8, start: f32, duration: f32| Note { pitch, start, duration, velocity: 80, tie: false };
+        // I - IV - V - I in C, the melody resting on the final tonic
+        let chords: [(&[u8], f32); 4] = [(&[48, 52, 55], 0.0), (&[53, 57, 60], 2.0), (&[55, 59, 62], 4.0), (&[48, 52, 55], 6.0)];
+        let harmony = chords.iter()
+            .flat_map(|(pitches, start)| pitches.iter().map(move |&p| note(p, *start, 2.0)))
+            .collect();
+        let melody = vec![note(76, 0.0, 1.0), note(77, 1.0, 1.0), note(72, 2.0, 1.0), note(74, 3.0, 1.0),
+                          note(74, 4.0, 1.0), note(71, 5.0, 1.0), note(72, 6.0, 4.0)];
+        let seq = Sequence {
+            tracks: vec![Track { notes: harmony, ..Track::default() }, Track { notes: melody, ..Track::default() }],
+            ..Sequence::default()
+        };
+        let cadences = seq.detect_cadences(2.0);
+        let (time, confidence) = cadences.iter().copied().fold((0.0, 0.0), |a, b| if b.1 > a.1 { b } else { a });
+        assert_eq!(time, 6.0);
+        assert!(confidence > 0.8);
+    }
+}
@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
+use crate::scheduler::Scheduler;
+use crate::sequence::Sequence;
+
+/// Seconds rendered after the last event so releases and reverb can ring out.
+const RELEASE_TAIL: f32 = 1.0;
+const BLOCK_SIZE: usize = 512;
+
+impl Sequence {
+    /// Render through the SoundFont at `soundfont` to interleaved stereo samples in
+    /// -1 to 1, using the events of `Scheduler` at `sample_rate`.
+    pub fn synthesize(&self, soundfont: &str, sample_rate: u32) -> Result<Vec<f32>, String> {
+        let mut file = File::open(soundfont).map_err(|e| format!("{}: {}", soundfont, e))?;
+        let font = Arc::new(SoundFont::new(&mut file).map_err(|e| format!("{}: {}", soundfont, e))?);
+        let settings = SynthesizerSettings::new(sample_rate as i32);
+        let mut synth = Synthesizer::new(&font, &settings).map_err(|e| e.to_string())?;
+
+        let mut scheduler = Scheduler::new(self, sample_rate, BLOCK_SIZE);
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        let mut render = |synth: &mut Synthesizer, samples: usize| {
+            let from = left.len();
+            left.resize(from + samples, 0.0);
+            right.resize(from + samples, 0.0);
+            synth.render(&mut left[from..], &mut right[from..]);
+        };
+        while !scheduler.is_finished() {
+            let mut rendered = 0;
+            for (offset, event) in scheduler.next_block() {
+                render(&mut synth, offset - rendered);
+                rendered = offset;
+                let bytes = event.bytes();
+                synth.process_midi_message(
+                    (bytes[0] & 0x0F) as i32,
+                    (bytes[0] & 0xF0) as i32,
+                    bytes.get(1).copied().unwrap_or(0) as i32,
+                    bytes.get(2).copied().unwrap_or(0) as i32,
+                );
+            }
+            render(&mut synth, BLOCK_SIZE - rendered);
+        }
+        render(&mut synth, (RELEASE_TAIL * sample_rate as f32) as usize);
+
+        Ok(left.iter().zip(&right).flat_map(|(&l, &r)| [l, r]).collect())
+    }
+
+    /// `synthesize` to a 16-bit stereo WAV file.
+    pub fn to_wav(&self, path: &str, soundfont: &str, sample_rate: u32) -> Result<(), String> {
+        let samples = self.synthesize(soundfont, sample_rate)?;
+        write_wav(path, &samples, sample_rate).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// Interleaved stereo `samples` as 16-bit PCM WAV.
+fn write_wav(path: &str, samples: &[f32], sample_rate: u32) -> std::io::Result<()> {
+    let data_len = samples.len() as u32 * 2;
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16_u32.to_le_bytes())?;
+    out.write_all(&1_u16.to_le_bytes())?; // PCM
+    out.write_all(&2_u16.to_le_bytes())?; // channels
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 4).to_le_bytes())?; // bytes per second
+    out.write_all(&4_u16.to_le_bytes())?; // bytes per frame
+    out.write_all(&16_u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        out.write_all(&value.to_le_bytes())?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_wav() {
+        let path = std::env::temp_dir().join("midiparse_test_write_wav.wav");
+        let path = path.to_str().unwrap();
+        write_wav(path, &[0.0, 0.0, 1.0, -1.0], 22050).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 22050);
+        assert_eq!(&bytes[44..], &[0, 0, 0, 0, 0xFF, 0x7F, 0x01, 0x80]);
+    }
+
+    #[test]
+    fn test_synthesize_missing_soundfont() {
+        let err = Sequence::default().synthesize("/nonexistent.sf2", 44100).unwrap_err();
+        assert!(err.starts_with("/nonexistent.sf2"));
+    }
+}
@@ -0,0 +1,264 @@
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::{PyByteArray, PyDict};
+use crate::sequence::Sequence;
+
+/// A typed column of a table, with the Arrow type it maps to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    F32(Vec<f32>),
+    Bool(Vec<bool>),
+    Str(Vec<String>),
+}
+
+impl Column {
+    pub fn arrow_type(&self) -> &'static str {
+        match self {
+            Column::U8(_) => "uint8",
+            Column::U16(_) => "uint16",
+            Column::F32(_) => "float32",
+            Column::Bool(_) => "bool_",
+            Column::Str(_) => "string",
+        }
+    }
+
+    /// A numpy array when numpy is given, filled from the Rust buffer in one copy, and a
+    /// list otherwise. Strings are always a list.
+    #[cfg(feature = "python")]
+    fn into_py(self, py: Python<'_>, numpy: Option<&PyModule>) -> PyResult<PyObject> {
+        let Some(numpy) = numpy else {
+            return Ok(match self {
+                Column::U8(values) => values.into_py(py),
+                Column::U16(values) => values.into_py(py),
+                Column::F32(values) => values.into_py(py),
+                Column::Bool(values) => values.into_py(py),
+                Column::Str(values) => values.into_py(py),
+            });
+        };
+        let (bytes, dtype): (Vec<u8>, &str) = match self {
+            Column::U8(values) => (values, "uint8"),
+            Column::U16(values) => (values.iter().flat_map(|v| v.to_ne_bytes()).collect(), "uint16"),
+            Column::F32(values) => (values.iter().flat_map(|v| v.to_ne_bytes()).collect(), "float32"),
+            Column::Bool(values) => (values.iter().map(|&v| v as u8).collect(), "bool"),
+            Column::Str(values) => return Ok(values.into_py(py)),
+        };
+        // A bytearray keeps the array writable
+        let buffer = PyByteArray::new(py, &bytes);
+        Ok(numpy.getattr("frombuffer")?.call1((buffer, dtype))?.into_py(py))
+    }
+}
+
+/// One row per note, the long layout dataframes and Arrow expect.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NoteTable {
+    pub track: Vec<u16>,
+    pub program: Vec<u8>,
+    pub is_drum: Vec<bool>,
+    pub pitch: Vec<u8>,
+    pub start: Vec<f32>,
+    pub duration: Vec<f32>,
+    pub velocity: Vec<u8>,
+}
+
+/// One row per control change, ordered by track, controller number and time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ControlTable {
+    pub track: Vec<u16>,
+    pub number: Vec<u8>,
+    pub time: Vec<f32>,
+    pub value: Vec<u8>,
+}
+
+/// Tempo, signature and text events as `(time, kind, value)` rows, the value as text:
+/// QPM, "3/4", "D major" or the text itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetaTable {
+    pub time: Vec<f32>,
+    pub kind: Vec<String>,
+    pub value: Vec<String>,
+}
+
+impl NoteTable {
+    pub fn columns(self) -> Vec<(&'static str, Column)> {
+        vec![
+            ("track", Column::U16(self.track)),
+            ("program", Column::U8(self.program)),
+            ("is_drum", Column::Bool(self.is_drum)),
+            ("pitch", Column::U8(self.pitch)),
+            ("start", Column::F32(self.start)),
+            ("duration", Column::F32(self.duration)),
+            ("velocity", Column::U8(self.velocity)),
+        ]
+    }
+}
+
+impl ControlTable {
+    pub fn columns(self) -> Vec<(&'static str, Column)> {
+        vec![
+            ("track", Column::U16(self.track)),
+            ("number", Column::U8(self.number)),
+            ("time", Column::F32(self.time)),
+            ("value", Column::U8(self.value)),
+        ]
+    }
+}
+
+impl MetaTable {
+    pub fn columns(self) -> Vec<(&'static str, Column)> {
+        vec![
+            ("time", Column::F32(self.time)),
+            ("kind", Column::Str(self.kind)),
+            ("value", Column::Str(self.value)),
+        ]
+    }
+
+    fn push(&mut self, time: f32, kind: &str, value: String) {
+        self.time.push(time);
+        self.kind.push(kind.to_string());
+        self.value.push(value);
+    }
+}
+
+impl Sequence {
+    pub fn note_table(&self) -> NoteTable {
+        let mut table = NoteTable::default();
+        for (idx, track) in self.tracks.iter().enumerate() {
+            for note in &track.notes {
+                table.track.push(idx as u16);
+                table.program.push(track.program);
+                table.is_drum.push(track.is_drum);
+                table.pitch.push(note.pitch);
+                table.start.push(note.start);
+                table.duration.push(note.duration);
+                table.velocity.push(note.velocity);
+            }
+        }
+        table
+    }
+
+    pub fn control_table(&self) -> ControlTable {
+        let mut table = ControlTable::default();
+        for (idx, track) in self.tracks.iter().enumerate() {
+            let mut numbers: Vec<&u8> = track.controls.keys().collect();
+            numbers.sort();
+            for number in numbers {
+                for control in &track.controls[number] {
+                    table.track.push(idx as u16);
+                    table.number.push(*number);
+                    table.time.push(control.time);
+                    table.value.push(control.value);
+                }
+            }
+        }
+        table
+    }
+
+    /// Meta events of every kind, in time order.
+    pub fn meta_table(&self) -> MetaTable {
+        let mut rows = MetaTable::default();
+        self.qpm.iter().for_each(|t| rows.push(t.time, "tempo", t.qpm.to_string()));
+        for t in &self.time_signatures {
+            rows.push(t.time, "time_signature", format!("{}/{}", t.numerator, t.denominator));
+        }
+        for k in &self.key_signatures {
+            rows.push(k.time, "key_signature", k.name());
+        }
+        self.texts.iter().for_each(|t| rows.push(t.time, t.kind.name(), t.text.clone()));
+
+        let mut order: Vec<usize> = (0..rows.time.len()).collect();
+        order.sort_by(|&a, &b| rows.time[a].total_cmp(&rows.time[b]));
+        MetaTable {
+            time: order.iter().map(|&i| rows.time[i]).collect(),
+            kind: order.iter().map(|&i| rows.kind[i].clone()).collect(),
+            value: order.iter().map(|&i| rows.value[i].clone()).collect(),
+        }
+    }
+
+    /// The tables by name, "notes", "controls" and "meta".
+    pub fn tables(&self) -> Vec<(&'static str, Vec<(&'static str, Column)>)> {
+        vec![
+            ("notes", self.note_table().columns()),
+            ("controls", self.control_table().columns()),
+            ("meta", self.meta_table().columns()),
+        ]
+    }
+}
+
+/// `Sequence.notes_dataframe()` and the like: columns by name, as numpy arrays if numpy
+/// is installed, ready for `pandas.DataFrame` or `polars.DataFrame`.
+#[cfg(feature = "python")]
+pub(crate) fn columns_to_dict<'py>(py: Python<'py>, columns: Vec<(&'static str, Column)>) -> PyResult<&'py PyDict> {
+    let numpy = py.import("numpy").ok();
+    let dict = PyDict::new(py);
+    for (name, column) in columns {
+        dict.set_item(name, column.into_py(py, numpy)?)?;
+    }
+    Ok(dict)
+}
+
+/// A `pyarrow.RecordBatch` with the column types of `Column::arrow_type`.
+#[cfg(feature = "python")]
+fn arrow_batch<'py>(py: Python<'py>, columns: Vec<(&'static str, Column)>) -> PyResult<&'py PyAny> {
+    let pa = py.import("pyarrow")?;
+    let numpy = py.import("numpy").ok();
+    let mut names = Vec::with_capacity(columns.len());
+    let mut arrays = Vec::with_capacity(columns.len());
+    for (name, column) in columns {
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("type", pa.getattr(column.arrow_type())?.call0()?)?;
+        arrays.push(pa.getattr("array")?.call((column.into_py(py, numpy)?,), Some(kwargs))?);
+        names.push(name);
+    }
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("names", names)?;
+    pa.getattr("RecordBatch")?.getattr("from_arrays")?.call((arrays,), Some(kwargs))
+}
+
+/// `Sequence.to_arrow()`: the note, control and meta tables as `pyarrow.RecordBatch`es.
+/// Needs pyarrow.
+#[cfg(feature = "python")]
+pub(crate) fn to_arrow<'py>(py: Python<'py>, seq: &Sequence) -> PyResult<(&'py PyAny, &'py PyAny, &'py PyAny)> {
+    Ok((
+        arrow_batch(py, seq.note_table().columns())?,
+        arrow_batch(py, seq.control_table().columns())?,
+        arrow_batch(py, seq.meta_table().columns())?,
+    ))
+}
+
+/// `Sequence.write_parquet()`: one of the tables as a Parquet file. Needs pyarrow.
+#[cfg(feature = "python")]
+pub(crate) fn write_parquet(py: Python<'_>, seq: &Sequence, path: &str, table: &str) -> PyResult<()> {
+    let columns = seq.tables().into_iter().find(|(name, _)| *name == table).map(|(_, columns)| columns)
+        .ok_or_else(|| pyo3::exceptions::PyValueError::new_err(
+            format!("Unknown table {:?}, expected \"notes\", \"controls\" or \"meta\"", table)))?;
+    let batch = arrow_batch(py, columns)?;
+    let table = py.import("pyarrow")?.getattr("Table")?.call_method1("from_batches", (vec![batch],))?;
+    py.import("pyarrow.parquet")?.getattr("write_table")?.call1((table, path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables() {
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        let notes = seq.note_table();
+        assert_eq!(notes.pitch.len(), seq.tracks[0].notes.len());
+        assert_eq!(notes.start[3], seq.tracks[0].notes[3].start);
+        assert!(notes.track.iter().all(|&t| t == 0));
+
+        let meta = seq.meta_table();
+        assert!(meta.time.windows(2).all(|w| w[0] <= w[1]));
+        assert!(meta.kind.iter().any(|k| k == "tempo"));
+        assert!(meta.kind.iter().zip(&meta.value).any(|(k, v)| k == "time_signature" && v == "4/4"));
+
+        let columns = seq.control_table().columns();
+        assert_eq!(columns.iter().map(|(name, _)| *name).collect::<Vec<_>>(), ["track", "number", "time", "value"]);
+        assert_eq!(columns[2].1.arrow_type(), "float32");
+    }
+}
@@ -0,0 +1,228 @@
+#[cfg(feature = "python")]
+use pyo3::exceptions::PyValueError;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::message::MetaStatus;
+use crate::sequence::Sequence;
+use crate::pymethods::pymethods_with;
+
+/// Which text meta event a `TextEvent` is stored as.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextKind {
+    Text,
+    Copyright,
+    Lyric,
+    Marker,
+    Cue,
+}
+
+impl std::str::FromStr for TextKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TextKind::Text),
+            "copyright" => Ok(TextKind::Copyright),
+            "lyric" => Ok(TextKind::Lyric),
+            "marker" => Ok(TextKind::Marker),
+            "cue" => Ok(TextKind::Cue),
+            _ => Err(format!(
+                "Unknown text kind {:?}, expected \"text\", \"copyright\", \"lyric\", \"marker\" or \"cue\"", s)),
+        }
+    }
+}
+
+impl TextKind {
+    pub fn from_meta(status: MetaStatus) -> Option<Self> {
+        match status {
+            MetaStatus::Text => Some(TextKind::Text),
+            MetaStatus::CopyrightNote => Some(TextKind::Copyright),
+            MetaStatus::Lyric => Some(TextKind::Lyric),
+            MetaStatus::Marker => Some(TextKind::Marker),
+            MetaStatus::CuePoint => Some(TextKind::Cue),
+            _ => None,
+        }
+    }
+
+    pub fn meta_type(&self) -> u8 {
+        let status = match self {
+            TextKind::Text => MetaStatus::Text,
+            TextKind::Copyright => MetaStatus::CopyrightNote,
+            TextKind::Lyric => MetaStatus::Lyric,
+            TextKind::Marker => MetaStatus::Marker,
+            TextKind::Cue => MetaStatus::CuePoint,
+        };
+        status as u8
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextKind::Text => "text",
+            TextKind::Copyright => "copyright",
+            TextKind::Lyric => "lyric",
+            TextKind::Marker => "marker",
+            TextKind::Cue => "cue",
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn parse_kind(kind: &str) -> PyResult<TextKind> {
+    kind.parse().map_err(PyValueError::new_err)
+}
+
+/// A timed text, lyric, marker or cue point meta event, written to the conductor track.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TextEvent {
+    pub time: f32,
+    pub text: String,
+    pub kind: TextKind,
+}
+
+pymethods_with! { [pickle] #[pymethods] impl TextEvent {
+    #[new]
+    #[pyo3(signature = (time, text, kind = "marker"))]
+    fn py_new(time: f32, text: String, kind: &str) -> PyResult<Self> {
+        Ok(TextEvent { time, text, kind: parse_kind(kind)? })
+    }
+
+    #[getter]
+    fn get_time(&self) -> f32 { self.time }
+
+    #[setter]
+    fn set_time(&mut self, time: f32) { self.time = time; }
+
+    #[getter]
+    fn get_text(&self) -> String { self.text.clone() }
+
+    #[setter]
+    fn set_text(&mut self, text: String) { self.text = text; }
+
+    #[getter]
+    fn get_kind(&self) -> &'static str { self.kind.name() }
+
+    #[setter]
+    fn set_kind(&mut self, kind: &str) -> PyResult<()> {
+        self.kind = parse_kind(kind)?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
+
+/// Song level metas gathered on read, for catalogs built without the raw events.
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Name of the first track, the song title in format 1. Written back as the name of
+    /// the conductor track.
+    pub title: Option<String>,
+    /// First CopyrightNote meta.
+    pub copyright: Option<String>,
+    /// InstrumentName metas of all tracks, without repeats.
+    pub instruments: Vec<String>,
+    /// `(time, text)` of the CuePoint metas.
+    pub cue_points: Vec<(f32, String)>,
+}
+
+pymethods_with! { [pickle] #[pymethods] impl Metadata {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}}
+
+impl Metadata {
+    /// Copyright and cue points come from `texts`, the title and instrument names from
+    /// metas the sequence does not keep otherwise.
+    pub(crate) fn collect(title: &str, instruments: Vec<String>, texts: &[TextEvent]) -> Self {
+        let of_kind = |kind| texts.iter().filter(move |t| t.kind == kind);
+        let mut unique = Vec::with_capacity(instruments.len());
+        for name in instruments {
+            if !unique.contains(&name) {
+                unique.push(name);
+            }
+        }
+        Metadata {
+            title: Some(title.to_string()).filter(|t| !t.is_empty()),
+            copyright: of_kind(TextKind::Copyright).next().map(|t| t.text.clone()),
+            instruments: unique,
+            cue_points: of_kind(TextKind::Cue).map(|t| (t.time, t.text.clone())).collect(),
+        }
+    }
+}
+
+impl Sequence {
+    /// Insert a text event after any existing events at the same time.
+    pub fn add_text_event(&mut self, event: TextEvent) {
+        let idx = self.texts.partition_point(|t| t.time <= event.time);
+        self.texts.insert(idx, event);
+    }
+
+    /// Text events of one kind, or all of them, in time order.
+    pub fn text_events(&self, kind: Option<TextKind>) -> Vec<TextEvent> {
+        self.texts.iter()
+            .filter(|t| kind.is_none_or(|k| t.kind == k))
+            .cloned()
+            .collect()
+    }
+
+    /// Remove text events of one kind, or all of them. Returns how many were removed.
+    pub fn remove_text_events(&mut self, kind: Option<TextKind>) -> usize {
+        let before = self.texts.len();
+        self.texts.retain(|t| kind.is_some_and(|k| t.kind != k));
+        before - self.texts.len()
+    }
+
+    /// Rehearsal marks as `(time, text)`.
+    pub fn markers(&self) -> Vec<(f32, String)> {
+        self.text_events(Some(TextKind::Marker)).into_iter().map(|t| (t.time, t.text)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MIDIFile;
+    use crate::writer::WriteOptions;
+
+    #[test]
+    fn test_text_events_round_trip() {
+        let mut seq = Sequence::default();
+        seq.add_text_event(TextEvent { time: 8.0, text: "B".to_string(), kind: TextKind::Marker });
+        seq.add_text_event(TextEvent { time: 0.0, text: "A".to_string(), kind: TextKind::Marker });
+        seq.add_text_event(TextEvent { time: 2.0, text: "go".to_string(), kind: TextKind::Cue });
+        assert_eq!(seq.markers(), vec![(0.0, "A".to_string()), (8.0, "B".to_string())]);
+
+        let bytes = seq.to_bytes(&WriteOptions::default()).unwrap();
+        let mut back = Sequence::from_midi(&MIDIFile::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(back.texts, seq.texts);
+        assert_eq!(back.remove_text_events(Some(TextKind::Cue)), 1);
+        assert_eq!(back.texts.len(), 2);
+    }
+
+    #[test]
+    fn test_metadata() {
+        use crate::io::MidiTrack;
+        use crate::message::Meta;
+        let mut seq = Sequence { metadata: Metadata { title: Some("Song".to_string()), ..Metadata::default() }, ..Sequence::default() };
+        seq.add_text_event(TextEvent { time: 0.0, text: "(c) Someone".to_string(), kind: TextKind::Copyright });
+        seq.add_text_event(TextEvent { time: 4.0, text: "verse".to_string(), kind: TextKind::Cue });
+        let mut midi = seq.to_midi(&WriteOptions::default()).unwrap();
+        let mut data = Vec::new();
+        for name in ["Violins", "Violas", "Violins"] {
+            data.push(0x00);
+            data.extend_from_slice(&Meta::new(0, MetaStatus::InstrumentName as u8, name.as_bytes()).data);
+        }
+        data.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+        midi.tracks.push(MidiTrack::new(1, data));
+
+        let metadata = Sequence::from_midi(&midi).unwrap().metadata;
+        assert_eq!(metadata, Metadata {
+            title: Some("Song".to_string()),
+            copyright: Some("(c) Someone".to_string()),
+            instruments: vec!["Violins".to_string(), "Violas".to_string()],
+            cue_points: vec![(4.0, "verse".to_string())],
+        });
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::sequence::{Sequence, Tempo, Track};
+
+/// Resolution used when quarter-note times are mapped onto an integer tick grid.
+pub const TICKS_PER_QUARTER: u32 = 960;
+
+const DEFAULT_US_PER_QUARTER: u64 = 500000;
+
+#[derive(Clone, Debug)]
+struct TempoSegment {
+    tick: u64,
+    us_per_quarter: u64,
+    // Elapsed time at `tick`, kept in (microseconds * tpq) so integer division only
+    // happens once per lookup and rounding errors never accumulate.
+    elapsed: u64,
+    quarter: f64,
+    second: f64,
+}
+
+/// Piecewise-constant tempo map for converting quarter positions to absolute time.
+#[derive(Clone, Debug)]
+pub struct TempoMap {
+    tpq: u64,
+    segments: Vec<TempoSegment>,
+}
+
+/// Unit for times returned to callers: quarter notes or seconds via the tempo map.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimeUnit {
+    Quarter,
+    Second,
+}
+
+impl std::str::FromStr for TimeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quarter" => Ok(TimeUnit::Quarter),
+            "second" => Ok(TimeUnit::Second),
+            _ => Err(format!("Unknown time unit {:?}, expected \"quarter\" or \"second\"", s)),
+        }
+    }
+}
+
+#[inline(always)]
+pub fn qpm2tempo(qpm: f32) -> u64 {
+    (6e7 / qpm as f64).round() as u64
+}
+
+#[inline(always)]
+fn valid_qpm(qpm: f32) -> bool {
+    qpm > 0.0 && qpm.is_finite()
+}
+
+impl TempoMap {
+    /// Tempos whose qpm is not positive and finite are skipped, so the one before holds;
+    /// `try_new` refuses them instead. Times past the range of `u64` microseconds saturate.
+    pub fn new(tempos: &[Tempo], tpq: u32) -> Self {
+        let tpq = tpq.max(1) as u64;
+        let mut sorted: Vec<Tempo> = tempos.iter().filter(|t| valid_qpm(t.qpm)).copied().collect();
+        sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let mut segments = vec![TempoSegment {
+            tick: 0,
+            us_per_quarter: DEFAULT_US_PER_QUARTER,
+            elapsed: 0,
+            quarter: 0.0,
+            second: 0.0,
+        }];
+        for tempo in sorted {
+            let tick = (tempo.time.max(0.0) as f64 * tpq as f64).round() as u64;
+            let last = segments.last().unwrap();
+            let elapsed = last.elapsed.saturating_add((tick - last.tick).saturating_mul(last.us_per_quarter));
+            let quarter = tick as f64 / tpq as f64;
+            let second = last.second
+                + (quarter - last.quarter) * last.us_per_quarter as f64 / 1e6;
+            let segment = TempoSegment {
+                tick,
+                us_per_quarter: qpm2tempo(tempo.qpm),
+                elapsed,
+                quarter,
+                second,
+            };
+            if last.tick == tick {
+                *segments.last_mut().unwrap() = segment;
+            } else {
+                segments.push(segment);
+            }
+        }
+        TempoMap { tpq, segments }
+    }
+
+    /// Like `new`, but fails on a tempo whose qpm is not positive and finite.
+    pub fn try_new(tempos: &[Tempo], tpq: u32) -> Result<Self, &'static str> {
+        if !tempos.iter().all(|t| valid_qpm(t.qpm)) {
+            return Err("Tempo must be positive and finite");
+        }
+        Ok(Self::new(tempos, tpq))
+    }
+
+    #[inline(always)]
+    pub fn quarter_to_tick(&self, quarter: f32) -> u64 {
+        (quarter.max(0.0) as f64 * self.tpq as f64).round() as u64
+    }
+
+    pub fn tick_to_us(&self, tick: u64) -> u64 {
+        let idx = self.segments.partition_point(|s| s.tick <= tick) - 1;
+        let seg = &self.segments[idx];
+        let elapsed = seg.elapsed.saturating_add((tick - seg.tick).saturating_mul(seg.us_per_quarter));
+        elapsed.saturating_add(self.tpq / 2) / self.tpq
+    }
+
+    #[inline(always)]
+    pub fn quarter_to_us(&self, quarter: f32) -> u64 {
+        self.tick_to_us(self.quarter_to_tick(quarter))
+    }
+
+    pub fn quarter_to_second(&self, quarter: f64) -> f64 {
+        let idx = self.segments.partition_point(|s| s.quarter <= quarter).max(1) - 1;
+        let seg = &self.segments[idx];
+        seg.second + (quarter - seg.quarter) * seg.us_per_quarter as f64 / 1e6
+    }
+
+    pub fn convert(&self, quarter: f32, unit: TimeUnit) -> f32 {
+        match unit {
+            TimeUnit::Quarter => quarter,
+            TimeUnit::Second => self.quarter_to_second(quarter as f64) as f32,
+        }
+    }
+
+    pub fn second_to_quarter(&self, second: f64) -> f64 {
+        let idx = self.segments.partition_point(|s| s.second <= second).max(1) - 1;
+        let seg = &self.segments[idx];
+        seg.quarter + (second - seg.second) * 1e6 / seg.us_per_quarter as f64
+    }
+}
+
+/// Times of every event of a `Track` in integer microseconds, in the shape of its own
+/// lists: notes as `(onset, offset)`, the other events by their time.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackTimes {
+    pub notes: Vec<(u64, u64)>,
+    pub controls: HashMap<u8, Vec<u64>>,
+    pub fine_controls: HashMap<u8, Vec<u64>>,
+    pub channel_pressure: Vec<u64>,
+    pub poly_pressure: HashMap<u8, Vec<u64>>,
+    pub sysex: Vec<u64>,
+}
+
+/// Times of every event of a `Sequence` in integer microseconds, see `event_times_us`.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventTimes {
+    pub tracks: Vec<TrackTimes>,
+    pub qpm: Vec<u64>,
+    pub time_signatures: Vec<u64>,
+    pub key_signatures: Vec<u64>,
+    pub texts: Vec<u64>,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl TrackTimes {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl EventTimes {
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+/// `time` of each event in integer microseconds.
+fn times_us<T>(map: &TempoMap, events: &[T], time: impl Fn(&T) -> f32) -> Vec<u64> {
+    events.iter().map(|e| map.quarter_to_us(time(e))).collect()
+}
+
+fn lanes_us<T>(map: &TempoMap, lanes: &HashMap<u8, Vec<T>>, time: impl Fn(&T) -> f32) -> HashMap<u8, Vec<u64>> {
+    lanes.iter().map(|(&number, lane)| (number, times_us(map, lane, &time))).collect()
+}
+
+impl TrackTimes {
+    fn new(track: &Track, map: &TempoMap) -> Self {
+        TrackTimes {
+            notes: track.notes.iter()
+                .map(|n| (map.quarter_to_us(n.start), map.quarter_to_us(n.start + n.duration)))
+                .collect(),
+            controls: lanes_us(map, &track.controls, |c| c.time),
+            fine_controls: lanes_us(map, &track.fine_controls, |c| c.time),
+            channel_pressure: times_us(map, &track.channel_pressure, |c| c.time),
+            poly_pressure: lanes_us(map, &track.poly_pressure, |c| c.time),
+            sysex: times_us(map, &track.sysex, |s| s.time),
+        }
+    }
+}
+
+impl Sequence {
+    /// Every event time in integer microseconds, computed on the tick grid of the file
+    /// (or `TICKS_PER_QUARTER` without one) with exact integer tempo math, so long pieces
+    /// don't drift as float seconds do. Fails on a tempo that is not positive and finite.
+    pub fn event_times_us(&self) -> Result<EventTimes, &'static str> {
+        let map = TempoMap::try_new(&self.qpm, self.tpq.map_or(TICKS_PER_QUARTER, u32::from))?;
+        Ok(EventTimes {
+            tracks: self.tracks.iter().map(|track| TrackTimes::new(track, &map)).collect(),
+            qpm: times_us(&map, &self.qpm, |t| t.time),
+            time_signatures: times_us(&map, &self.time_signatures, |t| t.time),
+            key_signatures: times_us(&map, &self.key_signatures, |k| k.time),
+            texts: times_us(&map, &self.texts, |t| t.time),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tempo_map() {
+        let tempos = [
+            Tempo { time: 0.0, qpm: 120.0 },
+            Tempo { time: 4.0, qpm: 60.0 },
+        ];
+        let map = TempoMap::new(&tempos, TICKS_PER_QUARTER);
+        assert_eq!(map.quarter_to_us(2.0), 1_000_000);
+        assert_eq!(map.quarter_to_us(5.0), 3_000_000);
+        assert!((map.quarter_to_second(5.0) - 3.0).abs() < 1e-9);
+        assert!((map.second_to_quarter(3.0) - 5.0).abs() < 1e-9);
+
+        // Broken tempos are skipped or refused, and huge times saturate
+        let broken = [Tempo { time: f32::NAN, qpm: 90.0 }, Tempo { time: 4.0, qpm: -1.0 }, Tempo { time: 8.0, qpm: f32::NAN }];
+        let map = TempoMap::new(&broken, TICKS_PER_QUARTER);
+        assert_eq!(map.quarter_to_us(4.0), 2_666_668);
+        assert_eq!(map.quarter_to_us(f32::INFINITY), u64::MAX / TICKS_PER_QUARTER as u64);
+        assert!(TempoMap::try_new(&broken, TICKS_PER_QUARTER).is_err());
+        assert!(TempoMap::try_new(&tempos, TICKS_PER_QUARTER).is_ok());
+    }
+
+    #[test]
+    fn test_event_times_us() {
+        use crate::sequence::{ControlChange, Note};
+        let mut track = Track {
+            notes: vec![Note { pitch: 60, start: 0.001, duration: 5.0, velocity: 90, tie: false }],
+            ..Track::default()
+        };
+        track.controls.insert(64, vec![ControlChange { time: 4.5, value: 127 }]);
+        let mut seq = Sequence::from_tracks(vec![track], 120.0);
+        seq.qpm.push(Tempo { time: 4.0, qpm: 60.0 });
+        // One tick at 1000 ticks per quarter, which the default grid can't hold
+        seq.tpq = Some(1000);
+        let times = seq.event_times_us().unwrap();
+        assert_eq!(times.tracks[0].notes, vec![(500, 3_001_000)]);
+        assert_eq!(times.tracks[0].controls[&64], vec![2_500_000]);
+        assert_eq!(times.qpm, vec![0, 2_000_000]);
+        seq.tpq = None;
+        assert_eq!(seq.event_times_us().unwrap().tracks[0].notes[0].0, 521);
+        seq.qpm[1].qpm = 0.0;
+        assert!(seq.event_times_us().is_err());
+    }
+}
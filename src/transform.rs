@@ -0,0 +1,845 @@
+use std::collections::{BinaryHeap, HashSet};
+use crate::sequence::{ControlChange, KeySignature, Note, Sequence, Tempo, TimeSignature, Track, SUSTAIN_PEDAL};
+use crate::text::TextEvent;
+use crate::timing::TICKS_PER_QUARTER;
+use crate::util::Rng;
+
+/// Items of a sorted time map that apply within `[start, end)`, shifted to start at 0.
+/// The entry in effect at `start` is carried over to time 0.
+fn slice_map<T: Copy>(
+    items: &[T], start: f32, end: f32,
+    time: impl Fn(&T) -> f32, shift: impl Fn(&T, f32) -> T,
+) -> Vec<T> {
+    let mut sliced: Vec<T> = Vec::new();
+    if let Some(active) = items.iter().rev().find(|x| time(x) <= start) {
+        sliced.push(shift(active, 0.0));
+    }
+    for item in items.iter().filter(|x| time(x) > start && time(x) < end) {
+        sliced.push(shift(item, time(item) - start));
+    }
+    sliced
+}
+
+/// Which voice gives way when a polyphony limit is hit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VoiceStealing {
+    /// Cut the note that started first.
+    Oldest,
+    /// Cut the softest sounding note, or drop the new one if it is the softest.
+    Quietest,
+}
+
+impl std::str::FromStr for VoiceStealing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "oldest" => Ok(VoiceStealing::Oldest),
+            "quietest" => Ok(VoiceStealing::Quietest),
+            _ => Err(format!("Unknown strategy {:?}, expected \"oldest\" or \"quietest\"", s)),
+        }
+    }
+}
+
+/// Which note stays when notes overlap in `Track::to_monophonic`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Monophony {
+    /// The highest pitch, as a melody line.
+    Highest,
+    /// The lowest pitch, as a bass line.
+    Lowest,
+    /// The most recent onset, the highest of notes starting together.
+    Last,
+}
+
+impl std::str::FromStr for Monophony {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "highest" => Ok(Monophony::Highest),
+            "lowest" => Ok(Monophony::Lowest),
+            "last" => Ok(Monophony::Last),
+            _ => Err(format!("Unknown policy {:?}, expected \"highest\", \"lowest\" or \"last\"", s)),
+        }
+    }
+}
+
+impl Track {
+    /// A copy with at most one note sounding at a time. A note that wins over the one
+    /// sounding cuts it off at its onset, a note that loses is dropped, and a repeated
+    /// pitch always cuts off the previous one.
+    pub fn to_monophonic(&self, policy: Monophony) -> Track {
+        let mut notes = self.notes.clone();
+        notes.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.pitch.cmp(&b.pitch)));
+        let mut kept: Vec<Note> = Vec::with_capacity(notes.len());
+        for note in notes {
+            let Some(prev) = kept.last_mut().filter(|p| p.start + p.duration > note.start) else {
+                kept.push(note);
+                continue;
+            };
+            let wins = note.pitch == prev.pitch || match policy {
+                Monophony::Highest | Monophony::Last if note.start == prev.start => true,
+                Monophony::Highest => note.pitch > prev.pitch,
+                Monophony::Lowest => note.pitch < prev.pitch,
+                Monophony::Last => true,
+            };
+            if !wins {
+                continue;
+            }
+            if note.start == prev.start {
+                *prev = note;
+            } else {
+                prev.duration = note.start - prev.start;
+                kept.push(note);
+            }
+        }
+        Track { notes: kept, ..self.clone() }
+    }
+
+    /// Cap the number of simultaneously sounding notes at `max_voices`. Stolen notes
+    /// are shortened to end where the new note starts, and dropped if nothing is left.
+    pub fn limit_polyphony(&mut self, max_voices: usize, strategy: VoiceStealing) {
+        self.sort();
+        let mut notes = std::mem::take(&mut self.notes);
+        let mut keep = vec![true; notes.len()];
+        let mut sounding: Vec<usize> = Vec::new();
+        for idx in 0..notes.len() {
+            let start = notes[idx].start;
+            sounding.retain(|&i| notes[i].start + notes[i].duration > start);
+            sounding.push(idx);
+            if sounding.len() <= max_voices {
+                continue;
+            }
+            let victim = match strategy {
+                VoiceStealing::Oldest => 0,
+                // Later notes win ties so the newest note is the first candidate to go
+                VoiceStealing::Quietest => (0..sounding.len())
+                    .rev()
+                    .min_by_key(|&i| notes[sounding[i]].velocity)
+                    .unwrap(),
+            };
+            let stolen = sounding.remove(victim);
+            notes[stolen].duration = start - notes[stolen].start;
+            if notes[stolen].duration <= 0.0 {
+                keep[stolen] = false;
+            }
+        }
+        let mut keep = keep.into_iter();
+        notes.retain(|_| keep.next().unwrap());
+        self.notes = notes;
+    }
+}
+
+impl Track {
+    /// Keep notes within the pitch range that are at least `min_velocity` loud and
+    /// `min_duration` long. Zero length notes are always dropped. Returns how many
+    /// notes were removed.
+    pub fn filter_notes(&mut self, min_pitch: u8, max_pitch: u8, min_velocity: u8, min_duration: f32) -> usize {
+        let before = self.notes.len();
+        self.notes.retain(|n| {
+            (min_pitch..=max_pitch).contains(&n.pitch) && n.velocity >= min_velocity
+                && n.duration > 0.0 && n.duration >= min_duration
+        });
+        before - self.notes.len()
+    }
+
+    /// Multiply every velocity by `factor`, rounded and kept within 1-127.
+    pub fn scale_velocity(&mut self, factor: f32) {
+        for note in self.notes.iter_mut() {
+            note.velocity = (note.velocity as f32 * factor).round().clamp(1.0, 127.0) as u8;
+        }
+    }
+
+    /// Scale velocities so their mean becomes `target_mean`, as far as clamping allows.
+    pub fn normalize_velocity(&mut self, target_mean: f32) {
+        if self.notes.is_empty() {
+            return;
+        }
+        let mean = self.notes.iter().map(|n| n.velocity as f32).sum::<f32>() / self.notes.len() as f32;
+        self.scale_velocity(target_mean / mean);
+    }
+
+    /// Drop notes repeating the pitch of another note starting on the same tick, keeping
+    /// the longest, then loudest, of them. Returns how many notes were removed.
+    pub fn remove_duplicate_notes(&mut self) -> usize {
+        let before = self.notes.len();
+        let tick = |t: f32| (t as f64 * TICKS_PER_QUARTER as f64).round() as i64;
+        self.notes.sort_by(|a, b| {
+            (tick(a.start), a.pitch).cmp(&(tick(b.start), b.pitch))
+                .then(b.duration.total_cmp(&a.duration))
+                .then(b.velocity.cmp(&a.velocity))
+        });
+        self.notes.dedup_by(|n, kept| (tick(n.start), n.pitch) == (tick(kept.start), kept.pitch));
+        self.sort();
+        before - self.notes.len()
+    }
+
+    /// Set every note to `gate` times the gap up to the next later onset in the track, so
+    /// below 1 is detached and 1 is legato. Onsets are untouched, and notes at the last
+    /// onset keep their length.
+    pub fn adjust_durations(&mut self, gate: f32) {
+        self.sort();
+        let mut onsets: Vec<f32> = self.notes.iter().map(|n| n.start).collect();
+        onsets.dedup();
+        for note in &mut self.notes {
+            let next = onsets.partition_point(|&s| s <= note.start);
+            if let Some(&next_onset) = onsets.get(next) {
+                note.duration = (next_onset - note.start) * gate;
+            }
+        }
+    }
+
+    /// Every note held until the next onset, `adjust_durations(1.0)`.
+    pub fn legato(&mut self) {
+        self.adjust_durations(1.0);
+    }
+
+    /// Hold notes released while the sustain pedal is down (CC 64 at 64 or above) until
+    /// the pedal comes up, or until the next onset of the same pitch if that is sooner.
+    /// Notes are never shortened.
+    pub fn apply_sustain(&mut self) {
+        let Some(pedal) = self.controls.get(&SUSTAIN_PEDAL) else { return };
+        let mut pedal = pedal.clone();
+        pedal.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let mut held = Vec::new();
+        let mut down: Option<f32> = None;
+        for cc in &pedal {
+            match (cc.value >= 64, down) {
+                (true, None) => down = Some(cc.time),
+                (false, Some(start)) => {
+                    held.push((start, cc.time));
+                    down = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = down {
+            held.push((start, self.end_time()));
+        }
+
+        self.notes.sort_by(|a, b| a.pitch.cmp(&b.pitch).then(a.start.total_cmp(&b.start)));
+        for idx in 0..self.notes.len() {
+            let note = self.notes[idx];
+            let end = note.start + note.duration;
+            let Some(&(_, release)) = held.iter().find(|&&(start, release)| start <= end && end < release) else {
+                continue;
+            };
+            let next_onset = self.notes.get(idx + 1).filter(|n| n.pitch == note.pitch).map_or(f32::MAX, |n| n.start);
+            self.notes[idx].duration = release.min(next_onset).max(end) - note.start;
+        }
+        self.sort();
+    }
+
+    /// Fraction of notes shared with `other`, counting a note as shared when `other` has
+    /// one at the same onset with the same pitch or an octave away. Measured against the
+    /// larger track, so a sparse part is not a doubling of a busy one.
+    pub fn doubling_similarity(&self, other: &Track) -> f32 {
+        let total = self.notes.len().max(other.notes.len());
+        if total == 0 {
+            return 0.0;
+        }
+        let tick = |t: f32| (t as f64 * TICKS_PER_QUARTER as f64).round() as i64;
+        let onsets: HashSet<(i64, i32)> = other.notes.iter()
+            .map(|n| (tick(n.start), n.pitch as i32))
+            .collect();
+        let shared = self.notes.iter()
+            .filter(|n| [0, 12, -12].iter().any(|d| onsets.contains(&(tick(n.start), n.pitch as i32 + d))))
+            .count();
+        shared as f32 / total as f32
+    }
+
+    /// Lowest and highest pitch, `None` without notes.
+    pub fn pitch_range(&self) -> Option<(u8, u8)> {
+        let low = self.notes.iter().map(|n| n.pitch).min()?;
+        let high = self.notes.iter().map(|n| n.pitch).max()?;
+        Some((low, high))
+    }
+}
+
+impl Sequence {
+    /// Lowest and highest pitch over the non-drum tracks.
+    pub fn pitch_range(&self) -> Option<(u8, u8)> {
+        self.tracks.iter()
+            .filter(|t| !t.is_drum)
+            .filter_map(|t| t.pitch_range())
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+    }
+
+    /// Shift every non-drum track by whole octaves so its notes lie in `[low, high]`,
+    /// keeping each track's voicing intact. A track that can't fit is moved to the octave
+    /// where the least of it sticks out. Returns the shift of each track in semitones.
+    pub fn fit_to_range(&mut self, low: u8, high: u8) -> Vec<i32> {
+        let (low, high) = (low.min(high) as i32, low.max(high) as i32);
+        self.tracks.iter_mut().map(|track| {
+            let Some((lo, hi)) = track.pitch_range().filter(|_| !track.is_drum) else { return 0 };
+            let (lo, hi) = (lo as i32, hi as i32);
+            let overflow = |shift: i32| (low - lo - shift).max(0) + (hi + shift - high).max(0);
+            let shift = (-10..=10)
+                .map(|octave| octave * 12)
+                .filter(|shift| lo + shift >= 0 && hi + shift <= 127)
+                .min_by_key(|&shift| (overflow(shift), shift.abs()))
+                .unwrap_or(0);
+            for note in &mut track.notes {
+                note.pitch = (note.pitch as i32 + shift) as u8;
+            }
+            shift
+        }).collect()
+    }
+
+    /// Drop non-drum tracks that double an earlier kept track, unison or an octave apart,
+    /// with a `doubling_similarity` of at least `threshold`. Returns the removed indices.
+    pub fn remove_doublings(&mut self, threshold: f32) -> Vec<usize> {
+        let mut kept: Vec<usize> = Vec::new();
+        let mut removed = Vec::new();
+        for (idx, track) in self.tracks.iter().enumerate() {
+            let doubled = !track.is_drum && kept.iter().any(|&k| {
+                !self.tracks[k].is_drum && track.doubling_similarity(&self.tracks[k]) >= threshold
+            });
+            if doubled { removed.push(idx) } else { kept.push(idx) }
+        }
+        let mut idx = 0;
+        self.tracks.retain(|_| {
+            idx += 1;
+            !removed.contains(&(idx - 1))
+        });
+        removed
+    }
+
+    /// Cut the sequence at the given quarter positions into `times.len() + 1` parts.
+    ///
+    /// Notes belong to the part they start in and are truncated at its end. Every part
+    /// starts with the tempo, time signature, key signature and controller values that
+    /// were in effect at its start, so it can be used as an independent piece. Positions
+    /// that are not positive and finite are ignored.
+    pub fn split_at(&self, times: &[f32]) -> Vec<Sequence> {
+        let mut sorted = self.clone();
+        sorted.sort();
+        let mut bounds: Vec<f32> = times.iter().copied().filter(|&t| t.is_finite() && t > 0.0).collect();
+        bounds.sort_by(|a, b| a.total_cmp(b));
+        bounds.dedup();
+        bounds.insert(0, 0.0);
+        bounds.push(f32::MAX);
+        // Carries every field that is not sliced below.
+        let base = Sequence {
+            tracks: Vec::new(),
+            time_signatures: Vec::new(),
+            key_signatures: Vec::new(),
+            qpm: Vec::new(),
+            texts: Vec::new(),
+            ..sorted.clone()
+        };
+
+        bounds.windows(2).map(|w| {
+            let (start, end) = (w[0], w[1]);
+            let tracks = sorted.tracks.iter().map(|track| {
+                let notes = track.notes.iter()
+                    .filter(|n| n.start >= start && n.start < end)
+                    .map(|n| Note {
+                        start: n.start - start,
+                        duration: n.duration.min(end - n.start),
+                        ..*n
+                    })
+                    .collect();
+                let controls = track.controls.iter()
+                    .map(|(&number, ccs)| (number, slice_map(
+                        ccs, start, end, |c| c.time,
+                        |c, time| ControlChange { time, ..*c },
+                    )))
+                    .filter(|(_, ccs)| !ccs.is_empty())
+                    .collect();
+                Track { notes, controls, ..track.clone() }
+            }).collect();
+            Sequence {
+                tracks,
+                time_signatures: slice_map(&sorted.time_signatures, start, end, |x| x.time,
+                    |x, time| TimeSignature { time, ..*x }),
+                key_signatures: slice_map(&sorted.key_signatures, start, end, |x| x.time,
+                    |x, time| KeySignature { time, ..*x }),
+                qpm: slice_map(&sorted.qpm, start, end, |x| x.time,
+                    |x, time| Tempo { time, ..*x }),
+                texts: sorted.texts.iter()
+                    .filter(|t| t.time >= start && t.time < end)
+                    .map(|t| TextEvent { time: t.time - start, ..t.clone() })
+                    .collect(),
+                ..base.clone()
+            }
+        }).collect()
+    }
+
+    /// A lighter copy with at most `max_notes` notes for display and audition. The
+    /// skyline melody is kept whole if it fits, and every track gives up the same share
+    /// of its remaining notes, thinned evenly over time.
+    pub fn preview(&self, max_notes: usize) -> Sequence {
+        let mut preview = self.clone();
+        let total: usize = self.tracks.iter().map(|t| t.notes.len()).sum();
+        if total <= max_notes {
+            return preview;
+        }
+        // The skyline: the highest non-drum note at every onset, as in `extract_melody`
+        let mut onsets: Vec<(f32, u8, usize, usize)> = self.tracks.iter().enumerate()
+            .filter(|(_, t)| !t.is_drum)
+            .flat_map(|(ti, t)| t.notes.iter().enumerate().map(move |(ni, n)| (n.start, n.pitch, ti, ni)))
+            .collect();
+        onsets.sort_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)));
+        onsets.dedup_by(|next, kept| next.0 == kept.0);
+        let skyline: Vec<(usize, usize)> = onsets.iter().map(|&(_, _, ti, ni)| (ti, ni)).collect();
+        let in_skyline: HashSet<(usize, usize)> = skyline.iter().copied().collect();
+
+        // Evenly spaced picks of `keep` out of `n` items
+        let spread = |n: usize, keep: usize| -> Vec<bool> {
+            (0..n).map(|i| (i + 1) * keep / n.max(1) > i * keep / n.max(1)).collect()
+        };
+        let mut keep: Vec<Vec<bool>> = self.tracks.iter().map(|t| vec![false; t.notes.len()]).collect();
+        for (&(ti, ni), kept) in skyline.iter().zip(spread(skyline.len(), max_notes)) {
+            keep[ti][ni] = kept;
+        }
+        let budget = max_notes.saturating_sub(skyline.len());
+        let others = total - skyline.len();
+        for (ti, track) in self.tracks.iter().enumerate() {
+            let mut order: Vec<usize> = (0..track.notes.len()).filter(|&ni| !in_skyline.contains(&(ti, ni))).collect();
+            order.sort_by(|&a, &b| track.notes[a].start.total_cmp(&track.notes[b].start));
+            // Each track gives up the same share of its accompaniment
+            let share = order.len() * budget / others.max(1);
+            for (&ni, kept) in order.iter().zip(spread(order.len(), share)) {
+                keep[ti][ni] = kept;
+            }
+        }
+        for (track, keep) in preview.tracks.iter_mut().zip(keep) {
+            let mut keep = keep.into_iter();
+            track.notes.retain(|_| keep.next().unwrap());
+        }
+        preview
+    }
+
+    /// Move onsets by Gaussian jitter of `timing_std` quarters and velocities by jitter of
+    /// `velocity_std`, the same for a given `seed`. Durations are kept, onsets stay at or
+    /// after 0 and velocities within 1-127.
+    pub fn humanize(&mut self, timing_std: f32, velocity_std: f32, seed: u64) {
+        let mut rng = Rng::new(seed);
+        for track in &mut self.tracks {
+            for note in &mut track.notes {
+                note.start = (note.start + rng.gaussian(timing_std as f64) as f32).max(0.0);
+                let velocity = note.velocity as f64 + rng.gaussian(velocity_std as f64);
+                note.velocity = velocity.round().clamp(1.0, 127.0) as u8;
+            }
+            track.sort();
+        }
+    }
+
+    /// Skyline melody across non-drum tracks: at each onset the highest pitch sounding
+    /// takes over, cutting off the melody note before it. A note that started earlier
+    /// joins the melody from the onset on, so the result is the monophonic top line.
+    pub fn extract_melody(&self) -> Track {
+        let mut sources: Vec<Note> = self.tracks.iter()
+            .filter(|t| !t.is_drum)
+            .flat_map(|t| t.notes.iter().copied())
+            .collect();
+        sources.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        // Sounding notes by pitch, then by index so the latest start wins a tie
+        let mut active: BinaryHeap<(u8, usize)> = BinaryHeap::new();
+        let mut notes: Vec<Note> = Vec::new();
+        let mut current = None;
+        let mut idx = 0;
+        while idx < sources.len() {
+            let time = sources[idx].start;
+            loop {
+                active.push((sources[idx].pitch, idx));
+                idx += 1;
+                if idx == sources.len() || sources[idx].start != time {
+                    break;
+                }
+            }
+            // Notes that ended before this onset are only removed once on top
+            while let Some(&(_, top)) = active.peek() {
+                let note = &sources[top];
+                if note.start < time && note.start + note.duration <= time {
+                    active.pop();
+                } else {
+                    break;
+                }
+            }
+            let Some(&(_, top)) = active.peek() else { continue };
+            if current == Some(top) {
+                continue;
+            }
+            if let Some(last) = notes.last_mut().filter(|last| last.start + last.duration > time) {
+                last.duration = time - last.start;
+            }
+            let note = sources[top];
+            notes.push(Note { start: time, duration: note.start + note.duration - time, tie: false, ..note });
+            current = Some(top);
+        }
+        let program = self.tracks.iter().find(|t| !t.is_drum).map_or(0, |t| t.program);
+        Track { name: "Melody".to_string(), program, notes, ..Track::default() }
+    }
+}
+
+/// What tracks must share to be combined by `merge_tracks`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MergeKey {
+    Program,
+    /// Bank select and program.
+    Instrument,
+    Channel,
+    /// GM instrument family, see `Track::instrument_family`.
+    Family,
+    All,
+}
+
+impl std::str::FromStr for MergeKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "program" => Ok(MergeKey::Program),
+            "instrument" => Ok(MergeKey::Instrument),
+            "channel" => Ok(MergeKey::Channel),
+            "family" => Ok(MergeKey::Family),
+            "all" => Ok(MergeKey::All),
+            _ => Err(format!(
+                "Unknown merge key {:?}, expected \"program\", \"instrument\", \"channel\", \"family\" or \"all\"", s)),
+        }
+    }
+}
+
+impl Track {
+    /// Move all notes and controller data of `other` into this track. Channel and source
+    /// track are only kept when both tracks agree on them.
+    pub fn merge(&mut self, other: Track) {
+        self.notes.extend(other.notes);
+        for (number, controls) in other.controls {
+            self.controls.entry(number).or_default().extend(controls);
+        }
+        for (number, controls) in other.fine_controls {
+            self.fine_controls.entry(number).or_default().extend(controls);
+        }
+        for (pitch, pressure) in other.poly_pressure {
+            self.poly_pressure.entry(pitch).or_default().extend(pressure);
+        }
+        self.channel_pressure.extend(other.channel_pressure);
+        self.sysex.extend(other.sysex);
+        if self.channel != other.channel {
+            self.channel = None;
+        }
+        if self.midi_track_index != other.midi_track_index {
+            self.midi_track_index = None;
+        }
+        self.sort();
+    }
+}
+
+impl Sequence {
+    /// Combine tracks sharing the same `by` key into the first of them. Drum and melodic
+    /// tracks are never combined with each other.
+    pub fn merge_tracks(&mut self, by: MergeKey) {
+        let mut merged: Vec<Track> = Vec::new();
+        let mut keys: Vec<(bool, u32)> = Vec::new();
+        for track in std::mem::take(&mut self.tracks) {
+            let key = match by {
+                MergeKey::Program => track.program as u32,
+                MergeKey::Instrument => u32::from_be_bytes([0, track.bank_msb, track.bank_lsb, track.program]),
+                MergeKey::Channel => track.channel.map_or(u32::MAX, u32::from),
+                MergeKey::Family => track.program as u32 / 8,
+                MergeKey::All => 0,
+            };
+            // Tracks with no channel are left alone when merging by channel
+            let found = keys.iter().position(|&k| k == (track.is_drum, key) && key != u32::MAX);
+            match found {
+                Some(idx) => merged[idx].merge(track),
+                None => {
+                    keys.push((track.is_drum, key));
+                    merged.push(track);
+                }
+            }
+        }
+        self.tracks = merged;
+    }
+
+    /// Keep the tracks for which `keep` holds. Returns how many were removed.
+    pub fn filter_tracks(&mut self, keep: impl FnMut(&Track) -> bool) -> usize {
+        let before = self.tracks.len();
+        self.tracks.retain(keep);
+        before - self.tracks.len()
+    }
+
+    /// Drop tracks without notes, and with them any controllers or SysEx they carry.
+    /// Returns how many were removed.
+    pub fn remove_empty_tracks(&mut self) -> usize {
+        self.filter_tracks(|t| !t.notes.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(pitch: u8, start: f32, duration: f32) -> Note {
+        Note { pitch, start, duration, velocity: 100, tie: false }
+    }
+
+    #[test]
+    fn test_adjust_durations() {
+        let mut track = Track {
+            notes: vec![note(60, 0.0, 0.1), note(64, 0.0, 2.0), note(62, 1.0, 0.5), note(65, 1.5, 0.25)],
+            ..Track::default()
+        };
+        track.adjust_durations(0.5);
+        let durations: Vec<(u8, f32)> = track.notes.iter().map(|n| (n.pitch, n.duration)).collect();
+        assert_eq!(durations, vec![(60, 0.5), (64, 0.5), (62, 0.25), (65, 0.25)]);
+        track.legato();
+        let durations: Vec<f32> = track.notes.iter().map(|n| n.duration).collect();
+        assert_eq!(durations, vec![1.0, 1.0, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_humanize() {
+        let notes: Vec<Note> = (0..64).map(|i| note(60, i as f32 * 0.5, 0.5)).collect();
+        let mut seq = Sequence { tracks: vec![Track { notes: notes.clone(), ..Track::default() }], ..Sequence::default() };
+        let mut again = seq.clone();
+        seq.humanize(0.02, 10.0, 42);
+        again.humanize(0.02, 10.0, 42);
+        assert_eq!(seq, again);
+        let humanized = &seq.tracks[0].notes;
+        assert!(humanized.iter().zip(&notes).any(|(h, n)| h.start != n.start && h.velocity != n.velocity));
+        assert!(humanized.iter().all(|n| n.start >= 0.0 && n.duration == 0.5 && (1..=127).contains(&n.velocity)));
+        let mut quiet = Sequence { tracks: vec![Track { notes, ..Track::default() }], ..Sequence::default() };
+        quiet.humanize(0.0, 0.0, 1);
+        assert_eq!(quiet.tracks[0].notes.iter().map(|n| n.start).collect::<Vec<_>>(), (0..64).map(|i| i as f32 * 0.5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_to_monophonic() {
+        let track = Track {
+            notes: vec![note(60, 0.0, 2.0), note(67, 0.0, 1.0), note(72, 0.5, 1.0), note(55, 1.0, 2.0), note(60, 3.0, 1.0)],
+            ..Track::default()
+        };
+        let line = |policy: Monophony| -> Vec<(u8, f32, f32)> {
+            track.to_monophonic(policy).notes.iter().map(|n| (n.pitch, n.start, n.duration)).collect()
+        };
+        assert_eq!(line(Monophony::Highest), vec![(67, 0.0, 0.5), (72, 0.5, 1.0), (60, 3.0, 1.0)]);
+        assert_eq!(line(Monophony::Lowest), vec![(60, 0.0, 1.0), (55, 1.0, 2.0), (60, 3.0, 1.0)]);
+        assert_eq!(line(Monophony::Last), vec![(67, 0.0, 0.5), (72, 0.5, 0.5), (55, 1.0, 2.0), (60, 3.0, 1.0)]);
+        assert!("top".parse::<Monophony>().is_err());
+        // A NaN start sorts last instead of panicking
+        let mut track = track;
+        track.notes.push(note(64, f32::NAN, 1.0));
+        assert!(track.to_monophonic(Monophony::Last).notes.last().unwrap().start.is_nan());
+    }
+
+    #[test]
+    fn test_apply_sustain() {
+        let pedal = |time: f32, value: u8| ControlChange { time, value };
+        let mut track = Track {
+            notes: vec![note(60, 0.0, 0.5), note(60, 1.0, 0.5), note(64, 0.0, 2.25), note(67, 2.5, 0.25), note(72, 4.0, 0.5)],
+            ..Track::default()
+        };
+        track.controls.insert(SUSTAIN_PEDAL, vec![pedal(0.0, 127), pedal(2.0, 0), pedal(2.5, 100)]);
+        track.apply_sustain();
+        let notes: Vec<(u8, f32, f32)> = track.notes.iter().map(|n| (n.pitch, n.start, n.duration)).collect();
+        assert_eq!(notes, vec![
+            // Cut at the next onset of the pitch, held to the pedal release, or released
+            // while the pedal is up
+            (60, 0.0, 1.0), (64, 0.0, 2.25), (60, 1.0, 1.0),
+            // The pedal is still down at the end of the track
+            (67, 2.5, 2.0), (72, 4.0, 0.5),
+        ]);
+        // A NaN time sorts last instead of panicking
+        track.notes.push(note(60, f32::NAN, 0.5));
+        track.apply_sustain();
+        assert!(track.notes.last().unwrap().start.is_nan());
+    }
+
+    #[test]
+    fn test_velocity_tools() {
+        let velocity = |v: u8| Note { velocity: v, ..note(60, 0.0, 1.0) };
+        let mut track = Track { notes: vec![velocity(40), velocity(80), velocity(120)], ..Track::default() };
+        track.normalize_velocity(60.0);
+        assert_eq!(track.notes.iter().map(|n| n.velocity).collect::<Vec<_>>(), vec![30, 60, 90]);
+        track.scale_velocity(2.0);
+        assert_eq!(track.notes.iter().map(|n| n.velocity).collect::<Vec<_>>(), vec![60, 120, 127]);
+        track.scale_velocity(0.0);
+        assert!(track.notes.iter().all(|n| n.velocity == 1));
+    }
+
+    #[test]
+    fn test_filter_notes() {
+        let mut track = Track {
+            notes: vec![
+                note(60, 0.0, 1.0), note(60, 0.0, 2.0), note(60, 0.0005, 0.5),
+                note(64, 0.0, 0.0), note(20, 1.0, 1.0), note(67, 1.0, 0.05),
+            ],
+            ..Track::default()
+        };
+        assert_eq!(track.remove_duplicate_notes(), 2);
+        assert_eq!(track.notes[0].duration, 2.0);
+        assert_eq!(track.filter_notes(21, 108, 1, 0.1), 3);
+        assert_eq!(track.notes.len(), 1);
+        // A NaN duration is compared rather than panicking
+        track.notes.extend([note(72, 2.0, f32::NAN), note(72, 2.0, 1.0)]);
+        assert_eq!(track.remove_duplicate_notes(), 1);
+    }
+
+    #[test]
+    fn test_merge_and_filter_tracks() {
+        let track = |program: u8, is_drum: bool, channel: u8, notes: Vec<Note>| Track {
+            program, is_drum, channel: Some(channel), notes, ..Track::default()
+        };
+        let mut seq = Sequence {
+            tracks: vec![
+                track(0, false, 0, vec![note(60, 1.0, 1.0)]),
+                track(0, true, 9, vec![note(36, 0.0, 0.5)]),
+                track(1, false, 1, vec![note(64, 0.0, 1.0)]),
+                track(0, false, 2, vec![note(48, 0.0, 1.0)]),
+                track(5, false, 3, Vec::new()),
+            ],
+            ..Sequence::default()
+        };
+        let mut by_program = seq.clone();
+        by_program.merge_tracks(MergeKey::Program);
+        assert_eq!(by_program.tracks.len(), 4);
+        assert_eq!(by_program.tracks[0].notes.iter().map(|n| n.pitch).collect::<Vec<_>>(), vec![48, 60]);
+        assert_eq!(by_program.tracks[0].channel, None);
+        let mut by_family = seq.clone();
+        by_family.merge_tracks(MergeKey::Family);
+        assert_eq!(by_family.tracks.len(), 2);
+        assert_eq!(by_family.tracks[0].notes.len(), 3);
+
+        assert_eq!(seq.remove_empty_tracks(), 1);
+        assert_eq!(seq.filter_tracks(|t| t.is_drum), 3);
+        assert_eq!(seq.tracks[0].program, 0);
+    }
+
+    #[test]
+    fn test_preview() {
+        // A melody over dense chords and a busy drum part
+        let melody = (0..16).map(|i| note(76, i as f32, 1.0)).collect();
+        let chords = (0..16).flat_map(|i| [48, 52, 55].map(|p| note(p, i as f32, 1.0))).collect();
+        let drums = (0..64).map(|i| note(42, i as f32 / 4.0, 0.25)).collect();
+        let seq = Sequence {
+            tracks: vec![
+                Track { notes: melody, ..Track::default() },
+                Track { notes: chords, ..Track::default() },
+                Track { notes: drums, is_drum: true, ..Track::default() },
+            ],
+            ..Sequence::default()
+        };
+        let preview = seq.preview(40);
+        let counts: Vec<usize> = preview.tracks.iter().map(|t| t.notes.len()).collect();
+        assert_eq!(counts[0], 16);
+        assert!(counts.iter().sum::<usize>() <= 40);
+        assert!(counts[1] > 0 && counts[2] > counts[1]);
+        // Kept accompaniment is spread over the whole piece
+        assert!(preview.tracks[2].notes.last().unwrap().start >= 15.0);
+        assert_eq!(seq.preview(1000).tracks[2].notes.len(), 64);
+        // A NaN start is sorted rather than panicking
+        let mut seq = seq;
+        seq.tracks[1].notes[0].start = f32::NAN;
+        seq.tracks[0].notes[0].start = f32::NAN;
+        assert!(seq.preview(40).tracks.iter().map(|t| t.notes.len()).sum::<usize>() <= 40);
+    }
+
+    #[test]
+    fn test_extract_melody() {
+        let seq = Sequence {
+            tracks: vec![
+                Track { notes: vec![note(48, 0.0, 4.0), note(60, 1.0, 2.0), note(52, 2.0, 2.0), note(55, 3.5, 1.0)], ..Track::default() },
+                Track { notes: vec![note(72, 0.0, 3.0), note(74, 2.0, 1.0)], ..Track::default() },
+                Track { is_drum: true, notes: vec![note(80, 1.0, 1.0)], ..Track::default() },
+            ],
+            ..Sequence::default()
+        };
+        let melody = seq.extract_melody();
+        let pitches: Vec<(u8, f32, f32)> = melody.notes.iter().map(|n| (n.pitch, n.start, n.duration)).collect();
+        // Onsets under the sounding top line are dropped, the next one after it is taken
+        assert_eq!(pitches, vec![(72, 0.0, 2.0), (74, 2.0, 1.0), (55, 3.5, 1.0)]);
+
+        // A held note higher than the next onset takes over from it
+        let seq = Sequence {
+            tracks: vec![Track { notes: vec![note(67, 0.0, 4.0), note(72, 0.0, 1.0), note(60, 2.0, 1.0)], ..Track::default() }],
+            ..Sequence::default()
+        };
+        let melody: Vec<(u8, f32, f32)> = seq.extract_melody().notes.iter().map(|n| (n.pitch, n.start, n.duration)).collect();
+        assert_eq!(melody, vec![(72, 0.0, 1.0), (67, 2.0, 2.0)]);
+    }
+
+    #[test]
+    fn test_limit_polyphony() {
+        let chord = vec![
+            Note { velocity: 90, ..note(60, 0.0, 4.0) },
+            Note { velocity: 50, ..note(64, 1.0, 4.0) },
+            Note { velocity: 70, ..note(67, 2.0, 4.0) },
+        ];
+        let mut track = Track { notes: chord.clone(), ..Track::default() };
+        track.limit_polyphony(2, VoiceStealing::Oldest);
+        let shape: Vec<(u8, f32)> = track.notes.iter().map(|n| (n.pitch, n.duration)).collect();
+        assert_eq!(shape, vec![(60, 2.0), (64, 4.0), (67, 4.0)]);
+
+        let mut track = Track { notes: chord, ..Track::default() };
+        track.limit_polyphony(2, VoiceStealing::Quietest);
+        let shape: Vec<(u8, f32)> = track.notes.iter().map(|n| (n.pitch, n.duration)).collect();
+        assert_eq!(shape, vec![(60, 4.0), (64, 1.0), (67, 4.0)]);
+
+        let mut track = Track { notes: vec![note(60, 0.0, 1.0), note(64, 0.0, 1.0)], ..Track::default() };
+        track.limit_polyphony(1, VoiceStealing::Quietest);
+        assert_eq!(track.notes.len(), 1);
+    }
+
+    #[test]
+    fn test_fit_to_range() {
+        let mut seq = Sequence {
+            tracks: vec![
+                Track { notes: vec![note(36, 0.0, 1.0), note(43, 1.0, 1.0)], ..Track::default() },
+                Track { notes: vec![note(84, 0.0, 1.0), note(88, 1.0, 1.0)], ..Track::default() },
+                Track { notes: vec![note(40, 0.0, 1.0), note(80, 1.0, 1.0)], ..Track::default() },
+                Track { is_drum: true, notes: vec![note(36, 0.0, 1.0)], ..Track::default() },
+            ],
+            ..Sequence::default()
+        };
+        assert_eq!(seq.pitch_range(), Some((36, 88)));
+        assert_eq!(seq.fit_to_range(48, 72), vec![12, -24, 0, 0]);
+        assert_eq!(seq.tracks[1].pitch_range(), Some((60, 64)));
+        assert_eq!(seq.tracks[3].notes[0].pitch, 36);
+    }
+
+    #[test]
+    fn test_remove_doublings() {
+        let line = [60, 62, 64, 65];
+        let make = |shift: i32, extra: bool| {
+            let mut notes: Vec<Note> = line.iter().enumerate()
+                .map(|(i, &p)| note((p + shift) as u8, i as f32, 1.0))
+                .collect();
+            if extra {
+                notes.push(note(70, 0.5, 0.5));
+            }
+            Track { notes, ..Track::default() }
+        };
+        let mut seq = Sequence {
+            tracks: vec![make(0, false), make(-12, true), make(4, false), make(0, false)],
+            ..Sequence::default()
+        };
+        assert_eq!(seq.tracks[1].doubling_similarity(&seq.tracks[0]), 0.8);
+        assert_eq!(seq.remove_doublings(0.8), vec![1, 3]);
+        assert_eq!(seq.tracks.len(), 2);
+    }
+
+    #[test]
+    fn test_split_at() {
+        let seq = Sequence {
+            tracks: vec![Track { notes: vec![note(60, 0.0, 6.0), note(62, 5.0, 1.0)], ..Track::default() }],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }, Tempo { time: 2.0, qpm: 90.0 }],
+            time_signatures: vec![TimeSignature { time: 0.0, numerator: 3, denominator: 4, ..Default::default() }],
+            ..Sequence::default()
+        };
+        let parts = seq.split_at(&[4.0]);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].tracks[0].notes[0].duration, 4.0);
+        assert_eq!(parts[1].tracks[0].notes[0].start, 1.0);
+        assert_eq!(parts[1].qpm.len(), 1);
+        assert_eq!((parts[1].qpm[0].time, parts[1].qpm[0].qpm), (0.0, 90.0));
+        assert_eq!(parts[1].time_signatures[0].numerator, 3);
+        // Positions that aren't finite are ignored
+        assert_eq!(seq.split_at(&[f32::NAN, 4.0, f32::INFINITY]).len(), 2);
+    }
+}
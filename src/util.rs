@@ -1,16 +1,90 @@
-pub fn read_variable_length(data: &[u8; 4]) -> (u8, usize) {
-    let mut bytes: u8 = 0;
-    let mut value: usize = 0;
-
-    for (i, &n) in data.iter().enumerate() {
-        value = (value << 7) + (n & 0x7f) as usize;
-        if n & 0x80 != 0x80 {
-            bytes = (i + 1) as u8;
-            break;
+/// Largest value a variable length quantity holds in SMF, 28 bits in four bytes.
+pub const MAX_VARIABLE_LENGTH: u32 = 0x0FFF_FFFF;
+
+/// Decode the variable length quantity at the start of `data` as `(bytes, value)`.
+/// Fails if `data` ends inside it or it runs past the four bytes SMF allows.
+pub fn read_variable_length(data: &[u8]) -> Result<(usize, u32), &'static str> {
+    let mut value: u32 = 0;
+    for (i, &n) in data.iter().take(4).enumerate() {
+        value = (value << 7) | (n & 0x7F) as u32;
+        if n & 0x80 == 0 {
+            return Ok((i + 1, value));
         }
     }
+    if data.len() < 4 {
+        Err("Truncated variable length quantity")
+    } else {
+        Err("Variable length quantity longer than 4 bytes")
+    }
+}
+
+/// Append `value` as a variable length quantity. Fails above `MAX_VARIABLE_LENGTH`.
+pub fn write_variable_length(bytes: &mut Vec<u8>, value: u32) -> Result<(), &'static str> {
+    if value > MAX_VARIABLE_LENGTH {
+        return Err("Value too large for a variable length quantity");
+    }
+    let mut value = value;
+    let mut buf = [0_u8; 4];
+    let mut idx = 3;
+    buf[idx] = (value & 0x7F) as u8;
+    value >>= 7;
+    while value > 0 {
+        idx -= 1;
+        buf[idx] = (value & 0x7F) as u8 | 0x80;
+        value >>= 7;
+    }
+    bytes.extend_from_slice(&buf[idx..]);
+    Ok(())
+}
+
+/// Append the delta time before an event. Gaps longer than a variable length quantity
+/// holds are split by empty sequencer specific metas, which readers pass over.
+pub fn write_delta_time(bytes: &mut Vec<u8>, mut delta: u64) {
+    while delta > MAX_VARIABLE_LENGTH as u64 {
+        bytes.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0x7F, 0x00]);
+        delta -= MAX_VARIABLE_LENGTH as u64;
+    }
+    write_variable_length(bytes, delta as u32).unwrap();
+}
+
+/// Channel for the `idx`-th track when none is known: drums go to the GM percussion
+/// channel and melodic tracks cycle over the other fifteen.
+#[inline(always)]
+pub fn default_channel(idx: usize, is_drum: bool) -> u8 {
+    match (is_drum, (idx % 15) as u8) {
+        (true, _) => 9,
+        (false, c) if c >= 9 => c + 1,
+        (false, c) => c,
+    }
+}
+
+/// SplitMix64, a small seeded generator so augmentations are reproducible everywhere.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
 
-    (bytes, value)
+    /// Normal with mean 0 and standard deviation `std`, by the Box-Muller transform.
+    pub fn gaussian(&mut self, std: f64) -> f64 {
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        std * (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
 }
 
 #[inline(always)]
@@ -24,9 +98,47 @@ mod tests {
 
     #[test]
     fn test_read_vlq() {
-        assert!(read_variable_length(&([0x40u8, 0x00u8, 0x00u8, 0x00u8])).1 == 0x40usize);
-        assert!(read_variable_length(&([0xC0u8, 0x00u8, 0x00u8, 0x00u8])).1 == 0x2000usize);
-        assert!(read_variable_length(&([0x81u8, 0x80u8, 0x00u8, 0x00u8])).1 == 0x4000usize);
-        assert!(read_variable_length(&([0xFFu8, 0xFFu8, 0x7Fu8, 0x00u8])).1 == 0x1FFFFFusize);
+        assert_eq!(read_variable_length(&[0x40]), Ok((1, 0x40)));
+        assert_eq!(read_variable_length(&[0xC0, 0x00, 0x00]), Ok((2, 0x2000)));
+        assert_eq!(read_variable_length(&[0x81, 0x80, 0x00]), Ok((3, 0x4000)));
+        assert_eq!(read_variable_length(&[0xFF, 0xFF, 0x7F, 0x00]), Ok((3, 0x1FFFFF)));
+        assert_eq!(read_variable_length(&[0xFF, 0xFF, 0xFF, 0x7F]), Ok((4, MAX_VARIABLE_LENGTH)));
+        assert!(read_variable_length(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F]).is_err());
+        assert!(read_variable_length(&[0x81, 0x80]).is_err());
+        assert!(read_variable_length(&[]).is_err());
+    }
+
+    #[test]
+    fn test_rng() {
+        let mut rng = Rng::new(7);
+        let samples: Vec<f64> = (0..10000).map(|_| rng.gaussian(2.0)).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let var = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.1 && (var.sqrt() - 2.0).abs() < 0.1, "{} {}", mean, var);
+        assert_eq!(Rng::new(7).next_u64(), Rng::new(7).next_u64());
+        assert_ne!(Rng::new(7).next_u64(), Rng::new(8).next_u64());
+    }
+
+    #[test]
+    fn test_write_vlq() {
+        for value in [0, 0x40, 0x7F, 0x80, 0x2000, 0x3FFF, 0x4000, 0x1FFFFF, 0x200000, MAX_VARIABLE_LENGTH] {
+            let mut bytes = Vec::new();
+            write_variable_length(&mut bytes, value).unwrap();
+            assert_eq!(read_variable_length(&bytes), Ok((bytes.len(), value)));
+        }
+        let mut bytes = Vec::new();
+        assert!(write_variable_length(&mut bytes, MAX_VARIABLE_LENGTH + 1).is_err());
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_write_delta_time() {
+        let mut bytes = Vec::new();
+        write_delta_time(&mut bytes, 0x80);
+        assert_eq!(bytes, [0x81, 0x00]);
+        // A gap past the largest quantity goes through a filler meta
+        let mut bytes = Vec::new();
+        write_delta_time(&mut bytes, MAX_VARIABLE_LENGTH as u64 + 5);
+        assert_eq!(bytes, [0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0x7F, 0x00, 0x05]);
     }
 }
@@ -0,0 +1,113 @@
+use std::fmt;
+use crate::io::{MIDIFile, MidiTrack};
+
+/// A structural problem found by `MIDIFile::validate`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// The MThd track count disagrees with the MTrk chunks present.
+    TrackCount { declared: u16, found: usize },
+    /// The MTrk length field disagrees with the bytes available.
+    ChunkLength { track: usize, declared: usize, actual: usize },
+    /// A message could not be read, the rest of the track is unreadable.
+    Malformed { track: usize, offset: usize, error: &'static str },
+    MissingEndOfTrack { track: usize },
+    /// Bytes follow the EndOfTrack meta.
+    EventsAfterEndOfTrack { track: usize, bytes: usize },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::TrackCount { declared, found } =>
+                write!(f, "Header declares {} tracks but {} were found", declared, found),
+            Diagnostic::ChunkLength { track, declared, actual } =>
+                write!(f, "Track {} chunk length is {} but {} bytes are present", track, declared, actual),
+            Diagnostic::Malformed { track, offset, error } =>
+                write!(f, "Track {}: {} at byte {}", track, error, offset),
+            Diagnostic::MissingEndOfTrack { track } =>
+                write!(f, "Track {} has no EndOfTrack", track),
+            Diagnostic::EventsAfterEndOfTrack { track, bytes } =>
+                write!(f, "Track {} has {} bytes after EndOfTrack", track, bytes),
+        }
+    }
+}
+
+const END_OF_TRACK: [u8; 4] = [0x00, 0xFF, 0x2F, 0x00];
+
+impl MIDIFile {
+    /// Check the chunk structure and every track for problems `repair` can fix.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if self.declared_tracks as usize != self.tracks.len() {
+            diagnostics.push(Diagnostic::TrackCount { declared: self.declared_tracks, found: self.tracks.len() });
+        }
+        for (track, midi_track) in self.tracks.iter().enumerate() {
+            let actual = midi_track.data().len();
+            if midi_track.declared_len != actual {
+                diagnostics.push(Diagnostic::ChunkLength { track, declared: midi_track.declared_len, actual });
+            }
+            let mut messages = midi_track.iter();
+            messages.by_ref().for_each(drop);
+            if let Some(error) = messages.error {
+                diagnostics.push(Diagnostic::Malformed { track, offset: messages.message_end, error });
+            }
+            if !messages.end_of_track {
+                diagnostics.push(Diagnostic::MissingEndOfTrack { track });
+            } else if messages.trailing_bytes > 0 {
+                diagnostics.push(Diagnostic::EventsAfterEndOfTrack { track, bytes: messages.trailing_bytes });
+            }
+        }
+        diagnostics
+    }
+
+    /// A corrected copy: every track is cut after its last readable message or its
+    /// EndOfTrack and terminated properly, and chunk lengths and the track count are
+    /// recomputed when written.
+    pub fn repair(&self) -> MIDIFile {
+        let tracks: Vec<MidiTrack> = self.tracks.iter().map(|midi_track| {
+            let mut messages = midi_track.iter();
+            messages.by_ref().for_each(drop);
+            let mut data = midi_track.data()[..messages.message_end].to_vec();
+            if !messages.end_of_track {
+                data.extend_from_slice(&END_OF_TRACK);
+            }
+            MidiTrack::new(midi_track.track_idx, data)
+        }).collect();
+        MIDIFile {
+            format: self.format,
+            division: self.division,
+            declared_tracks: tracks.len() as u16,
+            tracks,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_and_repair() {
+        let mut data = std::fs::read("tests/tiny.mid").unwrap();
+        data[11] += 1; // one more track than present
+        // Garbage after the last EndOfTrack, and a length field that overshoots it
+        let last = data.windows(4).rposition(|w| w == b"MTrk").unwrap() + 4;
+        let len = u32::from_be_bytes(data[last..last + 4].try_into().unwrap()) + 5;
+        data[last..last + 4].copy_from_slice(&len.to_be_bytes());
+        data.extend_from_slice(&[0x00, 0x90, 0x3C]);
+        let midi = MIDIFile::from_bytes(&data).unwrap();
+        let diagnostics = midi.validate();
+        assert!(diagnostics.contains(&Diagnostic::TrackCount { declared: 3, found: 2 }));
+        assert!(diagnostics.iter().any(|d| matches!(d, Diagnostic::ChunkLength { track: 1, .. })));
+        assert!(diagnostics.contains(&Diagnostic::EventsAfterEndOfTrack { track: 1, bytes: 3 }));
+
+        let truncated = MIDIFile { tracks: vec![MidiTrack::new(0, vec![0x00, 0x90, 0x3C, 0x40, 0x10])], ..midi.clone() };
+        assert!(truncated.validate().contains(&Diagnostic::MissingEndOfTrack { track: 0 }));
+
+        for broken in [midi, truncated] {
+            let fixed = MIDIFile::from_bytes(&broken.repair().to_bytes()).unwrap();
+            assert!(fixed.validate().is_empty());
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! JavaScript bindings for browsers and other WebAssembly hosts, built with
+//! `wasm-pack build --target web --no-default-features --features wasm`:
+//!
+//! ```js
+//! import init, { Sequence, analyze } from "./pkg/midiparse_core.js";
+//! await init();
+//! const seq = Sequence.fromBytes(new Uint8Array(await file.arrayBuffer()));
+//! console.log(seq.noteCount, seq.duration, seq.estimatedKey());
+//! const notes = JSON.parse(seq.toJson()).tracks[0].notes;
+//! const summary = JSON.parse(analyze(bytes));
+//! ```
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+use crate::analysis::key_name;
+use crate::error::MidiError;
+use crate::loader::load;
+use crate::sequence::Sequence;
+use crate::timing::TimeUnit;
+
+fn error_json(error: MidiError) -> Value {
+    let location = error.location();
+    json!({
+        "error": error.message(),
+        "track": location.track,
+        "tick": location.tick,
+        "offset": location.offset,
+    })
+}
+
+/// `{"sequence": ...}` for any format `load` reads, or `{"error": ...}`.
+#[wasm_bindgen(js_name = parse)]
+pub fn parse_json(data: &[u8]) -> String {
+    match load(data, false) {
+        Ok((seq, format, _)) => json!({ "format": format.name(), "sequence": seq }),
+        Err(error) => error_json(error),
+    }.to_string()
+}
+
+fn analysis(seq: &Sequence) -> Value {
+    let key = seq.estimate_key().map(key_name);
+    let quantization: Vec<Value> = seq.detect_quantization().into_iter()
+        .map(|q| q.map_or(Value::Null, |(grid, strength)| json!({ "grid": grid.name(), "strength": strength })))
+        .collect();
+    let chords: Vec<Value> = seq.chord_segments(1.0).into_iter()
+        .map(|(start, end, chord)| json!([start, end, chord.map(|c| c.label())]))
+        .collect();
+    json!({
+        "tracks": seq.tracks.len(),
+        "notes": seq.tracks.iter().map(|t| t.notes.len()).sum::<usize>(),
+        "duration": seq.end_time(TimeUnit::Second),
+        "pitch_range": seq.pitch_range(),
+        "estimated_key": key,
+        "estimated_tempo": seq.estimate_tempo(),
+        "quantization": quantization,
+        "chords": chords,
+        // As text, JavaScript numbers can't hold 64 bits
+        "fingerprint": format!("{:016x}", seq.fingerprint(4, false)),
+    })
+}
+
+/// Summary, key, tempo, grid and chord analysis of a file, or `{"error": ...}`.
+#[wasm_bindgen(js_name = analyze)]
+pub fn analyze_json(data: &[u8]) -> String {
+    match load(data, false) {
+        Ok((seq, _, _)) => analysis(&seq),
+        Err(error) => error_json(error),
+    }.to_string()
+}
+
+/// A parsed `Sequence` held on the WebAssembly side, so a page parses once and queries
+/// it repeatedly.
+#[wasm_bindgen(js_name = Sequence)]
+pub struct WasmSequence(Sequence);
+
+#[wasm_bindgen(js_class = Sequence)]
+impl WasmSequence {
+    /// Parse a Standard MIDI File or any other format `load` reads; throws with the
+    /// error and its location otherwise.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8]) -> Result<WasmSequence, JsError> {
+        load(data, false).map(|(seq, _, _)| WasmSequence(seq)).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    #[wasm_bindgen(getter, js_name = trackCount)]
+    pub fn track_count(&self) -> usize {
+        self.0.tracks.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = noteCount)]
+    pub fn note_count(&self) -> usize {
+        self.0.tracks.iter().map(|t| t.notes.len()).sum()
+    }
+
+    /// End of the last event in seconds.
+    #[wasm_bindgen(getter)]
+    pub fn duration(&self) -> f32 {
+        self.0.end_time(TimeUnit::Second)
+    }
+
+    /// Name of the estimated key, e.g. "A minor".
+    #[wasm_bindgen(js_name = estimatedKey)]
+    pub fn estimated_key(&self) -> Option<String> {
+        self.0.estimate_key().map(key_name)
+    }
+
+    /// Estimated tempo in quarters per minute.
+    #[wasm_bindgen(js_name = estimatedTempo)]
+    pub fn estimated_tempo(&self) -> Option<f32> {
+        self.0.estimate_tempo()
+    }
+
+    /// The whole sequence as JSON, in the shape `Sequence.to_json` writes.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.0).unwrap_or_default()
+    }
+
+    /// The analysis `analyze` returns, as JSON.
+    pub fn analyze(&self) -> String {
+        analysis(&self.0).to_string()
+    }
+
+    /// A Standard MIDI File at the sequence's own resolution.
+    #[wasm_bindgen(js_name = toMidi)]
+    pub fn to_midi(&self) -> Result<Vec<u8>, JsError> {
+        self.0.to_bytes(&self.0.write_options()).map_err(JsError::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exports() {
+        let midi = std::fs::read("tests/tiny.mid").unwrap();
+        let result: Value = serde_json::from_str(&analyze_json(&midi)).unwrap();
+        assert_eq!(result["notes"], 28);
+        assert_eq!(result["fingerprint"].as_str().unwrap().len(), 16);
+
+        let seq = WasmSequence(Sequence::from_bytes(&midi).unwrap());
+        assert_eq!(seq.note_count(), 28);
+        assert_eq!(serde_json::from_str::<Value>(&seq.analyze()).unwrap(), result);
+        assert_eq!(Sequence::from_bytes(&seq.to_midi().unwrap()).unwrap().tracks.len(), seq.track_count());
+
+        let parsed: Value = serde_json::from_str(&parse_json(&midi)).unwrap();
+        assert_eq!(parsed["format"], "midi");
+        assert_eq!(parsed["sequence"]["tracks"][0]["name"], "Tiny Track");
+        let broken: Value = serde_json::from_str(&parse_json(b"hello")).unwrap();
+        assert_eq!(broken["offset"], 0);
+    }
+}
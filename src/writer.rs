@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use crate::io::{MIDIFile, MidiTrack};
+use crate::message::{EventStatus, MIDIFormat, Meta, MetaStatus};
+use crate::sequence::{Sequence, Track, BANK_SELECT_LSB, BANK_SELECT_MSB};
+use crate::util::{write_delta_time, write_variable_length};
+
+const DEFAULT_TPQ: u16 = 480;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rounding {
+    Nearest,
+    Floor,
+    Ceil,
+}
+
+impl std::str::FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Rounding::Nearest),
+            "floor" => Ok(Rounding::Floor),
+            "ceil" => Ok(Rounding::Ceil),
+            _ => Err(format!("Unknown rounding {:?}, expected \"nearest\", \"floor\" or \"ceil\"", s)),
+        }
+    }
+}
+
+impl Rounding {
+    #[inline(always)]
+    fn apply(&self, x: f64) -> f64 {
+        match self {
+            Rounding::Nearest => x.round(),
+            Rounding::Floor => x.floor(),
+            Rounding::Ceil => x.ceil(),
+        }
+    }
+}
+
+/// Controls how quarter-note times are turned into ticks when writing.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    /// Ticks per quarter of the written file, 1 to 0x7FFF.
+    pub tpq: u16,
+    pub rounding: Rounding,
+    /// Round every note duration on its own and place the note off relative to the
+    /// rounded note on, so equal durations stay equal in ticks. Otherwise note offs are
+    /// rounded like every other event time.
+    pub per_note: bool,
+    /// MIDI ports tracks without a channel may be spread over, see `allocate_channels`.
+    /// Unlimited when unset.
+    pub max_ports: Option<u8>,
+}
+
+/// `tpq` if a MIDI header can hold it, 1 to 0x7FFF ticks per quarter.
+fn check_tpq(tpq: u16) -> Result<u16, &'static str> {
+    match tpq {
+        1..=0x7FFF => Ok(tpq),
+        _ => Err("Ticks per quarter must be between 1 and 32767"),
+    }
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions { tpq: DEFAULT_TPQ, rounding: Rounding::Nearest, per_note: false, max_ports: None }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl WriteOptions {
+    #[new]
+    #[pyo3(signature = (tpq = DEFAULT_TPQ, rounding = "nearest", per_note = false, max_ports = None))]
+    fn py_new(tpq: u16, rounding: &str, per_note: bool, max_ports: Option<u8>) -> PyResult<Self> {
+        let tpq = check_tpq(tpq).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let rounding = rounding.parse().map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(WriteOptions { tpq, rounding, per_note, max_ports })
+    }
+
+    #[getter]
+    fn get_tpq(&self) -> u16 { self.tpq }
+
+    #[setter]
+    fn set_tpq(&mut self, tpq: u16) -> PyResult<()> {
+        self.tpq = check_tpq(tpq).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn get_per_note(&self) -> bool { self.per_note }
+
+    #[setter]
+    fn set_per_note(&mut self, per_note: bool) { self.per_note = per_note; }
+
+    #[getter]
+    fn get_max_ports(&self) -> Option<u8> { self.max_ports }
+
+    #[setter]
+    fn set_max_ports(&mut self, max_ports: Option<u8>) { self.max_ports = max_ports; }
+
+    #[getter]
+    fn get_rounding(&self) -> &'static str {
+        match self.rounding {
+            Rounding::Nearest => "nearest",
+            Rounding::Floor => "floor",
+            Rounding::Ceil => "ceil",
+        }
+    }
+
+    fn __repr__(&self) -> String { format!("{:?}", self) }
+}
+
+/// Converts increasing quarter times to ticks. Deltas between consecutive events are
+/// rounded and the rounding error is carried into the next delta, so floor or ceil
+/// rounding never drifts away from the tempo grid over long pieces.
+struct TickRounder<'a> {
+    options: &'a WriteOptions,
+    last_time: f64,
+    last_tick: u64,
+    error: f64,
+}
+
+impl<'a> TickRounder<'a> {
+    fn new(options: &'a WriteOptions) -> Self {
+        TickRounder { options, last_time: 0.0, last_tick: 0, error: 0.0 }
+    }
+
+    fn tick(&mut self, time: f32) -> u64 {
+        let time = time.max(0.0) as f64;
+        let exact = (time - self.last_time) * self.options.tpq as f64 + self.error;
+        let delta = self.options.rounding.apply(exact).max(0.0);
+        self.error = exact - delta;
+        self.last_time = time;
+        self.last_tick += delta as u64;
+        self.last_tick
+    }
+}
+
+/// A pending event: (quarter time, order at equal times, encoded bytes without delta).
+type Pending = (f32, u8, Vec<u8>);
+
+
+/// Assign ticks, keep note offs after their note ons and encode as an MTrk payload.
+/// `notes` holds the (note on, note off) index pairs into `events`. Fails on a
+/// non-finite time.
+fn encode_track(mut events: Vec<Pending>, notes: &[(usize, usize)], options: &WriteOptions) -> Result<Vec<u8>, &'static str> {
+    if events.iter().any(|e| !e.0.is_finite()) {
+        return Err("Event times must be finite");
+    }
+    let mut order: Vec<usize> = (0..events.len()).collect();
+    order.sort_by(|&a, &b| events[a].0.total_cmp(&events[b].0).then(events[a].1.cmp(&events[b].1)));
+    let mut ticks = vec![0_u64; events.len()];
+    let mut rounder = TickRounder::new(options);
+    for &idx in &order {
+        ticks[idx] = rounder.tick(events[idx].0);
+    }
+    for &(on, off) in notes {
+        if options.per_note {
+            let duration = (events[off].0 - events[on].0) as f64 * options.tpq as f64;
+            ticks[off] = ticks[on] + options.rounding.apply(duration) as u64;
+        }
+        // Notes that rounded to zero length still need to sound for one tick.
+        ticks[off] = ticks[off].max(ticks[on] + 1);
+    }
+    order.sort_by_key(|&idx| (ticks[idx], events[idx].1));
+
+    let mut data = Vec::new();
+    let mut last_tick = 0;
+    for idx in order {
+        write_delta_time(&mut data, ticks[idx] - last_tick);
+        data.append(&mut events[idx].2);
+        last_tick = ticks[idx];
+    }
+    write_delta_time(&mut data, 0);
+    data.extend(Meta::new_end_of_track(0).data.iter());
+    Ok(data)
+}
+
+impl Sequence {
+    /// `(port, channel)` for every track. Tracks keep their own channel, on their own port
+    /// or port 0. The
+    /// others share a channel with a track playing the same instrument, or take the next
+    /// free one, drums on channel 9 and melodic parts on the rest, moving on to the next
+    /// port when one is full. Fails when `max_ports` ports are not enough; every port
+    /// may be used when it is `None`.
+    pub fn allocate_channels(&self, max_ports: Option<u8>) -> Result<Vec<(u8, u8)>, &'static str> {
+        let ports = max_ports.map_or(u8::MAX as u16 + 1, u16::from);
+        type Instrument = (bool, u8, u8, u8);
+        let mut used: HashMap<(u8, u8), Instrument> = HashMap::new();
+        let instrument = |idx: usize| {
+            let t = &self.tracks[idx];
+            (t.is_drum, t.bank_msb, t.bank_lsb, t.program)
+        };
+        let fixed = |track: &Track| track.channel.map(|channel| (track.port.unwrap_or(0), channel & 0x0F));
+        for (idx, track) in self.tracks.iter().enumerate() {
+            if let Some(slot) = fixed(track) {
+                used.entry(slot).or_insert(instrument(idx));
+            }
+        }
+        let mut allocation = Vec::with_capacity(self.tracks.len());
+        for (idx, track) in self.tracks.iter().enumerate() {
+            if let Some(slot) = fixed(track) {
+                allocation.push(slot);
+                continue;
+            }
+            let wanted = instrument(idx);
+            let slots: Vec<(u8, u8)> = (0..ports)
+                .flat_map(|port| (0..16).map(move |channel| (port as u8, channel)))
+                .filter(|&(_, channel)| (channel == 9) == track.is_drum)
+                .collect();
+            let slot = slots.iter().find(|s| used.get(s) == Some(&wanted))
+                .or_else(|| slots.iter().find(|s| !used.contains_key(s)))
+                .copied()
+                .ok_or("More distinct instruments than channels, allow more ports in WriteOptions.max_ports")?;
+            used.insert(slot, wanted);
+            allocation.push(slot);
+        }
+        Ok(allocation)
+    }
+
+    /// Default options, at the resolution of the source file if there is one.
+    pub fn write_options(&self) -> WriteOptions {
+        WriteOptions { tpq: self.tpq.unwrap_or(DEFAULT_TPQ), ..WriteOptions::default() }
+    }
+
+    /// Build a format 1 MIDI file: a conductor track with tempo, time and key
+    /// signatures, followed by one track per `Track`. Tracks are given channels by
+    /// `allocate_channels`, and a port meta when more than one port is used or the track
+    /// was read with one. A channel prefix read with the track is written back. Fails on
+    /// time or key signatures a MIDI file can't hold, non-finite event times and an
+    /// invalid `options.tpq`.
+    pub fn to_midi(&self, options: &WriteOptions) -> Result<MIDIFile, &'static str> {
+        check_tpq(options.tpq)?;
+        let allocation = self.allocate_channels(options.max_ports)?;
+        let multi_port = allocation.iter().any(|&(port, _)| port > 0);
+        let mut tracks = Vec::with_capacity(self.tracks.len() + 1);
+
+        let mut conductor: Vec<Pending> = Vec::new();
+        if let Some(title) = &self.metadata.title {
+            conductor.push((0.0, 0, Meta::new(0, MetaStatus::TrackName as u8, title.as_bytes()).data.into()));
+        }
+        for tempo in &self.qpm {
+            conductor.push((tempo.time, 0, Meta::new_tempo(0, tempo.qpm).data.into()));
+        }
+        for ts in &self.time_signatures {
+            let meta = Meta::new_time_signature(
+                0, ts.numerator, ts.denominator,
+                ts.clocks_per_click.unwrap_or(24), ts.thirty_seconds_per_quarter.unwrap_or(8),
+            )?;
+            conductor.push((ts.time, 0, meta.data.into()));
+        }
+        for ks in &self.key_signatures {
+            let meta = Meta::new_key_signature(0, ks.key.0, ks.key.1)?;
+            conductor.push((ks.time, 0, meta.data.into()));
+        }
+        for text in &self.texts {
+            conductor.push((text.time, 1, Meta::new_text(0, text.kind, &text.text).data.into()));
+        }
+        tracks.push(MidiTrack::new(0, encode_track(conductor, &[], options)?));
+
+        for (idx, (track, &(port, channel))) in self.tracks.iter().zip(&allocation).enumerate() {
+            let mut events: Vec<Pending> = Vec::new();
+            if multi_port || track.port.is_some() {
+                events.push((0.0, 0, Meta::new(0, MetaStatus::MIDIPort as u8, &[port]).data.into()));
+            }
+            if let Some(prefix) = track.channel_prefix {
+                events.push((0.0, 0, Meta::new(0, MetaStatus::MIDIChannelPrefix as u8, &[prefix & 0x0F]).data.into()));
+            }
+            if !track.name.is_empty() {
+                events.push((0.0, 0, Meta::new(0, MetaStatus::TrackName as u8, track.name.as_bytes()).data.into()));
+            }
+            for sysex in &track.sysex {
+                // Escapes go out as they are, without the framing F7
+                let framing = !sysex.escape as usize;
+                let mut bytes = vec![if sysex.escape { 0xF7 } else { 0xF0 }];
+                let len = u32::try_from(sysex.data.len() + framing).unwrap_or(u32::MAX);
+                write_variable_length(&mut bytes, len).map_err(|_| "SysEx too long for a MIDI file")?;
+                bytes.extend_from_slice(&sysex.data);
+                if !sysex.escape {
+                    bytes.push(0xF7);
+                }
+                events.push((sysex.time, 0, bytes));
+            }
+            // Bank select goes before the program change unless the controls carry it
+            for (number, value) in [(BANK_SELECT_MSB, track.bank_msb), (BANK_SELECT_LSB, track.bank_lsb)] {
+                if value != 0 && !track.controls.contains_key(&number) {
+                    events.push((0.0, 0, vec![EventStatus::ControlChange as u8 | channel, number, value & 0x7F]));
+                }
+            }
+            events.push((0.0, 1, vec![EventStatus::ProgramChange as u8 | channel, track.program & 0x7F]));
+            for (&number, controls) in &track.controls {
+                let order = if number == BANK_SELECT_MSB || number == BANK_SELECT_LSB { 0 } else { 2 };
+                for control in controls {
+                    events.push((control.time, order,
+                        vec![EventStatus::ControlChange as u8 | channel, number & 0x7F, control.value & 0x7F]));
+                }
+            }
+            for (&number, controls) in &track.fine_controls {
+                for control in controls {
+                    events.push((control.time, 2,
+                        vec![EventStatus::ControlChange as u8 | channel, number & 0x7F, control.msb()]));
+                    events.push((control.time, 2,
+                        vec![EventStatus::ControlChange as u8 | channel, (number + 32) & 0x7F, control.lsb()]));
+                }
+            }
+            for pressure in &track.channel_pressure {
+                events.push((pressure.time, 2,
+                    vec![EventStatus::ChannelAfterTouch as u8 | channel, pressure.value & 0x7F]));
+            }
+            for (&pitch, pressures) in &track.poly_pressure {
+                for pressure in pressures {
+                    // After a note on at the same time
+                    events.push((pressure.time, 4,
+                        vec![EventStatus::PolyphonicAfterTouch as u8 | channel, pitch & 0x7F, pressure.value & 0x7F]));
+                }
+            }
+            let mut notes = Vec::with_capacity(track.notes.len());
+            for note in &track.notes {
+                notes.push((events.len(), events.len() + 1));
+                // Note offs sort first so repeated pitches are released before retriggering
+                events.push((note.start, 3,
+                    vec![EventStatus::NoteOn as u8 | channel, note.pitch & 0x7F, note.velocity.clamp(1, 127)]));
+                events.push((note.start + note.duration.max(0.0), 0,
+                    vec![EventStatus::NoteOff as u8 | channel, note.pitch & 0x7F, 0]));
+            }
+            tracks.push(MidiTrack::new(idx as u16 + 1, encode_track(events, &notes, options)?));
+        }
+
+        let declared_tracks = tracks.len() as u16;
+        Ok(MIDIFile { format: MIDIFormat::MultiTrack, division: options.tpq, tracks, declared_tracks, warnings: Vec::new() })
+    }
+
+    pub fn to_bytes(&self, options: &WriteOptions) -> Result<Vec<u8>, &'static str> {
+        Ok(self.to_midi(options)?.to_bytes())
+    }
+
+    pub fn write(&self, path: &str, options: &WriteOptions) -> Result<(), &'static str> {
+        self.to_midi(options)?.write(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{ControlChange, KeySignature, Note, SysEx, Tempo, TimeSignature};
+
+    #[test]
+    fn test_native_resolution() {
+        let seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        assert_eq!(seq.tpq, Some(960));
+        assert_eq!(seq.to_midi(&seq.write_options()).unwrap().division, 960);
+        assert_eq!(Sequence::default().write_options().tpq, DEFAULT_TPQ);
+        for tpq in [0, 0x8000] {
+            assert!(seq.to_midi(&WriteOptions { tpq, ..WriteOptions::default() }).is_err());
+        }
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let mut seq = Sequence::from_file("tests/tiny.mid").unwrap();
+        let bytes = seq.to_bytes(&WriteOptions::default()).unwrap();
+        let mut back = Sequence::from_midi(&MIDIFile::from_bytes(&bytes).unwrap()).unwrap();
+        seq.sort();
+        back.sort();
+        assert_eq!(back.tracks.len(), seq.tracks.len());
+        assert_eq!(back.tracks[0].notes.len(), seq.tracks[0].notes.len());
+        for (a, b) in back.tracks[0].notes.iter().zip(&seq.tracks[0].notes) {
+            assert_eq!((a.pitch, a.velocity), (b.pitch, b.velocity));
+            // Written at half the file resolution, so allow one tick of rounding
+            let tick = 1.0 / DEFAULT_TPQ as f32;
+            assert!((a.start - b.start).abs() <= tick && (a.duration - b.duration).abs() <= tick);
+        }
+        assert_eq!(back.qpm[0].qpm, seq.qpm[0].qpm);
+        assert_eq!(back.time_signatures[0].numerator, seq.time_signatures[0].numerator);
+
+        // Signatures a file can't hold fail rather than go missing
+        let mut broken = seq.clone();
+        broken.time_signatures[0].denominator = 3;
+        assert!(broken.to_midi(&WriteOptions::default()).is_err());
+        let mut broken = seq.clone();
+        broken.key_signatures.push(KeySignature { time: 0.0, key: (true, 9) });
+        assert!(broken.to_midi(&WriteOptions::default()).is_err());
+        // So do times that aren't finite, instead of panicking while sorting
+        let mut broken = seq;
+        broken.tracks[0].notes[0].start = f32::NAN;
+        assert!(broken.to_midi(&WriteOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_long_gap() {
+        // Past the largest delta time at 960 ticks per quarter
+        let notes = vec![Note { pitch: 60, start: 300_000.0, duration: 1.0, velocity: 90, tie: false }];
+        let seq = Sequence::from_tracks(vec![Track { notes, ..Track::default() }], 120.0);
+        let options = WriteOptions { tpq: 960, ..WriteOptions::default() };
+        let back = Sequence::from_midi(&MIDIFile::from_bytes(&seq.to_bytes(&options).unwrap()).unwrap()).unwrap();
+        assert_eq!(back.tracks[0].notes[0].start, 300_000.0);
+    }
+
+    #[test]
+    fn test_tick_rounding() {
+        // Triplet eighths at 100 tpq can't be represented exactly
+        let notes = (0..300).map(|i| Note {
+            pitch: 60, start: i as f32 / 3.0, duration: 1.0 / 3.0, velocity: 80, tie: false,
+        }).collect();
+        let seq = Sequence {
+            tracks: vec![Track { notes, ..Track::default() }],
+            time_signatures: vec![TimeSignature { time: 0.0, numerator: 4, denominator: 4, ..Default::default() }],
+            qpm: vec![Tempo { time: 0.0, qpm: 120.0 }],
+            ..Sequence::default()
+        };
+        let read_back = |options: WriteOptions| {
+            let midi = MIDIFile::from_bytes(&seq.to_bytes(&options).unwrap()).unwrap();
+            Sequence::from_midi(&midi).unwrap().tracks.remove(0).notes
+        };
+        let floor = WriteOptions { tpq: 100, rounding: Rounding::Floor, per_note: false, max_ports: None };
+        let notes = read_back(floor.clone());
+        assert!((notes.last().unwrap().start - 99.66).abs() < 1e-3);
+        assert!(notes.iter().any(|n| (n.duration - 0.34).abs() < 1e-3));
+
+        let notes = read_back(WriteOptions { per_note: true, ..floor });
+        assert!((notes.last().unwrap().start - 99.66).abs() < 1e-3);
+        assert!(notes.iter().all(|n| (n.duration - 0.33).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_allocate_channels() {
+        let track = |program: u8, is_drum: bool| Track { program, is_drum, ..Track::default() };
+        let mut seq = Sequence {
+            tracks: (0..20).map(|program| track(program, false)).chain([track(0, true)]).collect(),
+            ..Sequence::default()
+        };
+        assert!(seq.to_midi(&WriteOptions { max_ports: Some(1), ..WriteOptions::default() }).is_err());
+        assert_eq!(seq.allocate_channels(None).unwrap(), seq.allocate_channels(Some(2)).unwrap());
+        let allocation = seq.allocate_channels(Some(2)).unwrap();
+        assert_eq!(&allocation[8..11], &[(0, 8), (0, 10), (0, 11)]);
+        assert_eq!((allocation[15], allocation[20]), ((1, 0), (0, 9)));
+        let midi = seq.to_midi(&WriteOptions::default()).unwrap();
+        let port = |idx: usize| midi.tracks[idx].iter().find_map(|m| match m {
+            crate::message::MIDIMessage::Meta(meta) if meta.status == MetaStatus::MIDIPort => Some(meta.meta_value()[0]),
+            _ => None,
+        });
+        assert_eq!((port(1), port(16)), (Some(0), Some(1)));
+
+        // The same instrument shares a channel, and explicit channels are kept
+        seq.tracks.iter_mut().for_each(|t| t.program = 0);
+        seq.tracks[0].channel = Some(3);
+        let allocation = seq.allocate_channels(Some(1)).unwrap();
+        assert_eq!((allocation[0], allocation[1], allocation[19]), ((0, 3), (0, 3), (0, 3)));
+    }
+
+    #[test]
+    fn test_port_round_trip() {
+        let note = Note { pitch: 60, start: 0.0, duration: 1.0, velocity: 80, tie: false };
+        let track = |port: u8| Track {
+            notes: vec![note],
+            channel: Some(0),
+            port: Some(port),
+            channel_prefix: Some(0),
+            ..Track::default()
+        };
+        let seq = Sequence { tracks: vec![track(0), track(1)], ..Sequence::default() };
+        assert_eq!(seq.allocate_channels(Some(1)).unwrap(), vec![(0, 0), (1, 0)]);
+        let midi = seq.to_midi(&WriteOptions::default()).unwrap();
+        let read = Sequence::from_midi(&midi).unwrap();
+        let mut ports: Vec<_> = read.tracks.iter()
+            .map(|t| (t.midi_track_index, t.port, t.channel, t.channel_prefix))
+            .collect();
+        ports.sort();
+        assert_eq!(ports, vec![(Some(1), Some(0), Some(0), Some(0)), (Some(2), Some(1), Some(0), Some(0))]);
+    }
+
+    #[test]
+    fn test_sysex_round_trip() {
+        let gs_reset = SysEx { time: 0.0, data: vec![0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41], escape: false };
+        let clock = SysEx { time: 0.5, data: vec![0xF8], escape: true };
+        let seq = Sequence {
+            tracks: vec![Track {
+                notes: vec![Note { pitch: 60, start: 0.0, duration: 1.0, velocity: 80, tie: false }],
+                sysex: vec![gs_reset.clone(), clock.clone()],
+                ..Track::default()
+            }],
+            ..Sequence::default()
+        };
+        let back = Sequence::from_bytes(&seq.to_bytes(&WriteOptions::default()).unwrap()).unwrap();
+        assert_eq!(back.tracks[0].sysex, vec![gs_reset, clock]);
+        assert_eq!(back.tracks[0].notes.len(), 1);
+    }
+
+    #[test]
+    fn test_aftertouch_round_trip() {
+        let pressure = |time: f32, value: u8| ControlChange { time, value };
+        let mut track = Track {
+            notes: vec![Note { pitch: 60, start: 0.0, duration: 2.0, velocity: 80, tie: false }],
+            channel_pressure: vec![pressure(0.5, 40), pressure(1.0, 90)],
+            ..Track::default()
+        };
+        track.poly_pressure.insert(60, vec![pressure(0.0, 10), pressure(1.5, 70), pressure(3.0, 5)]);
+        let seq = Sequence { tracks: vec![track], ..Sequence::default() };
+        let mut back = Sequence::from_bytes(&seq.to_bytes(&WriteOptions::default()).unwrap()).unwrap();
+        back.sort();
+        let values = |p: &[ControlChange]| p.iter().map(|c| (c.time, c.value)).collect::<Vec<_>>();
+        assert_eq!(values(&back.tracks[0].channel_pressure), vec![(0.5, 40), (1.0, 90)]);
+        assert_eq!(values(&back.tracks[0].note_pressure(0)), vec![(0.0, 10), (1.5, 70)]);
+    }
+}
@@ -0,0 +1,34 @@
+import numpy as np
+from midiparse import ControlChange, Note, Track, track2pianoroll
+
+
+def make_track():
+    notes = [Note(60, 0.0, 1.0, 100), Note(64, 0.5, 2.0, 80)]
+    pedal = [ControlChange(0.0, 127), ControlChange(1.0, 0)]
+    return Track("piano", 0, False, notes, {64: pedal})
+
+
+def test_velocity_and_pedal_planes():
+    piano = track2pianoroll(make_track(), quantize=4, velocity=True, pedal=True)
+    assert piano.shape == (3, 128, 10)
+    assert piano[0, 60].tolist() == [100] * 4 + [0] * 6
+    assert piano[1, 64].nonzero()[0].tolist() == [2]
+    assert piano[1, 64, 2] == 80
+    # The pedal plane is the same for every pitch
+    assert (piano[2, :, :4] == 1).all() and (piano[2, :, 4:] == 0).all()
+    assert track2pianoroll(make_track(), quantize=4).shape == (2, 128, 10)
+
+
+def test_max_len_shorter_than_notes():
+    piano = track2pianoroll(make_track(), max_len=3, quantize=4, velocity=True, pedal=True)
+    assert piano.shape == (3, 128, 3)
+    assert piano[0, 64].tolist() == [0, 0, 80]
+    assert piano[1, 64].tolist() == [0, 0, 80]
+    assert (piano[2] == 1).all()
+    assert not track2pianoroll(make_track(), max_len=2, quantize=4)[:, 64].any()
+
+
+def test_empty_track():
+    piano = track2pianoroll(Track("empty", 0, False, [], {}), pedal=True)
+    assert piano.shape == (3, 128, 0)
+    assert piano.dtype == np.uint8